@@ -0,0 +1,169 @@
+//! Measures the overhead of the read → transform → inject path per event, i.e. everything
+//! between libevdev handing over an event and uinput receiving it. Events come from a
+//! `MockDevice` and go to an injector which discards them, so neither the kernel nor I/O is
+//! timed.
+//!
+//! Run with `cargo bench --bench event_path`. Criterion reports each case's throughput in events
+//! per second, to hold against the time budget documented on `AsyncDevice`. Before measuring,
+//! each case is run once counting heap allocations, and the run fails if a case allocates per
+//! event.
+
+use criterion::measurement::WallTime;
+use criterion::{
+    criterion_group, criterion_main, BatchSize, BenchmarkGroup, Criterion, Throughput,
+};
+use evdev_rs::enums::{EventCode, EV_KEY, EV_SYN};
+use evdev_rs::{InputEvent, TimeVal};
+use evdev_utils::remap::{Remapper, Target};
+use evdev_utils::{EventStreamExt as _, Injector, MockDevice, WheelKeys};
+use futures::TryStreamExt as _;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// Events per iteration.
+const EVENTS: usize = 10_000;
+
+// Allocations per event; growing buffers once at startup is fine, allocating per event is not.
+const ALLOCATION_BUDGET: f64 = 0.001;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+struct CountingAllocator;
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _: usize = ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let _: usize = ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+/// Stands in for uinput, discarding events.
+struct NullInjector;
+
+impl Injector for NullInjector {
+    fn inject_event(&self, _event_code: EventCode, _value: i32) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Typing over a handful of keys, a press and a release per key with a report after each.
+fn typing() -> Vec<InputEvent> {
+    const KEYS: [EV_KEY; 4] = [EV_KEY::KEY_A, EV_KEY::KEY_S, EV_KEY::KEY_D, EV_KEY::KEY_F];
+    (0..EVENTS)
+        .map(|i| {
+            let time = TimeVal {
+                tv_sec: (i / 1000) as i64,
+                tv_usec: (i % 1000 * 1000) as i64,
+            };
+            let event_code = match i % 2 {
+                0 => EventCode::EV_KEY(KEYS[i / 4 % KEYS.len()]),
+                _ => EventCode::EV_SYN(EV_SYN::SYN_REPORT),
+            };
+            let value = match i % 4 {
+                0 => 1,
+                _ => 0,
+            };
+            InputEvent {
+                time,
+                event_code,
+                value,
+            }
+        })
+        .collect()
+}
+
+async fn forward(device: MockDevice) -> std::io::Result<()> {
+    let mut events = device;
+    while let Some(event) = events.try_next().await? {
+        NullInjector.inject_event_at(event.event_code, event.value, event.time)?;
+    }
+    Ok(())
+}
+
+async fn processor(device: MockDevice) -> std::io::Result<()> {
+    let mut events = device.process(
+        WheelKeys::volume()
+            .hwheel(
+                vec![vec![EV_KEY::KEY_NEXTSONG]],
+                vec![vec![EV_KEY::KEY_PREVIOUSSONG]],
+            )
+            .while_held(Some(EV_KEY::KEY_LEFTMETA)),
+    );
+    while let Some(event) = events.try_next().await? {
+        NullInjector.inject_event_at(event.event_code, event.value, event.time)?;
+    }
+    Ok(())
+}
+
+async fn remap(device: MockDevice) -> std::io::Result<()> {
+    Remapper::new(device, NullInjector)
+        .rule(EV_KEY::KEY_A, Target::Key(EV_KEY::KEY_B))
+        .rule(
+            EV_KEY::KEY_S,
+            Target::Chord(vec![EV_KEY::KEY_LEFTCTRL, EV_KEY::KEY_C]),
+        )
+        .rule(EV_KEY::KEY_CAPSLOCK, Target::Key(EV_KEY::KEY_ESC))
+        .run()
+        .await
+        .map_err(std::io::Error::other)
+}
+
+/// Runs `case` once, failing if it allocates more than the budget allows.
+fn check_allocations<F, Fut>(name: &str, case: F)
+where
+    F: FnOnce(MockDevice) -> Fut,
+    Fut: Future<Output = std::io::Result<()>>,
+{
+    let device = MockDevice::new(typing());
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    futures::executor::block_on(case(device)).expect("case failed");
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocations;
+    let per_event = allocations as f64 / EVENTS as f64;
+    assert!(
+        per_event <= ALLOCATION_BUDGET,
+        "{} makes {:.5} allocations per event, over the budget of {}",
+        name,
+        per_event,
+        ALLOCATION_BUDGET
+    );
+}
+
+fn bench_case<F, Fut>(group: &mut BenchmarkGroup<'_, WallTime>, name: &str, case: F)
+where
+    F: Fn(MockDevice) -> Fut,
+    Fut: Future<Output = std::io::Result<()>>,
+{
+    check_allocations(name, &case);
+    group.bench_function(name, |b| {
+        b.iter_batched(
+            || MockDevice::new(typing()),
+            |device| futures::executor::block_on(case(device)).expect("case failed"),
+            BatchSize::LargeInput,
+        )
+    });
+}
+
+fn event_path(c: &mut Criterion) {
+    let mut group = c.benchmark_group("event_path");
+    group.throughput(Throughput::Elements(EVENTS as u64));
+    bench_case(&mut group, "forward", forward);
+    bench_case(&mut group, "processor", processor);
+    bench_case(&mut group, "remap", remap);
+    group.finish();
+}
+
+criterion_group!(benches, event_path);
+criterion_main!(benches);
@@ -0,0 +1,86 @@
+use crate::Injector;
+use evdev_rs::enums::{EventCode, EV_ABS};
+use evdev_rs::{AbsInfo, DeviceWrapper, UInputDevice};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct AxisRange {
+    minimum: i32,
+    maximum: i32,
+}
+
+impl From<&AbsInfo> for AxisRange {
+    fn from(info: &AbsInfo) -> Self {
+        let AbsInfo {
+            minimum, maximum, ..
+        } = *info;
+        Self { minimum, maximum }
+    }
+}
+
+impl AxisRange {
+    fn denormalize(&self, value: f64) -> i32 {
+        let Self { minimum, maximum } = *self;
+        let fraction = (value.clamp(-1.0, 1.0) + 1.0) / 2.0;
+        (f64::from(minimum) + fraction * (f64::from(maximum) - f64::from(minimum))).round() as i32
+    }
+}
+
+/// Injects absolute axis values given in [-1.0, 1.0], converting them to the range each axis was
+/// configured with on the virtual device.
+pub struct AbsInjector<U = UInputDevice> {
+    uinput: U,
+    axes: HashMap<EV_ABS, AxisRange>,
+}
+
+impl<U: Injector> AbsInjector<U> {
+    pub fn new(uinput: U, axes: impl IntoIterator<Item = (EV_ABS, AbsInfo)>) -> Self {
+        Self {
+            uinput,
+            axes: axes
+                .into_iter()
+                .map(|(abs, info)| (abs, AxisRange::from(&info)))
+                .collect(),
+        }
+    }
+
+    /// Takes the axis ranges from the device `uinput` was created from.
+    pub fn from_template<D: DeviceWrapper>(uinput: U, template: &D) -> Self {
+        let axes = EventCode::EV_ABS(EV_ABS::ABS_X)
+            .iter()
+            .take_while(|code| matches!(code, EventCode::EV_ABS(_)))
+            .filter_map(|code| match code {
+                EventCode::EV_ABS(abs) => template.abs_info(&code).map(|info| (abs, info)),
+                _ => None,
+            });
+        Self::new(uinput, axes)
+    }
+
+    pub fn uinput(&self) -> &U {
+        &self.uinput
+    }
+
+    fn axis_value(&self, abs: EV_ABS, value: f64) -> std::io::Result<(EventCode, i32)> {
+        let range = self.axes.get(&abs).ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("{:?} is not configured on this device", abs),
+            )
+        })?;
+        Ok((EventCode::EV_ABS(abs), range.denormalize(value)))
+    }
+
+    /// Sets one axis, `value` being clamped to [-1.0, 1.0].
+    pub fn set_axis(&self, abs: EV_ABS, value: f64) -> std::io::Result<()> {
+        self.set_axes(&[(abs, value)])
+    }
+
+    /// Sets several axes in a single frame.
+    pub fn set_axes(&self, values: &[(EV_ABS, f64)]) -> std::io::Result<()> {
+        let events = values
+            .iter()
+            .map(|(abs, value)| self.axis_value(*abs, *value))
+            .collect::<std::io::Result<Vec<_>>>()?;
+        self.uinput.emit(&events)
+    }
+}
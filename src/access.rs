@@ -0,0 +1,196 @@
+use crate::{Processor, ZERO_TIME};
+use evdev_rs::enums::{EventCode, EV_KEY, EV_SYN};
+use evdev_rs::InputEvent;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::{Duration, Instant};
+
+/// Only registers keys held for at least `delay`, so that brief accidental presses are ignored.
+/// Keys released earlier are dropped altogether, as are their repeats while pending. Keys
+/// already held when the processor starts are released as usual.
+#[derive(Debug)]
+pub struct SlowKeys {
+    delay: Duration,
+    pending: HashMap<EV_KEY, Instant>,
+    accepted: HashSet<EV_KEY>,
+}
+
+impl SlowKeys {
+    pub fn new(delay: Duration) -> Self {
+        Self {
+            delay,
+            pending: HashMap::new(),
+            accepted: HashSet::new(),
+        }
+    }
+}
+
+impl Processor for SlowKeys {
+    type Output = InputEvent;
+
+    fn process(&mut self, input: InputEvent, now: Instant, out: &mut VecDeque<InputEvent>) {
+        let key = match input.event_code {
+            EventCode::EV_KEY(key) => key,
+            _ => return out.push_back(input),
+        };
+        match input.value {
+            1 if !self.accepted.contains(&key) => {
+                let _: Option<Instant> = self.pending.insert(key, now + self.delay);
+            }
+            // Keys seen pressed only now were pressed before the processor started, e.g. held
+            // while grabbing, and are forwarded so that they don't stay down.
+            0 => {
+                if self.pending.remove(&key).is_none() {
+                    let _: bool = self.accepted.remove(&key);
+                    out.push_back(input);
+                }
+            }
+            _ => {
+                if !self.pending.contains_key(&key) {
+                    out.push_back(input);
+                }
+            }
+        }
+    }
+
+    fn deadline(&self) -> Option<Instant> {
+        self.pending.values().min().copied()
+    }
+
+    fn timeout(&mut self, now: Instant, out: &mut VecDeque<InputEvent>) {
+        let due = self
+            .pending
+            .iter()
+            .filter(|(_, deadline)| **deadline <= now)
+            .map(|(key, _)| *key)
+            .collect::<Vec<_>>();
+        if due.is_empty() {
+            return;
+        }
+        for key in due {
+            let _: Option<Instant> = self.pending.remove(&key);
+            let _: bool = self.accepted.insert(key);
+            out.push_back(InputEvent {
+                time: ZERO_TIME,
+                event_code: EventCode::EV_KEY(key),
+                value: 1,
+            });
+        }
+        out.push_back(InputEvent {
+            time: ZERO_TIME,
+            event_code: EventCode::EV_SYN(EV_SYN::SYN_REPORT),
+            value: 0,
+        });
+    }
+}
+
+/// Ignores presses of a key within `window` of its last release, for users who unintentionally
+/// press keys twice. An ignored press's repeats and release are dropped too.
+///
+/// Unlike `Debounce`, which smooths over switch chatter lasting a few milliseconds, the window is
+/// typically hundreds of milliseconds and only applies to presses.
+#[derive(Debug)]
+pub struct BounceKeys {
+    window: Duration,
+    released: HashMap<EV_KEY, Instant>,
+    ignored: HashSet<EV_KEY>,
+}
+
+impl BounceKeys {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            released: HashMap::new(),
+            ignored: HashSet::new(),
+        }
+    }
+}
+
+impl Processor for BounceKeys {
+    type Output = InputEvent;
+
+    fn process(&mut self, input: InputEvent, now: Instant, out: &mut VecDeque<InputEvent>) {
+        let key = match input.event_code {
+            EventCode::EV_KEY(key) => key,
+            _ => return out.push_back(input),
+        };
+        match input.value {
+            1 => {
+                let bounced = self
+                    .released
+                    .get(&key)
+                    .is_some_and(|released| now.saturating_duration_since(*released) < self.window);
+                if bounced {
+                    let _: bool = self.ignored.insert(key);
+                } else {
+                    out.push_back(input);
+                }
+            }
+            0 => {
+                if !self.ignored.remove(&key) {
+                    let _: Option<Instant> = self.released.insert(key, now);
+                    out.push_back(input);
+                }
+            }
+            _ => {
+                if !self.ignored.contains(&key) {
+                    out.push_back(input);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{key, keys, run};
+    use evdev_rs::enums::EV_KEY::{KEY_A, KEY_B};
+
+    const DELAY: Duration = Duration::from_millis(50);
+
+    #[test]
+    fn slow_keys_drops_brief_presses() {
+        let output = run(
+            vec![key(0, KEY_A, 1), key(10, KEY_A, 0)],
+            SlowKeys::new(DELAY),
+        );
+        assert_eq!(keys(&output), vec![]);
+    }
+
+    #[test]
+    fn slow_keys_accepts_held_keys() {
+        let output = run(
+            vec![key(0, KEY_A, 1), key(150, KEY_A, 2), key(200, KEY_A, 0)],
+            SlowKeys::new(DELAY),
+        );
+        assert_eq!(keys(&output), vec![(KEY_A, 1), (KEY_A, 2), (KEY_A, 0)]);
+    }
+
+    #[test]
+    fn slow_keys_releases_keys_held_at_start() {
+        let output = run(
+            vec![key(0, KEY_B, 2), key(10, KEY_B, 0)],
+            SlowKeys::new(DELAY),
+        );
+        assert_eq!(keys(&output), vec![(KEY_B, 2), (KEY_B, 0)]);
+    }
+
+    #[test]
+    fn bounce_keys_ignores_quick_second_press() {
+        let output = run(
+            vec![
+                key(0, KEY_A, 1),
+                key(10, KEY_A, 0),
+                key(20, KEY_A, 1),
+                key(30, KEY_A, 0),
+                key(200, KEY_A, 1),
+                key(210, KEY_A, 0),
+            ],
+            BounceKeys::new(DELAY),
+        );
+        assert_eq!(
+            keys(&output),
+            vec![(KEY_A, 1), (KEY_A, 0), (KEY_A, 1), (KEY_A, 0)]
+        );
+    }
+}
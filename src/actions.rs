@@ -0,0 +1,295 @@
+//! Actions bound to hotkeys: injecting keys, running programs and shell commands, calling D-Bus
+//! methods or anything else async, as the foundation of a hotkey daemon.
+//!
+//! ```no_run
+//! # use evdev_utils::actions::{Action, ActionRunner, Hotkeys};
+//! # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+//! let keyboard = evdev_utils::AsyncDevice::new("/dev/input/event0")?;
+//! let uinput = evdev_utils::VirtualDeviceBuilder::new().keyboard().build()?;
+//! Hotkeys::new()
+//!     .bind("meta+enter".parse()?, Action::RunCommand(vec!["foot".into()]))
+//!     .bind("meta+p".parse()?, Action::Shell("grim - | wl-copy".into()))
+//!     .run(keyboard, ActionRunner::new(uinput))
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::remap::KeyCombo;
+use crate::{DeviceError, EventSource, Injector};
+use evdev_rs::enums::{EventCode, EV_KEY};
+use futures::future::{self, Either, LocalBoxFuture};
+use futures::stream::FuturesUnordered;
+use futures::{FutureExt as _, StreamExt as _, TryStreamExt as _};
+use std::collections::HashSet;
+use std::fmt;
+use std::rc::Rc;
+use thiserror::Error;
+
+pub type ActionError = Box<dyn std::error::Error + Send + Sync>;
+
+type CustomFn = dyn Fn() -> LocalBoxFuture<'static, Result<(), ActionError>>;
+
+/// A D-Bus method call. Arguments are all passed as strings.
+#[cfg(feature = "dbus")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DBusCall {
+    /// Whether to call on the system bus rather than the session bus.
+    pub system: bool,
+    pub destination: String,
+    pub path: String,
+    pub interface: String,
+    pub method: String,
+    pub args: Vec<String>,
+}
+
+/// What a binding does.
+#[derive(Clone)]
+pub enum Action {
+    /// Taps each chord in turn.
+    InjectKeys(Vec<Vec<EV_KEY>>),
+    /// Runs a program with arguments, without a shell. The program is looked up in `PATH`.
+    RunCommand(Vec<String>),
+    /// Runs a command line with `sh -c`.
+    Shell(String),
+    #[cfg(feature = "dbus")]
+    DBusCall(DBusCall),
+    /// Runs a future created anew each time, see `Action::custom`.
+    Custom(Rc<CustomFn>),
+}
+
+impl Action {
+    pub fn custom<F, Fut>(f: F) -> Self
+    where
+        F: Fn() -> Fut + 'static,
+        Fut: std::future::Future<Output = Result<(), ActionError>> + 'static,
+    {
+        Action::Custom(Rc::new(move || f().boxed_local()))
+    }
+}
+
+impl fmt::Debug for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Action::InjectKeys(chords) => f.debug_tuple("InjectKeys").field(chords).finish(),
+            Action::RunCommand(command) => f.debug_tuple("RunCommand").field(command).finish(),
+            Action::Shell(command) => f.debug_tuple("Shell").field(command).finish(),
+            #[cfg(feature = "dbus")]
+            Action::DBusCall(call) => f.debug_tuple("DBusCall").field(call).finish(),
+            Action::Custom(_) => f.write_str("Custom(..)"),
+        }
+    }
+}
+
+fn command(program: &str, args: &[String]) -> LocalBoxFuture<'static, Result<(), ActionError>> {
+    let spawned = async_process::Command::new(program)
+        .args(args)
+        .stdin(async_process::Stdio::null())
+        .spawn();
+    async move {
+        let status = spawned?.status().await?;
+        if !status.success() {
+            return Err(format!("exited with {}", status).into());
+        }
+        Ok(())
+    }
+    .boxed_local()
+}
+
+#[cfg(feature = "dbus")]
+async fn dbus_call(call: DBusCall) -> Result<(), ActionError> {
+    let connection = if call.system {
+        zbus::Connection::system().await?
+    } else {
+        zbus::Connection::session().await?
+    };
+    let destination = call.destination.as_str();
+    let (path, interface, method) = (call.path.as_str(), call.interface.as_str(), &call.method);
+    let _: zbus::Message = if call.args.is_empty() {
+        connection
+            .call_method(
+                Some(destination),
+                path,
+                Some(interface),
+                method.as_str(),
+                &(),
+            )
+            .await?
+    } else {
+        let body = call
+            .args
+            .iter()
+            .fold(zbus::zvariant::StructureBuilder::new(), |body, arg| {
+                body.add_field(arg.clone())
+            })
+            .build()?;
+        connection
+            .call_method(
+                Some(destination),
+                path,
+                Some(interface),
+                method.as_str(),
+                &body,
+            )
+            .await?
+    };
+    Ok(())
+}
+
+/// Performs actions, injecting keys through `injector`. Everything else runs in the background
+/// while `run_pending` is awaited, so the event loop isn't held up; failures are logged.
+pub struct ActionRunner<U> {
+    injector: U,
+    pending: FuturesUnordered<LocalBoxFuture<'static, ()>>,
+}
+
+impl<U: Injector> ActionRunner<U> {
+    pub fn new(injector: U) -> Self {
+        Self {
+            injector,
+            pending: FuturesUnordered::new(),
+        }
+    }
+
+    pub fn injector(&self) -> &U {
+        &self.injector
+    }
+
+    /// Whether no action is running in the background.
+    pub fn is_idle(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Starts `action`. Only injecting keys can fail here.
+    pub fn execute(&mut self, action: &Action) -> std::io::Result<()> {
+        let (description, future) = match action {
+            Action::InjectKeys(chords) => {
+                for keys in chords {
+                    let press = keys.iter().map(|key| (EventCode::EV_KEY(*key), 1));
+                    self.injector.emit(&press.collect::<Vec<_>>())?;
+                    let release = keys.iter().rev().map(|key| (EventCode::EV_KEY(*key), 0));
+                    self.injector.emit(&release.collect::<Vec<_>>())?;
+                }
+                return Ok(());
+            }
+            Action::RunCommand(argv) => match argv.split_first() {
+                Some((program, args)) => (argv.join(" "), command(program, args)),
+                None => return Ok(()),
+            },
+            Action::Shell(line) => (line.clone(), command("sh", &["-c".into(), line.clone()])),
+            #[cfg(feature = "dbus")]
+            Action::DBusCall(call) => (
+                format!("{}.{}", call.interface, call.method),
+                dbus_call(call.clone()).boxed_local(),
+            ),
+            Action::Custom(f) => ("custom action".to_string(), f()),
+        };
+        log::info!("running {}", description);
+        self.pending.push(
+            future
+                .map(move |result| {
+                    if let Err(e) = result {
+                        log::warn!("{} failed: {}", description, e);
+                    }
+                })
+                .boxed_local(),
+        );
+        Ok(())
+    }
+
+    /// Drives the actions running in the background, completing whenever one finishes. Never
+    /// completes while none are running.
+    pub async fn run_pending(&mut self) {
+        match self.pending.next().await {
+            Some(()) => {}
+            None => future::pending().await,
+        }
+    }
+
+    /// Waits for all the actions running in the background to finish.
+    pub async fn finish(&mut self) {
+        while let Some(()) = self.pending.next().await {}
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum HotkeyError {
+    #[error("error when reading an event")]
+    ReadEvent(#[source] DeviceError),
+    #[error("failed to inject event")]
+    Inject(#[source] std::io::Error),
+}
+
+/// Performs actions when key combinations are pressed. The device isn't grabbed, so the keys
+/// still reach applications too.
+#[derive(Debug, Default)]
+pub struct Hotkeys {
+    bindings: Vec<(HashSet<EV_KEY>, Action)>,
+    held: HashSet<EV_KEY>,
+}
+
+impl Hotkeys {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Performs `action` when exactly the keys of `combo` are held, on the press of the last.
+    pub fn bind(mut self, combo: KeyCombo, action: Action) -> Self {
+        self.bindings.push((combo.0.into_iter().collect(), action));
+        self
+    }
+
+    /// The action bound to the keys held after a press of `key`, if any.
+    fn press(&mut self, key: EV_KEY) -> Option<&Action> {
+        let _: bool = self.held.insert(key);
+        let held = &self.held;
+        self.bindings
+            .iter()
+            .find(|(keys, _)| keys == held)
+            .map(|(_, action)| action)
+    }
+
+    /// Listens to `device` until its event stream ends, performing actions with `runner`, then
+    /// waits for those still running.
+    pub async fn run<D, U>(
+        mut self,
+        mut device: D,
+        mut runner: ActionRunner<U>,
+    ) -> Result<(), HotkeyError>
+    where
+        D: EventSource,
+        U: Injector,
+    {
+        loop {
+            let event = {
+                let pending = std::pin::pin!(runner.run_pending());
+                match future::select(device.try_next(), pending).await {
+                    Either::Left((event, _)) => event.map_err(HotkeyError::ReadEvent)?,
+                    Either::Right(((), _)) => continue,
+                }
+            };
+            let event = match event {
+                Some(event) => event,
+                None => {
+                    runner.finish().await;
+                    return Ok(());
+                }
+            };
+            let key = match event.event_code {
+                EventCode::EV_KEY(key) => key,
+                _ => continue,
+            };
+            match event.value {
+                1 => {
+                    if let Some(action) = self.press(key) {
+                        runner.execute(action).map_err(HotkeyError::Inject)?;
+                    }
+                }
+                0 => {
+                    let _: bool = self.held.remove(&key);
+                }
+                _ => {}
+            }
+        }
+    }
+}
@@ -0,0 +1,63 @@
+use crate::AsyncDevice;
+use std::os::unix::io::RawFd;
+use thiserror::Error;
+
+// The first file descriptor passed, after stdin, stdout and stderr.
+const LISTEN_FDS_START: RawFd = 3;
+
+#[derive(Error, Debug)]
+pub enum ActivationError {
+    #[error("invalid {0} in the environment")]
+    Environment(&'static str),
+    #[error("failed to initialize passed file descriptor {fd} ({name})")]
+    Init {
+        fd: RawFd,
+        name: String,
+        #[source]
+        source: std::io::Error,
+    },
+}
+
+fn parse_var(var: &'static str) -> Result<Option<u32>, ActivationError> {
+    match std::env::var(var) {
+        Ok(value) => value
+            .parse()
+            .map(Some)
+            .map_err(|_| ActivationError::Environment(var)),
+        Err(std::env::VarError::NotPresent) => Ok(None),
+        Err(std::env::VarError::NotUnicode(_)) => Err(ActivationError::Environment(var)),
+    }
+}
+
+/// Takes the event devices passed by systemd through `LISTEN_FDS`, e.g. with
+/// `OpenFile=/dev/input/by-id/...-event-kbd:keyboard:read-only` in the unit, so the service itself
+/// needs no access to /dev/input. Each comes with its name from `LISTEN_FDNAMES`, `unknown` if
+/// unnamed.
+///
+/// Returns nothing if the process wasn't passed any. The variables are removed from the
+/// environment so that children don't take the descriptors too; later calls return nothing.
+pub fn activated_devices() -> Result<Vec<(String, AsyncDevice)>, ActivationError> {
+    let pid = parse_var("LISTEN_PID")?;
+    let count = parse_var("LISTEN_FDS")?;
+    let names = std::env::var("LISTEN_FDNAMES").ok();
+    for var in &["LISTEN_PID", "LISTEN_FDS", "LISTEN_FDNAMES"] {
+        std::env::remove_var(var);
+    }
+    let count = match (pid, count) {
+        (Some(pid), Some(count)) if pid == std::process::id() => count as RawFd,
+        // Meant for another process, e.g. the parent that exec'd this one.
+        _ => return Ok(Vec::new()),
+    };
+    let mut names = names.iter().flat_map(|names| names.split(':'));
+    (LISTEN_FDS_START..LISTEN_FDS_START + count)
+        .map(|fd| {
+            let name = names.next().unwrap_or("unknown").to_string();
+            // The descriptors are inherited without FD_CLOEXEC.
+            let _: libc::c_int = unsafe { libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) };
+            match unsafe { AsyncDevice::from_raw_fd(fd) } {
+                Ok(device) => Ok((name, device)),
+                Err(source) => Err(ActivationError::Init { fd, name, source }),
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,211 @@
+use crate::{
+    AsyncDevice, DeviceError, EventStreamExt as _, Injector as _, Processor, VirtualDeviceBuilder,
+    ZERO_TIME,
+};
+use evdev_rs::enums::{EventCode, EV_KEY, EV_SYN};
+use evdev_rs::InputEvent;
+use futures::TryStreamExt as _;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum AutoClickerError {
+    #[error("failed to create uinput device")]
+    CreateUInput(#[source] std::io::Error),
+    #[error("error when reading an event")]
+    ReadEvent(#[source] DeviceError),
+    #[error("failed to inject event")]
+    Inject(#[source] std::io::Error),
+}
+
+/// Clicks a mouse button at a steady rate while turned on by a press of the toggle key. Each
+/// click can be moved by a random jitter so the rhythm looks less mechanical, and clicks can come
+/// in bursts separated by pauses. Only the clicks are output; the input device is just listened
+/// to for the toggle key.
+#[derive(Debug)]
+pub struct AutoClicker {
+    toggle: EV_KEY,
+    button: EV_KEY,
+    interval: Duration,
+    hold: Duration,
+    jitter: Duration,
+    burst: Option<(usize, Duration)>,
+    rng: fastrand::Rng,
+    active: bool,
+    down: bool,
+    clicks: usize,
+    last_press: Option<Instant>,
+    next: Option<Instant>,
+}
+
+impl AutoClicker {
+    /// Clicks the left button `clicks_per_second` times per second, holding it for 10ms. The hold
+    /// is cut to half the interval at faster rates, so the button is released between clicks.
+    /// `clicks_per_second` must be positive; panics otherwise.
+    pub fn new(toggle: EV_KEY, clicks_per_second: f64) -> Self {
+        Self {
+            toggle,
+            button: EV_KEY::BTN_LEFT,
+            interval: Duration::from_secs_f64(1.0 / clicks_per_second),
+            hold: Duration::from_millis(10),
+            jitter: Duration::ZERO,
+            burst: None,
+            rng: fastrand::Rng::new(),
+            active: false,
+            down: false,
+            clicks: 0,
+            last_press: None,
+            next: None,
+        }
+    }
+
+    pub fn button(mut self, button: EV_KEY) -> Self {
+        self.button = button;
+        self
+    }
+
+    /// How long the button stays down in each click, at most half the interval.
+    pub fn hold(mut self, hold: Duration) -> Self {
+        self.hold = hold;
+        self
+    }
+
+    /// Moves each click by up to `jitter` earlier or later, uniformly at random.
+    pub fn jitter(mut self, jitter: Duration) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Pauses for `pause` after every `clicks` clicks.
+    pub fn burst(mut self, clicks: usize, pause: Duration) -> Self {
+        self.burst = Some((clicks.max(1), pause));
+        self
+    }
+
+    /// Seeds the jitter, for reproducible patterns.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.rng = fastrand::Rng::with_seed(seed);
+        self
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// Listens to `device` for the toggle key, without grabbing it, and clicks through a new
+    /// virtual mouse until the device's event stream ends.
+    pub async fn run(self, device: AsyncDevice) -> Result<(), AutoClickerError> {
+        let mouse = VirtualDeviceBuilder::new()
+            .name("evdev-utils autoclicker")
+            .mouse()
+            .build()
+            .map_err(AutoClickerError::CreateUInput)?;
+        let mut events = device.process(self);
+        while let Some(InputEvent {
+            time,
+            event_code,
+            value,
+        }) = events
+            .try_next()
+            .await
+            .map_err(AutoClickerError::ReadEvent)?
+        {
+            mouse
+                .inject_event_at(event_code, value, time)
+                .map_err(AutoClickerError::Inject)?;
+        }
+        Ok(())
+    }
+
+    fn click(&self, value: i32, out: &mut VecDeque<InputEvent>) {
+        out.push_back(InputEvent {
+            time: ZERO_TIME,
+            event_code: EventCode::EV_KEY(self.button),
+            value,
+        });
+        out.push_back(InputEvent {
+            time: ZERO_TIME,
+            event_code: EventCode::EV_SYN(EV_SYN::SYN_REPORT),
+            value: 0,
+        });
+    }
+
+    /// When the press after one at `pressed` is due, released at `released`.
+    fn next_press(&mut self, pressed: Instant, released: Instant) -> Instant {
+        let mut next = pressed + self.interval;
+        if let Some((clicks, pause)) = self.burst {
+            if self.clicks >= clicks {
+                self.clicks = 0;
+                next += pause;
+            }
+        }
+        if !self.jitter.is_zero() {
+            let offset = self.jitter.mul_f64(self.rng.f64());
+            next = if self.rng.bool() {
+                next + offset
+            } else {
+                next.checked_sub(offset).unwrap_or(next)
+            };
+        }
+        next.max(released)
+    }
+}
+
+impl Processor for AutoClicker {
+    type Output = InputEvent;
+
+    fn process(&mut self, input: InputEvent, now: Instant, out: &mut VecDeque<InputEvent>) {
+        if input.event_code != EventCode::EV_KEY(self.toggle) || input.value != 1 {
+            return;
+        }
+        self.active = !self.active;
+        if self.active {
+            self.clicks = 0;
+            self.next = Some(now);
+        } else {
+            if self.down {
+                self.down = false;
+                self.click(0, out);
+            }
+            self.next = None;
+        }
+    }
+
+    fn deadline(&self) -> Option<Instant> {
+        self.next
+    }
+
+    fn timeout(&mut self, now: Instant, out: &mut VecDeque<InputEvent>) {
+        self.down = !self.down;
+        self.click(self.down.into(), out);
+        if self.down {
+            self.last_press = Some(now);
+            self.next = Some(now + self.hold.min(self.interval / 2));
+        } else {
+            self.clicks += 1;
+            let pressed = self.last_press.unwrap_or(now);
+            self.next = Some(self.next_press(pressed, now));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::key;
+
+    #[test]
+    fn releases_between_clicks_above_hold_rate() {
+        // At 200 clicks per second the 10ms hold would take up the whole 5ms interval.
+        let mut clicker = AutoClicker::new(EV_KEY::KEY_F8, 200.0);
+        let mut out = VecDeque::new();
+        let start = Instant::now();
+        clicker.process(key(0, EV_KEY::KEY_F8, 1), start, &mut out);
+        clicker.timeout(start, &mut out);
+        let released = start + Duration::from_micros(2500);
+        assert_eq!(clicker.deadline(), Some(released));
+        clicker.timeout(released, &mut out);
+        assert_eq!(clicker.deadline(), Some(start + Duration::from_millis(5)));
+    }
+}
@@ -0,0 +1,292 @@
+use crate::Processor;
+use evdev_rs::enums::{EventCode, EV_ABS, EV_SYN};
+use evdev_rs::{DeviceWrapper, InputEvent};
+use std::collections::{HashMap, VecDeque};
+use std::time::Instant;
+
+/// How far from rest, as a fraction of the full range, input is ignored.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Deadzone {
+    /// Applied to each axis separately, which snaps a stick to the axes near them.
+    Axial(f64),
+    /// Applied to the distance of a stick from its center.
+    Radial(f64),
+}
+
+/// A response curve over normalized input magnitudes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Curve {
+    deadzone: Deadzone,
+    anti_deadzone: f64,
+    exponent: f64,
+}
+
+impl Default for Curve {
+    fn default() -> Self {
+        Self {
+            deadzone: Deadzone::Radial(0.0),
+            anti_deadzone: 0.0,
+            exponent: 1.0,
+        }
+    }
+}
+
+impl Curve {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn deadzone(mut self, deadzone: Deadzone) -> Self {
+        self.deadzone = deadzone;
+        self
+    }
+
+    /// The output magnitude just outside the deadzone, to overcome a game's own deadzone.
+    pub fn anti_deadzone(mut self, anti_deadzone: f64) -> Self {
+        self.anti_deadzone = anti_deadzone;
+        self
+    }
+
+    /// Values above 1 give finer control near rest, values below 1 near the edges.
+    pub fn exponent(mut self, exponent: f64) -> Self {
+        self.exponent = exponent;
+        self
+    }
+
+    fn deadzone_size(&self) -> f64 {
+        match self.deadzone {
+            Deadzone::Axial(size) | Deadzone::Radial(size) => size,
+        }
+    }
+
+    fn map(&self, magnitude: f64, deadzone: f64) -> f64 {
+        let magnitude = magnitude.min(1.0);
+        if magnitude <= deadzone {
+            return 0.0;
+        }
+        let t = ((magnitude - deadzone) / (1.0 - deadzone)).powf(self.exponent);
+        self.anti_deadzone + (1.0 - self.anti_deadzone) * t
+    }
+
+    pub(crate) fn apply_axis(&self, value: f64) -> f64 {
+        self.map(value.abs(), self.deadzone_size()).copysign(value)
+    }
+
+    pub(crate) fn apply_stick(&self, (x, y): (f64, f64)) -> (f64, f64) {
+        match self.deadzone {
+            Deadzone::Axial(deadzone) => (
+                self.map(x.abs(), deadzone).copysign(x),
+                self.map(y.abs(), deadzone).copysign(y),
+            ),
+            Deadzone::Radial(deadzone) => {
+                let magnitude = x.hypot(y);
+                if magnitude == 0.0 {
+                    return (0.0, 0.0);
+                }
+                let scale = self.map(magnitude, deadzone) / magnitude;
+                (x * scale, y * scale)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    /// Rests in the middle of its range.
+    Centered,
+    /// Rests at the minimum of its range, like a trigger.
+    Trigger,
+}
+
+#[derive(Debug)]
+struct Axis {
+    minimum: i32,
+    maximum: i32,
+    kind: Kind,
+    raw: i32,
+    output: i32,
+}
+
+impl Axis {
+    fn normalize(&self, value: i32) -> f64 {
+        let (minimum, maximum) = (f64::from(self.minimum), f64::from(self.maximum));
+        let value = f64::from(value);
+        match self.kind {
+            Kind::Centered => {
+                (2.0 * (value - minimum) / (maximum - minimum) - 1.0).clamp(-1.0, 1.0)
+            }
+            Kind::Trigger => ((value - minimum) / (maximum - minimum)).clamp(0.0, 1.0),
+        }
+    }
+
+    fn denormalize(&self, value: f64) -> i32 {
+        let (minimum, maximum) = (f64::from(self.minimum), f64::from(self.maximum));
+        let value = match self.kind {
+            Kind::Centered => minimum + (value + 1.0) / 2.0 * (maximum - minimum),
+            Kind::Trigger => minimum + value * (maximum - minimum),
+        };
+        value.round() as i32
+    }
+}
+
+#[derive(Debug)]
+enum Group {
+    Single(EV_ABS, Curve),
+    Stick(EV_ABS, EV_ABS, Curve),
+}
+
+/// Applies deadzones and response curves to configured EV_ABS axes, normalized against the
+/// device's absinfo, and emits the results in the device's own units. Unconfigured axes and all
+/// other events pass through unchanged.
+#[derive(Debug)]
+pub struct AxisProcessor {
+    ranges: HashMap<EV_ABS, (i32, i32, i32)>,
+    axes: HashMap<EV_ABS, Axis>,
+    groups: Vec<Group>,
+    dirty: Vec<bool>,
+    frame: Vec<InputEvent>,
+}
+
+impl AxisProcessor {
+    /// Takes the axis ranges and current values from `device`.
+    pub fn new<D: DeviceWrapper + ?Sized>(device: &D) -> Self {
+        let ranges = EventCode::EV_ABS(EV_ABS::ABS_X)
+            .iter()
+            .take_while(|code| matches!(code, EventCode::EV_ABS(_)))
+            .filter_map(|code| match code {
+                EventCode::EV_ABS(abs) => device
+                    .abs_info(&code)
+                    .filter(|info| info.maximum > info.minimum)
+                    .map(|info| (abs, (info.minimum, info.maximum, info.value))),
+                _ => None,
+            })
+            .collect();
+        Self {
+            ranges,
+            axes: HashMap::new(),
+            groups: Vec::new(),
+            dirty: Vec::new(),
+            frame: Vec::new(),
+        }
+    }
+
+    fn add_axis(&mut self, abs: EV_ABS, kind: Kind) -> bool {
+        match self.ranges.get(&abs) {
+            Some(&(minimum, maximum, value)) => {
+                let _: Option<Axis> = self.axes.insert(
+                    abs,
+                    Axis {
+                        minimum,
+                        maximum,
+                        kind,
+                        raw: value,
+                        output: value,
+                    },
+                );
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn add_group(&mut self, group: Group) {
+        self.groups.push(group);
+        self.dirty.push(false);
+    }
+
+    /// Processes a single axis resting in the middle of its range. Axes the device doesn't have
+    /// are ignored.
+    pub fn axis(mut self, abs: EV_ABS, curve: Curve) -> Self {
+        if self.add_axis(abs, Kind::Centered) {
+            self.add_group(Group::Single(abs, curve));
+        }
+        self
+    }
+
+    /// Processes an axis resting at its minimum, e.g. an analog trigger.
+    pub fn trigger(mut self, abs: EV_ABS, curve: Curve) -> Self {
+        if self.add_axis(abs, Kind::Trigger) {
+            self.add_group(Group::Single(abs, curve));
+        }
+        self
+    }
+
+    /// Processes a pair of axes together, which radial deadzones require.
+    pub fn stick(mut self, x: EV_ABS, y: EV_ABS, curve: Curve) -> Self {
+        if self.add_axis(x, Kind::Centered) && self.add_axis(y, Kind::Centered) {
+            self.add_group(Group::Stick(x, y, curve));
+        }
+        self
+    }
+
+    fn group_of(&self, abs: EV_ABS) -> Option<usize> {
+        self.groups.iter().position(|group| match *group {
+            Group::Single(axis, _) => axis == abs,
+            Group::Stick(x, y, _) => x == abs || y == abs,
+        })
+    }
+
+    fn update(&mut self, index: usize) -> Vec<(EV_ABS, i32)> {
+        let outputs = match self.groups[index] {
+            Group::Single(abs, curve) => {
+                let axis = &self.axes[&abs];
+                let value = curve.apply_axis(axis.normalize(axis.raw));
+                vec![(abs, axis.denormalize(value))]
+            }
+            Group::Stick(x, y, curve) => {
+                let (x_axis, y_axis) = (&self.axes[&x], &self.axes[&y]);
+                let (dx, dy) =
+                    curve.apply_stick((x_axis.normalize(x_axis.raw), y_axis.normalize(y_axis.raw)));
+                vec![(x, x_axis.denormalize(dx)), (y, y_axis.denormalize(dy))]
+            }
+        };
+        outputs
+            .into_iter()
+            .filter(|(abs, value)| {
+                let axis = self.axes.get_mut(abs).expect("grouped axes are configured");
+                let changed = axis.output != *value;
+                axis.output = *value;
+                changed
+            })
+            .collect()
+    }
+}
+
+impl Processor for AxisProcessor {
+    type Output = InputEvent;
+
+    fn process(&mut self, event: InputEvent, _now: Instant, out: &mut VecDeque<InputEvent>) {
+        match event.event_code {
+            EventCode::EV_ABS(abs) => match self.group_of(abs) {
+                Some(index) => {
+                    if let Some(axis) = self.axes.get_mut(&abs) {
+                        axis.raw = event.value;
+                    }
+                    self.dirty[index] = true;
+                }
+                None => self.frame.push(event),
+            },
+            EventCode::EV_SYN(EV_SYN::SYN_REPORT) => {
+                for index in 0..self.groups.len() {
+                    if !std::mem::replace(&mut self.dirty[index], false) {
+                        continue;
+                    }
+                    for (abs, value) in self.update(index) {
+                        self.frame.push(InputEvent {
+                            time: event.time,
+                            event_code: EventCode::EV_ABS(abs),
+                            value,
+                        });
+                    }
+                }
+                out.extend(self.frame.drain(..));
+                out.push_back(event);
+            }
+            _ => self.frame.push(event),
+        }
+    }
+
+    fn finish(&mut self, out: &mut VecDeque<InputEvent>) {
+        out.extend(self.frame.drain(..));
+    }
+}
@@ -0,0 +1,318 @@
+use clap::{Parser, Subcommand, ValueEnum};
+use evdev_rs::enums::{EventCode, EV_KEY};
+use evdev_utils::diagnostics::{RolloverTester, GAMING_COMBOS};
+use evdev_utils::remap::KeyCombo;
+use evdev_utils::{
+    AsyncDevice, AutoClicker, CodeName, EventStreamExt as _, Injector as _, VirtualComboDevice,
+    DEFAULT_ESCAPE_KEYS,
+};
+use futures::TryStreamExt as _;
+use std::path::PathBuf;
+use std::time::Duration;
+
+#[derive(Parser)]
+#[command(version, about = "Inspect and drive evdev input devices")]
+struct Args {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// List readable input devices.
+    List,
+    /// Print events from a device as they arrive.
+    Watch {
+        path: PathBuf,
+        /// Grab the device so events are not delivered elsewhere, until both Ctrl keys and Esc
+        /// are held together.
+        #[arg(long)]
+        grab: bool,
+    },
+    /// Print the path of the first device of the given kind to be used.
+    Identify {
+        #[arg(value_enum, default_value = "keyboard")]
+        kind: Kind,
+        /// Identify the device that sends this key instead, e.g. KEY_POWER.
+        #[arg(long)]
+        key: Option<String>,
+    },
+    /// Inject events through a virtual keyboard and mouse, e.g. `inject KEY_A 1 KEY_A 0`.
+    Inject {
+        #[arg(required = true, num_args = 2.., value_names = ["CODE", "VALUE"])]
+        events: Vec<String>,
+        /// Delay between events in milliseconds.
+        #[arg(long, default_value_t = 10)]
+        delay: u64,
+    },
+    /// Measure the round trip from injecting through uinput to reading the event back.
+    Latency {
+        #[arg(long, default_value_t = 1000)]
+        iterations: usize,
+    },
+    /// Click a mouse button repeatedly while toggled on by a key on the given device.
+    Autoclick {
+        path: PathBuf,
+        /// The key turning clicking on and off.
+        #[arg(long, default_value = "KEY_F8")]
+        toggle: String,
+        #[arg(long, default_value = "BTN_LEFT")]
+        button: String,
+        #[arg(long, default_value_t = 10.0, value_parser = parse_rate)]
+        clicks_per_second: f64,
+        /// Random offset of each click of up to this many milliseconds either way.
+        #[arg(long, default_value_t = 0)]
+        jitter: u64,
+        /// Click in bursts of this many clicks.
+        #[arg(long)]
+        burst: Option<usize>,
+        /// Pause between bursts in milliseconds.
+        #[arg(long, default_value_t = 500)]
+        pause: u64,
+    },
+    /// Test which key combinations a keyboard registers while held together.
+    NkroTest {
+        path: PathBuf,
+        /// A combination to test instead of the defaults, e.g. `w+a+shift+space`. Repeatable.
+        #[arg(long)]
+        combo: Vec<KeyCombo>,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Kind {
+    Keyboard,
+    Mouse,
+    Gamepad,
+}
+
+type Error = Box<dyn std::error::Error>;
+
+fn parse_code(name: &str) -> Result<EventCode, Error> {
+    evdev_utils::parse_event_code(name).ok_or_else(|| format!("unknown event code {}", name).into())
+}
+
+fn parse_rate(rate: &str) -> Result<f64, String> {
+    match rate.parse::<f64>() {
+        Ok(rate) if rate.is_finite() && rate > 0.0 => Ok(rate),
+        Ok(_) => Err(format!("{} is not a positive finite rate", rate)),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn list() -> Result<(), Error> {
+    for (path, info) in evdev_utils::scan()? {
+        println!(
+            "{}\t{:04x}:{:04x}\t{}",
+            path.display(),
+            info.vendor,
+            info.product,
+            info.name
+        );
+    }
+    Ok(())
+}
+
+async fn watch(path: PathBuf, grab: bool) -> Result<(), Error> {
+    let mut device = AsyncDevice::new(path)?;
+    if grab {
+        device.grab(evdev_rs::GrabMode::Grab)?;
+    }
+    let info = device.info();
+    println!("{} ({:04x}:{:04x})", info.name, info.vendor, info.product);
+    if grab {
+        eprintln!(
+            "Hold {} to release the device.",
+            key_names(DEFAULT_ESCAPE_KEYS)
+        );
+    }
+    let mut events = device.escape_hatch();
+    while let Some(event) = events.try_next().await? {
+        let event_type = event
+            .event_type()
+            .map_or_else(|| "?".to_string(), |event_type| event_type.to_string());
+        println!(
+            "{}.{:06}\t{}\t{}\t{}",
+            event.time.tv_sec, event.time.tv_usec, event_type, event.event_code, event.value
+        );
+    }
+    Ok(())
+}
+
+async fn identify(kind: Kind, key: Option<String>) -> Result<(), Error> {
+    if let Some(key) = key {
+        let key = evdev_utils::parse_key(&key)?;
+        eprintln!("Press {:?} to identify its device.", key);
+        println!("{}", evdev_utils::identify_by_key(key).await?.display());
+        return Ok(());
+    }
+    let path = match kind {
+        Kind::Keyboard => {
+            eprintln!("Press a key on the keyboard to identify.");
+            evdev_utils::identify_keyboard().await?
+        }
+        Kind::Mouse => {
+            eprintln!("Move or click the mouse to identify.");
+            evdev_utils::identify_mouse().await?
+        }
+        Kind::Gamepad => {
+            eprintln!("Press a button or move a stick on the gamepad to identify.");
+            evdev_utils::identify_gamepad().await?
+        }
+    };
+    println!("{}", path.display());
+    Ok(())
+}
+
+async fn inject(events: Vec<String>, delay: Duration) -> Result<(), Error> {
+    if events.len() % 2 == 1 {
+        return Err("events must be given as CODE VALUE pairs".into());
+    }
+    let events = events
+        .chunks(2)
+        .map(|pair| Ok((parse_code(&pair[0])?, pair[1].parse::<i32>()?)))
+        .collect::<Result<Vec<_>, Error>>()?;
+    let uinput = VirtualComboDevice::new("evdev-utils inject")?;
+    // Give userspace a moment to pick up the new device before events arrive.
+    let _: std::time::Instant = async_io::Timer::after(Duration::from_millis(200)).await;
+    for (code, value) in events {
+        uinput.emit(&[(code, value)])?;
+        let _: std::time::Instant = async_io::Timer::after(delay).await;
+    }
+    Ok(())
+}
+
+async fn latency(iterations: usize) -> Result<(), Error> {
+    let stats = evdev_utils::latency::measure_uinput(iterations).await?;
+    let stat = |stat: Option<Duration>| stat.map(|stat| format!("{:?}", stat)).unwrap_or_default();
+    println!("min\t{}", stat(stats.min()));
+    println!("p50\t{}", stat(stats.p50()));
+    println!("p99\t{}", stat(stats.p99()));
+    println!("max\t{}", stat(stats.max()));
+    for (start, count) in stats.histogram(Duration::from_micros(10)) {
+        if count > 0 {
+            println!("{:?}\t{}", start, count);
+        }
+    }
+    Ok(())
+}
+
+fn key_names(keys: &[EV_KEY]) -> String {
+    keys.iter()
+        .map(|key| format!("{:#}", CodeName(&EventCode::EV_KEY(*key))))
+        .collect::<Vec<_>>()
+        .join("+")
+}
+
+async fn nkro_test(path: PathBuf, combos: Vec<KeyCombo>) -> Result<(), Error> {
+    let mut device = AsyncDevice::new(path)?;
+    device.grab(evdev_rs::GrabMode::Grab)?;
+    let combos = if combos.is_empty() {
+        GAMING_COMBOS
+            .iter()
+            .map(|combo| KeyCombo(combo.to_vec()))
+            .collect()
+    } else {
+        combos
+    };
+    eprintln!("Hold {} to stop.", key_names(DEFAULT_ESCAPE_KEYS));
+    let mut events = device.escape_hatch();
+    let mut tester = RolloverTester::new();
+    let mut blocked = 0;
+    for KeyCombo(combo) in &combos {
+        eprintln!("Hold {} together, then release.", key_names(combo));
+        let attempt = loop {
+            let event = match events.try_next().await? {
+                Some(event) => event,
+                None if events.triggered() => return Ok(()),
+                None => return Err("device closed".into()),
+            };
+            if let Some(attempt) = tester.handle(&event) {
+                break attempt;
+            }
+        };
+        let result = attempt.check(combo);
+        if result.is_blocked() {
+            blocked += 1;
+            print!(
+                "BLOCKED\t{}\tmissing {}",
+                key_names(combo),
+                key_names(&result.missing)
+            );
+        } else {
+            print!("OK\t{}", key_names(combo));
+        }
+        if !result.extra.is_empty() {
+            print!("\tghost {}", key_names(&result.extra));
+        }
+        println!();
+    }
+    println!("max rollover\t{}", tester.max_rollover());
+    println!("blocked\t{}/{}", blocked, combos.len());
+    Ok(())
+}
+
+async fn autoclick(
+    path: PathBuf,
+    toggle: &str,
+    button: &str,
+    clicks_per_second: f64,
+    jitter: Duration,
+    burst: Option<(usize, Duration)>,
+) -> Result<(), Error> {
+    let toggle = evdev_utils::parse_key(toggle)?;
+    let mut clicker = AutoClicker::new(toggle, clicks_per_second)
+        .button(evdev_utils::parse_key(button)?)
+        .jitter(jitter);
+    if let Some((clicks, pause)) = burst {
+        clicker = clicker.burst(clicks, pause);
+    }
+    eprintln!("Press {:?} to start and stop clicking.", toggle);
+    clicker.run(AsyncDevice::new(path)?).await?;
+    Ok(())
+}
+
+fn run() -> Result<(), Error> {
+    let Args { command } = Args::parse();
+    async_io::block_on(async {
+        match command {
+            Command::List => list(),
+            Command::Watch { path, grab } => watch(path, grab).await,
+            Command::Identify { kind, key } => identify(kind, key).await,
+            Command::Inject { events, delay } => inject(events, Duration::from_millis(delay)).await,
+            Command::Latency { iterations } => latency(iterations).await,
+            Command::Autoclick {
+                path,
+                toggle,
+                button,
+                clicks_per_second,
+                jitter,
+                burst,
+                pause,
+            } => {
+                autoclick(
+                    path,
+                    &toggle,
+                    &button,
+                    clicks_per_second,
+                    Duration::from_millis(jitter),
+                    burst.map(|clicks| (clicks, Duration::from_millis(pause))),
+                )
+                .await
+            }
+            Command::NkroTest { path, combo } => nkro_test(path, combo).await,
+        }
+    })
+}
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("error: {}", e);
+        let mut source = e.source();
+        while let Some(e) = source {
+            eprintln!("  caused by: {}", e);
+            source = e.source();
+        }
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,37 @@
+use crate::{read_event, DeviceError};
+use evdev_rs::InputEvent;
+use std::collections::VecDeque;
+
+/// The most events `AsyncDevice::read_burst` returns at once. The rest stay buffered in libevdev
+/// until the next call, so a consumer falling behind bounds the memory used rather than it
+/// growing with the backlog.
+pub const BURST_CAPACITY: usize = 1024;
+
+/// The ring buffer behind `AsyncDevice::read_burst`, reused between bursts.
+#[derive(Debug, Default)]
+pub(crate) struct BurstBuffer {
+    pub(crate) events: VecDeque<InputEvent>,
+    // An error hit after some events were read, returned after those.
+    pub(crate) error: Option<DeviceError>,
+}
+
+impl BurstBuffer {
+    /// Reads events until libevdev has none left, the buffer is full or reading fails.
+    pub(crate) fn fill(&mut self, device: &evdev_rs::Device, syncing: &mut bool) {
+        while self.error.is_none() && self.events.len() < BURST_CAPACITY {
+            match read_event(device, syncing) {
+                Some(Ok(event)) => self.events.push_back(event),
+                Some(Err(e)) => self.error = Some(e),
+                None => break,
+            }
+        }
+    }
+
+    /// Whether a burst or an error is ready to be returned.
+    pub(crate) fn take_ready(&mut self) -> Option<Result<(), DeviceError>> {
+        if !self.events.is_empty() {
+            return Some(Ok(()));
+        }
+        self.error.take().map(Err)
+    }
+}
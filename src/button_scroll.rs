@@ -0,0 +1,141 @@
+use crate::{HiResWheel, Processor, WHEEL_DETENT};
+use evdev_rs::enums::{EventCode, EV_KEY, EV_REL, EV_SYN};
+use evdev_rs::InputEvent;
+use std::collections::VecDeque;
+use std::time::Instant;
+
+/// Button scrolling, as libinput does it: while the button is held, pointer motion scrolls
+/// instead, e.g. for trackballs without a wheel. A press and release without motion beyond the
+/// threshold clicks the button as usual. Both REL_WHEEL and the high-resolution REL_WHEEL_HI_RES
+/// are output, and likewise horizontally.
+#[derive(Debug)]
+pub struct ButtonScroll {
+    button: EV_KEY,
+    threshold: i32,
+    distance: f64,
+    natural: bool,
+    hi_res: bool,
+    held: bool,
+    scrolling: bool,
+    travel: i32,
+    vertical: HiResWheel,
+    horizontal: HiResWheel,
+}
+
+impl ButtonScroll {
+    /// Motion of 2 counts starts scrolling, and 15 counts scroll by a detent.
+    pub fn new(button: EV_KEY) -> Self {
+        Self {
+            button,
+            threshold: 2,
+            distance: 15.0,
+            natural: false,
+            hi_res: true,
+            held: false,
+            scrolling: false,
+            travel: 0,
+            vertical: HiResWheel::new(),
+            horizontal: HiResWheel::new(),
+        }
+    }
+
+    /// How far the pointer has to move with the button held before scrolling starts.
+    pub fn threshold(mut self, threshold: i32) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// How far the pointer moves per detent scrolled.
+    pub fn distance(mut self, distance: f64) -> Self {
+        self.distance = distance;
+        self
+    }
+
+    /// Scrolls the content along with the pointer instead of the view.
+    pub fn natural(mut self, natural: bool) -> Self {
+        self.natural = natural;
+        self
+    }
+
+    /// Whether to output REL_WHEEL_HI_RES and REL_HWHEEL_HI_RES too.
+    pub fn hi_res(mut self, hi_res: bool) -> Self {
+        self.hi_res = hi_res;
+        self
+    }
+
+    fn scroll(&mut self, input: &InputEvent, out: &mut VecDeque<InputEvent>) {
+        let direction = if self.natural { -1.0 } else { 1.0 };
+        let units = f64::from(input.value) * f64::from(WHEEL_DETENT) / self.distance * direction;
+        let (wheel, wheel_hi_res, (hi_res, detents)) = match input.event_code {
+            // Moving down scrolls down, which is negative on the wheel.
+            EventCode::EV_REL(EV_REL::REL_Y) => (
+                EV_REL::REL_WHEEL,
+                EV_REL::REL_WHEEL_HI_RES,
+                self.vertical.scroll(-units),
+            ),
+            _ => (
+                EV_REL::REL_HWHEEL,
+                EV_REL::REL_HWHEEL_HI_RES,
+                self.horizontal.scroll(units),
+            ),
+        };
+        if detents != 0 {
+            out.push_back(InputEvent {
+                event_code: EventCode::EV_REL(wheel),
+                value: detents,
+                ..*input
+            });
+        }
+        if self.hi_res && hi_res != 0 {
+            out.push_back(InputEvent {
+                event_code: EventCode::EV_REL(wheel_hi_res),
+                value: hi_res,
+                ..*input
+            });
+        }
+    }
+}
+
+impl Processor for ButtonScroll {
+    type Output = InputEvent;
+
+    fn process(&mut self, input: InputEvent, _now: Instant, out: &mut VecDeque<InputEvent>) {
+        match input.event_code {
+            EventCode::EV_KEY(key) if key == self.button => match input.value {
+                1 => {
+                    self.held = true;
+                    self.scrolling = false;
+                    self.travel = 0;
+                    self.vertical.reset();
+                    self.horizontal.reset();
+                }
+                0 => {
+                    self.held = false;
+                    if !self.scrolling {
+                        out.push_back(InputEvent {
+                            value: 1,
+                            ..input.clone()
+                        });
+                        out.push_back(InputEvent {
+                            event_code: EventCode::EV_SYN(EV_SYN::SYN_REPORT),
+                            value: 0,
+                            ..input.clone()
+                        });
+                        out.push_back(input);
+                    }
+                }
+                _ => {}
+            },
+            EventCode::EV_REL(EV_REL::REL_X) | EventCode::EV_REL(EV_REL::REL_Y) if self.held => {
+                if !self.scrolling {
+                    self.travel += input.value.abs();
+                    self.scrolling = self.travel >= self.threshold;
+                }
+                if self.scrolling {
+                    self.scroll(&input, out);
+                }
+            }
+            _ => out.push_back(input),
+        }
+    }
+}
@@ -0,0 +1,228 @@
+use crate::Processor;
+use evdev_rs::enums::{EventCode, EV_KEY};
+use evdev_rs::InputEvent;
+use std::collections::{HashSet, VecDeque};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChordEvent {
+    /// An event that isn't part of a recognized chord, passed through unchanged.
+    Event(InputEvent),
+    /// All keys of a configured chord were pressed within the window. The presses and the
+    /// corresponding releases are swallowed.
+    ChordPressed(Vec<EV_KEY>),
+}
+
+/// Recognizes configured key combinations pressed within a time window.
+///
+/// Once a key belonging to any chord is pressed, events are held back until either a chord
+/// completes, the pressed keys can no longer form a chord, a held-back key is released, or the
+/// window runs out. In all but the first case the held-back events are forwarded unchanged.
+pub struct ChordDetector {
+    chords: Vec<Vec<EV_KEY>>,
+    window: Duration,
+    buffer: Vec<InputEvent>,
+    pressed: HashSet<EV_KEY>,
+    deadline: Option<Instant>,
+    swallowed: HashSet<EV_KEY>,
+}
+
+fn key_event(event: &InputEvent) -> Option<(EV_KEY, i32)> {
+    match event.event_code {
+        EventCode::EV_KEY(key) => Some((key, event.value)),
+        _ => None,
+    }
+}
+
+impl ChordDetector {
+    pub fn new(chords: Vec<Vec<EV_KEY>>, window: Duration) -> Self {
+        Self {
+            chords,
+            window,
+            buffer: Vec::new(),
+            pressed: HashSet::new(),
+            deadline: None,
+            swallowed: HashSet::new(),
+        }
+    }
+
+    fn is_chord_key(&self, key: EV_KEY) -> bool {
+        self.chords.iter().any(|chord| chord.contains(&key))
+    }
+
+    fn could_complete(&self) -> bool {
+        self.chords
+            .iter()
+            .any(|chord| self.pressed.iter().all(|key| chord.contains(key)))
+    }
+
+    fn exact_match(&self) -> Option<&Vec<EV_KEY>> {
+        self.chords.iter().find(|chord| {
+            chord.len() == self.pressed.len() && chord.iter().all(|key| self.pressed.contains(key))
+        })
+    }
+
+    fn has_longer_candidate(&self) -> bool {
+        self.chords.iter().any(|chord| {
+            chord.len() > self.pressed.len() && self.pressed.iter().all(|key| chord.contains(key))
+        })
+    }
+
+    fn flush(&mut self, out: &mut VecDeque<ChordEvent>) {
+        out.extend(self.buffer.drain(..).map(ChordEvent::Event));
+        self.pressed.clear();
+        self.deadline = None;
+    }
+
+    fn fire(&mut self, chord: Vec<EV_KEY>, out: &mut VecDeque<ChordEvent>) {
+        // Everything but the chord's own key presses is still forwarded.
+        out.extend(
+            self.buffer
+                .drain(..)
+                .filter(|event| !matches!(key_event(event), Some((key, 1)) if chord.contains(&key)))
+                .map(ChordEvent::Event),
+        );
+        self.swallowed.extend(chord.iter().copied());
+        self.pressed.clear();
+        self.deadline = None;
+        out.push_back(ChordEvent::ChordPressed(chord));
+    }
+}
+
+impl Processor for ChordDetector {
+    type Output = ChordEvent;
+
+    fn process(&mut self, event: InputEvent, now: Instant, out: &mut VecDeque<ChordEvent>) {
+        let key = key_event(&event);
+        // A fired chord's keys are swallowed until released, also while another chord is
+        // being buffered.
+        match key {
+            Some((key, 0)) if self.swallowed.remove(&key) => return,
+            Some((key, 2)) if self.swallowed.contains(&key) => return,
+            _ => {}
+        }
+        if self.deadline.is_none() {
+            match key {
+                Some((key, 1)) if self.is_chord_key(key) && !self.swallowed.contains(&key) => {
+                    self.deadline = Some(now + self.window);
+                }
+                _ => {
+                    out.push_back(ChordEvent::Event(event));
+                    return;
+                }
+            }
+        }
+        self.buffer.push(event);
+        match key {
+            Some((key, 1)) => {
+                let _: bool = self.pressed.insert(key);
+                if !self.could_complete() {
+                    self.flush(out);
+                } else if !self.has_longer_candidate() {
+                    if let Some(chord) = self.exact_match().cloned() {
+                        self.fire(chord, out);
+                    }
+                }
+            }
+            Some((key, 0)) if self.pressed.contains(&key) => self.flush(out),
+            _ => {}
+        }
+    }
+
+    fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    fn timeout(&mut self, _now: Instant, out: &mut VecDeque<ChordEvent>) {
+        match self.exact_match().cloned() {
+            Some(chord) => self.fire(chord, out),
+            None => self.flush(out),
+        }
+    }
+
+    fn finish(&mut self, out: &mut VecDeque<ChordEvent>) {
+        self.flush(out);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{key, run, syn};
+    use evdev_rs::enums::EV_KEY::{KEY_A, KEY_B, KEY_C};
+
+    const WINDOW: Duration = Duration::from_millis(50);
+
+    fn detector() -> ChordDetector {
+        ChordDetector::new(vec![vec![KEY_A, KEY_B]], WINDOW)
+    }
+
+    fn summarize(output: Vec<ChordEvent>) -> Vec<Result<(EV_KEY, i32), Vec<EV_KEY>>> {
+        output
+            .into_iter()
+            .filter_map(|event| match event {
+                ChordEvent::Event(event) => key_event(&event).map(Ok),
+                ChordEvent::ChordPressed(chord) => Some(Err(chord)),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn fires_and_swallows_chord() {
+        let output = run(
+            vec![
+                key(0, KEY_A, 1),
+                key(10, KEY_B, 1),
+                syn(10),
+                key(100, KEY_A, 0),
+                key(110, KEY_B, 0),
+                syn(110),
+            ],
+            detector(),
+        );
+        assert_eq!(summarize(output), vec![Err(vec![KEY_A, KEY_B])]);
+    }
+
+    #[test]
+    fn forwards_unfinished_chord_after_window() {
+        let output = run(
+            vec![key(0, KEY_A, 1), syn(0), key(200, KEY_A, 0), syn(200)],
+            detector(),
+        );
+        assert_eq!(summarize(output), vec![Ok((KEY_A, 1)), Ok((KEY_A, 0))]);
+    }
+
+    #[test]
+    fn forwards_non_chord_keys() {
+        let output = run(vec![key(0, KEY_C, 1), key(10, KEY_C, 0)], detector());
+        assert_eq!(summarize(output), vec![Ok((KEY_C, 1)), Ok((KEY_C, 0))]);
+    }
+
+    #[test]
+    fn swallows_release_of_chord_key_while_buffering() {
+        let output = run(
+            vec![
+                key(0, KEY_A, 1),
+                key(10, KEY_B, 1),
+                key(100, KEY_A, 0),
+                // Starts buffering, during which the chord's B is released.
+                key(200, KEY_A, 1),
+                key(210, KEY_B, 0),
+                key(220, KEY_A, 0),
+                key(400, KEY_B, 1),
+                key(410, KEY_B, 0),
+            ],
+            detector(),
+        );
+        assert_eq!(
+            summarize(output),
+            vec![
+                Err(vec![KEY_A, KEY_B]),
+                Ok((KEY_A, 1)),
+                Ok((KEY_A, 0)),
+                Ok((KEY_B, 1)),
+                Ok((KEY_B, 0)),
+            ]
+        );
+    }
+}
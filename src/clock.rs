@@ -0,0 +1,71 @@
+use crate::AsyncDevice;
+use evdev_rs::TimeVal;
+use std::time::{Duration, Instant};
+
+/// The clock event timestamps are taken from. The kernel defaults to `Realtime`, which jumps when
+/// the wall clock is set, while `Monotonic` doesn't and `Boottime` also counts time suspended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ClockId {
+    Realtime,
+    Monotonic,
+    Boottime,
+}
+
+impl ClockId {
+    fn raw(self) -> libc::clockid_t {
+        match self {
+            ClockId::Realtime => libc::CLOCK_REALTIME,
+            ClockId::Monotonic => libc::CLOCK_MONOTONIC,
+            ClockId::Boottime => libc::CLOCK_BOOTTIME,
+        }
+    }
+}
+
+impl AsyncDevice {
+    /// Sets the clock of the timestamps of further events, with EVIOCSCLOCKID.
+    pub fn set_clock(&mut self, clock: ClockId) -> std::io::Result<()> {
+        self.evdev().set_clock_id(clock.raw())
+    }
+}
+
+/// An event timestamp, as the time since its clock's epoch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Timestamp(pub Duration);
+
+impl From<TimeVal> for Timestamp {
+    fn from(time: TimeVal) -> Self {
+        let micros = i128::from(time.tv_sec) * 1_000_000 + i128::from(time.tv_usec);
+        Self(Duration::from_micros(micros.max(0) as u64))
+    }
+}
+
+impl From<Timestamp> for TimeVal {
+    fn from(Timestamp(time): Timestamp) -> Self {
+        TimeVal::new(time.as_secs() as i64, i64::from(time.subsec_micros()))
+    }
+}
+
+impl Timestamp {
+    /// The current time of `clock`.
+    pub fn now(clock: ClockId) -> Self {
+        let mut now = libc::timespec {
+            tv_sec: 0,
+            tv_nsec: 0,
+        };
+        // Only fails for invalid clocks or pointers.
+        let _: libc::c_int = unsafe { libc::clock_gettime(clock.raw(), &mut now) };
+        Self(Duration::new(now.tv_sec as u64, now.tv_nsec as u32))
+    }
+
+    /// The time elapsed since `earlier`, or zero if `earlier` is later.
+    pub fn duration_since(self, earlier: Timestamp) -> Duration {
+        self.0.saturating_sub(earlier.0)
+    }
+
+    /// The `Instant` of a timestamp taken from `ClockId::Monotonic`, the clock `Instant` uses,
+    /// e.g. to measure latency with `Instant::elapsed`. Meaningless for other clocks.
+    pub fn monotonic_instant(self) -> Option<Instant> {
+        let age = Timestamp::now(ClockId::Monotonic).duration_since(self);
+        Instant::now().checked_sub(age)
+    }
+}
@@ -0,0 +1,61 @@
+use crate::{Injector, VirtualDeviceBuilder};
+use evdev_rs::enums::EventCode;
+use evdev_rs::{DeviceWrapper as _, UInputDevice, UninitDevice};
+
+/// A single virtual device with the capabilities of a keyboard and a mouse, and optionally a
+/// gamepad, for remappers whose output mixes them.
+///
+/// Injection checks each event against the capabilities the device was created with. The kernel
+/// silently drops events a uinput device doesn't support, so this turns e.g. a mouse button
+/// injected into a device built without a mouse into an error instead.
+pub struct VirtualComboDevice {
+    uinput: UInputDevice,
+    template: UninitDevice,
+}
+
+impl VirtualComboDevice {
+    /// A keyboard and mouse.
+    pub fn new(name: &str) -> std::io::Result<Self> {
+        VirtualDeviceBuilder::new()
+            .name(name)
+            .keyboard()
+            .mouse()
+            .build_combo()
+    }
+
+    /// A keyboard, mouse and gamepad. Note that some applications treat any device with gamepad
+    /// capabilities as a gamepad only.
+    pub fn with_gamepad(name: &str) -> std::io::Result<Self> {
+        VirtualDeviceBuilder::new()
+            .name(name)
+            .keyboard()
+            .mouse()
+            .gamepad()
+            .build_combo()
+    }
+
+    pub(crate) fn from_template(uinput: UInputDevice, template: UninitDevice) -> Self {
+        Self { uinput, template }
+    }
+
+    pub fn uinput(&self) -> &UInputDevice {
+        &self.uinput
+    }
+
+    /// Whether the device was created with `event_code`.
+    pub fn supports(&self, event_code: &EventCode) -> bool {
+        matches!(event_code, EventCode::EV_SYN(_)) || self.template.has(event_code)
+    }
+}
+
+impl Injector for VirtualComboDevice {
+    fn inject_event(&self, event_code: EventCode, value: i32) -> std::io::Result<()> {
+        if !self.supports(&event_code) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                format!("virtual device doesn't support {}", event_code),
+            ));
+        }
+        self.uinput.inject_event(event_code, value)
+    }
+}
@@ -0,0 +1,99 @@
+//! A D-Bus control interface for long-running remapping daemons, so desktop applets and scripts
+//! can switch profiles, reload the configuration and inspect the daemon's state, e.g.
+//!
+//! ```sh
+//! busctl --user call org.evdev_utils.Remapper /org/evdev_utils/Control \
+//!     org.evdev_utils.Control1 SetProfileEnabled sb gaming true
+//! ```
+
+use thiserror::Error;
+
+pub const BUS_NAME: &str = "org.evdev_utils.Remapper";
+pub const OBJECT_PATH: &str = "/org/evdev_utils/Control";
+
+/// A failure reported back to the D-Bus caller.
+pub type HandlerError = Box<dyn std::error::Error + Send + Sync>;
+
+/// What the control interface does, implemented by the daemon over its own state. The methods
+/// are called from the connection's executor, so they should return quickly.
+pub trait ControlHandler: Send + Sync + 'static {
+    /// The names of all profiles, enabled or not.
+    fn profiles(&self) -> Vec<String>;
+
+    fn enabled_profiles(&self) -> Vec<String>;
+
+    fn set_profile_enabled(&self, profile: &str, enabled: bool) -> Result<(), HandlerError>;
+
+    fn reload_config(&self) -> Result<(), HandlerError>;
+
+    /// The indices of the active layers, see `layers::Layers::active_layers`.
+    fn active_layers(&self) -> Vec<u32> {
+        vec![0]
+    }
+
+    /// The paths of the devices being remapped.
+    fn devices(&self) -> Vec<String>;
+}
+
+#[derive(Error, Debug)]
+pub enum DbusError {
+    #[error("failed to connect to the session bus")]
+    Connect(#[source] zbus::Error),
+    #[error("failed to serve the control interface")]
+    Serve(#[source] zbus::Error),
+}
+
+struct Control(Box<dyn ControlHandler>);
+
+fn failed(e: HandlerError) -> zbus::fdo::Error {
+    zbus::fdo::Error::Failed(e.to_string())
+}
+
+#[zbus::interface(name = "org.evdev_utils.Control1")]
+impl Control {
+    fn list_profiles(&self) -> Vec<String> {
+        self.0.profiles()
+    }
+
+    fn enabled_profiles(&self) -> Vec<String> {
+        self.0.enabled_profiles()
+    }
+
+    fn set_profile_enabled(&self, profile: &str, enabled: bool) -> zbus::fdo::Result<()> {
+        self.0.set_profile_enabled(profile, enabled).map_err(failed)
+    }
+
+    fn reload_config(&self) -> zbus::fdo::Result<()> {
+        self.0.reload_config().map_err(failed)
+    }
+
+    fn active_layers(&self) -> Vec<u32> {
+        self.0.active_layers()
+    }
+
+    fn list_devices(&self) -> Vec<String> {
+        self.0.devices()
+    }
+}
+
+/// Serves `handler` on the session bus under `BUS_NAME` and `OBJECT_PATH`, until the returned
+/// connection is dropped.
+pub async fn serve<H: ControlHandler>(handler: H) -> Result<zbus::Connection, DbusError> {
+    serve_as(handler, BUS_NAME).await
+}
+
+/// Like `serve`, but under another bus name, e.g. to run several daemons side by side.
+pub async fn serve_as<H: ControlHandler>(
+    handler: H,
+    name: &str,
+) -> Result<zbus::Connection, DbusError> {
+    zbus::connection::Builder::session()
+        .map_err(DbusError::Connect)?
+        .name(name)
+        .map_err(DbusError::Serve)?
+        .serve_at(OBJECT_PATH, Control(Box::new(handler)))
+        .map_err(DbusError::Serve)?
+        .build()
+        .await
+        .map_err(DbusError::Connect)
+}
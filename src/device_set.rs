@@ -0,0 +1,181 @@
+use crate::{read_event, AsyncDevice, DeviceError};
+use async_io::Async;
+use evdev_rs::enums::{EventCode, EV_SYN};
+use evdev_rs::InputEvent;
+use futures::{ready, Stream};
+use std::collections::{HashMap, VecDeque};
+use std::fs::File;
+use std::os::unix::io::{AsRawFd as _, FromRawFd as _};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use thiserror::Error;
+
+/// Multiplexes the events of many devices, as an alternative to `select_all` over their streams.
+///
+/// All devices are watched through one epoll instance, which is the only source registered with
+/// the reactor, so the task is only woken once however many devices became readable. Devices with
+/// events take turns a whole frame at a time, so a busy mouse can't starve a power button, and the
+/// frames of different devices are never interleaved.
+pub struct DeviceSet {
+    epoll: Async<File>,
+    devices: HashMap<usize, AsyncDevice>,
+    next_id: usize,
+    // Devices that may have events available, in turn order.
+    ready: VecDeque<usize>,
+}
+
+impl DeviceSet {
+    pub fn new() -> std::io::Result<Self> {
+        let fd = unsafe { libc::epoll_create1(libc::EPOLL_CLOEXEC) };
+        if fd < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(Self {
+            epoll: Async::new(unsafe { File::from_raw_fd(fd) })?,
+            devices: HashMap::new(),
+            next_id: 0,
+            ready: VecDeque::new(),
+        })
+    }
+
+    /// Adds a device, returning the id its events are reported with.
+    pub fn insert(&mut self, device: AsyncDevice) -> std::io::Result<usize> {
+        let id = self.next_id;
+        let mut event = libc::epoll_event {
+            events: libc::EPOLLIN as u32,
+            u64: id as u64,
+        };
+        let fd = device.evdev().file().as_raw_fd();
+        if unsafe { libc::epoll_ctl(self.epoll.as_raw_fd(), libc::EPOLL_CTL_ADD, fd, &mut event) }
+            < 0
+        {
+            return Err(std::io::Error::last_os_error());
+        }
+        self.next_id += 1;
+        let _: Option<AsyncDevice> = self.devices.insert(id, device);
+        // libevdev may already have buffered events, which epoll won't report.
+        self.ready.push_back(id);
+        Ok(id)
+    }
+
+    pub fn remove(&mut self, id: usize) -> Option<AsyncDevice> {
+        let device = self.devices.remove(&id)?;
+        let fd = device.evdev().file().as_raw_fd();
+        // Closing the fd would deregister it too, but the device is handed back open.
+        let _: libc::c_int = unsafe {
+            libc::epoll_ctl(
+                self.epoll.as_raw_fd(),
+                libc::EPOLL_CTL_DEL,
+                fd,
+                std::ptr::null_mut(),
+            )
+        };
+        self.ready.retain(|ready| *ready != id);
+        Some(device)
+    }
+
+    pub fn get(&self, id: usize) -> Option<&AsyncDevice> {
+        self.devices.get(&id)
+    }
+
+    pub fn get_mut(&mut self, id: usize) -> Option<&mut AsyncDevice> {
+        self.devices.get_mut(&id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.devices.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.devices.is_empty()
+    }
+
+    // Queues the devices epoll reports as readable, without blocking.
+    fn collect_ready(&mut self) -> std::io::Result<()> {
+        let mut events = [libc::epoll_event { events: 0, u64: 0 }; 32];
+        loop {
+            let count = unsafe {
+                libc::epoll_wait(
+                    self.epoll.as_raw_fd(),
+                    events.as_mut_ptr(),
+                    events.len() as libc::c_int,
+                    0,
+                )
+            };
+            if count < 0 {
+                let e = std::io::Error::last_os_error();
+                if e.kind() == std::io::ErrorKind::Interrupted {
+                    continue;
+                }
+                return Err(e);
+            }
+            for event in &events[..count as usize] {
+                let id = event.u64 as usize;
+                if self.devices.contains_key(&id) && !self.ready.contains(&id) {
+                    self.ready.push_back(id);
+                }
+            }
+            if (count as usize) < events.len() {
+                return Ok(());
+            }
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum DeviceSetError {
+    /// The device has been removed from the set, e.g. because it was unplugged.
+    #[error("failed to read from device {id}")]
+    Device {
+        id: usize,
+        #[source]
+        source: DeviceError,
+    },
+    #[error("failed to poll devices")]
+    Poll(#[source] std::io::Error),
+}
+
+impl Stream for DeviceSet {
+    /// The id of the device and its event.
+    type Item = Result<(usize, InputEvent), DeviceSetError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        loop {
+            if let Some(&id) = this.ready.front() {
+                let AsyncDevice {
+                    device, syncing, ..
+                } = this
+                    .devices
+                    .get_mut(&id)
+                    .expect("ready devices are in the set");
+                match read_event(&device.get_ref().0, syncing) {
+                    Some(Ok(event)) => {
+                        if event.event_code == EventCode::EV_SYN(EV_SYN::SYN_REPORT) {
+                            this.ready.rotate_left(1);
+                        }
+                        return Poll::Ready(Some(Ok((id, event))));
+                    }
+                    Some(Err(e)) => {
+                        let _: Option<AsyncDevice> = this.remove(id);
+                        return Poll::Ready(Some(Err(DeviceSetError::Device { id, source: e })));
+                    }
+                    // Drained; epoll reports it again once the fd is readable.
+                    None => {
+                        let _: Option<usize> = this.ready.pop_front();
+                        continue;
+                    }
+                }
+            }
+            if this.devices.is_empty() {
+                return Poll::Ready(None);
+            }
+            if let Err(e) = ready!(this.epoll.poll_readable(cx)) {
+                return Poll::Ready(Some(Err(DeviceSetError::Poll(e))));
+            }
+            if let Err(e) = this.collect_ready() {
+                return Poll::Ready(Some(Err(DeviceSetError::Poll(e))));
+            }
+        }
+    }
+}
@@ -0,0 +1,137 @@
+//! Keyboard diagnostics: an N-key rollover tester finding combinations a keyboard's matrix
+//! blocks, or reports phantom keys for.
+
+use evdev_rs::enums::{EventCode, EV_KEY};
+use evdev_rs::InputEvent;
+
+/// Combinations commonly held together in games, with movement keys, modifiers and space.
+pub const GAMING_COMBOS: &[&[EV_KEY]] = &[
+    &[EV_KEY::KEY_W, EV_KEY::KEY_A, EV_KEY::KEY_SPACE],
+    &[EV_KEY::KEY_W, EV_KEY::KEY_D, EV_KEY::KEY_SPACE],
+    &[
+        EV_KEY::KEY_W,
+        EV_KEY::KEY_A,
+        EV_KEY::KEY_LEFTSHIFT,
+        EV_KEY::KEY_SPACE,
+    ],
+    &[
+        EV_KEY::KEY_W,
+        EV_KEY::KEY_D,
+        EV_KEY::KEY_LEFTSHIFT,
+        EV_KEY::KEY_SPACE,
+    ],
+    &[
+        EV_KEY::KEY_W,
+        EV_KEY::KEY_A,
+        EV_KEY::KEY_LEFTCTRL,
+        EV_KEY::KEY_R,
+    ],
+    &[
+        EV_KEY::KEY_S,
+        EV_KEY::KEY_D,
+        EV_KEY::KEY_LEFTCTRL,
+        EV_KEY::KEY_E,
+    ],
+    &[EV_KEY::KEY_Q, EV_KEY::KEY_W, EV_KEY::KEY_E, EV_KEY::KEY_R],
+    &[EV_KEY::KEY_A, EV_KEY::KEY_S, EV_KEY::KEY_D, EV_KEY::KEY_F],
+];
+
+/// The keys held together at the peak of one attempt, from the first press until all keys were
+/// released again.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Attempt {
+    pub keys: Vec<EV_KEY>,
+}
+
+impl Attempt {
+    /// Compares the keys registered with those that were meant to be held.
+    pub fn check(&self, combo: &[EV_KEY]) -> ComboResult {
+        ComboResult {
+            combo: combo.to_vec(),
+            missing: combo
+                .iter()
+                .filter(|key| !self.keys.contains(key))
+                .copied()
+                .collect(),
+            extra: self
+                .keys
+                .iter()
+                .filter(|key| !combo.contains(key))
+                .copied()
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ComboResult {
+    pub combo: Vec<EV_KEY>,
+    /// Keys of the combination that never registered while the others were held.
+    pub missing: Vec<EV_KEY>,
+    /// Keys that registered without being part of the combination, which are either phantom
+    /// keys from ghosting or pressed by mistake.
+    pub extra: Vec<EV_KEY>,
+}
+
+impl ComboResult {
+    pub fn is_blocked(&self) -> bool {
+        !self.missing.is_empty()
+    }
+
+    pub fn passed(&self) -> bool {
+        self.missing.is_empty() && self.extra.is_empty()
+    }
+}
+
+/// Tracks held keys from a keyboard's events, recording how many were held at once.
+#[derive(Debug, Default)]
+pub struct RolloverTester {
+    held: Vec<EV_KEY>,
+    peak: Vec<EV_KEY>,
+    max_rollover: usize,
+}
+
+impl RolloverTester {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feeds an event from the device, returning the attempt once all keys are released.
+    pub fn handle(&mut self, event: &InputEvent) -> Option<Attempt> {
+        let key = match event.event_code {
+            EventCode::EV_KEY(key) => key,
+            _ => return None,
+        };
+        match event.value {
+            1 if !self.held.contains(&key) => {
+                self.held.push(key);
+                if self.held.len() > self.peak.len() {
+                    self.peak = self.held.clone();
+                }
+                self.max_rollover = self.max_rollover.max(self.held.len());
+                None
+            }
+            0 => {
+                self.held.retain(|held| *held != key);
+                if self.held.is_empty() && !self.peak.is_empty() {
+                    Some(Attempt {
+                        keys: std::mem::take(&mut self.peak),
+                    })
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// The keys held right now, in the order they were pressed.
+    pub fn held(&self) -> &[EV_KEY] {
+        &self.held
+    }
+
+    /// The most keys held at once so far.
+    pub fn max_rollover(&self) -> usize {
+        self.max_rollover
+    }
+}
@@ -0,0 +1,52 @@
+use crate::{format_event, ClockId, Injector, Timestamp};
+use evdev_rs::enums::EventCode;
+use evdev_rs::{InputEvent, TimeVal};
+use std::cell::RefCell;
+use std::io::Write;
+
+/// An injector printing the events it would inject with `format_event` instead, to try out a
+/// pipeline on a real device before letting it take over, e.g. a `Remapper` with `grab(false)`.
+/// Events injected without a timestamp are stamped with the current time.
+#[derive(Debug)]
+pub struct DryRun<W = std::io::Stdout> {
+    out: RefCell<W>,
+}
+
+impl DryRun {
+    pub fn stdout() -> Self {
+        Self::new(std::io::stdout())
+    }
+}
+
+impl<W: Write> DryRun<W> {
+    pub fn new(out: W) -> Self {
+        Self {
+            out: RefCell::new(out),
+        }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out.into_inner()
+    }
+}
+
+impl<W: Write> Injector for DryRun<W> {
+    fn inject_event(&self, event_code: EventCode, value: i32) -> std::io::Result<()> {
+        let time = Timestamp::now(ClockId::Realtime).into();
+        self.inject_event_at(event_code, value, time)
+    }
+
+    fn inject_event_at(
+        &self,
+        event_code: EventCode,
+        value: i32,
+        time: TimeVal,
+    ) -> std::io::Result<()> {
+        let event = InputEvent {
+            time,
+            event_code,
+            value,
+        };
+        writeln!(self.out.borrow_mut(), "{}", format_event(&event))
+    }
+}
@@ -0,0 +1,108 @@
+use crate::grab::ungrab_all;
+use evdev_rs::enums::{EventCode, EV_KEY};
+use evdev_rs::InputEvent;
+use futures::{Stream, StreamExt as _};
+use std::collections::HashSet;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Both Ctrl keys and Esc, which are unlikely to be pressed together by accident.
+pub const DEFAULT_ESCAPE_KEYS: &[EV_KEY] =
+    &[EV_KEY::KEY_LEFTCTRL, EV_KEY::KEY_RIGHTCTRL, EV_KEY::KEY_ESC];
+
+/// Recognizes a set of keys held together, in any order.
+#[derive(Debug, Clone)]
+pub struct EscapeSequence {
+    keys: Vec<EV_KEY>,
+    held: HashSet<EV_KEY>,
+}
+
+impl Default for EscapeSequence {
+    fn default() -> Self {
+        Self::new(DEFAULT_ESCAPE_KEYS.iter().copied())
+    }
+}
+
+impl EscapeSequence {
+    /// An empty set of keys is never recognized.
+    pub fn new(keys: impl IntoIterator<Item = EV_KEY>) -> Self {
+        Self {
+            keys: keys.into_iter().collect(),
+            held: HashSet::new(),
+        }
+    }
+
+    pub fn keys(&self) -> &[EV_KEY] {
+        &self.keys
+    }
+
+    /// Feeds an event, returning whether it completed the sequence.
+    pub fn handle(&mut self, event: &InputEvent) -> bool {
+        let key = match event.event_code {
+            EventCode::EV_KEY(key) if self.keys.contains(&key) => key,
+            _ => return false,
+        };
+        match event.value {
+            1 => {
+                let _: bool = self.held.insert(key);
+                self.held.len() == self.keys.len()
+            }
+            0 => {
+                let _: bool = self.held.remove(&key);
+                false
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Watches a stream for an escape sequence. When it's pressed, releases every grab in the
+/// process with `ungrab_all` and ends the stream, so the pipeline reading it shuts down and the
+/// user gets their devices back even if the pipeline misbehaves.
+pub struct EscapeHatch<S> {
+    stream: S,
+    sequence: EscapeSequence,
+    triggered: bool,
+}
+
+impl<S> EscapeHatch<S> {
+    pub fn new(stream: S, sequence: EscapeSequence) -> Self {
+        Self {
+            stream,
+            sequence,
+            triggered: false,
+        }
+    }
+
+    /// Whether the stream ended because the escape sequence was pressed.
+    pub fn triggered(&self) -> bool {
+        self.triggered
+    }
+
+    pub fn into_inner(self) -> S {
+        self.stream
+    }
+}
+
+impl<S, E> Stream for EscapeHatch<S>
+where
+    S: Stream<Item = Result<InputEvent, E>> + Unpin,
+{
+    type Item = S::Item;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if self.triggered {
+            return Poll::Ready(None);
+        }
+        let item = futures::ready!(self.stream.poll_next_unpin(cx));
+        if let Some(Ok(event)) = &item {
+            if self.sequence.handle(event) {
+                log::warn!("escape sequence pressed, releasing all grabs");
+                ungrab_all();
+                self.triggered = true;
+                return Poll::Ready(None);
+            }
+        }
+        Poll::Ready(item)
+    }
+}
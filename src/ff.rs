@@ -0,0 +1,415 @@
+//! Force feedback: uploading and playing effects on physical devices, and serving effect
+//! requests for virtual devices created through uinput.
+
+use async_io::Async;
+use evdev_rs::enums::{int_to_ev_ff, EV_FF};
+use evdev_rs::UInputDevice;
+use std::convert::TryFrom;
+use std::fs::{File, OpenOptions};
+use std::os::unix::fs::OpenOptionsExt as _;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::path::Path;
+use std::time::Duration;
+
+const EVIOCSFF: libc::Ioctl = libc::_IOW::<libc::ff_effect>(b'E' as u32, 0x80);
+const EVIOCRMFF: libc::Ioctl = libc::_IOW::<libc::c_int>(b'E' as u32, 0x81);
+const EVIOCGEFFECTS: libc::Ioctl = libc::_IOR::<libc::c_int>(b'E' as u32, 0x84);
+
+const UI_BEGIN_FF_UPLOAD: libc::Ioctl = libc::_IOWR::<libc::uinput_ff_upload>(b'U' as u32, 200);
+const UI_END_FF_UPLOAD: libc::Ioctl = libc::_IOW::<libc::uinput_ff_upload>(b'U' as u32, 201);
+const UI_BEGIN_FF_ERASE: libc::Ioctl = libc::_IOWR::<libc::uinput_ff_erase>(b'U' as u32, 202);
+const UI_END_FF_ERASE: libc::Ioctl = libc::_IOW::<libc::uinput_ff_erase>(b'U' as u32, 203);
+
+const EV_FF_TYPE: u16 = 0x15;
+const EV_UINPUT: u16 = 0x0101;
+const UI_FF_UPLOAD: u16 = 1;
+const UI_FF_ERASE: u16 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Waveform {
+    Square,
+    Triangle,
+    Sine,
+    SawUp,
+    SawDown,
+}
+
+impl Waveform {
+    fn code(self) -> EV_FF {
+        match self {
+            Waveform::Square => EV_FF::FF_SQUARE,
+            Waveform::Triangle => EV_FF::FF_TRIANGLE,
+            Waveform::Sine => EV_FF::FF_SINE,
+            Waveform::SawUp => EV_FF::FF_SAW_UP,
+            Waveform::SawDown => EV_FF::FF_SAW_DOWN,
+        }
+    }
+
+    fn from_code(code: EV_FF) -> Option<Self> {
+        match code {
+            EV_FF::FF_SQUARE => Some(Waveform::Square),
+            EV_FF::FF_TRIANGLE => Some(Waveform::Triangle),
+            EV_FF::FF_SINE => Some(Waveform::Sine),
+            EV_FF::FF_SAW_UP => Some(Waveform::SawUp),
+            EV_FF::FF_SAW_DOWN => Some(Waveform::SawDown),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EffectKind {
+    Rumble {
+        strong_magnitude: u16,
+        weak_magnitude: u16,
+    },
+    Periodic {
+        waveform: Waveform,
+        period: Duration,
+        magnitude: i16,
+        offset: i16,
+        phase: u16,
+    },
+}
+
+/// A force feedback effect. Durations are truncated to milliseconds and saturate at the kernel's
+/// 16-bit limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Effect {
+    pub kind: EffectKind,
+    pub direction: u16,
+    pub length: Duration,
+    pub delay: Duration,
+}
+
+fn millis(duration: Duration) -> u16 {
+    u16::try_from(duration.as_millis()).unwrap_or(u16::MAX)
+}
+
+impl Effect {
+    pub fn rumble(strong_magnitude: u16, weak_magnitude: u16, length: Duration) -> Self {
+        Self {
+            kind: EffectKind::Rumble {
+                strong_magnitude,
+                weak_magnitude,
+            },
+            direction: 0,
+            length,
+            delay: Duration::ZERO,
+        }
+    }
+
+    pub fn periodic(
+        waveform: Waveform,
+        period: Duration,
+        magnitude: i16,
+        length: Duration,
+    ) -> Self {
+        Self {
+            kind: EffectKind::Periodic {
+                waveform,
+                period,
+                magnitude,
+                offset: 0,
+                phase: 0,
+            },
+            direction: 0,
+            length,
+            delay: Duration::ZERO,
+        }
+    }
+
+    fn to_raw(self, id: i16) -> libc::ff_effect {
+        let mut raw: libc::ff_effect = unsafe { std::mem::zeroed() };
+        raw.id = id;
+        raw.direction = self.direction;
+        raw.replay = libc::ff_replay {
+            length: millis(self.length),
+            delay: millis(self.delay),
+        };
+        let u = raw.u.as_mut_ptr();
+        match self.kind {
+            EffectKind::Rumble {
+                strong_magnitude,
+                weak_magnitude,
+            } => {
+                raw.type_ = EV_FF::FF_RUMBLE as u16;
+                unsafe {
+                    (u as *mut libc::ff_rumble_effect).write(libc::ff_rumble_effect {
+                        strong_magnitude,
+                        weak_magnitude,
+                    })
+                };
+            }
+            EffectKind::Periodic {
+                waveform,
+                period,
+                magnitude,
+                offset,
+                phase,
+            } => {
+                raw.type_ = EV_FF::FF_PERIODIC as u16;
+                unsafe {
+                    (u as *mut libc::ff_periodic_effect).write(libc::ff_periodic_effect {
+                        waveform: waveform.code() as u16,
+                        period: millis(period),
+                        magnitude,
+                        offset,
+                        phase,
+                        envelope: libc::ff_envelope {
+                            attack_length: 0,
+                            attack_level: 0,
+                            fade_length: 0,
+                            fade_level: 0,
+                        },
+                        custom_len: 0,
+                        custom_data: std::ptr::null_mut(),
+                    })
+                };
+            }
+        }
+        raw
+    }
+
+    /// Returns `None` for effect types other than rumble and non-custom periodic effects.
+    fn from_raw(raw: &libc::ff_effect) -> Option<Self> {
+        let u = raw.u.as_ptr();
+        let kind = match int_to_ev_ff(raw.type_.into())? {
+            EV_FF::FF_RUMBLE => {
+                let rumble = unsafe { (u as *const libc::ff_rumble_effect).read() };
+                EffectKind::Rumble {
+                    strong_magnitude: rumble.strong_magnitude,
+                    weak_magnitude: rumble.weak_magnitude,
+                }
+            }
+            EV_FF::FF_PERIODIC => {
+                let periodic = unsafe { (u as *const libc::ff_periodic_effect).read() };
+                EffectKind::Periodic {
+                    waveform: Waveform::from_code(int_to_ev_ff(periodic.waveform.into())?)?,
+                    period: Duration::from_millis(periodic.period.into()),
+                    magnitude: periodic.magnitude,
+                    offset: periodic.offset,
+                    phase: periodic.phase,
+                }
+            }
+            _ => return None,
+        };
+        Some(Self {
+            kind,
+            direction: raw.direction,
+            length: Duration::from_millis(raw.replay.length.into()),
+            delay: Duration::from_millis(raw.replay.delay.into()),
+        })
+    }
+}
+
+fn check(ret: libc::c_int) -> std::io::Result<libc::c_int> {
+    if ret < 0 {
+        Err(std::io::Error::last_os_error())
+    } else {
+        Ok(ret)
+    }
+}
+
+fn write_event(fd: RawFd, type_: u16, code: u16, value: i32) -> std::io::Result<()> {
+    let mut event: libc::input_event = unsafe { std::mem::zeroed() };
+    event.type_ = type_;
+    event.code = code;
+    event.value = value;
+    let len = std::mem::size_of::<libc::input_event>();
+    let written = unsafe { libc::write(fd, &event as *const _ as *const libc::c_void, len) };
+    if written < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// A physical device opened read-write for force feedback.
+pub struct FfDevice {
+    file: File,
+}
+
+impl FfDevice {
+    pub fn open<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(path)?;
+        Ok(Self::from_file(file))
+    }
+
+    /// `file` must have been opened for writing.
+    pub fn from_file(file: File) -> Self {
+        Self { file }
+    }
+
+    /// How many effects the device can hold at once.
+    pub fn max_effects(&self) -> std::io::Result<usize> {
+        let mut count: libc::c_int = 0;
+        let _: libc::c_int =
+            check(unsafe { libc::ioctl(self.file.as_raw_fd(), EVIOCGEFFECTS, &mut count) })?;
+        Ok(count as usize)
+    }
+
+    /// Uploads a new effect, returning its id.
+    pub fn upload(&self, effect: &Effect) -> std::io::Result<i16> {
+        let mut raw = effect.to_raw(-1);
+        let _: libc::c_int =
+            check(unsafe { libc::ioctl(self.file.as_raw_fd(), EVIOCSFF, &mut raw) })?;
+        Ok(raw.id)
+    }
+
+    /// Replaces a previously uploaded effect, which may be playing.
+    pub fn update(&self, id: i16, effect: &Effect) -> std::io::Result<()> {
+        let mut raw = effect.to_raw(id);
+        let _: libc::c_int =
+            check(unsafe { libc::ioctl(self.file.as_raw_fd(), EVIOCSFF, &mut raw) })?;
+        Ok(())
+    }
+
+    pub fn erase(&self, id: i16) -> std::io::Result<()> {
+        let id = libc::c_int::from(id);
+        let _: libc::c_int = check(unsafe { libc::ioctl(self.file.as_raw_fd(), EVIOCRMFF, id) })?;
+        Ok(())
+    }
+
+    /// Plays an uploaded effect `count` times.
+    pub fn play(&self, id: i16, count: i32) -> std::io::Result<()> {
+        write_event(self.file.as_raw_fd(), EV_FF_TYPE, id as u16, count)
+    }
+
+    pub fn stop(&self, id: i16) -> std::io::Result<()> {
+        self.play(id, 0)
+    }
+
+    /// Sets the overall strength of all effects, from 0 to `u16::MAX`.
+    pub fn set_gain(&self, gain: u16) -> std::io::Result<()> {
+        write_event(
+            self.file.as_raw_fd(),
+            EV_FF_TYPE,
+            EV_FF::FF_GAIN as u16,
+            gain.into(),
+        )
+    }
+}
+
+impl AsRawFd for FfDevice {
+    fn as_raw_fd(&self) -> RawFd {
+        self.file.as_raw_fd()
+    }
+}
+
+/// A force feedback request made of a virtual device by one of its clients.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FfEvent {
+    /// An effect was uploaded or updated. `effect` is `None` for unsupported effect types.
+    Upload {
+        id: i16,
+        effect: Option<Effect>,
+    },
+    Erase {
+        id: i16,
+    },
+    Play {
+        id: i16,
+        count: i32,
+    },
+    Gain(u16),
+}
+
+struct UInputFd(RawFd);
+
+impl AsRawFd for UInputFd {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0
+    }
+}
+
+/// Serves force feedback requests for a uinput device created with `EV_FF` capabilities, e.g.
+/// through `VirtualDeviceBuilder::rumble`. Uploads and erasures are acknowledged as successful.
+pub struct UInputFf {
+    // Declared before `uinput` so that it is deregistered before the fd is closed.
+    fd: Async<UInputFd>,
+    uinput: UInputDevice,
+}
+
+impl UInputFf {
+    pub fn new(uinput: UInputDevice) -> std::io::Result<Self> {
+        let fd = uinput
+            .as_fd()
+            .ok_or_else(|| std::io::Error::other("uinput device has no fd"))?;
+        Ok(Self {
+            fd: Async::new(UInputFd(fd))?,
+            uinput,
+        })
+    }
+
+    pub fn uinput(&self) -> &UInputDevice {
+        &self.uinput
+    }
+
+    pub fn into_inner(self) -> UInputDevice {
+        self.uinput
+    }
+
+    fn read_event(fd: RawFd) -> std::io::Result<libc::input_event> {
+        let mut event: libc::input_event = unsafe { std::mem::zeroed() };
+        let len = std::mem::size_of::<libc::input_event>();
+        let read = unsafe { libc::read(fd, &mut event as *mut _ as *mut libc::c_void, len) };
+        if read < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        if read as usize != len {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                "short read from uinput",
+            ));
+        }
+        Ok(event)
+    }
+
+    fn upload(fd: RawFd, request_id: u32) -> std::io::Result<FfEvent> {
+        let mut upload: libc::uinput_ff_upload = unsafe { std::mem::zeroed() };
+        upload.request_id = request_id;
+        let _: libc::c_int = check(unsafe { libc::ioctl(fd, UI_BEGIN_FF_UPLOAD, &mut upload) })?;
+        upload.retval = 0;
+        let _: libc::c_int = check(unsafe { libc::ioctl(fd, UI_END_FF_UPLOAD, &upload) })?;
+        Ok(FfEvent::Upload {
+            id: upload.effect.id,
+            effect: Effect::from_raw(&upload.effect),
+        })
+    }
+
+    fn erase(fd: RawFd, request_id: u32) -> std::io::Result<FfEvent> {
+        let mut erase: libc::uinput_ff_erase = unsafe { std::mem::zeroed() };
+        erase.request_id = request_id;
+        let _: libc::c_int = check(unsafe { libc::ioctl(fd, UI_BEGIN_FF_ERASE, &mut erase) })?;
+        erase.retval = 0;
+        let _: libc::c_int = check(unsafe { libc::ioctl(fd, UI_END_FF_ERASE, &erase) })?;
+        Ok(FfEvent::Erase {
+            id: erase.effect_id as i16,
+        })
+    }
+
+    /// Waits for the next request. Uploads and erasures must be served promptly, since the
+    /// requesting client blocks until they are.
+    pub async fn next_request(&self) -> std::io::Result<FfEvent> {
+        loop {
+            let event = self.fd.read_with(|fd| Self::read_event(fd.0)).await?;
+            let request = match (event.type_, event.code) {
+                (EV_UINPUT, UI_FF_UPLOAD) => Self::upload(self.fd.get_ref().0, event.value as u32)?,
+                (EV_UINPUT, UI_FF_ERASE) => Self::erase(self.fd.get_ref().0, event.value as u32)?,
+                (EV_FF_TYPE, code) if code == EV_FF::FF_GAIN as u16 => {
+                    FfEvent::Gain(event.value as u16)
+                }
+                (EV_FF_TYPE, id) => FfEvent::Play {
+                    id: id as i16,
+                    count: event.value,
+                },
+                _ => continue,
+            };
+            return Ok(request);
+        }
+    }
+}
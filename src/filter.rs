@@ -0,0 +1,69 @@
+use evdev_rs::enums::EventCode;
+use evdev_rs::DeviceWrapper;
+
+/// Criteria for selecting devices when enumerating. An empty filter matches every device.
+#[derive(Debug, Clone, Default)]
+pub struct DeviceFilter {
+    name_contains: Option<String>,
+    #[cfg(feature = "regex")]
+    name_regex: Option<regex::Regex>,
+    vendor: Option<u16>,
+    product: Option<u16>,
+    codes: Vec<EventCode>,
+}
+
+impl DeviceFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn name_contains(mut self, name: impl Into<String>) -> Self {
+        self.name_contains = Some(name.into());
+        self
+    }
+
+    #[cfg(feature = "regex")]
+    pub fn name_regex(mut self, regex: regex::Regex) -> Self {
+        self.name_regex = Some(regex);
+        self
+    }
+
+    pub fn vendor(mut self, vendor: u16) -> Self {
+        self.vendor = Some(vendor);
+        self
+    }
+
+    pub fn product(mut self, product: u16) -> Self {
+        self.product = Some(product);
+        self
+    }
+
+    /// Requires the device to support `code`. May be given multiple times.
+    pub fn has_code(mut self, code: EventCode) -> Self {
+        self.codes.push(code);
+        self
+    }
+
+    pub fn matches<D: DeviceWrapper>(&self, device: &D) -> bool {
+        let name = device.name().unwrap_or_default();
+        if let Some(substring) = &self.name_contains {
+            if !name.contains(substring.as_str()) {
+                return false;
+            }
+        }
+        #[cfg(feature = "regex")]
+        {
+            if let Some(regex) = &self.name_regex {
+                if !regex.is_match(name) {
+                    return false;
+                }
+            }
+        }
+        self.vendor
+            .is_none_or(|vendor| device.vendor_id() == vendor)
+            && self
+                .product
+                .is_none_or(|product| device.product_id() == product)
+            && self.codes.iter().all(|code| device.has(code))
+    }
+}
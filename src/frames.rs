@@ -0,0 +1,85 @@
+use crate::Processor;
+use evdev_rs::enums::{EventCode, EV_SYN};
+use evdev_rs::InputEvent;
+use std::collections::VecDeque;
+use std::time::Instant;
+
+/// Groups events into the reports delimited by `SYN_REPORT`, leaving out the marker itself.
+///
+/// Events after the last `SYN_REPORT` when the stream ends don't form a complete report and are
+/// dropped, as is a report cut short by `SYN_DROPPED`. The events a device reports after
+/// `SYN_DROPPED` are libevdev's resync of the device state, which ends in a `SYN_REPORT` of its
+/// own and so forms the next report.
+#[derive(Debug, Default)]
+pub struct Frames {
+    frame: Vec<InputEvent>,
+}
+
+impl Frames {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Processor for Frames {
+    type Output = Vec<InputEvent>;
+
+    fn process(&mut self, event: InputEvent, _now: Instant, out: &mut VecDeque<Vec<InputEvent>>) {
+        match event.event_code {
+            EventCode::EV_SYN(EV_SYN::SYN_REPORT) => out.push_back(std::mem::take(&mut self.frame)),
+            EventCode::EV_SYN(EV_SYN::SYN_DROPPED) => self.frame.clear(),
+            _ => self.frame.push(event),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{key, keys, run, syn};
+    use evdev_rs::enums::EV_KEY::{KEY_A, KEY_B};
+
+    fn summarize(frames: Vec<Vec<InputEvent>>) -> Vec<Vec<(evdev_rs::enums::EV_KEY, i32)>> {
+        frames.iter().map(|frame| keys(frame)).collect()
+    }
+
+    #[test]
+    fn groups_events_into_reports() {
+        let frames = run(
+            vec![
+                key(0, KEY_A, 1),
+                key(0, KEY_B, 1),
+                syn(0),
+                key(10, KEY_A, 0),
+                syn(10),
+                key(20, KEY_B, 0),
+            ],
+            Frames::new(),
+        );
+        assert_eq!(
+            summarize(frames),
+            vec![vec![(KEY_A, 1), (KEY_B, 1)], vec![(KEY_A, 0)]]
+        );
+    }
+
+    #[test]
+    fn discards_report_cut_short_by_syn_dropped() {
+        let dropped = InputEvent {
+            event_code: EventCode::EV_SYN(EV_SYN::SYN_DROPPED),
+            ..syn(10)
+        };
+        let frames = run(
+            vec![
+                key(0, KEY_A, 1),
+                syn(0),
+                key(10, KEY_B, 1),
+                dropped,
+                // The resynced state.
+                key(10, KEY_A, 0),
+                syn(10),
+            ],
+            Frames::new(),
+        );
+        assert_eq!(summarize(frames), vec![vec![(KEY_A, 1)], vec![(KEY_A, 0)]]);
+    }
+}
@@ -0,0 +1,160 @@
+use crate::key_state::kernel_keys;
+use crate::{AsyncDevice, Injector};
+use evdev_rs::enums::{EventCode, EV_KEY};
+use evdev_rs::GrabMode;
+use std::collections::HashSet;
+use std::ops::{Deref, DerefMut};
+use std::os::unix::io::{AsRawFd as _, RawFd};
+use std::sync::{Mutex, MutexGuard, Once, PoisonError, TryLockError};
+
+const EVIOCGRAB: libc::Ioctl = libc::_IOW::<libc::c_int>(b'E' as u32, 0x90);
+
+// Fds currently grabbed through `AsyncDevice::grab`, for `ungrab_all` and the panic hook to
+// release.
+static GRABBED: Mutex<Option<HashSet<RawFd>>> = Mutex::new(None);
+
+fn grabbed() -> MutexGuard<'static, Option<HashSet<RawFd>>> {
+    GRABBED.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+pub(crate) fn track(fd: RawFd, grabbed: bool) {
+    let mut fds = self::grabbed();
+    let fds = fds.get_or_insert_with(HashSet::new);
+    let _: bool = if grabbed {
+        fds.insert(fd)
+    } else {
+        fds.remove(&fd)
+    };
+}
+
+// Grabs or releases `fd` directly rather than through libevdev, for devices whose libevdev
+// handle is owned by another thread.
+#[cfg(feature = "threaded")]
+pub(crate) fn grab_fd(fd: RawFd, grab: GrabMode) -> std::io::Result<()> {
+    let grabbed = matches!(grab, GrabMode::Grab);
+    if unsafe { libc::ioctl(fd, EVIOCGRAB, libc::c_int::from(grabbed)) } < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    track(fd, grabbed);
+    Ok(())
+}
+
+fn release(fds: Option<HashSet<RawFd>>) {
+    for fd in fds.unwrap_or_default() {
+        let _: libc::c_int = unsafe { libc::ioctl(fd, EVIOCGRAB, 0 as libc::c_int) };
+    }
+}
+
+// Releases the grab on `fd` if it's still tracked, i.e. its device hasn't been dropped and the
+// fd can't have been reused.
+pub(crate) fn release_tracked(fd: RawFd) {
+    let mut fds = grabbed();
+    if fds.as_mut().is_some_and(|fds| fds.remove(&fd)) {
+        let _: libc::c_int = unsafe { libc::ioctl(fd, EVIOCGRAB, 0 as libc::c_int) };
+    }
+}
+
+/// Releases the grab of every `AsyncDevice` in the process, e.g. to hand the keyboard back to
+/// the user when something went wrong. Devices grabbed again afterwards are tracked as usual.
+pub fn ungrab_all() {
+    release(grabbed().take());
+}
+
+/// Holds an exclusive grab on an `AsyncDevice`, releasing it when dropped, including while
+/// unwinding from a panic. Dereferences to the device, so events can be read through it.
+pub struct GrabGuard<'a> {
+    device: &'a mut AsyncDevice,
+}
+
+impl AsyncDevice {
+    /// Grabs the device until the returned guard is dropped.
+    pub fn grab_guard(&mut self) -> std::io::Result<GrabGuard<'_>> {
+        self.grab(GrabMode::Grab)?;
+        Ok(GrabGuard { device: self })
+    }
+}
+
+impl AsyncDevice {
+    /// Grabs the device and presses the keys held on it right then on `uinput`, so that their
+    /// releases, which only reach the grabber from now on, release a key that is down. Returns
+    /// the keys pressed.
+    pub fn grab_mirrored<U: Injector>(&mut self, uinput: &U) -> std::io::Result<HashSet<EV_KEY>> {
+        // Grabbing first means a key changing meanwhile is either in the state queried or among
+        // the events read afterwards. A press or release both ways is ignored by the kernel.
+        self.grab(GrabMode::Grab)?;
+        emit_keys(uinput, self.evdev().file().as_raw_fd(), 1)
+    }
+
+    /// Releases the keys held on the device on `uinput`, as their physical releases will go
+    /// elsewhere, and then the grab.
+    pub fn ungrab_mirrored<U: Injector>(&mut self, uinput: &U) -> std::io::Result<()> {
+        let _: HashSet<EV_KEY> = emit_keys(uinput, self.evdev().file().as_raw_fd(), 0)?;
+        self.grab(GrabMode::Ungrab)
+    }
+}
+
+// Injects the keys the kernel reports as held on `fd` with `value`, in one frame, returning
+// them.
+pub(crate) fn emit_keys<U: Injector>(
+    uinput: &U,
+    fd: RawFd,
+    value: i32,
+) -> std::io::Result<HashSet<EV_KEY>> {
+    let held = kernel_keys(fd)?;
+    if held.is_empty() {
+        return Ok(held);
+    }
+    let mut keys = held.iter().copied().collect::<Vec<_>>();
+    keys.sort_by_key(|key| *key as u32);
+    let events = keys
+        .into_iter()
+        .map(|key| (EventCode::EV_KEY(key), value))
+        .collect::<Vec<_>>();
+    uinput.emit(&events)?;
+    Ok(held)
+}
+
+impl Deref for GrabGuard<'_> {
+    type Target = AsyncDevice;
+
+    fn deref(&self) -> &AsyncDevice {
+        self.device
+    }
+}
+
+impl DerefMut for GrabGuard<'_> {
+    fn deref_mut(&mut self) -> &mut AsyncDevice {
+        self.device
+    }
+}
+
+impl Drop for GrabGuard<'_> {
+    fn drop(&mut self) {
+        // Nothing useful can be done about a failure here, and the grab is released when the fd
+        // is closed anyway.
+        let _: std::io::Result<()> = self.device.grab(GrabMode::Ungrab);
+    }
+}
+
+/// Installs a panic hook, chained before any existing one, that releases every grab held by an
+/// `AsyncDevice`, as `ungrab_all` does. Unlike a `GrabGuard` this also works with
+/// `panic = "abort"` and when the panicking thread doesn't own the device. Installing it more
+/// than once has no further effect.
+pub fn install_panic_ungrab() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            // The lock may be held by the panicking thread itself.
+            let fds = match GRABBED.try_lock() {
+                Ok(fds) => Some(fds),
+                Err(TryLockError::Poisoned(e)) => Some(e.into_inner()),
+                Err(TryLockError::WouldBlock) => None,
+            };
+            if let Some(mut fds) = fds {
+                release(fds.take());
+            }
+            previous(info)
+        }));
+    });
+}
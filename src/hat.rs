@@ -0,0 +1,121 @@
+use crate::Processor;
+use evdev_rs::enums::{EventCode, EV_ABS, EV_KEY};
+use evdev_rs::InputEvent;
+use std::collections::VecDeque;
+use std::time::Instant;
+
+// Down and right are positive on the hat.
+const DIRECTIONS: [(EV_ABS, EV_KEY, EV_KEY); 2] = [
+    (
+        EV_ABS::ABS_HAT0X,
+        EV_KEY::BTN_DPAD_LEFT,
+        EV_KEY::BTN_DPAD_RIGHT,
+    ),
+    (
+        EV_ABS::ABS_HAT0Y,
+        EV_KEY::BTN_DPAD_UP,
+        EV_KEY::BTN_DPAD_DOWN,
+    ),
+];
+
+fn button(axis: usize, position: i32) -> Option<EV_KEY> {
+    let (_, negative, positive) = DIRECTIONS[axis];
+    match position {
+        -1 => Some(negative),
+        1 => Some(positive),
+        _ => None,
+    }
+}
+
+/// Turns the ABS_HAT0X/Y hat of a controller into BTN_DPAD_* buttons, for programs expecting
+/// the latter. Going from one direction straight to the opposite one, or from a diagonal to a
+/// neighbouring direction, releases the old button before pressing the new one. Other events
+/// are passed on.
+#[derive(Debug, Default)]
+pub struct HatToDpad {
+    // -1, 0 or 1 on each axis.
+    position: [i32; 2],
+}
+
+impl HatToDpad {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Processor for HatToDpad {
+    type Output = InputEvent;
+
+    fn process(&mut self, input: InputEvent, _now: Instant, out: &mut VecDeque<InputEvent>) {
+        let axis = match DIRECTIONS
+            .iter()
+            .position(|(abs, _, _)| input.event_code == EventCode::EV_ABS(*abs))
+        {
+            Some(axis) => axis,
+            None => return out.push_back(input),
+        };
+        let position = input.value.signum();
+        let old = std::mem::replace(&mut self.position[axis], position);
+        if old == position {
+            return;
+        }
+        for (button, value) in [(button(axis, old), 0), (button(axis, position), 1)] {
+            if let Some(button) = button {
+                out.push_back(InputEvent {
+                    event_code: EventCode::EV_KEY(button),
+                    value,
+                    ..input
+                });
+            }
+        }
+    }
+}
+
+/// Turns BTN_DPAD_* buttons into an ABS_HAT0X/Y hat with a range of [-1, 1], for programs
+/// expecting the latter. Opposite buttons held together center their axis, and releasing one of
+/// them moves the hat towards the other. Other events are passed on.
+#[derive(Debug, Default)]
+pub struct DpadToHat {
+    // Whether the negative and the positive button of each axis are held.
+    held: [(bool, bool); 2],
+    position: [i32; 2],
+}
+
+impl DpadToHat {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Processor for DpadToHat {
+    type Output = InputEvent;
+
+    fn process(&mut self, input: InputEvent, _now: Instant, out: &mut VecDeque<InputEvent>) {
+        let key = match input.event_code {
+            EventCode::EV_KEY(key) => key,
+            _ => return out.push_back(input),
+        };
+        let axis = match DIRECTIONS
+            .iter()
+            .position(|(_, negative, positive)| key == *negative || key == *positive)
+        {
+            Some(axis) => axis,
+            None => return out.push_back(input),
+        };
+        let (abs, negative, _) = DIRECTIONS[axis];
+        let held = &mut self.held[axis];
+        if key == negative {
+            held.0 = input.value != 0;
+        } else {
+            held.1 = input.value != 0;
+        }
+        let position = i32::from(held.1) - i32::from(held.0);
+        if std::mem::replace(&mut self.position[axis], position) != position {
+            out.push_back(InputEvent {
+                event_code: EventCode::EV_ABS(abs),
+                value: position,
+                ..input
+            });
+        }
+    }
+}
@@ -0,0 +1,90 @@
+use async_io::Timer;
+use futures::{Future as _, Stream, StreamExt as _};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IdleState {
+    Idle,
+    Active,
+}
+
+/// Reports transitions between activity and inactivity of an event stream, see `idle_watcher`.
+pub struct IdleWatcher<S> {
+    events: S,
+    threshold: Duration,
+    last_activity: Instant,
+    timer: Timer,
+    idle: bool,
+}
+
+/// Watches `events`, e.g. `all_devices_hotplug()` or `all_devices_matching(filter)`, and reports
+/// `Idle` once no event has arrived for `threshold`, then `Active` with the next event. The
+/// stream starts out active, and ends with `events`.
+pub fn idle_watcher<S, T, E>(events: S, threshold: Duration) -> IdleWatcher<S>
+where
+    S: Stream<Item = Result<T, E>> + Unpin,
+{
+    let now = Instant::now();
+    IdleWatcher {
+        events,
+        threshold,
+        last_activity: now,
+        timer: Timer::at(now + threshold),
+        idle: false,
+    }
+}
+
+impl<S> IdleWatcher<S> {
+    pub fn state(&self) -> IdleState {
+        if self.idle {
+            IdleState::Idle
+        } else {
+            IdleState::Active
+        }
+    }
+
+    pub fn get_ref(&self) -> &S {
+        &self.events
+    }
+}
+
+impl<S, T, E> Stream for IdleWatcher<S>
+where
+    S: Stream<Item = Result<T, E>> + Unpin,
+{
+    type Item = Result<IdleState, E>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        loop {
+            match this.events.poll_next_unpin(cx) {
+                Poll::Ready(Some(Ok(_))) => {
+                    this.last_activity = Instant::now();
+                    if std::mem::replace(&mut this.idle, false) {
+                        this.timer.set_at(this.last_activity + this.threshold);
+                        return Poll::Ready(Some(Ok(IdleState::Active)));
+                    }
+                }
+                Poll::Ready(Some(Err(e))) => return Poll::Ready(Some(Err(e))),
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => break,
+            }
+        }
+        if this.idle {
+            return Poll::Pending;
+        }
+        // The timer is only moved when it fires rather than on every event, which keeps busy
+        // devices cheap to watch.
+        while Pin::new(&mut this.timer).poll(cx).is_ready() {
+            let deadline = this.last_activity + this.threshold;
+            if Instant::now() >= deadline {
+                this.idle = true;
+                return Poll::Ready(Some(Ok(IdleState::Idle)));
+            }
+            this.timer.set_at(deadline);
+        }
+        Poll::Pending
+    }
+}
@@ -0,0 +1,33 @@
+use evdev_rs::enums::EventType;
+use evdev_rs::DeviceWrapper;
+
+/// Identifying information and capabilities of an input device.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DeviceInfo {
+    pub name: String,
+    pub phys: Option<String>,
+    pub uniq: Option<String>,
+    pub bustype: u16,
+    pub vendor: u16,
+    pub product: u16,
+    pub version: u16,
+    pub event_types: Vec<EventType>,
+}
+
+impl DeviceInfo {
+    pub fn from_device<D: DeviceWrapper>(device: &D) -> Self {
+        Self {
+            name: device.name().unwrap_or_default().to_string(),
+            phys: device.phys().map(str::to_string),
+            uniq: device.uniq().map(str::to_string),
+            bustype: device.bustype(),
+            vendor: device.vendor_id(),
+            product: device.product_id(),
+            version: device.version(),
+            event_types: EventType::EV_SYN
+                .iter()
+                .filter(|event_type| device.has(event_type))
+                .collect(),
+        }
+    }
+}
@@ -0,0 +1,230 @@
+use crate::{UnicodeEntry, WHEEL_DETENT};
+use evdev_rs::enums::{EventCode, EV_ABS, EV_KEY, EV_MSC, EV_REL, EV_SYN};
+use evdev_rs::{InputEvent, UInputDevice};
+use std::cell::RefCell;
+
+fn wheel_events(hi_res: (EV_REL, i32), detents: (EV_REL, i32)) -> Vec<(EventCode, i32)> {
+    [hi_res, detents]
+        .iter()
+        .filter(|(_, value)| *value != 0)
+        .map(|(rel, value)| (EventCode::EV_REL(*rel), *value))
+        .collect()
+}
+
+/// A sink for input events, e.g. a uinput device. Everything built on top of injection in this
+/// crate is generic over it, so other backends or a `MockInjector` can stand in for uinput.
+pub trait Injector {
+    fn inject_event(&self, event_code: EventCode, value: i32) -> std::io::Result<()>;
+
+    /// Like `inject_event`, but with an explicit timestamp instead of zero. Note that the kernel
+    /// restamps events written to uinput devices, so this only matters to implementations that
+    /// record or forward events elsewhere.
+    fn inject_event_at(
+        &self,
+        event_code: EventCode,
+        value: i32,
+        _time: evdev_rs::TimeVal,
+    ) -> std::io::Result<()> {
+        self.inject_event(event_code, value)
+    }
+
+    fn inject_key_press(&self, btn: EV_KEY) -> std::io::Result<()> {
+        self.inject_event(EventCode::EV_KEY(btn), 1)?;
+        self.inject_event(EventCode::EV_SYN(EV_SYN::SYN_REPORT), 0)?;
+        self.inject_event(EventCode::EV_KEY(btn), 0)?;
+        self.inject_event(EventCode::EV_SYN(EV_SYN::SYN_REPORT), 0)?;
+        Ok(())
+    }
+
+    /// Emits a single key event as its own frame.
+    fn key(&self, key: EV_KEY, value: i32) -> std::io::Result<()> {
+        self.emit(&[(EventCode::EV_KEY(key), value)])
+    }
+
+    /// Emits a key event preceded by its scancode, for applications that read scancodes.
+    fn key_with_scancode(&self, key: EV_KEY, scancode: i32, value: i32) -> std::io::Result<()> {
+        self.emit(&[
+            (EventCode::EV_MSC(EV_MSC::MSC_SCAN), scancode),
+            (EventCode::EV_KEY(key), value),
+        ])
+    }
+
+    fn rel(&self, rel: EV_REL, value: i32) -> std::io::Result<()> {
+        self.emit(&[(EventCode::EV_REL(rel), value)])
+    }
+
+    fn abs(&self, abs: EV_ABS, value: i32) -> std::io::Result<()> {
+        self.emit(&[(EventCode::EV_ABS(abs), value)])
+    }
+
+    fn inject_xy(
+        &self,
+        (abs_x, abs_y): (EV_ABS, EV_ABS),
+        (x, y): (i32, i32),
+    ) -> std::io::Result<()> {
+        self.emit(&[(EventCode::EV_ABS(abs_x), x), (EventCode::EV_ABS(abs_y), y)])
+    }
+
+    fn inject_rel_move(&self, dx: i32, dy: i32) -> std::io::Result<()> {
+        self.emit(&[
+            (EventCode::EV_REL(EV_REL::REL_X), dx),
+            (EventCode::EV_REL(EV_REL::REL_Y), dy),
+        ])
+    }
+
+    /// Scrolls by `amount` detents, with the matching high-resolution event for consumers like
+    /// libinput, which ignore REL_WHEEL on devices that have REL_WHEEL_HI_RES.
+    fn inject_scroll(&self, amount: i32) -> std::io::Result<()> {
+        self.inject_scroll_hi_res((amount * WHEEL_DETENT, amount))
+    }
+
+    fn inject_hscroll(&self, amount: i32) -> std::io::Result<()> {
+        self.inject_hscroll_hi_res((amount * WHEEL_DETENT, amount))
+    }
+
+    /// Scrolls by high-resolution units and detents, as returned by `HiResWheel::scroll`. Either
+    /// is left out if zero.
+    fn inject_scroll_hi_res(&self, (hi_res, detents): (i32, i32)) -> std::io::Result<()> {
+        self.emit(&wheel_events(
+            (EV_REL::REL_WHEEL_HI_RES, hi_res),
+            (EV_REL::REL_WHEEL, detents),
+        ))
+    }
+
+    fn inject_hscroll_hi_res(&self, (hi_res, detents): (i32, i32)) -> std::io::Result<()> {
+        self.emit(&wheel_events(
+            (EV_REL::REL_HWHEEL_HI_RES, hi_res),
+            (EV_REL::REL_HWHEEL, detents),
+        ))
+    }
+
+    fn inject_click(&self, btn: EV_KEY) -> std::io::Result<()> {
+        self.inject_key_press(btn)
+    }
+
+    /// Types ASCII text as on a US layout, holding shift where needed. Fails without injecting
+    /// anything if `text` contains characters that can't be typed. See `Macro::from_text` for
+    /// typing with delays between keys.
+    fn type_str(&self, text: &str) -> std::io::Result<()> {
+        for (event_code, value) in crate::text::text_frames(text)? {
+            self.emit(&[(event_code, value)])?;
+        }
+        Ok(())
+    }
+
+    /// Types a single character: as on a US layout if it has a key there, or else by its code
+    /// point with Ctrl+Shift+U, for IBus and GTK applications.
+    fn type_char(&self, c: char) -> std::io::Result<()> {
+        self.type_char_with(c, &UnicodeEntry::default())
+    }
+
+    /// Like `type_char`, but entering characters without a key with `entry`.
+    fn type_char_with(&self, c: char, entry: &UnicodeEntry) -> std::io::Result<()> {
+        for (event_code, value) in crate::text::char_frames(c, entry) {
+            self.emit(&[(event_code, value)])?;
+        }
+        Ok(())
+    }
+
+    /// Writes all events followed by a single SYN_REPORT, so they are delivered as one frame.
+    fn emit(&self, events: &[(EventCode, i32)]) -> std::io::Result<()> {
+        for (event_code, value) in events {
+            self.inject_event(*event_code, *value)?;
+        }
+        self.inject_event(EventCode::EV_SYN(EV_SYN::SYN_REPORT), 0)?;
+        Ok(())
+    }
+}
+
+impl<T: Injector + ?Sized> Injector for &T {
+    fn inject_event(&self, event_code: EventCode, value: i32) -> std::io::Result<()> {
+        (**self).inject_event(event_code, value)
+    }
+
+    fn inject_event_at(
+        &self,
+        event_code: EventCode,
+        value: i32,
+        time: evdev_rs::TimeVal,
+    ) -> std::io::Result<()> {
+        (**self).inject_event_at(event_code, value, time)
+    }
+}
+
+impl<T: Injector + ?Sized> Injector for Box<T> {
+    fn inject_event(&self, event_code: EventCode, value: i32) -> std::io::Result<()> {
+        (**self).inject_event(event_code, value)
+    }
+
+    fn inject_event_at(
+        &self,
+        event_code: EventCode,
+        value: i32,
+        time: evdev_rs::TimeVal,
+    ) -> std::io::Result<()> {
+        (**self).inject_event_at(event_code, value, time)
+    }
+}
+
+impl Injector for UInputDevice {
+    fn inject_event(&self, event_code: EventCode, value: i32) -> std::io::Result<()> {
+        self.inject_event_at(event_code, value, crate::ZERO_TIME)
+    }
+
+    fn inject_event_at(
+        &self,
+        event_code: EventCode,
+        value: i32,
+        time: evdev_rs::TimeVal,
+    ) -> std::io::Result<()> {
+        self.write_event(&InputEvent {
+            event_code,
+            value,
+            time,
+        })
+    }
+}
+
+/// Collects injected events instead of delivering them anywhere, for testing code built on
+/// `Injector` without uinput access.
+#[derive(Debug, Default)]
+pub struct MockInjector {
+    events: RefCell<Vec<(EventCode, i32)>>,
+}
+
+impl MockInjector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// All events injected so far, including SYN_REPORTs.
+    pub fn events(&self) -> Vec<(EventCode, i32)> {
+        self.events.borrow().clone()
+    }
+
+    /// The events injected so far, split into frames at each SYN_REPORT, which is left out. Events
+    /// after the last SYN_REPORT are left out as well.
+    pub fn frames(&self) -> Vec<Vec<(EventCode, i32)>> {
+        let mut frames = Vec::new();
+        let mut frame = Vec::new();
+        for (event_code, value) in self.events.borrow().iter() {
+            match event_code {
+                EventCode::EV_SYN(EV_SYN::SYN_REPORT) => frames.push(std::mem::take(&mut frame)),
+                _ => frame.push((*event_code, *value)),
+            }
+        }
+        frames
+    }
+
+    /// Returns the events injected so far and forgets them.
+    pub fn take(&self) -> Vec<(EventCode, i32)> {
+        std::mem::take(&mut *self.events.borrow_mut())
+    }
+}
+
+impl Injector for MockInjector {
+    fn inject_event(&self, event_code: EventCode, value: i32) -> std::io::Result<()> {
+        self.events.borrow_mut().push((event_code, value));
+        Ok(())
+    }
+}
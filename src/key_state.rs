@@ -0,0 +1,53 @@
+use crate::AsyncDevice;
+use evdev_rs::enums::{int_to_ev_key, EventCode, EV_KEY};
+use evdev_rs::DeviceWrapper;
+use std::collections::HashSet;
+use std::os::unix::io::RawFd;
+
+pub(crate) fn key_state<D: DeviceWrapper + ?Sized>(device: &D, key: EV_KEY) -> bool {
+    device
+        .event_value(&EventCode::EV_KEY(key))
+        .is_some_and(|value| value != 0)
+}
+
+pub(crate) fn current_keys<D: DeviceWrapper + ?Sized>(device: &D) -> HashSet<EV_KEY> {
+    EventCode::EV_KEY(EV_KEY::KEY_RESERVED)
+        .iter()
+        .take_while(|code| matches!(code, EventCode::EV_KEY(_)))
+        .filter_map(|code| match code {
+            EventCode::EV_KEY(key) if device.event_value(&code).is_some_and(|value| value != 0) => {
+                Some(key)
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+impl AsyncDevice {
+    /// Whether `key` is held, as of the last event read from the device. libevdev queries the
+    /// kernel when the device is opened, so this includes keys that were already held then.
+    pub fn key_state(&self, key: EV_KEY) -> bool {
+        key_state(self.evdev(), key)
+    }
+
+    /// All keys held, as of the last event read from the device.
+    pub fn current_keys(&self) -> HashSet<EV_KEY> {
+        current_keys(self.evdev())
+    }
+}
+
+const KEY_BYTES: usize = EV_KEY::KEY_MAX as usize / 8 + 1;
+const EVIOCGKEY: libc::Ioctl = libc::_IOR::<[u8; KEY_BYTES]>(b'E' as u32, 0x18);
+
+/// Queries the kernel for the held keys, bypassing libevdev's state, which goes stale while
+/// another process has the device grabbed.
+pub(crate) fn kernel_keys(fd: RawFd) -> std::io::Result<HashSet<EV_KEY>> {
+    let mut bits = [0u8; KEY_BYTES];
+    if unsafe { libc::ioctl(fd, EVIOCGKEY, bits.as_mut_ptr()) } < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok((0..KEY_BYTES * 8)
+        .filter(|bit| bits[bit / 8] & (1 << (bit % 8)) != 0)
+        .filter_map(|bit| int_to_ev_key(bit as u32))
+        .collect())
+}
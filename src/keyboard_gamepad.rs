@@ -0,0 +1,272 @@
+use crate::{AsyncDevice, ControllerPreset, EventStreamExt as _, Injector as _};
+use crate::{DeviceError, Processor, VirtualDeviceBuilder};
+use evdev_rs::enums::{EventCode, EV_ABS, EV_KEY};
+use evdev_rs::{GrabMode, InputEvent};
+use futures::TryStreamExt as _;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum KeyboardGamepadError {
+    #[error("failed to create uinput device")]
+    CreateUInput(#[source] std::io::Error),
+    #[error("failed to grab device")]
+    Grab(#[source] std::io::Error),
+    #[error("error when reading an event")]
+    ReadEvent(#[source] DeviceError),
+    #[error("failed to inject event")]
+    Inject(#[source] std::io::Error),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Stick {
+    Left,
+    Right,
+}
+
+impl Stick {
+    pub(crate) fn axes(self) -> (EV_ABS, EV_ABS) {
+        match self {
+            Stick::Left => (EV_ABS::ABS_X, EV_ABS::ABS_Y),
+            Stick::Right => (EV_ABS::ABS_RX, EV_ABS::ABS_RY),
+        }
+    }
+}
+
+/// What `KeyboardGamepad` outputs, with axis values in [-1.0, 1.0] as for `AbsInjector`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum GamepadEvent {
+    Button(EV_KEY, bool),
+    /// Axes changed in the same frame.
+    Axes(Vec<(EV_ABS, f64)>),
+}
+
+/// Turns a keyboard into a gamepad. Stick keys deflect a stick while held, their directions
+/// adding up, with the deflection ramping up over `ramp_up` to emulate an analog stick being
+/// pushed, and back to the center over `release`. Button keys press gamepad buttons and trigger
+/// keys pull triggers all the way. Other keys are dropped.
+///
+/// `run` grabs a keyboard and drives a virtual controller with it.
+#[derive(Debug)]
+pub struct KeyboardGamepad {
+    stick_keys: HashMap<EV_KEY, (Stick, (f64, f64))>,
+    buttons: HashMap<EV_KEY, EV_KEY>,
+    triggers: HashMap<EV_KEY, EV_ABS>,
+    ramp_up: Duration,
+    release: Duration,
+    tick: Duration,
+    held: HashSet<EV_KEY>,
+    positions: HashMap<Stick, (f64, f64)>,
+    last_tick: Option<Instant>,
+}
+
+impl Default for KeyboardGamepad {
+    fn default() -> Self {
+        Self {
+            stick_keys: HashMap::new(),
+            buttons: HashMap::new(),
+            triggers: HashMap::new(),
+            ramp_up: Duration::from_millis(100),
+            release: Duration::from_millis(50),
+            tick: Duration::from_millis(5),
+            held: HashSet::new(),
+            positions: HashMap::new(),
+            last_tick: None,
+        }
+    }
+}
+
+impl KeyboardGamepad {
+    /// No keys mapped.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// WASD for the left stick and the arrow keys for the right one, J, K, U and I for the south,
+    /// east, west and north buttons, Q and E for the bumpers, Z and C for the triggers, Enter
+    /// for start and Backspace for select.
+    pub fn wasd() -> Self {
+        Self::new()
+            .stick_key(EV_KEY::KEY_W, Stick::Left, (0.0, -1.0))
+            .stick_key(EV_KEY::KEY_A, Stick::Left, (-1.0, 0.0))
+            .stick_key(EV_KEY::KEY_S, Stick::Left, (0.0, 1.0))
+            .stick_key(EV_KEY::KEY_D, Stick::Left, (1.0, 0.0))
+            .stick_key(EV_KEY::KEY_UP, Stick::Right, (0.0, -1.0))
+            .stick_key(EV_KEY::KEY_LEFT, Stick::Right, (-1.0, 0.0))
+            .stick_key(EV_KEY::KEY_DOWN, Stick::Right, (0.0, 1.0))
+            .stick_key(EV_KEY::KEY_RIGHT, Stick::Right, (1.0, 0.0))
+            .button(EV_KEY::KEY_J, EV_KEY::BTN_SOUTH)
+            .button(EV_KEY::KEY_K, EV_KEY::BTN_EAST)
+            .button(EV_KEY::KEY_U, EV_KEY::BTN_WEST)
+            .button(EV_KEY::KEY_I, EV_KEY::BTN_NORTH)
+            .button(EV_KEY::KEY_Q, EV_KEY::BTN_TL)
+            .button(EV_KEY::KEY_E, EV_KEY::BTN_TR)
+            .button(EV_KEY::KEY_ENTER, EV_KEY::BTN_START)
+            .button(EV_KEY::KEY_BACKSPACE, EV_KEY::BTN_SELECT)
+            .trigger(EV_KEY::KEY_Z, EV_ABS::ABS_Z)
+            .trigger(EV_KEY::KEY_C, EV_ABS::ABS_RZ)
+    }
+
+    /// Deflects `stick` along `direction` while `key` is held, y pointing down.
+    pub fn stick_key(mut self, key: EV_KEY, stick: Stick, direction: (f64, f64)) -> Self {
+        let _: Option<(Stick, (f64, f64))> = self.stick_keys.insert(key, (stick, direction));
+        self
+    }
+
+    /// Holds `button` while `key` is held.
+    pub fn button(mut self, key: EV_KEY, button: EV_KEY) -> Self {
+        let _: Option<EV_KEY> = self.buttons.insert(key, button);
+        self
+    }
+
+    /// Pulls the trigger axis `abs` all the way while `key` is held.
+    pub fn trigger(mut self, key: EV_KEY, abs: EV_ABS) -> Self {
+        let _: Option<EV_ABS> = self.triggers.insert(key, abs);
+        self
+    }
+
+    /// How long a stick takes to go from the center to full deflection, and back. Zero moves it
+    /// instantly.
+    pub fn smoothing(mut self, ramp_up: Duration, release: Duration) -> Self {
+        self.ramp_up = ramp_up;
+        self.release = release;
+        self
+    }
+
+    /// How often stick positions are updated while moving.
+    pub fn tick(mut self, tick: Duration) -> Self {
+        self.tick = tick;
+        self
+    }
+
+    /// Where the held keys push `stick`, clamped to the unit circle.
+    fn target(&self, stick: Stick) -> (f64, f64) {
+        let (x, y) = self
+            .held
+            .iter()
+            .filter_map(|key| self.stick_keys.get(key))
+            .filter(|(key_stick, _)| *key_stick == stick)
+            .fold((0.0, 0.0), |(x, y), (_, (dx, dy))| (x + dx, y + dy));
+        let length = x.hypot(y);
+        if length > 1.0 {
+            (x / length, y / length)
+        } else {
+            (x, y)
+        }
+    }
+
+    fn approach(&self, current: f64, target: f64, elapsed: Duration) -> f64 {
+        let pushing = target.abs() > current.abs() && target * current >= 0.0;
+        let time = if pushing { self.ramp_up } else { self.release };
+        if time.is_zero() {
+            return target;
+        }
+        let step = elapsed.as_secs_f64() / time.as_secs_f64();
+        if (target - current).abs() <= step {
+            target
+        } else {
+            current + step * (target - current).signum()
+        }
+    }
+
+    /// Moves the sticks towards their targets by the time elapsed since the last update.
+    fn advance(&mut self, now: Instant, out: &mut VecDeque<GamepadEvent>) {
+        let elapsed = self
+            .last_tick
+            .map(|last_tick| now.saturating_duration_since(last_tick))
+            .unwrap_or_default();
+        let mut axes = Vec::new();
+        let mut settled = true;
+        for stick in [Stick::Left, Stick::Right].iter().copied() {
+            let (x, y) = self.positions.get(&stick).copied().unwrap_or_default();
+            let (target_x, target_y) = self.target(stick);
+            let position = (
+                self.approach(x, target_x, elapsed),
+                self.approach(y, target_y, elapsed),
+            );
+            settled &= position == (target_x, target_y);
+            if position != (x, y) {
+                let (abs_x, abs_y) = stick.axes();
+                axes.push((abs_x, position.0));
+                axes.push((abs_y, position.1));
+                let _: Option<(f64, f64)> = self.positions.insert(stick, position);
+            }
+        }
+        self.last_tick = if settled { None } else { Some(now) };
+        if !axes.is_empty() {
+            out.push_back(GamepadEvent::Axes(axes));
+        }
+    }
+
+    /// Grabs `keyboard` and drives a virtual controller of the kind `preset` with it until the
+    /// keyboard's event stream ends, or its escape sequence is pressed.
+    pub async fn run(
+        self,
+        mut keyboard: AsyncDevice,
+        preset: ControllerPreset,
+    ) -> Result<(), KeyboardGamepadError> {
+        let gamepad = VirtualDeviceBuilder::new()
+            .preset(preset)
+            .build_abs_injector()
+            .map_err(KeyboardGamepadError::CreateUInput)?;
+        keyboard
+            .grab(GrabMode::Grab)
+            .map_err(KeyboardGamepadError::Grab)?;
+        let mut events = keyboard.escape_hatch().process(self);
+        while let Some(event) = events
+            .try_next()
+            .await
+            .map_err(KeyboardGamepadError::ReadEvent)?
+        {
+            match event {
+                GamepadEvent::Button(button, pressed) => gamepad
+                    .uinput()
+                    .key(button, pressed.into())
+                    .map_err(KeyboardGamepadError::Inject)?,
+                GamepadEvent::Axes(axes) => gamepad
+                    .set_axes(&axes)
+                    .map_err(KeyboardGamepadError::Inject)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Processor for KeyboardGamepad {
+    type Output = GamepadEvent;
+
+    fn process(&mut self, input: InputEvent, now: Instant, out: &mut VecDeque<GamepadEvent>) {
+        let (key, pressed) = match (input.event_code, input.value) {
+            (EventCode::EV_KEY(key), 0) => (key, false),
+            (EventCode::EV_KEY(key), 1) => (key, true),
+            _ => return,
+        };
+        if self.stick_keys.contains_key(&key) {
+            let _: bool = if pressed {
+                self.held.insert(key)
+            } else {
+                self.held.remove(&key)
+            };
+            if self.last_tick.is_none() {
+                self.last_tick = Some(now);
+            }
+            self.advance(now, out);
+        } else if let Some(button) = self.buttons.get(&key) {
+            out.push_back(GamepadEvent::Button(*button, pressed));
+        } else if let Some(abs) = self.triggers.get(&key) {
+            out.push_back(GamepadEvent::Axes(vec![(
+                *abs,
+                if pressed { 1.0 } else { -1.0 },
+            )]));
+        }
+    }
+
+    fn deadline(&self) -> Option<Instant> {
+        self.last_tick.map(|last_tick| last_tick + self.tick)
+    }
+
+    fn timeout(&mut self, now: Instant, out: &mut VecDeque<GamepadEvent>) {
+        self.advance(now, out);
+    }
+}
@@ -0,0 +1,167 @@
+//! Round-trip latency measurements of uinput injection, and of event pipelines built with this
+//! crate.
+
+use crate::{AsyncDevice, DeviceError, Injector, OpenError};
+use evdev_rs::enums::{EventCode, EventType, EV_MSC};
+use evdev_rs::{DeviceWrapper as _, InputEvent, UInputDevice, UninitDevice};
+use futures::{Stream, TryStreamExt as _};
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum LatencyError {
+    #[error("failed to create uinput device")]
+    CreateUInput(#[source] std::io::Error),
+    #[error("uinput device has no event node")]
+    NoDevnode,
+    #[error("failed to open the uinput device's event node")]
+    Open(#[source] OpenError),
+    #[error("failed to inject event")]
+    Inject(#[source] std::io::Error),
+    #[error("error when reading an event")]
+    ReadEvent(#[source] DeviceError),
+    #[error("event stream ended before the injected event came back")]
+    StreamEnded,
+}
+
+/// Latency samples, with summary statistics.
+#[derive(Debug, Clone, Default)]
+pub struct LatencyStats {
+    samples: Vec<Duration>,
+}
+
+impl LatencyStats {
+    pub fn new(mut samples: Vec<Duration>) -> Self {
+        samples.sort();
+        Self { samples }
+    }
+
+    /// Samples in ascending order.
+    pub fn samples(&self) -> &[Duration] {
+        &self.samples
+    }
+
+    pub fn min(&self) -> Option<Duration> {
+        self.samples.first().copied()
+    }
+
+    pub fn max(&self) -> Option<Duration> {
+        self.samples.last().copied()
+    }
+
+    pub fn mean(&self) -> Option<Duration> {
+        if self.samples.is_empty() {
+            return None;
+        }
+        Some(self.samples.iter().sum::<Duration>() / self.samples.len() as u32)
+    }
+
+    /// The sample below which `percentile` percent of samples fall, by the nearest rank method.
+    pub fn percentile(&self, percentile: f64) -> Option<Duration> {
+        if self.samples.is_empty() {
+            return None;
+        }
+        let rank = (percentile / 100.0 * self.samples.len() as f64).ceil() as usize;
+        Some(self.samples[rank.clamp(1, self.samples.len()) - 1])
+    }
+
+    pub fn p50(&self) -> Option<Duration> {
+        self.percentile(50.0)
+    }
+
+    pub fn p99(&self) -> Option<Duration> {
+        self.percentile(99.0)
+    }
+
+    /// Sample counts in buckets of width `bucket`, from zero up to the bucket holding the
+    /// largest sample, each paired with the lower bound of its bucket.
+    pub fn histogram(&self, bucket: Duration) -> Vec<(Duration, usize)> {
+        let bucket_nanos = bucket.as_nanos().max(1);
+        let mut counts = Vec::<usize>::new();
+        for sample in &self.samples {
+            let index = (sample.as_nanos() / bucket_nanos) as usize;
+            if counts.len() <= index {
+                counts.resize(index + 1, 0);
+            }
+            counts[index] += 1;
+        }
+        counts
+            .into_iter()
+            .enumerate()
+            .map(|(index, count)| (bucket * index as u32, count))
+            .collect()
+    }
+}
+
+/// Injects `inject` through `uinput` `iterations` times, each time timing how long it takes until
+/// an event with code `expect` comes out of `events`. Injected values alternate between 1 and 0,
+/// so keys don't stay held.
+///
+/// To measure a pipeline, inject into the device it reads from and pass the output of the
+/// pipeline, e.g. the event node of its uinput device, as `events`.
+pub async fn round_trip<U, S>(
+    uinput: &U,
+    events: &mut S,
+    inject: EventCode,
+    expect: EventCode,
+    iterations: usize,
+) -> Result<LatencyStats, LatencyError>
+where
+    U: Injector,
+    S: Stream<Item = Result<InputEvent, DeviceError>> + Unpin,
+{
+    let mut samples = Vec::with_capacity(iterations);
+    for iteration in 0..iterations {
+        let value = (iteration % 2 == 0).into();
+        let start = Instant::now();
+        uinput
+            .emit(&[(inject, value)])
+            .map_err(LatencyError::Inject)?;
+        loop {
+            let event = events
+                .try_next()
+                .await
+                .map_err(LatencyError::ReadEvent)?
+                .ok_or(LatencyError::StreamEnded)?;
+            if event.event_code == expect {
+                break;
+            }
+        }
+        samples.push(start.elapsed());
+    }
+    Ok(LatencyStats::new(samples))
+}
+
+// The event node is created before udev has had a chance to set its permissions.
+async fn open_retrying(devnode: &str) -> Result<AsyncDevice, LatencyError> {
+    let mut attempts = 0;
+    loop {
+        match AsyncDevice::new(devnode) {
+            Ok(device) => return Ok(device),
+            Err(e) if attempts >= 50 => return Err(LatencyError::Open(e)),
+            Err(_) => {
+                attempts += 1;
+                let _: Instant = async_io::Timer::after(Duration::from_millis(20)).await;
+            }
+        }
+    }
+}
+
+/// Measures the round trip from writing to a uinput device to reading the event back from its
+/// event node, which is the overhead every uinput-based pipeline pays at least once. The device
+/// only reports `MSC_SCAN`, so nothing else on the system reacts to it.
+pub async fn measure_uinput(iterations: usize) -> Result<LatencyStats, LatencyError> {
+    let template = UninitDevice::new().ok_or_else(|| {
+        LatencyError::CreateUInput(std::io::Error::other("failed to allocate libevdev device"))
+    })?;
+    template.set_name("evdev-utils latency probe");
+    let code = EventCode::EV_MSC(EV_MSC::MSC_SCAN);
+    template
+        .enable(&EventType::EV_MSC)
+        .and_then(|()| template.enable(&code))
+        .map_err(LatencyError::CreateUInput)?;
+    let uinput = UInputDevice::create_from_device(&template).map_err(LatencyError::CreateUInput)?;
+    let devnode = uinput.devnode().ok_or(LatencyError::NoDevnode)?;
+    let mut device = open_retrying(devnode).await?;
+    round_trip(&uinput, &mut device, code, code, iterations).await
+}
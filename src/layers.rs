@@ -0,0 +1,497 @@
+//! QMK-style keymap layers: momentary, toggle and one-shot layers stacked over a base layer.
+//!
+//! `Layers` is a `Processor`, so it can sit in front of a `Proxy` with `Proxy::run_processor` or
+//! on any event stream with `EventStreamExt::process`:
+//!
+//! ```no_run
+//! # use evdev_rs::enums::EV_KEY;
+//! # use evdev_utils::layers::{Action, Layer, Layers};
+//! # async fn run(proxy: evdev_utils::Proxy) -> Result<(), evdev_utils::ProxyError> {
+//! let layers = Layers::new(Layer::new().key(EV_KEY::KEY_CAPSLOCK, Action::Momentary(1)))
+//!     .layer(
+//!         Layer::new()
+//!             .key(EV_KEY::KEY_H, Action::Key(EV_KEY::KEY_LEFT))
+//!             .key(EV_KEY::KEY_J, Action::Key(EV_KEY::KEY_DOWN))
+//!             .key(EV_KEY::KEY_K, Action::Key(EV_KEY::KEY_UP))
+//!             .key(EV_KEY::KEY_L, Action::Key(EV_KEY::KEY_RIGHT)),
+//!     );
+//! proxy.run_processor(layers).await
+//! # }
+//! ```
+//!
+//! A `LayerIndicator` is told whenever the active layers change, e.g. a `LedIndicator` lighting
+//! keyboard LEDs for them.
+
+use crate::remap::{Diagnostic, Location, Problem};
+use crate::{hid_scancode, LedExt, Processor};
+use evdev_rs::enums::{EventCode, EV_KEY, EV_LED, EV_MSC};
+use evdev_rs::InputEvent;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::time::Instant;
+
+/// What a key does on a layer. Layers are referred to by their index, the base layer being 0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// Acts as another key.
+    Key(EV_KEY),
+    /// Activates a layer while held (QMK's `MO`).
+    Momentary(usize),
+    /// Activates or deactivates a layer on each press (QMK's `TG`).
+    Toggle(usize),
+    /// Activates a layer for the next key press, or while held if other keys are pressed
+    /// meanwhile (QMK's `OSL`).
+    OneShot(usize),
+    /// Falls through to the next active layer below. Keys without an action on a layer are
+    /// transparent, and keys transparent on every layer act as themselves.
+    Transparent,
+    /// Does nothing.
+    Block,
+}
+
+/// The actions of one layer.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Layer {
+    keys: HashMap<EV_KEY, Action>,
+}
+
+impl Layer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn key(mut self, key: EV_KEY, action: Action) -> Self {
+        let _: Option<Action> = self.keys.insert(key, action);
+        self
+    }
+
+    pub fn get(&self, key: EV_KEY) -> Action {
+        self.keys.get(&key).copied().unwrap_or(Action::Transparent)
+    }
+}
+
+/// Shows the user which layers are active.
+pub trait LayerIndicator {
+    /// Called with the indices of the active layers, from the base layer up, whenever they
+    /// change.
+    fn show(&mut self, active: &[usize]) -> std::io::Result<()>;
+}
+
+impl<F: FnMut(&[usize]) -> std::io::Result<()>> LayerIndicator for F {
+    fn show(&mut self, active: &[usize]) -> std::io::Result<()> {
+        self(active)
+    }
+}
+
+/// Lights keyboard LEDs while layers are active, e.g. Scroll Lock and Compose, which are rarely
+/// used otherwise. The device has to be opened read-write; it can be the grabbed keyboard itself,
+/// opened a second time.
+#[derive(Debug)]
+pub struct LedIndicator<D> {
+    device: D,
+    leds: Vec<(usize, EV_LED)>,
+}
+
+impl<D: LedExt> LedIndicator<D> {
+    pub fn new(device: D) -> Self {
+        Self {
+            device,
+            leds: Vec::new(),
+        }
+    }
+
+    /// Lights `led` while `layer` is active.
+    pub fn led(mut self, layer: usize, led: EV_LED) -> Self {
+        self.leds.push((layer, led));
+        self
+    }
+}
+
+impl<D: LedExt> LayerIndicator for LedIndicator<D> {
+    fn show(&mut self, active: &[usize]) -> std::io::Result<()> {
+        for (layer, led) in &self.leds {
+            self.device.set_led(*led, active.contains(layer))?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OneShot {
+    Inactive,
+    /// The one-shot key is held and no other key has been pressed yet.
+    Held(usize),
+    /// The one-shot key was tapped; the layer applies to the next key press.
+    Armed(usize),
+    /// Another key was pressed while the one-shot key was held, so it acts as momentary.
+    Momentary(usize),
+}
+
+/// Resolves keys through a stack of layers. The highest active layer with an action other than
+/// `Transparent` for a key decides what it does, and the key keeps that action until released,
+/// even if the active layers change meanwhile. Events other than keys pass through unchanged.
+///
+/// Actions referring to layers that don't exist are ignored.
+pub struct Layers {
+    layers: Vec<Layer>,
+    toggled: Vec<bool>,
+    // How many keys are holding each layer active.
+    held: Vec<usize>,
+    one_shot: OneShot,
+    pressed: HashMap<EV_KEY, Action>,
+    // An MSC_SCAN held back until it's known what the key it belongs to resolves to.
+    pending_scan: Option<InputEvent>,
+    indicator: Option<Box<dyn LayerIndicator>>,
+    shown: Vec<usize>,
+}
+
+impl fmt::Debug for Layers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Layers")
+            .field("layers", &self.layers)
+            .field("toggled", &self.toggled)
+            .field("held", &self.held)
+            .field("one_shot", &self.one_shot)
+            .field("pressed", &self.pressed)
+            .field("pending_scan", &self.pending_scan)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Layers {
+    pub fn new(base: Layer) -> Self {
+        Self {
+            layers: vec![base],
+            toggled: vec![false],
+            held: vec![0],
+            one_shot: OneShot::Inactive,
+            pressed: HashMap::new(),
+            pending_scan: None,
+            indicator: None,
+            shown: vec![0],
+        }
+    }
+
+    /// Shows the active layers through `indicator`, starting with the next change. Errors it
+    /// returns are logged.
+    pub fn indicator(mut self, indicator: impl LayerIndicator + 'static) -> Self {
+        self.indicator = Some(Box::new(indicator));
+        self
+    }
+
+    /// Adds a layer above the existing ones. Its index is the number of layers added before it.
+    pub fn layer(mut self, layer: Layer) -> Self {
+        self.layers.push(layer);
+        self.toggled.push(false);
+        self.held.push(0);
+        self
+    }
+
+    /// Finds actions referring to layers that don't exist, layers nothing activates, and actions
+    /// that can never apply because their layer is only active while that very key is held.
+    pub fn check(&self) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+        // The layer and key of each action activating a layer, by that layer.
+        let mut activators: Vec<Vec<(usize, EV_KEY, Action)>> = vec![Vec::new(); self.layers.len()];
+        for (index, layer) in self.layers.iter().enumerate() {
+            let mut keys = layer.keys.iter().collect::<Vec<_>>();
+            keys.sort_by_key(|(key, _)| **key as u32);
+            for (key, action) in keys {
+                let location = Location::LayerKey {
+                    layer: index,
+                    key: *key,
+                };
+                match *action {
+                    Action::Momentary(0) | Action::Toggle(0) | Action::OneShot(0) => {
+                        diagnostics.push(Diagnostic::new(location, Problem::NoOp));
+                    }
+                    Action::Momentary(target)
+                    | Action::Toggle(target)
+                    | Action::OneShot(target)
+                        if !self.exists(target) =>
+                    {
+                        diagnostics.push(Diagnostic::new(location, Problem::MissingLayer(target)));
+                    }
+                    Action::Momentary(target)
+                    | Action::Toggle(target)
+                    | Action::OneShot(target) => {
+                        activators[target].push((index, *key, *action));
+                    }
+                    Action::Key(to) if to == *key => {
+                        diagnostics.push(Diagnostic::new(location, Problem::NoOp));
+                    }
+                    _ => {}
+                }
+            }
+        }
+        let mut reachable = HashSet::new();
+        let mut queue = vec![0];
+        while let Some(layer) = queue.pop() {
+            if reachable.insert(layer) {
+                queue.extend((0..self.layers.len()).filter(|target| {
+                    activators[*target]
+                        .iter()
+                        .any(|(from, _, _)| *from == layer)
+                }));
+            }
+        }
+        for (index, layer) in self.layers.iter().enumerate().skip(1) {
+            if !reachable.contains(&index) {
+                diagnostics.push(Diagnostic::new(
+                    Location::Layer(index),
+                    Problem::UnreachableLayer,
+                ));
+                continue;
+            }
+            let activators = activators[index]
+                .iter()
+                .filter(|(from, _, _)| reachable.contains(from))
+                .collect::<Vec<_>>();
+            // A held key keeps its action, so its action on the layer it holds active is never
+            // used, unless the layer can be activated some other way.
+            let (from, key) = match activators.as_slice() {
+                [(from, key, Action::Momentary(_)), rest @ ..]
+                    if rest.iter().all(|(_, other, action)| {
+                        other == key && matches!(action, Action::Momentary(_))
+                    }) =>
+                {
+                    (*from, *key)
+                }
+                _ => continue,
+            };
+            if layer.get(key) != Action::Transparent {
+                diagnostics.push(Diagnostic::new(
+                    Location::LayerKey { layer: index, key },
+                    Problem::UnreachableBinding {
+                        activator: Location::LayerKey { layer: from, key },
+                    },
+                ));
+            }
+        }
+        diagnostics
+    }
+
+    /// Whether `layer` is currently active. The base layer always is.
+    pub fn is_active(&self, layer: usize) -> bool {
+        layer == 0
+            || self.toggled.get(layer).copied().unwrap_or(false)
+            || self.held.get(layer).is_some_and(|held| *held > 0)
+            || match self.one_shot {
+                OneShot::Held(one_shot)
+                | OneShot::Armed(one_shot)
+                | OneShot::Momentary(one_shot) => one_shot == layer,
+                OneShot::Inactive => false,
+            }
+    }
+
+    /// The indices of the active layers, from the base layer up.
+    pub fn active_layers(&self) -> Vec<usize> {
+        (0..self.layers.len())
+            .filter(|layer| self.is_active(*layer))
+            .collect()
+    }
+
+    /// Deactivates all layers but the base layer and forgets held keys, e.g. after the device
+    /// was regrabbed.
+    pub fn reset(&mut self) {
+        self.toggled.iter_mut().for_each(|toggled| *toggled = false);
+        self.held.iter_mut().for_each(|held| *held = 0);
+        self.one_shot = OneShot::Inactive;
+        self.pressed.clear();
+        self.pending_scan = None;
+        self.update_indicator();
+    }
+
+    fn update_indicator(&mut self) {
+        if self.indicator.is_none() {
+            return;
+        }
+        let active = self.active_layers();
+        if active == self.shown {
+            return;
+        }
+        if let Some(Err(e)) = self
+            .indicator
+            .as_mut()
+            .map(|indicator| indicator.show(&active))
+        {
+            log::warn!("failed to show active layers: {}", e);
+        }
+        self.shown = active;
+    }
+
+    fn resolve(&self, key: EV_KEY) -> Action {
+        (0..self.layers.len())
+            .rev()
+            .filter(|layer| self.is_active(*layer))
+            .map(|layer| self.layers[layer].get(key))
+            .find(|action| *action != Action::Transparent)
+            .unwrap_or(Action::Key(key))
+    }
+
+    fn exists(&self, layer: usize) -> bool {
+        layer > 0 && layer < self.layers.len()
+    }
+
+    fn press(&mut self, key: EV_KEY) -> Action {
+        let action = self.resolve(key);
+        match action {
+            Action::Momentary(layer) if self.exists(layer) => self.held[layer] += 1,
+            Action::Toggle(layer) if self.exists(layer) => {
+                self.toggled[layer] = !self.toggled[layer];
+            }
+            Action::OneShot(layer) if self.exists(layer) => self.one_shot = OneShot::Held(layer),
+            Action::Key(_) | Action::Block => match self.one_shot {
+                OneShot::Held(layer) => self.one_shot = OneShot::Momentary(layer),
+                OneShot::Armed(_) => self.one_shot = OneShot::Inactive,
+                OneShot::Inactive | OneShot::Momentary(_) => {}
+            },
+            _ => {}
+        }
+        let _: Option<Action> = self.pressed.insert(key, action);
+        action
+    }
+
+    // Keys pressed before the processor started, e.g. held while grabbing, act as themselves.
+    fn release(&mut self, key: EV_KEY) -> Action {
+        let action = self.pressed.remove(&key).unwrap_or(Action::Key(key));
+        match action {
+            Action::Momentary(layer) if self.exists(layer) => {
+                self.held[layer] = self.held[layer].saturating_sub(1);
+            }
+            Action::OneShot(layer) => match self.one_shot {
+                OneShot::Held(one_shot) if one_shot == layer => {
+                    self.one_shot = OneShot::Armed(layer);
+                }
+                OneShot::Momentary(one_shot) if one_shot == layer => {
+                    self.one_shot = OneShot::Inactive;
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+        action
+    }
+}
+
+impl Processor for Layers {
+    type Output = InputEvent;
+
+    fn process(&mut self, event: InputEvent, _now: Instant, out: &mut VecDeque<InputEvent>) {
+        let scan = self.pending_scan.take();
+        let key = match event.event_code {
+            EventCode::EV_MSC(EV_MSC::MSC_SCAN) => {
+                out.extend(scan);
+                self.pending_scan = Some(event);
+                return;
+            }
+            EventCode::EV_KEY(key) => key,
+            _ => {
+                out.extend(scan);
+                out.push_back(event);
+                return;
+            }
+        };
+        let action = match event.value {
+            0 => self.release(key),
+            1 => self.press(key),
+            _ => self.pressed.get(&key).copied().unwrap_or(Action::Key(key)),
+        };
+        self.update_indicator();
+        if let Action::Key(target) = action {
+            // Scancodes of keys resolving to layer actions are dropped with the key.
+            if let Some(scan) = scan {
+                let value = if target == key {
+                    Some(scan.value)
+                } else {
+                    hid_scancode(target).map(|scan| scan as i32)
+                };
+                out.extend(value.map(|value| InputEvent { value, ..scan }));
+            }
+            out.push_back(InputEvent {
+                event_code: EventCode::EV_KEY(target),
+                ..event
+            });
+        }
+    }
+
+    fn finish(&mut self, out: &mut VecDeque<InputEvent>) {
+        out.extend(self.pending_scan.take());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{key, keys, run};
+    use evdev_rs::enums::EV_KEY::{KEY_CAPSLOCK, KEY_F1, KEY_H, KEY_LEFT, KEY_TAB};
+
+    fn layers() -> Layers {
+        Layers::new(
+            Layer::new()
+                .key(KEY_CAPSLOCK, Action::Momentary(1))
+                .key(KEY_F1, Action::Toggle(1))
+                .key(KEY_TAB, Action::OneShot(1)),
+        )
+        .layer(Layer::new().key(KEY_H, Action::Key(KEY_LEFT)))
+    }
+
+    #[test]
+    fn momentary_layer_applies_while_held() {
+        let output = run(
+            vec![
+                key(0, KEY_CAPSLOCK, 1),
+                key(10, KEY_H, 1),
+                key(20, KEY_CAPSLOCK, 0),
+                // Released with the action it was pressed with.
+                key(30, KEY_H, 0),
+                key(40, KEY_H, 1),
+                key(50, KEY_H, 0),
+            ],
+            layers(),
+        );
+        assert_eq!(
+            keys(&output),
+            vec![(KEY_LEFT, 1), (KEY_LEFT, 0), (KEY_H, 1), (KEY_H, 0)]
+        );
+    }
+
+    #[test]
+    fn toggle_layer_stays_active() {
+        let output = run(
+            vec![
+                key(0, KEY_F1, 1),
+                key(10, KEY_F1, 0),
+                key(20, KEY_H, 1),
+                key(30, KEY_H, 0),
+            ],
+            layers(),
+        );
+        assert_eq!(keys(&output), vec![(KEY_LEFT, 1), (KEY_LEFT, 0)]);
+    }
+
+    #[test]
+    fn one_shot_layer_applies_to_next_key() {
+        let output = run(
+            vec![
+                key(0, KEY_TAB, 1),
+                key(10, KEY_TAB, 0),
+                key(20, KEY_H, 1),
+                key(30, KEY_H, 0),
+                key(40, KEY_H, 1),
+                key(50, KEY_H, 0),
+            ],
+            layers(),
+        );
+        assert_eq!(
+            keys(&output),
+            vec![(KEY_LEFT, 1), (KEY_LEFT, 0), (KEY_H, 1), (KEY_H, 0)]
+        );
+    }
+
+    #[test]
+    fn forwards_release_of_key_held_at_start() {
+        let output = run(vec![key(0, KEY_H, 2), key(10, KEY_H, 0)], layers());
+        assert_eq!(keys(&output), vec![(KEY_H, 2), (KEY_H, 0)]);
+    }
+}
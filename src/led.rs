@@ -0,0 +1,58 @@
+use crate::{AsyncDevice, Injector};
+use evdev_rs::enums::{EventCode, EV_LED};
+use evdev_rs::{DeviceWrapper, LedState};
+
+/// The LEDs reflecting keyboard lock state.
+pub const LOCK_LEDS: [EV_LED; 3] = [EV_LED::LED_CAPSL, EV_LED::LED_NUML, EV_LED::LED_SCROLLL];
+
+/// LED control. Setting LEDs requires the device to have been opened read-write, e.g. through
+/// `AsyncDevice::from_file`.
+pub trait LedExt {
+    fn set_led(&self, led: EV_LED, on: bool) -> std::io::Result<()>;
+
+    /// Whether `led` is currently lit, or `None` if the device has no such LED.
+    fn led(&self, led: EV_LED) -> Option<bool>;
+}
+
+impl LedExt for evdev_rs::Device {
+    fn set_led(&self, led: EV_LED, on: bool) -> std::io::Result<()> {
+        let state = if on { LedState::On } else { LedState::Off };
+        self.kernel_set_led_value(&EventCode::EV_LED(led), state)
+    }
+
+    fn led(&self, led: EV_LED) -> Option<bool> {
+        self.event_value(&EventCode::EV_LED(led))
+            .map(|value| value != 0)
+    }
+}
+
+impl LedExt for AsyncDevice {
+    fn set_led(&self, led: EV_LED, on: bool) -> std::io::Result<()> {
+        self.evdev().set_led(led, on)
+    }
+
+    fn led(&self, led: EV_LED) -> Option<bool> {
+        self.evdev().led(led)
+    }
+}
+
+/// Copies the lock LED state of `from` onto the virtual device `to`, e.g. so that a virtual
+/// keyboard replacing a grabbed one starts out in the same state.
+pub fn mirror_lock_leds<D, U>(from: &D, to: &U) -> std::io::Result<()>
+where
+    D: DeviceWrapper + ?Sized,
+    U: Injector,
+{
+    let events = LOCK_LEDS
+        .iter()
+        .filter_map(|led| {
+            let code = EventCode::EV_LED(*led);
+            from.event_value(&code)
+                .map(|value| (code, (value != 0).into()))
+        })
+        .collect::<Vec<_>>();
+    if events.is_empty() {
+        return Ok(());
+    }
+    to.emit(&events)
+}
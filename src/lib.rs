@@ -1,65 +1,171 @@
 #![deny(unused_results)]
 
 use async_io::Async;
-use evdev_rs::enums::{EventCode, EventType, EV_ABS, EV_KEY, EV_REL, EV_SYN};
-use evdev_rs::{InputEvent, UInputDevice};
-use futures::{ready, Stream, StreamExt as _, TryStreamExt as _};
+use evdev_rs::enums::{EventCode, EventType, InputProp, EV_ABS, EV_KEY, EV_MSC, EV_REL, EV_SYN};
+use evdev_rs::{DeviceWrapper as _, InputEvent, TimeVal};
+use futures::{ready, Stream, TryStreamExt as _};
 use std::fs::File;
-use std::os::unix::io::{AsRawFd, RawFd};
+use std::os::unix::fs::OpenOptionsExt as _;
+use std::os::unix::io::{AsRawFd, FromRawFd as _, RawFd};
 use std::path::{Path, PathBuf};
 use std::pin::Pin;
+use std::sync::Arc;
 use std::task::{Context, Poll};
 use thiserror::Error;
 
-pub trait UInputExt {
-    fn inject_event(&self, event_code: EventCode, value: i32) -> std::io::Result<()>;
+mod abs;
+mod access;
+pub mod actions;
+mod activation;
+mod autoclick;
+mod axis;
+mod burst;
+mod button_scroll;
+mod chord;
+mod clock;
+mod combo;
+#[cfg(feature = "dbus")]
+pub mod dbus;
+mod device_set;
+pub mod diagnostics;
+mod dry_run;
+mod escape;
+pub mod ff;
+mod filter;
+mod frames;
+mod grab;
+mod hat;
+mod idle;
+mod info;
+mod injector;
+mod key_state;
+mod keyboard_gamepad;
+pub mod latency;
+pub mod layers;
+mod led;
+mod logging;
+pub mod macros;
+mod managed;
+mod merge;
+mod mock;
+mod modifiers;
+mod monitor;
+mod mouse_keys;
+mod mouse_stick;
+mod mt;
+mod names;
+mod pen;
+mod pipeline;
+mod preset;
+pub mod privileges;
+mod process;
+pub mod profile;
+mod proxy;
+mod record;
+pub mod remap;
+mod repeat;
+mod scancode;
+mod scheduler;
+mod sequences;
+pub mod stats;
+mod sticky;
+mod switches;
+mod tap_hold;
+#[cfg(test)]
+mod test_util;
+mod text;
+#[cfg(feature = "threaded")]
+pub mod threaded;
+mod throttle;
+mod ticker;
+#[cfg(feature = "tokio")]
+pub mod tokio;
+mod touchpad;
+pub mod transform;
+mod turbo;
+mod typed;
+mod virtual_device;
+#[cfg(feature = "wayland")]
+pub mod wayland;
+mod wheel;
+mod wheel_keys;
+#[cfg(feature = "xkb")]
+mod xkb;
+#[cfg(feature = "x11")]
+pub mod xtest;
 
-    fn inject_key_press(&self, btn: EV_KEY) -> std::io::Result<()> {
-        self.inject_event(EventCode::EV_KEY(btn), 1)?;
-        self.inject_event(EventCode::EV_SYN(EV_SYN::SYN_REPORT), 0)?;
-        self.inject_event(EventCode::EV_KEY(btn), 0)?;
-        self.inject_event(EventCode::EV_SYN(EV_SYN::SYN_REPORT), 0)?;
-        Ok(())
-    }
+pub use abs::AbsInjector;
+pub use access::{BounceKeys, SlowKeys};
+pub use activation::{activated_devices, ActivationError};
+pub use autoclick::{AutoClicker, AutoClickerError};
+pub use axis::{AxisProcessor, Curve, Deadzone};
+pub use burst::BURST_CAPACITY;
+pub use button_scroll::ButtonScroll;
+pub use chord::{ChordDetector, ChordEvent};
+pub use clock::{ClockId, Timestamp};
+pub use combo::VirtualComboDevice;
+pub use device_set::{DeviceSet, DeviceSetError};
+pub use dry_run::DryRun;
+pub use escape::{EscapeHatch, EscapeSequence, DEFAULT_ESCAPE_KEYS};
+pub use filter::DeviceFilter;
+pub use frames::Frames;
+pub use grab::{install_panic_ungrab, ungrab_all, GrabGuard};
+pub use hat::{DpadToHat, HatToDpad};
+pub use idle::{idle_watcher, IdleState, IdleWatcher};
+pub use info::DeviceInfo;
+pub use injector::{Injector, MockInjector};
+pub use keyboard_gamepad::{GamepadEvent, KeyboardGamepad, KeyboardGamepadError, Stick};
+pub use led::{mirror_lock_leds, LedExt, LOCK_LEDS};
+pub use logging::{format_event, Logged};
+pub use managed::{is_disconnect, DeviceEvent, ManagedDevice};
+pub use merge::{merged_stream, DeviceId, MergedStream};
+pub use mock::{EventSource, MockDevice};
+pub use modifiers::{ModifierTracker, Modifiers};
+pub use monitor::{DeviceMonitor, HotplugDevices, MonitorEvent};
+pub use mouse_keys::MouseKeys;
+pub use mouse_stick::MouseStick;
+pub use mt::{MtInjector, Touch};
+pub use names::{parse_event_code, parse_key, CodeName, KeyNameError};
+pub use pen::VirtualPen;
+pub use pipeline::Pipeline;
+pub use preset::ControllerPreset;
+pub use process::{EventStreamExt, Processed, Processor};
+pub use proxy::{Proxy, ProxyError};
+pub use record::{Player, Record, Recorder};
+pub use repeat::{RepeatScheduler, DEFAULT_REPEAT_DELAY, DEFAULT_REPEAT_PERIOD};
+pub use scancode::{hid_scancode, key_from_hid_scancode};
+pub use scheduler::{ScheduleHandle, Scheduler};
+pub use sequences::{SequenceState, Sequences};
+pub use sticky::StickyKeys;
+pub use switches::{switch_states, watch_switches, SwitchEvent};
+pub use tap_hold::{Interrupt, TapHold};
+pub use text::UnicodeEntry;
+pub use throttle::{Debounce, RateLimit};
+pub use ticker::OutputTicker;
+pub use touchpad::{SwipeDirection, VirtualTouchpad};
+pub use turbo::Turbo;
+pub use typed::{KeyState, Timed, TimedTyper, TypedEvent, Typer};
+pub use virtual_device::VirtualDeviceBuilder;
+pub use wheel::{HiResWheel, WHEEL_DETENT};
+pub use wheel_keys::WheelKeys;
+#[cfg(feature = "xkb")]
+pub use xkb::{XkbError, XkbTranslator};
 
-    fn inject_key_syn(&self, key: EV_KEY, value: i32) -> std::io::Result<()> {
-        self.inject_event(EventCode::EV_KEY(key), value)?;
-        self.inject_event(EventCode::EV_SYN(EV_SYN::SYN_REPORT), 0)?;
-        Ok(())
-    }
+// Timestamp of events generated rather than read, which the kernel stamps when they're injected.
+pub(crate) const ZERO_TIME: TimeVal = TimeVal {
+    tv_sec: 0,
+    tv_usec: 0,
+};
 
-    fn inject_abs_syn(&self, abs: EV_ABS, value: i32) -> std::io::Result<()> {
-        self.inject_event(EventCode::EV_ABS(abs), value)?;
-        self.inject_event(EventCode::EV_SYN(EV_SYN::SYN_REPORT), 0)?;
-        Ok(())
-    }
-
-    fn inject_xy(
-        &self,
-        (abs_x, abs_y): (EV_ABS, EV_ABS),
-        (x, y): (i32, i32),
-    ) -> std::io::Result<()> {
-        self.inject_event(EventCode::EV_ABS(abs_x), x)?;
-        self.inject_event(EventCode::EV_ABS(abs_y), y)?;
-        self.inject_event(EventCode::EV_SYN(EV_SYN::SYN_REPORT), 0)?;
-        Ok(())
+pub(crate) fn event(time: TimeVal, event_code: EventCode, value: i32) -> InputEvent {
+    InputEvent {
+        time,
+        event_code,
+        value,
     }
 }
 
-impl UInputExt for UInputDevice {
-    fn inject_event(&self, event_code: EventCode, value: i32) -> std::io::Result<()> {
-        self.write_event(&InputEvent {
-            event_code,
-            value,
-            time: evdev_rs::TimeVal {
-                tv_sec: 0,
-                tv_usec: 0,
-            },
-        })
-    }
-}
-
-struct Device(evdev_rs::Device);
+pub(crate) struct Device(evdev_rs::Device);
 
 impl AsRawFd for Device {
     fn as_raw_fd(&self) -> RawFd {
@@ -67,64 +173,278 @@ impl AsRawFd for Device {
     }
 }
 
-pub struct AsyncDevice(Async<Device>);
+/// An evdev device whose events are read as a stream.
+///
+/// Reading an event, running it through a `Processor` or a `Remapper` and injecting the result
+/// is held to a budget of 1µs and no heap allocations per event, not counting the kernel's side
+/// of reading and injecting; `cargo bench --bench event_path` measures it.
+pub struct AsyncDevice {
+    device: Async<Device>,
+    // Set after libevdev reports SYN_DROPPED, until the state delta has been drained with
+    // `ReadFlag::SYNC`.
+    syncing: bool,
+    burst: burst::BurstBuffer,
+}
 
-impl futures::Stream for AsyncDevice {
-    type Item = Result<InputEvent, std::io::Error>;
-
-    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        // XXX This logic is hideous because libevdev's `next_event` function will read all
-        // available events from the fd and buffer them internally, so when the fd becomes readable
-        // it's necessary to continue from libevdev until the buffer is exhausted before the fd
-        // will signal readable again.
-        Poll::Ready(Some(if self.has_event_pending() {
-            self.next_event(evdev_rs::ReadFlag::NORMAL)
-                .map(|(_, event)| event)
+// Reads the next event from libevdev, following libevdev's resync protocol after SYN_DROPPED.
+// Returns `None` when the caller needs to wait for the fd to become readable.
+//
+// libevdev's `next_event` will read all available events from the fd and buffer them internally,
+// so the fd only signals readable again once that buffer is exhausted. This must therefore always
+// be tried before waiting on the fd.
+pub(crate) fn read_event(
+    device: &evdev_rs::Device,
+    syncing: &mut bool,
+) -> Option<Result<InputEvent, DeviceError>> {
+    loop {
+        let flags = if *syncing {
+            evdev_rs::ReadFlag::SYNC
         } else {
-            match ready!(self.0.poll_readable(cx)) {
-                Ok(()) => {
-                    match self
-                        .next_event(evdev_rs::ReadFlag::NORMAL)
-                        .map(|(_, event)| event)
-                    {
-                        Ok(event) => Ok(event),
-                        Err(e) => {
-                            if e.kind() == std::io::ErrorKind::WouldBlock {
-                                return self.poll_next(cx);
-                            } else {
-                                Err(e)
-                            }
-                        }
-                    }
+            evdev_rs::ReadFlag::NORMAL
+        };
+        match device.next_event(flags) {
+            Ok((evdev_rs::ReadStatus::Success, event)) => return Some(Ok(event)),
+            // Either the SYN_DROPPED event itself, which is passed on so that consumers know to
+            // discard any partial frame, or one of the events making up the state delta since the
+            // drop.
+            Ok((evdev_rs::ReadStatus::Sync, event)) => {
+                *syncing = true;
+                return Some(Ok(event));
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                if *syncing {
+                    *syncing = false;
+                    continue;
                 }
-                Err(e) => Err(e),
+                return None;
             }
-        }))
+            Err(e) if *syncing => {
+                *syncing = false;
+                return Some(Err(match DeviceError::from(e) {
+                    DeviceError::Other(e) => DeviceError::SyncRequired(e),
+                    e => e,
+                }));
+            }
+            Err(e) => return Some(Err(e.into())),
+        }
+    }
+}
+
+impl futures::Stream for AsyncDevice {
+    type Item = Result<InputEvent, DeviceError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let Self {
+            device, syncing, ..
+        } = &mut *self;
+        loop {
+            if let Some(event) = read_event(&device.get_ref().0, syncing) {
+                return Poll::Ready(Some(event));
+            }
+            if let Err(e) = ready!(device.poll_readable(cx)) {
+                return Poll::Ready(Some(Err(e.into())));
+            }
+        }
+    }
+}
+
+impl Drop for AsyncDevice {
+    fn drop(&mut self) {
+        // Closing the fd releases any grab, and the fd may be reused.
+        grab::track(self.evdev().file().as_raw_fd(), false);
     }
 }
 
 impl AsyncDevice {
-    pub fn new<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
-        File::open(path)
-            .and_then(|file| evdev_rs::Device::new_from_file(file))
-            .and_then(|device| Async::new(Device(device)))
-            .map(AsyncDevice)
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<Self, OpenError> {
+        Self::from_file(open_nonblocking(path.as_ref())?).map_err(OpenError::Init)
+    }
+
+    /// Useful when the file needs to be opened with different flags, e.g. read-write for LEDs.
+    pub fn from_file(file: File) -> std::io::Result<Self> {
+        evdev_rs::Device::new_from_file(file).and_then(Self::from_device)
+    }
+
+    pub fn from_device(device: evdev_rs::Device) -> std::io::Result<Self> {
+        Async::new(Device(device)).map(|device| AsyncDevice {
+            device,
+            syncing: false,
+            burst: burst::BurstBuffer::default(),
+        })
+    }
+
+    /// Takes ownership of an already open evdev fd, e.g. one passed down by a privileged parent
+    /// process.
+    ///
+    /// # Safety
+    ///
+    /// `fd` must be an open file descriptor that is not owned by anything else.
+    pub unsafe fn from_raw_fd(fd: RawFd) -> std::io::Result<Self> {
+        Self::from_file(File::from_raw_fd(fd))
     }
 
     pub fn grab(&mut self, grab: evdev_rs::GrabMode) -> std::io::Result<()> {
-        self.0.get_mut().0.grab(grab)
+        let grabbed = matches!(grab, evdev_rs::GrabMode::Grab);
+        self.device.get_mut().0.grab(grab)?;
+        grab::track(self.evdev().file().as_raw_fd(), grabbed);
+        Ok(())
+    }
+
+    pub(crate) fn evdev(&self) -> &evdev_rs::Device {
+        &self.device.get_ref().0
+    }
+
+    pub fn info(&self) -> DeviceInfo {
+        DeviceInfo::from_device(self.evdev())
     }
 
     pub fn next_event(
         &self,
         flags: evdev_rs::ReadFlag,
     ) -> std::io::Result<(evdev_rs::ReadStatus, InputEvent)> {
-        self.0.get_ref().0.next_event(flags)
+        self.device.get_ref().0.next_event(flags)
     }
 
     pub fn has_event_pending(&self) -> bool {
-        self.0.get_ref().0.has_event_pending()
+        self.device.get_ref().0.has_event_pending()
+    }
+
+    /// Waits for events and returns all that are available, up to `BURST_CAPACITY`, at once.
+    /// For high polling rate devices, e.g. 8 kHz mice, this saves a task wakeup per event over
+    /// reading the stream. Don't mix with reading the stream within a frame.
+    pub async fn read_burst(
+        &mut self,
+    ) -> Result<std::collections::vec_deque::Drain<'_, InputEvent>, DeviceError> {
+        futures::future::poll_fn(|cx| self.poll_burst(cx)).await?;
+        Ok(self.burst.events.drain(..))
     }
+
+    fn poll_burst(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), DeviceError>> {
+        let Self {
+            device,
+            syncing,
+            burst,
+        } = self;
+        loop {
+            burst.fill(&device.get_ref().0, syncing);
+            if let Some(ready) = burst.take_ready() {
+                return Poll::Ready(ready);
+            }
+            if let Err(e) = ready!(device.poll_readable(cx)) {
+                return Poll::Ready(Err(e.into()));
+            }
+        }
+    }
+
+    /// Returns a stream of whole reports instead of individual events.
+    pub fn frames(self) -> Processed<Self, Frames> {
+        self.process(Frames::new())
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum OpenError {
+    #[error(
+        "permission denied opening {}; the user likely needs to be in the `input` group or be \
+         granted access by a udev rule",
+        .path.display()
+    )]
+    PermissionDenied {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("failed to open {}", .path.display())]
+    Open {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("failed to initialize device")]
+    Init(#[source] std::io::Error),
+}
+
+impl From<OpenError> for std::io::Error {
+    fn from(e: OpenError) -> Self {
+        let kind = match &e {
+            OpenError::PermissionDenied { source, .. }
+            | OpenError::Open { source, .. }
+            | OpenError::Init(source) => source.kind(),
+        };
+        std::io::Error::new(kind, e)
+    }
+}
+
+/// An error reading events from a device, classified so that consumers can decide whether to
+/// retry, reconnect or give up without matching on errno.
+#[derive(Error, Debug)]
+pub enum DeviceError {
+    /// No event is available right now. Device streams wait for the next one instead of
+    /// yielding this.
+    #[error("no event available")]
+    WouldBlock,
+    /// The device was unplugged, or its fd revoked, e.g. by logind on a session switch. Every
+    /// further read fails the same way.
+    #[error("device disconnected")]
+    Disconnected(#[source] std::io::Error),
+    /// Access to the device was taken away.
+    #[error("permission to read the device was lost")]
+    PermissionLost(#[source] std::io::Error),
+    /// Events were dropped and resyncing the device state failed, so the state the consumer
+    /// tracks, e.g. held keys, may be stale.
+    #[error("events were dropped and the device state couldn't be resynced")]
+    SyncRequired(#[source] std::io::Error),
+    #[error("error when reading an event")]
+    Other(#[source] std::io::Error),
+}
+
+impl DeviceError {
+    /// Whether reading again may succeed, as opposed to the device being gone for good.
+    pub fn is_recoverable(&self) -> bool {
+        !matches!(
+            self,
+            DeviceError::Disconnected(_) | DeviceError::PermissionLost(_)
+        )
+    }
+}
+
+impl From<std::io::Error> for DeviceError {
+    fn from(e: std::io::Error) -> Self {
+        match e.raw_os_error() {
+            _ if e.kind() == std::io::ErrorKind::WouldBlock => DeviceError::WouldBlock,
+            _ if is_disconnect(&e) => DeviceError::Disconnected(e),
+            Some(libc::EACCES) | Some(libc::EPERM) => DeviceError::PermissionLost(e),
+            _ => DeviceError::Other(e),
+        }
+    }
+}
+
+impl From<DeviceError> for std::io::Error {
+    fn from(e: DeviceError) -> Self {
+        match e {
+            DeviceError::WouldBlock => std::io::ErrorKind::WouldBlock.into(),
+            DeviceError::Disconnected(e)
+            | DeviceError::PermissionLost(e)
+            | DeviceError::SyncRequired(e)
+            | DeviceError::Other(e) => e,
+        }
+    }
+}
+
+pub(crate) fn open_nonblocking(path: &Path) -> Result<File, OpenError> {
+    std::fs::OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_NONBLOCK)
+        .open(path)
+        .map_err(|source| {
+            let path = path.to_path_buf();
+            match source.kind() {
+                std::io::ErrorKind::PermissionDenied => {
+                    OpenError::PermissionDenied { path, source }
+                }
+                _ => OpenError::Open { path, source },
+            }
+        })
 }
 
 #[derive(Error, Debug)]
@@ -134,27 +454,45 @@ pub enum IdentifyError {
     #[error("glob iterator error")]
     GlobError(#[from] glob::GlobError),
     #[error("failed to create async device")]
-    AsyncDeviceNew(#[source] std::io::Error),
+    AsyncDeviceNew(#[source] OpenError),
+    #[error("failed to start device monitor")]
+    Monitor(#[source] std::io::Error),
+    #[error("failed to open device")]
+    OpenDevice(#[source] OpenError),
     #[error("combined device event stream ended")]
     EventStreamEnded,
     #[error("error when yielding an event")]
-    ReadEvent(#[source] std::io::Error),
+    ReadEvent(#[source] DeviceError),
+    #[error("failed to list the virtual devices of this process")]
+    OwnDevices(#[source] std::io::Error),
 }
 
-fn all_devices() -> Result<impl Stream<Item = std::io::Result<(PathBuf, InputEvent)>>, IdentifyError>
-{
-    let paths = glob::glob("/dev/input/event*")?
-        .into_iter()
-        .collect::<Result<Vec<_>, _>>()?;
-    let devices = paths
-        .into_iter()
-        .map(|path| {
-            AsyncDevice::new(&path)
-                .map(|stream| stream.map(move |event| event.map(|event| (path.clone(), event))))
-        })
-        .collect::<Result<futures::stream::SelectAll<_>, _>>()
-        .map_err(IdentifyError::AsyncDeviceNew)?;
-    Ok(devices)
+fn all_devices(
+) -> Result<impl Stream<Item = Result<(Arc<Path>, InputEvent), DeviceError>>, IdentifyError> {
+    all_devices_matching(&DeviceFilter::default())
+}
+
+/// Combined event stream over the devices matching `filter`, tagged with the device path. The
+/// path is shared between the events of a device rather than copied for each. Virtual devices
+/// created by this process are left out, so that a pipeline doesn't read back its own output.
+pub fn all_devices_matching(
+    filter: &DeviceFilter,
+) -> Result<impl Stream<Item = Result<(Arc<Path>, InputEvent), DeviceError>>, IdentifyError> {
+    let own = virtual_device::own_event_nodes().map_err(IdentifyError::OwnDevices)?;
+    let paths = glob::glob("/dev/input/event*")?.collect::<Result<Vec<_>, _>>()?;
+    let mut devices = Vec::new();
+    for path in paths.into_iter().filter(|path| !own.contains(path)) {
+        let device = AsyncDevice::new(&path).map_err(IdentifyError::AsyncDeviceNew)?;
+        if filter.matches(device.evdev()) {
+            devices.push((path, device));
+        }
+    }
+    Ok(merge::merge(devices).map_ok(|(id, event)| (id.path, event)))
+}
+
+/// Event stream over all input devices which grows as devices are plugged in.
+pub fn all_devices_hotplug() -> Result<HotplugDevices, IdentifyError> {
+    HotplugDevices::new().map_err(IdentifyError::Monitor)
 }
 
 pub async fn identify_keyboard() -> Result<PathBuf, IdentifyError> {
@@ -171,19 +509,90 @@ pub async fn identify_keyboard() -> Result<PathBuf, IdentifyError> {
             .try_next()
             .await
             .map_err(IdentifyError::ReadEvent)?
-            .ok_or_else(|| IdentifyError::EventStreamEnded)?;
+            .ok_or(IdentifyError::EventStreamEnded)?;
         if let EventCode::EV_KEY(k) = event_code {
             if k as u32 >= EV_KEY::KEY_RESERVED as u32
                 && k as u32 <= EV_KEY::KEY_MICMUTE as u32
                 && value == 0
             {
-                return Ok(path);
+                return Ok(path.to_path_buf());
             }
         }
     }
 }
 
+/// Returns the path of the first device to release `target`, e.g. KEY_POWER or KEY_PLAYPAUSE,
+/// which often come from their own device rather than the keyboard. Only devices that advertise
+/// `target` are watched.
+pub async fn identify_by_key(target: EV_KEY) -> Result<PathBuf, IdentifyError> {
+    all_devices_matching(&DeviceFilter::new().has_code(EventCode::EV_KEY(target)))?
+        .try_filter_map(|(path, event)| {
+            futures::future::ok(
+                if event.event_code == EventCode::EV_KEY(target) && event.value == 0 {
+                    Some(path.to_path_buf())
+                } else {
+                    None
+                },
+            )
+        })
+        .try_next()
+        .await
+        .map_err(IdentifyError::ReadEvent)?
+        .ok_or(IdentifyError::EventStreamEnded)
+}
+
+// The letter keys, all of which a keyboard advertises.
+const LETTER_KEYS: [EV_KEY; 26] = [
+    EV_KEY::KEY_A,
+    EV_KEY::KEY_B,
+    EV_KEY::KEY_C,
+    EV_KEY::KEY_D,
+    EV_KEY::KEY_E,
+    EV_KEY::KEY_F,
+    EV_KEY::KEY_G,
+    EV_KEY::KEY_H,
+    EV_KEY::KEY_I,
+    EV_KEY::KEY_J,
+    EV_KEY::KEY_K,
+    EV_KEY::KEY_L,
+    EV_KEY::KEY_M,
+    EV_KEY::KEY_N,
+    EV_KEY::KEY_O,
+    EV_KEY::KEY_P,
+    EV_KEY::KEY_Q,
+    EV_KEY::KEY_R,
+    EV_KEY::KEY_S,
+    EV_KEY::KEY_T,
+    EV_KEY::KEY_U,
+    EV_KEY::KEY_V,
+    EV_KEY::KEY_W,
+    EV_KEY::KEY_X,
+    EV_KEY::KEY_Y,
+    EV_KEY::KEY_Z,
+];
+
+// How many different letters a device must type to be taken for the keyboard, so that a stray
+// key from e.g. a macro pad or a mouse's keyboard interface doesn't count.
+const KEYBOARD_MIN_LETTERS: usize = 3;
+
+// All letters and no left button, which rules out mice with a keyboard interface and combined
+// receivers reporting both through one device.
+fn is_keyboard(device: &evdev_rs::Device) -> bool {
+    LETTER_KEYS
+        .iter()
+        .all(|key| device.has(&EventCode::EV_KEY(*key)))
+        && !device.has(&EventCode::EV_KEY(EV_KEY::BTN_LEFT))
+}
+
+/// Waits for the user to use their mouse and type on their keyboard, and returns the paths of
+/// both. The keyboard is the first device advertising a full set of letters, and no mouse
+/// button, to type three different letters. Virtual devices created by this process are
+/// ignored.
 pub async fn identify_mkb() -> Result<(PathBuf, PathBuf), IdentifyError> {
+    let keyboards = find_devices(is_keyboard)?
+        .into_iter()
+        .collect::<std::collections::HashSet<_>>();
+    let mut letters = std::collections::HashMap::<_, std::collections::HashSet<_>>::new();
     let (mut keeb_path, mut mouse_path) = (None, None);
     let mut streams = all_devices()?;
     loop {
@@ -198,7 +607,7 @@ pub async fn identify_mkb() -> Result<(PathBuf, PathBuf), IdentifyError> {
             .try_next()
             .await
             .map_err(IdentifyError::ReadEvent)?
-            .ok_or_else(|| IdentifyError::EventStreamEnded)?;
+            .ok_or(IdentifyError::EventStreamEnded)?;
         match event_code {
             EventCode::EV_KEY(EV_KEY::BTN_LEFT)
             | EventCode::EV_KEY(EV_KEY::BTN_RIGHT)
@@ -209,20 +618,24 @@ pub async fn identify_mkb() -> Result<(PathBuf, PathBuf), IdentifyError> {
             | EventCode::EV_REL(EV_REL::REL_Y)
             | EventCode::EV_REL(EV_REL::REL_WHEEL)
             | EventCode::EV_REL(EV_REL::REL_HWHEEL) => {
-                if mouse_path.is_none() {
-                    mouse_path = Some(path);
-                }
+                let _: &mut Arc<Path> = mouse_path.get_or_insert(path);
             }
-            // TODO this is grossly inaccurate
-            EventCode::EV_KEY(_) => {
-                if value == 0 && keeb_path.is_none() {
+            EventCode::EV_KEY(key)
+                if value == 0
+                    && keeb_path.is_none()
+                    && LETTER_KEYS.contains(&key)
+                    && keyboards.contains(&*path) =>
+            {
+                let typed = letters.entry(Arc::clone(&path)).or_default();
+                let _: bool = typed.insert(key);
+                if typed.len() >= KEYBOARD_MIN_LETTERS {
                     keeb_path = Some(path);
                 }
             }
             _ => {}
         }
         if let (Some(keeb_path), Some(mouse_path)) = (&keeb_path, &mouse_path) {
-            return Ok((keeb_path.clone(), mouse_path.clone()));
+            return Ok((keeb_path.to_path_buf(), mouse_path.to_path_buf()));
         }
     }
 }
@@ -247,7 +660,7 @@ pub async fn identify_mouse() -> Result<PathBuf, IdentifyError> {
                     | EventCode::EV_REL(EV_REL::REL_X)
                     | EventCode::EV_REL(EV_REL::REL_Y)
                     | EventCode::EV_REL(EV_REL::REL_WHEEL)
-                    | EventCode::EV_REL(EV_REL::REL_HWHEEL) => Some(path),
+                    | EventCode::EV_REL(EV_REL::REL_HWHEEL) => Some(path.to_path_buf()),
                     _ => None,
                 })
             },
@@ -255,7 +668,214 @@ pub async fn identify_mouse() -> Result<PathBuf, IdentifyError> {
         .try_next()
         .await
         .map_err(IdentifyError::ReadEvent)?
-        .ok_or_else(|| IdentifyError::EventStreamEnded)
+        .ok_or(IdentifyError::EventStreamEnded)
+}
+
+// Fraction of an axis' half-range a stick must be moved away from its center to count as input.
+const GAMEPAD_DEADZONE: f64 = 0.5;
+
+pub async fn identify_gamepad() -> Result<PathBuf, IdentifyError> {
+    let mut streams = all_devices()?;
+    let mut thresholds = std::collections::HashMap::new();
+    for (path, device) in open_all()? {
+        for abs in [EV_ABS::ABS_X, EV_ABS::ABS_Y].iter() {
+            if let Some(info) = device.abs_info(&EventCode::EV_ABS(*abs)) {
+                let center = (f64::from(info.minimum) + f64::from(info.maximum)) / 2.0;
+                let threshold =
+                    GAMEPAD_DEADZONE * (f64::from(info.maximum) - f64::from(info.minimum)) / 2.0;
+                let _: Option<(f64, f64)> = thresholds
+                    .entry(path.clone())
+                    .or_insert_with(std::collections::HashMap::new)
+                    .insert(*abs, (center, threshold));
+            }
+        }
+    }
+    loop {
+        let (
+            path,
+            InputEvent {
+                time: _,
+                event_code,
+                value,
+            },
+        ) = streams
+            .try_next()
+            .await
+            .map_err(IdentifyError::ReadEvent)?
+            .ok_or(IdentifyError::EventStreamEnded)?;
+        match event_code {
+            EventCode::EV_KEY(k)
+                if k as u32 >= EV_KEY::BTN_TRIGGER as u32
+                    && k as u32 <= EV_KEY::BTN_THUMBR as u32
+                    && value == 1 =>
+            {
+                return Ok(path.to_path_buf());
+            }
+            EventCode::EV_ABS(abs @ EV_ABS::ABS_X) | EventCode::EV_ABS(abs @ EV_ABS::ABS_Y) => {
+                if let Some((center, threshold)) =
+                    thresholds.get(&*path).and_then(|axes| axes.get(&abs))
+                {
+                    if (f64::from(value) - center).abs() > *threshold {
+                        return Ok(path.to_path_buf());
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn open_all() -> Result<Vec<(PathBuf, evdev_rs::Device)>, IdentifyError> {
+    glob::glob("/dev/input/event*")?
+        .map(|path| {
+            let path = path?;
+            let device = open_nonblocking(&path)
+                .and_then(|file| evdev_rs::Device::new_from_file(file).map_err(OpenError::Init))
+                .map_err(IdentifyError::OpenDevice)?;
+            Ok((path, device))
+        })
+        .collect()
+}
+
+// Like `open_all`, but leaves out the nodes that can't be opened, e.g. because they are root-only
+// or were unplugged after being listed, instead of failing.
+fn open_available() -> Result<Vec<(PathBuf, evdev_rs::Device)>, IdentifyError> {
+    let mut devices = Vec::new();
+    for path in glob::glob("/dev/input/event*")? {
+        let path = path?;
+        match open_nonblocking(&path)
+            .and_then(|file| evdev_rs::Device::new_from_file(file).map_err(OpenError::Init))
+        {
+            Ok(device) => devices.push((path, device)),
+            Err(e) => log::debug!("skipping {}: {}", path.display(), e),
+        }
+    }
+    Ok(devices)
+}
+
+// Leaves out virtual devices created by this process, like `all_devices_matching`.
+fn find_devices(
+    predicate: impl Fn(&evdev_rs::Device) -> bool,
+) -> Result<Vec<PathBuf>, IdentifyError> {
+    let own = virtual_device::own_event_nodes().map_err(IdentifyError::OwnDevices)?;
+    Ok(open_all()?
+        .into_iter()
+        .filter_map(|(path, device)| {
+            if !own.contains(&path) && predicate(&device) {
+                Some(path)
+            } else {
+                None
+            }
+        })
+        .collect())
+}
+
+/// Lists all input devices along with their identifying information.
+pub fn scan() -> Result<Vec<(PathBuf, DeviceInfo)>, IdentifyError> {
+    Ok(open_all()?
+        .into_iter()
+        .map(|(path, device)| (path, DeviceInfo::from_device(&device)))
+        .collect())
+}
+
+fn has_all(device: &evdev_rs::Device, codes: &[EventCode]) -> bool {
+    codes.iter().all(|code| device.has(code))
+}
+
+/// Returns the devices that advertise alphabetic keys, without waiting for any input.
+pub fn find_keyboards() -> Result<Vec<PathBuf>, IdentifyError> {
+    find_devices(|device| {
+        has_all(
+            device,
+            &[
+                EventCode::EV_KEY(EV_KEY::KEY_A),
+                EventCode::EV_KEY(EV_KEY::KEY_Z),
+                EventCode::EV_KEY(EV_KEY::KEY_SPACE),
+                EventCode::EV_KEY(EV_KEY::KEY_ENTER),
+            ],
+        )
+    })
+}
+
+/// Returns the devices that advertise relative motion and a left button, without waiting for any
+/// input.
+pub fn find_mice() -> Result<Vec<PathBuf>, IdentifyError> {
+    find_devices(|device| {
+        has_all(
+            device,
+            &[
+                EventCode::EV_REL(EV_REL::REL_X),
+                EventCode::EV_REL(EV_REL::REL_Y),
+                EventCode::EV_KEY(EV_KEY::BTN_LEFT),
+            ],
+        )
+    })
+}
+
+/// Returns the devices that advertise gamepad or joystick buttons along with an analog stick,
+/// without waiting for any input.
+pub fn find_gamepads() -> Result<Vec<PathBuf>, IdentifyError> {
+    find_devices(|device| {
+        (device.has(&EventCode::EV_KEY(EV_KEY::BTN_SOUTH))
+            || device.has(&EventCode::EV_KEY(EV_KEY::BTN_TRIGGER)))
+            && device.has(&EventCode::EV_ABS(EV_ABS::ABS_X))
+    })
+}
+
+pub(crate) fn enable_abs_info<D: evdev_rs::DeviceWrapper + ?Sized>(
+    device: &D,
+    abs: EV_ABS,
+    info: &evdev_rs::AbsInfo,
+) -> std::io::Result<()> {
+    let code = EventCode::EV_ABS(abs);
+    device.enable(&EventType::EV_ABS)?;
+    device.enable_event_code(&code, Some(info))?;
+    // evdev-rs hands libevdev a pointer to a temporary when enabling, so set the info again
+    // through the call that keeps it alive.
+    device.set_abs_info(&code, info);
+    Ok(())
+}
+
+/// Axis ranges for `DeviceWrapperExt::enable_gamepad_with_axes`. The defaults match those of
+/// xpad driven controllers.
+pub struct GamepadRanges {
+    /// ABS_X/Y and ABS_RX/RY.
+    pub stick: evdev_rs::AbsInfo,
+    /// ABS_Z and ABS_RZ.
+    pub trigger: evdev_rs::AbsInfo,
+    /// ABS_HAT0X/Y.
+    pub hat: evdev_rs::AbsInfo,
+}
+
+impl Default for GamepadRanges {
+    fn default() -> Self {
+        Self {
+            stick: evdev_rs::AbsInfo {
+                value: 0,
+                minimum: -32768,
+                maximum: 32767,
+                fuzz: 16,
+                flat: 128,
+                resolution: 0,
+            },
+            trigger: evdev_rs::AbsInfo {
+                value: 0,
+                minimum: 0,
+                maximum: 255,
+                fuzz: 0,
+                flat: 0,
+                resolution: 0,
+            },
+            hat: evdev_rs::AbsInfo {
+                value: 0,
+                minimum: -1,
+                maximum: 1,
+                fuzz: 0,
+                flat: 0,
+                resolution: 0,
+            },
+        }
+    }
 }
 
 pub trait DeviceWrapperExt: evdev_rs::DeviceWrapper {
@@ -269,15 +889,51 @@ pub trait DeviceWrapperExt: evdev_rs::DeviceWrapper {
         Ok(())
     }
 
+    /// Copies every event type, event code, ABS axis info and property enabled on `other`.
+    fn enable_from<D: evdev_rs::DeviceWrapper>(&self, other: &D) -> std::io::Result<()> {
+        for event_type in EventType::EV_SYN.iter() {
+            if other.has(&event_type) {
+                self.enable(&event_type)?;
+            }
+        }
+        for code in EventCode::EV_SYN(EV_SYN::SYN_REPORT).iter() {
+            if !other.has(&code) {
+                continue;
+            }
+            match code {
+                EventCode::EV_ABS(abs) => {
+                    if let Some(info) = other.abs_info(&code) {
+                        enable_abs_info(self, abs, &info)?;
+                    }
+                }
+                EventCode::EV_REP(_) => {
+                    let value = other.event_value(&code).unwrap_or(0);
+                    self.enable_event_code(&code, Some(&value))?;
+                }
+                code => self.enable(&code)?,
+            }
+        }
+        for prop in InputProp::INPUT_PROP_POINTER.iter() {
+            if other.has(&prop) {
+                self.enable(&prop)?;
+            }
+        }
+        Ok(())
+    }
+
     fn enable_keys(&self) -> std::io::Result<()> {
         self.enable(&EventType::EV_KEY)?;
         self.enable_codes(
             EventCode::EV_KEY(EV_KEY::KEY_RESERVED),
             EventCode::EV_KEY(EV_KEY::KEY_MICMUTE),
         )?;
+        // Passed along with key events for applications that read scancodes.
+        self.enable(&EventType::EV_MSC)?;
+        self.enable(&EventCode::EV_MSC(EV_MSC::MSC_SCAN))?;
         Ok(())
     }
 
+    /// The mouse buttons and all relative axes, including the high-resolution wheels.
     fn enable_mouse(&self) -> std::io::Result<()> {
         self.enable(&EventType::EV_REL)?;
         self.enable(&EventType::EV_KEY)?;
@@ -292,15 +948,14 @@ pub trait DeviceWrapperExt: evdev_rs::DeviceWrapper {
         Ok(())
     }
 
+    /// The standard gamepad buttons, BTN_SOUTH through BTN_THUMBR, and the d-pad buttons. The
+    /// legacy joystick buttons are left out, as they make SDL and udev treat the device as a
+    /// joystick rather than a gamepad.
     fn enable_gamepad(&self) -> std::io::Result<()> {
         self.enable(&EventType::EV_KEY)?;
         self.enable(&EventType::EV_ABS)?;
         self.enable_codes(
-            EventCode::EV_KEY(EV_KEY::BTN_0),
-            EventCode::EV_KEY(EV_KEY::BTN_9),
-        )?;
-        self.enable_codes(
-            EventCode::EV_KEY(EV_KEY::BTN_TRIGGER),
+            EventCode::EV_KEY(EV_KEY::BTN_SOUTH),
             EventCode::EV_KEY(EV_KEY::BTN_THUMBR),
         )?;
         self.enable_codes(
@@ -309,6 +964,37 @@ pub trait DeviceWrapperExt: evdev_rs::DeviceWrapper {
         )?;
         Ok(())
     }
+
+    /// Exactly the buttons and axes of the controller `preset` poses as. Together with the
+    /// controller's ids, see `VirtualDeviceBuilder::preset`, this lets SDL and games apply their
+    /// built-in mapping.
+    fn enable_controller(&self, preset: ControllerPreset) -> std::io::Result<()> {
+        preset.enable(self)
+    }
+
+    fn enable_abs(&self, abs: EV_ABS, info: &evdev_rs::AbsInfo) -> std::io::Result<()> {
+        enable_abs_info(self, abs, info)
+    }
+
+    /// The buttons of `enable_gamepad` along with two sticks, two analog triggers and a hat.
+    fn enable_gamepad_with_axes(&self, ranges: &GamepadRanges) -> std::io::Result<()> {
+        self.enable_gamepad()?;
+        for (abs, info) in [
+            (EV_ABS::ABS_X, &ranges.stick),
+            (EV_ABS::ABS_Y, &ranges.stick),
+            (EV_ABS::ABS_RX, &ranges.stick),
+            (EV_ABS::ABS_RY, &ranges.stick),
+            (EV_ABS::ABS_Z, &ranges.trigger),
+            (EV_ABS::ABS_RZ, &ranges.trigger),
+            (EV_ABS::ABS_HAT0X, &ranges.hat),
+            (EV_ABS::ABS_HAT0Y, &ranges.hat),
+        ]
+        .iter()
+        {
+            self.enable_abs(*abs, info)?;
+        }
+        Ok(())
+    }
 }
 
 impl<D: evdev_rs::DeviceWrapper> DeviceWrapperExt for D {}
@@ -0,0 +1,77 @@
+use evdev_rs::enums::{EventCode, EV_MSC, EV_SYN};
+use evdev_rs::util::event_code_to_int;
+use evdev_rs::InputEvent;
+use futures::{Stream, StreamExt as _};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Formats an event like evtest does, e.g.
+/// `Event: time 1700000000.123456, type 1 (EV_KEY), code 30 (KEY_A), value 1 (pressed)`, with
+/// frames ending in `Event: time 1700000000.123456, -------------- SYN_REPORT ------------`.
+pub fn format_event(event: &InputEvent) -> String {
+    let time = format!("{}.{:06}", event.time.tv_sec, event.time.tv_usec);
+    if let EventCode::EV_SYN(syn @ EV_SYN::SYN_REPORT)
+    | EventCode::EV_SYN(syn @ EV_SYN::SYN_DROPPED) = event.event_code
+    {
+        return format!(
+            "Event: time {}, -------------- {} ------------",
+            time,
+            EventCode::EV_SYN(syn)
+        );
+    }
+    let (event_type, code) = event_code_to_int(&event.event_code);
+    let type_name = event
+        .event_type()
+        .map_or_else(|| "?".to_string(), |event_type| event_type.to_string());
+    let code_name = match event.event_code.to_string() {
+        name if name.is_empty() => "?".to_string(),
+        name => name,
+    };
+    // Like evtest, scancodes are printed in hex.
+    let value = match event.event_code {
+        EventCode::EV_MSC(EV_MSC::MSC_SCAN) => format!("{:02x}", event.value),
+        _ => event.value.to_string(),
+    };
+    let state = match (&event.event_code, event.value) {
+        (EventCode::EV_KEY(_), 0) => " (released)",
+        (EventCode::EV_KEY(_), 1) => " (pressed)",
+        (EventCode::EV_KEY(_), 2) => " (repeat)",
+        _ => "",
+    };
+    format!(
+        "Event: time {}, type {} ({}), code {} ({}), value {}{}",
+        time, event_type, type_name, code, code_name, value, state
+    )
+}
+
+/// Stream adapter logging every event with `format_event` as it passes through, see
+/// `EventStreamExt::logged`.
+pub struct Logged<S> {
+    stream: S,
+    level: log::Level,
+}
+
+impl<S> Logged<S> {
+    pub fn new(stream: S, level: log::Level) -> Self {
+        Self { stream, level }
+    }
+
+    pub fn into_inner(self) -> S {
+        self.stream
+    }
+}
+
+impl<S, E> Stream for Logged<S>
+where
+    S: Stream<Item = Result<InputEvent, E>> + Unpin,
+{
+    type Item = S::Item;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let item = futures::ready!(self.stream.poll_next_unpin(cx));
+        if let Some(Ok(event)) = &item {
+            log::log!(self.level, "{}", format_event(event));
+        }
+        Poll::Ready(item)
+    }
+}
@@ -0,0 +1,114 @@
+use crate::text::text_frames;
+use crate::{Injector, ScheduleHandle, Scheduler};
+use evdev_rs::enums::{EventCode, EV_SYN};
+use evdev_rs::{InputEvent, TimeVal};
+use futures::{Stream, TryStreamExt as _};
+use std::time::{Duration, Instant};
+
+/// A recorded sequence of events along with the delay preceding each of them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Macro {
+    events: Vec<(Duration, EventCode, i32)>,
+}
+
+pub(crate) fn time_since(earlier: &TimeVal, later: &TimeVal) -> Duration {
+    let micros = |time: &TimeVal| i128::from(time.tv_sec) * 1_000_000 + i128::from(time.tv_usec);
+    Duration::from_micros((micros(later) - micros(earlier)).max(0) as u64)
+}
+
+impl Macro {
+    pub fn new(events: Vec<(Duration, EventCode, i32)>) -> Self {
+        Self { events }
+    }
+
+    /// A macro typing ASCII text as on a US layout, waiting `delay` before each key change.
+    pub fn from_text(text: &str, delay: Duration) -> std::io::Result<Self> {
+        let events = text_frames(text)?
+            .into_iter()
+            .flat_map(|(event_code, value)| {
+                [
+                    (delay, event_code, value),
+                    (Duration::ZERO, EventCode::EV_SYN(EV_SYN::SYN_REPORT), 0),
+                ]
+            })
+            .collect();
+        Ok(Self { events })
+    }
+
+    pub fn events(&self) -> &[(Duration, EventCode, i32)] {
+        &self.events
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.events.iter().map(|(delay, _, _)| *delay).sum()
+    }
+
+    /// Replays the macro, scaling the original delays down by `speed`, which must be positive.
+    pub async fn play<U: Injector>(&self, uinput: &U, speed: f64) -> std::io::Result<()> {
+        for (delay, event_code, value) in &self.events {
+            let delay = delay.div_f64(speed);
+            if delay > Duration::ZERO {
+                let _: std::time::Instant = async_io::Timer::after(delay).await;
+            }
+            uinput.inject_event(*event_code, *value)?;
+        }
+        Ok(())
+    }
+
+    /// Schedules the macro on `scheduler`, starting now, with the original delays scaled down by
+    /// `speed` like `play`. Each frame, up to and including a SYN_REPORT, becomes one entry due
+    /// at the time of its last event, which can be cancelled through the returned handles.
+    pub fn schedule<U: Injector>(
+        &self,
+        scheduler: &Scheduler<U>,
+        speed: f64,
+    ) -> Vec<ScheduleHandle> {
+        let mut at = Instant::now();
+        let mut frame = Vec::new();
+        let mut handles = Vec::new();
+        for (delay, event_code, value) in &self.events {
+            at += delay.div_f64(speed);
+            match event_code {
+                EventCode::EV_SYN(EV_SYN::SYN_REPORT) => {
+                    handles.push(scheduler.emit_at(at, &frame));
+                    frame.clear();
+                }
+                _ => frame.push((*event_code, *value)),
+            }
+        }
+        if !frame.is_empty() {
+            handles.push(scheduler.emit_at(at, &frame));
+        }
+        handles
+    }
+}
+
+/// Records events from `device`, using the kernel timestamps for timing, until `stop` returns
+/// true for an event or the stream ends. The event that triggers `stop` is not recorded.
+pub async fn record<S, E>(
+    device: &mut S,
+    mut stop: impl FnMut(&InputEvent) -> bool,
+) -> Result<Macro, E>
+where
+    S: Stream<Item = Result<InputEvent, E>> + Unpin,
+{
+    let mut events = Vec::new();
+    let mut last_time = None;
+    while let Some(event) = device.try_next().await? {
+        if stop(&event) {
+            break;
+        }
+        let InputEvent {
+            time,
+            event_code,
+            value,
+        } = event;
+        let delay = last_time
+            .as_ref()
+            .map(|last_time| time_since(last_time, &time))
+            .unwrap_or(Duration::ZERO);
+        last_time = Some(time);
+        events.push((delay, event_code, value));
+    }
+    Ok(Macro { events })
+}
@@ -0,0 +1,177 @@
+use crate::{AsyncDevice, DeviceError, DeviceInfo, DeviceMonitor, MonitorEvent};
+use async_io::Timer;
+use evdev_rs::{GrabMode, InputEvent};
+use futures::{Future as _, Stream, StreamExt as _};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+const RETRY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Whether `e` is what reading from an unplugged device fails with, or EIO, which devices that
+/// didn't survive a system suspend can fail with while their node stays in place. Device streams
+/// report these as `DeviceError::Disconnected`.
+pub fn is_disconnect(e: &std::io::Error) -> bool {
+    matches!(e.raw_os_error(), Some(libc::ENODEV) | Some(libc::EIO))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeviceEvent {
+    Event(InputEvent),
+    /// The device was unplugged. Unless reconnecting, this is the last item.
+    Disconnected,
+    /// A device matching the unplugged one appeared at the given path and events continue from
+    /// it.
+    Reconnected(PathBuf),
+}
+
+struct Reconnect {
+    monitor: DeviceMonitor,
+    regrab: bool,
+    retry: Timer,
+}
+
+/// An `AsyncDevice` stream that reports unplugging as `DeviceEvent::Disconnected` instead of
+/// erroring with `DeviceError::Disconnected`, and can optionally reattach to the device when it
+/// is plugged back in, including when it comes back from a system suspend.
+pub struct ManagedDevice {
+    device: Option<AsyncDevice>,
+    info: DeviceInfo,
+    reconnect: Option<Reconnect>,
+}
+
+// Identifies the same physical device across replugging, which may change its path and physical
+// location.
+fn same_device(a: &DeviceInfo, b: &DeviceInfo) -> bool {
+    a.name == b.name
+        && a.bustype == b.bustype
+        && a.vendor == b.vendor
+        && a.product == b.product
+        && a.uniq == b.uniq
+}
+
+impl ManagedDevice {
+    pub fn new(device: AsyncDevice) -> Self {
+        Self {
+            info: device.info(),
+            device: Some(device),
+            reconnect: None,
+        }
+    }
+
+    /// Reattaches when a device with the same name, ids and serial appears after a disconnect,
+    /// grabbing it if `regrab` is set. The process' own virtual devices are never reattached to.
+    /// The hotplug monitor starts now, so the device can't return unnoticed. Devices which don't
+    /// announce their return, e.g. after failing with EIO, are also looked for every second.
+    pub fn reconnect(mut self, regrab: bool) -> std::io::Result<Self> {
+        self.reconnect = Some(Reconnect {
+            monitor: DeviceMonitor::new()?,
+            regrab,
+            retry: Timer::after(RETRY_INTERVAL),
+        });
+        Ok(self)
+    }
+
+    /// The current device, or `None` while disconnected.
+    pub fn device(&self) -> Option<&AsyncDevice> {
+        self.device.as_ref()
+    }
+
+    pub fn device_mut(&mut self) -> Option<&mut AsyncDevice> {
+        self.device.as_mut()
+    }
+
+    fn open_matching(&self, path: &Path) -> Option<AsyncDevice> {
+        // A uinput clone of the device, e.g. a `Proxy`'s, matches it as long as the device has
+        // no serial, and reattaching to it would read back what is injected into it.
+        let own = crate::virtual_device::own_event_nodes().unwrap_or_default();
+        if own.contains(path) {
+            return None;
+        }
+        AsyncDevice::new(path)
+            .ok()
+            .filter(|device| same_device(&device.info(), &self.info))
+    }
+
+    fn find_present(&self) -> Option<PathBuf> {
+        // Nodes that can't be opened are skipped rather than failing the scan, as some are likely
+        // to come and go while devices return from a suspend.
+        crate::open_available()
+            .ok()?
+            .into_iter()
+            .find(|(_, device)| same_device(&DeviceInfo::from_device(device), &self.info))
+            .map(|(path, _)| path)
+    }
+}
+
+impl Stream for ManagedDevice {
+    type Item = Result<DeviceEvent, DeviceError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        loop {
+            match (&mut this.device, &mut this.reconnect) {
+                (Some(device), reconnect) => {
+                    // Keep the monitor drained while connected; only devices added after a
+                    // disconnect are of interest.
+                    if let Some(Reconnect { monitor, .. }) = reconnect {
+                        while let Poll::Ready(Some(event)) = monitor.poll_next_unpin(cx) {
+                            if let Err(e) = event {
+                                return Poll::Ready(Some(Err(e.into())));
+                            }
+                        }
+                    }
+                    return match device.poll_next_unpin(cx) {
+                        Poll::Ready(Some(Ok(event))) => {
+                            Poll::Ready(Some(Ok(DeviceEvent::Event(event))))
+                        }
+                        Poll::Ready(Some(Err(DeviceError::Disconnected(_)))) => {
+                            if let Some(Reconnect { retry, .. }) = reconnect {
+                                retry.set_after(RETRY_INTERVAL);
+                            }
+                            this.device = None;
+                            Poll::Ready(Some(Ok(DeviceEvent::Disconnected)))
+                        }
+                        poll => poll.map(|event| event.map(|event| event.map(DeviceEvent::Event))),
+                    };
+                }
+                (None, None) => return Poll::Ready(None),
+                (
+                    None,
+                    Some(Reconnect {
+                        monitor,
+                        regrab,
+                        retry,
+                    }),
+                ) => {
+                    let regrab = *regrab;
+                    let path = match monitor.poll_next_unpin(cx) {
+                        Poll::Ready(Some(Ok(MonitorEvent::Added(path)))) => path,
+                        Poll::Ready(Some(Ok(MonitorEvent::Removed(_)))) => continue,
+                        Poll::Ready(Some(Err(e))) => return Poll::Ready(Some(Err(e.into()))),
+                        Poll::Ready(None) => return Poll::Ready(None),
+                        // A node that stayed in place, or came back before its removal was
+                        // noticed, isn't announced.
+                        Poll::Pending => {
+                            let _: std::time::Instant =
+                                futures::ready!(Pin::new(&mut *retry).poll(cx));
+                            retry.set_after(RETRY_INTERVAL);
+                            match this.find_present() {
+                                Some(path) => path,
+                                None => continue,
+                            }
+                        }
+                    };
+                    if let Some(mut device) = this.open_matching(&path) {
+                        if regrab {
+                            device.grab(GrabMode::Grab)?;
+                        }
+                        this.device = Some(device);
+                        return Poll::Ready(Some(Ok(DeviceEvent::Reconnected(path))));
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,63 @@
+use crate::{AsyncDevice, DeviceError, OpenError};
+use evdev_rs::{DeviceWrapper as _, InputEvent};
+use futures::{Stream, StreamExt as _};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Which device a merged event came from: its position among the merged devices, which stays
+/// the same for as long as the stream lives, along with its path and name for matching, e.g.
+/// to only remap an external keyboard and leave a laptop's internal one alone.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DeviceId {
+    pub index: usize,
+    pub path: Arc<Path>,
+    pub name: Arc<str>,
+}
+
+/// Event stream over several devices, each event tagged with its device.
+pub type MergedStream = futures::stream::SelectAll<
+    Box<dyn Stream<Item = Result<(DeviceId, InputEvent), DeviceError>> + Send + Unpin>,
+>;
+
+pub(crate) fn merge(devices: impl IntoIterator<Item = (PathBuf, AsyncDevice)>) -> MergedStream {
+    let mut merged = futures::stream::SelectAll::new();
+    for (index, (path, device)) in devices.into_iter().enumerate() {
+        let id = DeviceId {
+            index,
+            path: path.into(),
+            name: device.evdev().name().unwrap_or_default().into(),
+        };
+        merged.push(
+            Box::new(device.map(move |event| event.map(|event| (id.clone(), event))))
+                as Box<dyn Stream<Item = _> + Send + Unpin>,
+        );
+    }
+    merged
+}
+
+/// Merges the event streams of the devices at `paths`, numbering them in order from zero, e.g.
+///
+/// ```no_run
+/// # use futures::TryStreamExt as _;
+/// # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+/// let mut events = evdev_utils::merged_stream(&["/dev/input/event3", "/dev/input/event7"])?;
+/// while let Some((id, event)) = events.try_next().await? {
+///     if !id.name.contains("AT Translated") {
+///         println!("{} {} {}", id.index, event.event_code, event.value);
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub fn merged_stream<P: AsRef<Path>>(
+    paths: impl IntoIterator<Item = P>,
+) -> Result<MergedStream, OpenError> {
+    let devices = paths
+        .into_iter()
+        .map(|path| {
+            let path = path.as_ref();
+            AsyncDevice::new(path).map(|device| (path.to_path_buf(), device))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(merge(devices))
+}
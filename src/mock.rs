@@ -0,0 +1,102 @@
+use crate::macros::time_since;
+use crate::{AsyncDevice, DeviceError};
+use async_io::Timer;
+use evdev_rs::{GrabMode, InputEvent, TimeVal};
+use futures::{ready, Future as _, Stream};
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// A device that events can be read from, so that code driving a device can run against a
+/// `MockDevice` in tests instead of real hardware.
+pub trait EventSource: Stream<Item = Result<InputEvent, DeviceError>> + Unpin {
+    fn grab(&mut self, grab: GrabMode) -> std::io::Result<()>;
+
+    /// The libevdev device behind the source, if there is one, for capabilities and state.
+    fn libevdev(&self) -> Option<&evdev_rs::Device> {
+        None
+    }
+}
+
+impl EventSource for AsyncDevice {
+    fn grab(&mut self, grab: GrabMode) -> std::io::Result<()> {
+        AsyncDevice::grab(self, grab)
+    }
+
+    fn libevdev(&self) -> Option<&evdev_rs::Device> {
+        Some(self.evdev())
+    }
+}
+
+/// Replays a fixed list of events and then ends, without needing any device access.
+pub struct MockDevice {
+    events: VecDeque<InputEvent>,
+    paced: bool,
+    last_time: Option<TimeVal>,
+    timer: Option<Timer>,
+    grabbed: bool,
+}
+
+impl MockDevice {
+    /// Yields all events immediately.
+    pub fn new(events: Vec<InputEvent>) -> Self {
+        Self {
+            events: events.into(),
+            paced: false,
+            last_time: None,
+            timer: None,
+            grabbed: false,
+        }
+    }
+
+    /// Yields the events spaced out according to their timestamps, for exercising timeouts.
+    pub fn paced(events: Vec<InputEvent>) -> Self {
+        Self {
+            paced: true,
+            ..Self::new(events)
+        }
+    }
+
+    pub fn is_grabbed(&self) -> bool {
+        self.grabbed
+    }
+}
+
+impl EventSource for MockDevice {
+    fn grab(&mut self, grab: GrabMode) -> std::io::Result<()> {
+        self.grabbed = matches!(grab, GrabMode::Grab);
+        Ok(())
+    }
+}
+
+impl Stream for MockDevice {
+    type Item = Result<InputEvent, DeviceError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        if let Some(timer) = &mut this.timer {
+            let _: std::time::Instant = ready!(Pin::new(timer).poll(cx));
+            this.timer = None;
+            return Poll::Ready(this.events.pop_front().map(Ok));
+        }
+        let time = match this.events.front() {
+            Some(event) => event.time,
+            None => return Poll::Ready(None),
+        };
+        if this.paced {
+            let delay = this
+                .last_time
+                .replace(time)
+                .map(|last_time| time_since(&last_time, &time))
+                .unwrap_or_default();
+            if !delay.is_zero() {
+                let mut timer = Timer::after(delay);
+                if Pin::new(&mut timer).poll(cx).is_pending() {
+                    this.timer = Some(timer);
+                    return Poll::Pending;
+                }
+            }
+        }
+        Poll::Ready(this.events.pop_front().map(Ok))
+    }
+}
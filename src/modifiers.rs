@@ -0,0 +1,171 @@
+use crate::key_state::kernel_keys;
+use crate::{AsyncDevice, Processor};
+use evdev_rs::enums::{EventCode, EV_KEY};
+use evdev_rs::InputEvent;
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+use std::ops::{BitOr, BitOrAssign};
+use std::os::unix::io::AsRawFd as _;
+use std::time::Instant;
+
+/// A set of held modifier keys, distinguishing left and right.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Modifiers(u8);
+
+const MODIFIERS: [(EV_KEY, Modifiers, &str); 8] = [
+    (EV_KEY::KEY_LEFTCTRL, Modifiers::LEFT_CTRL, "LEFT_CTRL"),
+    (EV_KEY::KEY_RIGHTCTRL, Modifiers::RIGHT_CTRL, "RIGHT_CTRL"),
+    (EV_KEY::KEY_LEFTSHIFT, Modifiers::LEFT_SHIFT, "LEFT_SHIFT"),
+    (
+        EV_KEY::KEY_RIGHTSHIFT,
+        Modifiers::RIGHT_SHIFT,
+        "RIGHT_SHIFT",
+    ),
+    (EV_KEY::KEY_LEFTALT, Modifiers::LEFT_ALT, "LEFT_ALT"),
+    (EV_KEY::KEY_RIGHTALT, Modifiers::RIGHT_ALT, "RIGHT_ALT"),
+    (EV_KEY::KEY_LEFTMETA, Modifiers::LEFT_META, "LEFT_META"),
+    (EV_KEY::KEY_RIGHTMETA, Modifiers::RIGHT_META, "RIGHT_META"),
+];
+
+impl Modifiers {
+    pub const NONE: Self = Self(0);
+    pub const LEFT_CTRL: Self = Self(1 << 0);
+    pub const RIGHT_CTRL: Self = Self(1 << 1);
+    pub const LEFT_SHIFT: Self = Self(1 << 2);
+    pub const RIGHT_SHIFT: Self = Self(1 << 3);
+    pub const LEFT_ALT: Self = Self(1 << 4);
+    pub const RIGHT_ALT: Self = Self(1 << 5);
+    pub const LEFT_META: Self = Self(1 << 6);
+    pub const RIGHT_META: Self = Self(1 << 7);
+
+    /// The modifier `key` is, if any.
+    pub fn from_key(key: EV_KEY) -> Option<Self> {
+        MODIFIERS
+            .iter()
+            .find(|(modifier_key, _, _)| *modifier_key == key)
+            .map(|(_, modifier, _)| *modifier)
+    }
+
+    pub fn from_keys<'a, I: IntoIterator<Item = &'a EV_KEY>>(keys: I) -> Self {
+        keys.into_iter()
+            .filter_map(|key| Self::from_key(*key))
+            .fold(Self::NONE, BitOr::bitor)
+    }
+
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Whether either ctrl key is held.
+    pub fn ctrl(self) -> bool {
+        self.0 & (Self::LEFT_CTRL.0 | Self::RIGHT_CTRL.0) != 0
+    }
+
+    pub fn shift(self) -> bool {
+        self.0 & (Self::LEFT_SHIFT.0 | Self::RIGHT_SHIFT.0) != 0
+    }
+
+    pub fn alt(self) -> bool {
+        self.0 & (Self::LEFT_ALT.0 | Self::RIGHT_ALT.0) != 0
+    }
+
+    pub fn meta(self) -> bool {
+        self.0 & (Self::LEFT_META.0 | Self::RIGHT_META.0) != 0
+    }
+
+    fn remove(&mut self, other: Self) {
+        self.0 &= !other.0;
+    }
+}
+
+impl BitOr for Modifiers {
+    type Output = Self;
+
+    fn bitor(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+}
+
+impl BitOrAssign for Modifiers {
+    fn bitor_assign(&mut self, other: Self) {
+        self.0 |= other.0;
+    }
+}
+
+impl fmt::Debug for Modifiers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut names = MODIFIERS
+            .iter()
+            .filter(|(_, modifier, _)| self.contains(*modifier))
+            .map(|(_, _, name)| *name);
+        match names.next() {
+            Some(first) => {
+                f.write_str(first)?;
+                names.try_for_each(|name| write!(f, " | {}", name))
+            }
+            None => f.write_str("NONE"),
+        }
+    }
+}
+
+/// Annotates each event with the modifiers held after it.
+///
+/// Releases that happen while the device is grabbed by another process are never seen, which
+/// would leave modifiers stuck; call `resync` after getting the device back to catch up.
+#[derive(Debug, Default)]
+pub struct ModifierTracker {
+    modifiers: Modifiers,
+}
+
+impl ModifierTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts from the modifiers among `keys`, e.g. `AsyncDevice::current_keys`.
+    pub fn from_keys(keys: &HashSet<EV_KEY>) -> Self {
+        Self {
+            modifiers: Modifiers::from_keys(keys),
+        }
+    }
+
+    pub fn modifiers(&self) -> Modifiers {
+        self.modifiers
+    }
+
+    pub fn reset(&mut self) {
+        self.modifiers = Modifiers::NONE;
+    }
+
+    /// Replaces the tracked state with the modifiers the kernel reports as held on `device`.
+    pub fn resync(&mut self, device: &AsyncDevice) -> std::io::Result<()> {
+        let keys = kernel_keys(device.evdev().file().as_raw_fd())?;
+        self.modifiers = Modifiers::from_keys(&keys);
+        Ok(())
+    }
+}
+
+impl Processor for ModifierTracker {
+    type Output = (InputEvent, Modifiers);
+
+    fn process(
+        &mut self,
+        event: InputEvent,
+        _now: Instant,
+        out: &mut VecDeque<(InputEvent, Modifiers)>,
+    ) {
+        if let EventCode::EV_KEY(key) = event.event_code {
+            if let Some(modifier) = Modifiers::from_key(key) {
+                match event.value {
+                    0 => self.modifiers.remove(modifier),
+                    _ => self.modifiers |= modifier,
+                }
+            }
+        }
+        out.push_back((event, self.modifiers));
+    }
+}
@@ -0,0 +1,202 @@
+use async_io::Async;
+use futures::{ready, Stream, StreamExt as _};
+use std::collections::{HashSet, VecDeque};
+use std::ffi::{CString, OsStr};
+use std::fs::File;
+use std::io::Read as _;
+use std::os::unix::ffi::OsStrExt as _;
+use std::os::unix::io::FromRawFd as _;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+pub(crate) const INPUT_DIR: &str = "/dev/input";
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum MonitorEvent {
+    Added(PathBuf),
+    Removed(PathBuf),
+}
+
+/// Watches `/dev/input` with inotify and reports event nodes as they appear and disappear.
+///
+/// A node is only reported as added once it is readable by this process, since udev usually
+/// fixes up permissions some time after the node is created.
+pub struct DeviceMonitor {
+    inotify: Async<File>,
+    known: HashSet<PathBuf>,
+    pending: VecDeque<MonitorEvent>,
+}
+
+pub(crate) fn is_event_node(path: &Path) -> bool {
+    path.file_name()
+        .map(|name| name.as_bytes().starts_with(b"event"))
+        .unwrap_or(false)
+}
+
+fn is_readable(path: &Path) -> bool {
+    CString::new(path.as_os_str().as_bytes())
+        .map(|path| unsafe { libc::access(path.as_ptr(), libc::R_OK) } == 0)
+        .unwrap_or(false)
+}
+
+impl DeviceMonitor {
+    pub fn new() -> std::io::Result<Self> {
+        let fd = unsafe { libc::inotify_init1(libc::IN_NONBLOCK | libc::IN_CLOEXEC) };
+        if fd < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        let inotify = unsafe { File::from_raw_fd(fd) };
+        let dir = CString::new(INPUT_DIR).expect("no interior nul");
+        let wd = unsafe {
+            libc::inotify_add_watch(
+                fd,
+                dir.as_ptr(),
+                libc::IN_CREATE | libc::IN_ATTRIB | libc::IN_DELETE,
+            )
+        };
+        if wd < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        // Seed after the watch is in place so that nothing created in between is missed.
+        let known = std::fs::read_dir(INPUT_DIR)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<std::io::Result<Vec<_>>>()?
+            .into_iter()
+            .filter(|path| is_event_node(path) && is_readable(path))
+            .collect();
+        Ok(Self {
+            inotify: Async::new(inotify)?,
+            known,
+            pending: VecDeque::new(),
+        })
+    }
+
+    /// Event nodes that currently exist and are readable.
+    pub fn devices(&self) -> impl Iterator<Item = &Path> {
+        self.known.iter().map(PathBuf::as_path)
+    }
+
+    fn handle_buffer(&mut self, mut buf: &[u8]) {
+        const HEADER_LEN: usize = std::mem::size_of::<libc::inotify_event>();
+        while buf.len() >= HEADER_LEN {
+            let header =
+                unsafe { std::ptr::read_unaligned(buf.as_ptr() as *const libc::inotify_event) };
+            let name_len = header.len as usize;
+            let name = &buf[HEADER_LEN..HEADER_LEN + name_len];
+            buf = &buf[HEADER_LEN + name_len..];
+            let name = match name.iter().position(|&b| b == 0) {
+                Some(end) => &name[..end],
+                None => name,
+            };
+            let path = Path::new(INPUT_DIR).join(OsStr::from_bytes(name));
+            if !is_event_node(&path) {
+                continue;
+            }
+            if header.mask & libc::IN_DELETE != 0 {
+                if self.known.remove(&path) {
+                    self.pending.push_back(MonitorEvent::Removed(path));
+                }
+            } else if !self.known.contains(&path) && is_readable(&path) {
+                let _: bool = self.known.insert(path.clone());
+                self.pending.push_back(MonitorEvent::Added(path));
+            }
+        }
+    }
+}
+
+impl Stream for DeviceMonitor {
+    type Item = std::io::Result<MonitorEvent>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut buf = [0u8; 4096];
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Poll::Ready(Some(Ok(event)));
+            }
+            match self.inotify.get_ref().read(&mut buf) {
+                Ok(len) => self.handle_buffer(&buf[..len]),
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                    if let Err(e) = ready!(self.inotify.poll_readable(cx)) {
+                        return Poll::Ready(Some(Err(e)));
+                    }
+                }
+                Err(e) => return Poll::Ready(Some(Err(e))),
+            }
+        }
+    }
+}
+
+type TaggedEvents = futures::stream::LocalBoxStream<
+    'static,
+    Result<(Arc<Path>, evdev_rs::InputEvent), crate::DeviceError>,
+>;
+
+fn tagged_events(path: PathBuf) -> std::io::Result<TaggedEvents> {
+    let device = crate::AsyncDevice::new(&path)?;
+    let path = Arc::<Path>::from(path);
+    Ok(device
+        // A device that has been unplugged errors with ENODEV forever; drop it from the set
+        // instead.
+        .take_while(|event| {
+            futures::future::ready(match event {
+                Err(e) => !matches!(e, crate::DeviceError::Disconnected(_)),
+                Ok(_) => true,
+            })
+        })
+        .map(move |event| event.map(|event| (path.clone(), event)))
+        .boxed_local())
+}
+
+/// Combined event stream over all input devices which picks up devices plugged in after it was
+/// created. Virtual devices created by this process are left out, as by `all_devices_matching`.
+pub struct HotplugDevices {
+    monitor: DeviceMonitor,
+    devices: futures::stream::SelectAll<TaggedEvents>,
+}
+
+impl HotplugDevices {
+    pub(crate) fn new() -> std::io::Result<Self> {
+        let monitor = DeviceMonitor::new()?;
+        let own = crate::virtual_device::own_event_nodes()?;
+        let devices = monitor
+            .devices()
+            .filter(|path| !own.contains(*path))
+            .map(|path| tagged_events(path.to_path_buf()))
+            .collect::<std::io::Result<_>>()?;
+        Ok(Self { monitor, devices })
+    }
+}
+
+impl Stream for HotplugDevices {
+    type Item = Result<(Arc<Path>, evdev_rs::InputEvent), crate::DeviceError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        while let Poll::Ready(Some(event)) = self.monitor.poll_next_unpin(cx) {
+            match event {
+                Ok(MonitorEvent::Added(path)) => {
+                    let events = crate::virtual_device::own_event_nodes().and_then(|own| {
+                        if own.contains(&path) {
+                            Ok(None)
+                        } else {
+                            tagged_events(path).map(Some)
+                        }
+                    });
+                    match events {
+                        Ok(Some(events)) => self.devices.push(events),
+                        Ok(None) => {}
+                        Err(e) => return Poll::Ready(Some(Err(e.into()))),
+                    }
+                }
+                Ok(MonitorEvent::Removed(_)) => {}
+                Err(e) => return Poll::Ready(Some(Err(e.into()))),
+            }
+        }
+        match self.devices.poll_next_unpin(cx) {
+            // An empty set is not the end of the stream, more devices may still show up.
+            Poll::Ready(None) => Poll::Pending,
+            poll => poll,
+        }
+    }
+}
@@ -0,0 +1,236 @@
+use crate::{Processor, ZERO_TIME};
+use evdev_rs::enums::{EventCode, EV_KEY, EV_REL, EV_SYN};
+use evdev_rs::InputEvent;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::{Duration, Instant};
+
+/// Drives the pointer from the keyboard. Direction keys move the pointer, starting at
+/// `initial_speed` and accelerating to `max_speed` over the acceleration time while held. Button
+/// keys press a mouse button while held, and drag lock keys press or release one on each press.
+/// Other events pass through, so the output needs a virtual device with both the keyboard and
+/// mouse, e.g.
+///
+/// ```no_run
+/// # use evdev_utils::{AsyncDevice, EventStreamExt as _, Injector as _};
+/// # use evdev_utils::{MouseKeys, VirtualDeviceBuilder};
+/// # use futures::TryStreamExt as _;
+/// # async fn run(mut keyboard: AsyncDevice) -> Result<(), Box<dyn std::error::Error>> {
+/// let uinput = VirtualDeviceBuilder::new().keyboard().mouse().build()?;
+/// keyboard.grab(evdev_rs::GrabMode::Grab)?;
+/// let mut events = keyboard.process(MouseKeys::keypad());
+/// while let Some(event) = events.try_next().await? {
+///     uinput.inject_event_at(event.event_code, event.value, event.time)?;
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct MouseKeys {
+    directions: HashMap<EV_KEY, (f64, f64)>,
+    buttons: HashMap<EV_KEY, EV_KEY>,
+    drag_locks: HashMap<EV_KEY, EV_KEY>,
+    initial_speed: f64,
+    max_speed: f64,
+    acceleration: Duration,
+    tick: Duration,
+    held: HashSet<EV_KEY>,
+    locked: HashSet<EV_KEY>,
+    moving_since: Option<Instant>,
+    last_tick: Option<Instant>,
+    remainder: (f64, f64),
+}
+
+impl Default for MouseKeys {
+    fn default() -> Self {
+        Self {
+            directions: HashMap::new(),
+            buttons: HashMap::new(),
+            drag_locks: HashMap::new(),
+            initial_speed: 100.0,
+            max_speed: 1200.0,
+            acceleration: Duration::from_secs(1),
+            tick: Duration::from_millis(10),
+            held: HashSet::new(),
+            locked: HashSet::new(),
+            moving_since: None,
+            last_tick: None,
+            remainder: (0.0, 0.0),
+        }
+    }
+}
+
+impl MouseKeys {
+    /// No keys mapped.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keypad layout, as in X11: the digits around 5 move, 5 clicks and 0 holds or releases the
+    /// left button for dragging. Plus right-clicks.
+    pub fn keypad() -> Self {
+        let diagonal = std::f64::consts::FRAC_1_SQRT_2;
+        Self::new()
+            .direction(EV_KEY::KEY_KP8, (0.0, -1.0))
+            .direction(EV_KEY::KEY_KP2, (0.0, 1.0))
+            .direction(EV_KEY::KEY_KP4, (-1.0, 0.0))
+            .direction(EV_KEY::KEY_KP6, (1.0, 0.0))
+            .direction(EV_KEY::KEY_KP7, (-diagonal, -diagonal))
+            .direction(EV_KEY::KEY_KP9, (diagonal, -diagonal))
+            .direction(EV_KEY::KEY_KP1, (-diagonal, diagonal))
+            .direction(EV_KEY::KEY_KP3, (diagonal, diagonal))
+            .button(EV_KEY::KEY_KP5, EV_KEY::BTN_LEFT)
+            .button(EV_KEY::KEY_KPPLUS, EV_KEY::BTN_RIGHT)
+            .drag_lock(EV_KEY::KEY_KP0, EV_KEY::BTN_LEFT)
+    }
+
+    /// Moves the pointer along `direction` while `key` is held. Directions of keys held together
+    /// add up.
+    pub fn direction(mut self, key: EV_KEY, direction: (f64, f64)) -> Self {
+        let _: Option<(f64, f64)> = self.directions.insert(key, direction);
+        self
+    }
+
+    /// Holds `button` while `key` is held.
+    pub fn button(mut self, key: EV_KEY, button: EV_KEY) -> Self {
+        let _: Option<EV_KEY> = self.buttons.insert(key, button);
+        self
+    }
+
+    /// Presses `button` on a press of `key` if it isn't held, and releases it otherwise.
+    pub fn drag_lock(mut self, key: EV_KEY, button: EV_KEY) -> Self {
+        let _: Option<EV_KEY> = self.drag_locks.insert(key, button);
+        self
+    }
+
+    /// Pointer speeds in pixels per second.
+    pub fn speed(mut self, initial_speed: f64, max_speed: f64) -> Self {
+        self.initial_speed = initial_speed;
+        self.max_speed = max_speed;
+        self
+    }
+
+    /// How long a direction key has to be held to reach the maximum speed.
+    pub fn acceleration(mut self, acceleration: Duration) -> Self {
+        self.acceleration = acceleration;
+        self
+    }
+
+    /// How often motion is injected while moving.
+    pub fn tick(mut self, tick: Duration) -> Self {
+        self.tick = tick;
+        self
+    }
+
+    fn speed_at(&self, now: Instant) -> f64 {
+        let held = self
+            .moving_since
+            .map(|since| now.saturating_duration_since(since))
+            .unwrap_or_default();
+        let ramp = if self.acceleration.is_zero() {
+            1.0
+        } else {
+            (held.as_secs_f64() / self.acceleration.as_secs_f64()).min(1.0)
+        };
+        self.initial_speed + (self.max_speed - self.initial_speed) * ramp
+    }
+
+    fn direction_key(&mut self, key: EV_KEY, value: i32, now: Instant) {
+        match value {
+            1 => {
+                let _: bool = self.held.insert(key);
+                if self.moving_since.is_none() {
+                    self.moving_since = Some(now);
+                    self.last_tick = Some(now);
+                }
+            }
+            0 => {
+                let _: bool = self.held.remove(&key);
+                if self.held.is_empty() {
+                    self.moving_since = None;
+                    self.last_tick = None;
+                    self.remainder = (0.0, 0.0);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Processor for MouseKeys {
+    type Output = InputEvent;
+
+    fn process(&mut self, input: InputEvent, now: Instant, out: &mut VecDeque<InputEvent>) {
+        let key = match input.event_code {
+            EventCode::EV_KEY(key) => key,
+            _ => return out.push_back(input),
+        };
+        if self.directions.contains_key(&key) {
+            self.direction_key(key, input.value, now);
+        } else if let Some(button) = self.buttons.get(&key) {
+            if input.value != 2 {
+                out.push_back(InputEvent {
+                    event_code: EventCode::EV_KEY(*button),
+                    ..input
+                });
+            }
+        } else if let Some(button) = self.drag_locks.get(&key).copied() {
+            if input.value == 1 {
+                let value = if self.locked.remove(&button) {
+                    0
+                } else {
+                    let _: bool = self.locked.insert(button);
+                    1
+                };
+                out.push_back(InputEvent {
+                    event_code: EventCode::EV_KEY(button),
+                    value,
+                    ..input
+                });
+            }
+        } else {
+            out.push_back(input);
+        }
+    }
+
+    fn deadline(&self) -> Option<Instant> {
+        self.last_tick.map(|last_tick| last_tick + self.tick)
+    }
+
+    fn timeout(&mut self, now: Instant, out: &mut VecDeque<InputEvent>) {
+        let elapsed = self
+            .last_tick
+            .map(|last_tick| now.saturating_duration_since(last_tick))
+            .unwrap_or_default()
+            .as_secs_f64();
+        self.last_tick = Some(now);
+        let (x, y) = self
+            .held
+            .iter()
+            .filter_map(|key| self.directions.get(key))
+            .fold((0.0, 0.0), |(x, y), (dx, dy)| (x + dx, y + dy));
+        let distance = self.speed_at(now) * elapsed;
+        let (dx, dy) = (
+            x * distance + self.remainder.0,
+            y * distance + self.remainder.1,
+        );
+        let (whole_x, whole_y) = (dx.trunc(), dy.trunc());
+        self.remainder = (dx - whole_x, dy - whole_y);
+        let motion = [(EV_REL::REL_X, whole_x), (EV_REL::REL_Y, whole_y)];
+        let mut any = false;
+        for (rel, value) in motion.iter().filter(|(_, value)| *value != 0.0) {
+            out.push_back(InputEvent {
+                time: ZERO_TIME,
+                event_code: EventCode::EV_REL(*rel),
+                value: *value as i32,
+            });
+            any = true;
+        }
+        if any {
+            out.push_back(InputEvent {
+                time: ZERO_TIME,
+                event_code: EventCode::EV_SYN(EV_SYN::SYN_REPORT),
+                value: 0,
+            });
+        }
+    }
+}
@@ -0,0 +1,133 @@
+use crate::{GamepadEvent, Processor, Stick};
+use evdev_rs::enums::{EventCode, EV_KEY, EV_REL};
+use evdev_rs::InputEvent;
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+/// Below this deflection a stick without motion counts as centered.
+const REST: f64 = 1e-3;
+
+/// Turns mouse motion into a stick position, for aiming with a mouse on a virtual controller.
+/// Motion is accumulated between ticks of a fixed-rate ticker, and on each tick its speed in
+/// counts per second times the sensitivity gives the deflection the stick moves towards, with
+/// `decay` as the time constant. So the stick follows the mouse's speed and settles back to the
+/// center once it stops. Mapped buttons press gamepad buttons; other events are dropped.
+///
+/// Its output is that of `KeyboardGamepad`, so both can drive the same `AbsInjector`.
+#[derive(Debug)]
+pub struct MouseStick {
+    stick: Stick,
+    sensitivity: f64,
+    decay: Duration,
+    tick: Duration,
+    buttons: HashMap<EV_KEY, EV_KEY>,
+    motion: (f64, f64),
+    position: (f64, f64),
+    last_tick: Option<Instant>,
+}
+
+impl MouseStick {
+    /// Full deflection at 1000 counts per second, a decay of 30ms and 200 ticks per second.
+    pub fn new(stick: Stick) -> Self {
+        Self {
+            stick,
+            sensitivity: 0.001,
+            decay: Duration::from_millis(30),
+            tick: Duration::from_millis(5),
+            buttons: HashMap::new(),
+            motion: (0.0, 0.0),
+            position: (0.0, 0.0),
+            last_tick: None,
+        }
+    }
+
+    /// Deflection per count per second.
+    pub fn sensitivity(mut self, sensitivity: f64) -> Self {
+        self.sensitivity = sensitivity;
+        self
+    }
+
+    /// How quickly the stick follows the mouse's speed. Zero follows it at once.
+    pub fn decay(mut self, decay: Duration) -> Self {
+        self.decay = decay;
+        self
+    }
+
+    /// The interval of the output ticker.
+    pub fn tick(mut self, tick: Duration) -> Self {
+        self.tick = tick;
+        self
+    }
+
+    /// Holds gamepad `button` while the mouse's `mouse_button` is held.
+    pub fn button(mut self, mouse_button: EV_KEY, button: EV_KEY) -> Self {
+        let _: Option<EV_KEY> = self.buttons.insert(mouse_button, button);
+        self
+    }
+}
+
+impl Processor for MouseStick {
+    type Output = GamepadEvent;
+
+    fn process(&mut self, input: InputEvent, now: Instant, out: &mut VecDeque<GamepadEvent>) {
+        match input.event_code {
+            EventCode::EV_REL(EV_REL::REL_X) => self.motion.0 += f64::from(input.value),
+            EventCode::EV_REL(EV_REL::REL_Y) => self.motion.1 += f64::from(input.value),
+            EventCode::EV_KEY(key) if input.value != 2 => {
+                if let Some(button) = self.buttons.get(&key) {
+                    out.push_back(GamepadEvent::Button(*button, input.value == 1));
+                }
+                return;
+            }
+            _ => return,
+        }
+        // Start ticking at the first motion. Later motion waits for the next tick, so that
+        // the output rate doesn't depend on the mouse's.
+        if self.last_tick.is_none() {
+            self.last_tick = Some(now);
+        }
+    }
+
+    fn deadline(&self) -> Option<Instant> {
+        self.last_tick.map(|last_tick| last_tick + self.tick)
+    }
+
+    fn timeout(&mut self, now: Instant, out: &mut VecDeque<GamepadEvent>) {
+        let elapsed = self
+            .last_tick
+            .map(|last_tick| now.saturating_duration_since(last_tick))
+            .unwrap_or(self.tick)
+            .max(self.tick)
+            .as_secs_f64();
+        let (dx, dy) = std::mem::take(&mut self.motion);
+        let (mut target_x, mut target_y) = (
+            dx / elapsed * self.sensitivity,
+            dy / elapsed * self.sensitivity,
+        );
+        let length = target_x.hypot(target_y);
+        if length > 1.0 {
+            target_x /= length;
+            target_y /= length;
+        }
+        let follow = if self.decay.is_zero() {
+            1.0
+        } else {
+            1.0 - (-elapsed / self.decay.as_secs_f64()).exp()
+        };
+        let (x, y) = self.position;
+        let mut position = (x + (target_x - x) * follow, y + (target_y - y) * follow);
+        let resting = (dx, dy) == (0.0, 0.0) && position.0.hypot(position.1) < REST;
+        if resting {
+            position = (0.0, 0.0);
+        }
+        self.last_tick = if resting { None } else { Some(now) };
+        if position != self.position {
+            self.position = position;
+            let (abs_x, abs_y) = self.stick.axes();
+            out.push_back(GamepadEvent::Axes(vec![
+                (abs_x, position.0),
+                (abs_y, position.1),
+            ]));
+        }
+    }
+}
@@ -0,0 +1,149 @@
+use crate::Injector;
+use evdev_rs::enums::{EventCode, EV_ABS, EV_KEY};
+use evdev_rs::UInputDevice;
+
+const TRACKING_ID_MAX: i32 = 0xffff;
+
+const TOOLS: [EV_KEY; 5] = [
+    EV_KEY::BTN_TOOL_FINGER,
+    EV_KEY::BTN_TOOL_DOUBLETAP,
+    EV_KEY::BTN_TOOL_TRIPLETAP,
+    EV_KEY::BTN_TOOL_QUADTAP,
+    EV_KEY::BTN_TOOL_QUINTTAP,
+];
+
+/// A single contact change within a frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Touch {
+    Down { slot: usize, x: i32, y: i32 },
+    Move { slot: usize, x: i32, y: i32 },
+    Up { slot: usize },
+}
+
+/// Synthesizes multitouch protocol B frames, taking care of slot selection, tracking IDs,
+/// `BTN_TOUCH`/`BTN_TOOL_*` and single-touch pointer emulation.
+pub struct MtInjector<U = UInputDevice> {
+    uinput: U,
+    slots: Vec<Option<(i32, i32)>>,
+    current_slot: Option<usize>,
+    next_tracking_id: i32,
+}
+
+fn invalid_input(message: String) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidInput, message)
+}
+
+fn tool(contacts: usize) -> Option<EV_KEY> {
+    contacts
+        .checked_sub(1)
+        .map(|index| TOOLS[index.min(TOOLS.len() - 1)])
+}
+
+impl<U: Injector> MtInjector<U> {
+    pub fn new(uinput: U, slots: usize) -> Self {
+        Self {
+            uinput,
+            slots: vec![None; slots],
+            current_slot: None,
+            next_tracking_id: 0,
+        }
+    }
+
+    pub fn uinput(&self) -> &U {
+        &self.uinput
+    }
+
+    pub fn contacts(&self) -> usize {
+        self.slots.iter().filter(|slot| slot.is_some()).count()
+    }
+
+    pub fn touch_down(&mut self, slot: usize, x: i32, y: i32) -> std::io::Result<()> {
+        self.frame(&[Touch::Down { slot, x, y }])
+    }
+
+    pub fn touch_move(&mut self, slot: usize, x: i32, y: i32) -> std::io::Result<()> {
+        self.frame(&[Touch::Move { slot, x, y }])
+    }
+
+    pub fn touch_up(&mut self, slot: usize) -> std::io::Result<()> {
+        self.frame(&[Touch::Up { slot }])
+    }
+
+    fn validate(&self, touches: &[Touch]) -> std::io::Result<()> {
+        let mut slots = self.slots.iter().map(Option::is_some).collect::<Vec<_>>();
+        for touch in touches {
+            let (slot, active_before, active_after) = match *touch {
+                Touch::Down { slot, .. } => (slot, false, true),
+                Touch::Move { slot, .. } => (slot, true, true),
+                Touch::Up { slot } => (slot, true, false),
+            };
+            let active = slots
+                .get_mut(slot)
+                .ok_or_else(|| invalid_input(format!("slot {} is out of range", slot)))?;
+            if *active != active_before {
+                return Err(invalid_input(format!("{:?} on slot in wrong state", touch)));
+            }
+            *active = active_after;
+        }
+        Ok(())
+    }
+
+    /// Applies all changes in a single frame.
+    pub fn frame(&mut self, touches: &[Touch]) -> std::io::Result<()> {
+        self.validate(touches)?;
+        let contacts_before = self.contacts();
+        let mut events = Vec::new();
+        for touch in touches {
+            let slot = match *touch {
+                Touch::Down { slot, .. } | Touch::Move { slot, .. } | Touch::Up { slot } => slot,
+            };
+            if self.current_slot != Some(slot) {
+                events.push((EventCode::EV_ABS(EV_ABS::ABS_MT_SLOT), slot as i32));
+                self.current_slot = Some(slot);
+            }
+            match *touch {
+                Touch::Down { slot, x, y } => {
+                    events.push((
+                        EventCode::EV_ABS(EV_ABS::ABS_MT_TRACKING_ID),
+                        self.next_tracking_id,
+                    ));
+                    self.next_tracking_id = (self.next_tracking_id + 1) % (TRACKING_ID_MAX + 1);
+                    events.push((EventCode::EV_ABS(EV_ABS::ABS_MT_POSITION_X), x));
+                    events.push((EventCode::EV_ABS(EV_ABS::ABS_MT_POSITION_Y), y));
+                    self.slots[slot] = Some((x, y));
+                }
+                Touch::Move { slot, x, y } => {
+                    events.push((EventCode::EV_ABS(EV_ABS::ABS_MT_POSITION_X), x));
+                    events.push((EventCode::EV_ABS(EV_ABS::ABS_MT_POSITION_Y), y));
+                    self.slots[slot] = Some((x, y));
+                }
+                Touch::Up { slot } => {
+                    events.push((EventCode::EV_ABS(EV_ABS::ABS_MT_TRACKING_ID), -1));
+                    self.slots[slot] = None;
+                }
+            }
+        }
+        let contacts_after = self.contacts();
+        if (contacts_before == 0) != (contacts_after == 0) {
+            events.push((
+                EventCode::EV_KEY(EV_KEY::BTN_TOUCH),
+                (contacts_after > 0).into(),
+            ));
+        }
+        let (tool_before, tool_after) = (tool(contacts_before), tool(contacts_after));
+        if tool_before != tool_after {
+            if let Some(tool) = tool_before {
+                events.push((EventCode::EV_KEY(tool), 0));
+            }
+            if let Some(tool) = tool_after {
+                events.push((EventCode::EV_KEY(tool), 1));
+            }
+        }
+        // Single-touch emulation follows the lowest active slot.
+        if let Some((x, y)) = self.slots.iter().flatten().next() {
+            events.push((EventCode::EV_ABS(EV_ABS::ABS_X), *x));
+            events.push((EventCode::EV_ABS(EV_ABS::ABS_Y), *y));
+        }
+        self.uinput.emit(&events)
+    }
+}
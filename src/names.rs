@@ -0,0 +1,119 @@
+//! Case-insensitive parsing and formatting of event code names.
+
+use evdev_rs::enums::{EventCode, EventType, EV_KEY};
+use evdev_rs::util::event_code_to_int;
+use std::fmt;
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum KeyNameError {
+    #[error("empty key combination")]
+    Empty,
+    #[error(
+        "unknown key name `{0}`; expected an evdev name like `KEY_LEFTCTRL` or `BTN_LEFT`, a key \
+         without its prefix like `a` or `f1`, or an alias like `esc` or `lctrl`"
+    )]
+    UnknownKey(String),
+}
+
+/// Friendly names, the first for each key being the one used when formatting.
+const KEY_ALIASES: &[(&str, EV_KEY)] = &[
+    ("esc", EV_KEY::KEY_ESC),
+    ("escape", EV_KEY::KEY_ESC),
+    ("space", EV_KEY::KEY_SPACE),
+    ("enter", EV_KEY::KEY_ENTER),
+    ("return", EV_KEY::KEY_ENTER),
+    ("tab", EV_KEY::KEY_TAB),
+    ("backspace", EV_KEY::KEY_BACKSPACE),
+    ("del", EV_KEY::KEY_DELETE),
+    ("delete", EV_KEY::KEY_DELETE),
+    ("ins", EV_KEY::KEY_INSERT),
+    ("insert", EV_KEY::KEY_INSERT),
+    ("pgup", EV_KEY::KEY_PAGEUP),
+    ("pageup", EV_KEY::KEY_PAGEUP),
+    ("pgdn", EV_KEY::KEY_PAGEDOWN),
+    ("pagedown", EV_KEY::KEY_PAGEDOWN),
+    ("caps", EV_KEY::KEY_CAPSLOCK),
+    ("capslock", EV_KEY::KEY_CAPSLOCK),
+    ("ctrl", EV_KEY::KEY_LEFTCTRL),
+    ("control", EV_KEY::KEY_LEFTCTRL),
+    ("lctrl", EV_KEY::KEY_LEFTCTRL),
+    ("rctrl", EV_KEY::KEY_RIGHTCTRL),
+    ("shift", EV_KEY::KEY_LEFTSHIFT),
+    ("lshift", EV_KEY::KEY_LEFTSHIFT),
+    ("rshift", EV_KEY::KEY_RIGHTSHIFT),
+    ("alt", EV_KEY::KEY_LEFTALT),
+    ("lalt", EV_KEY::KEY_LEFTALT),
+    ("altgr", EV_KEY::KEY_RIGHTALT),
+    ("ralt", EV_KEY::KEY_RIGHTALT),
+    ("meta", EV_KEY::KEY_LEFTMETA),
+    ("super", EV_KEY::KEY_LEFTMETA),
+    ("win", EV_KEY::KEY_LEFTMETA),
+    ("lmeta", EV_KEY::KEY_LEFTMETA),
+    ("rmeta", EV_KEY::KEY_RIGHTMETA),
+];
+
+fn code_by_name(event_type: EventType, name: &str) -> Option<EventCode> {
+    EventCode::from_str(&event_type, name)
+}
+
+fn key_by_name(name: &str) -> Option<EV_KEY> {
+    match code_by_name(EventType::EV_KEY, name) {
+        Some(EventCode::EV_KEY(key)) => Some(key),
+        _ => None,
+    }
+}
+
+/// Parses a key name, case-insensitively. Accepts full names like `KEY_LEFTCTRL` and `BTN_LEFT`,
+/// names without their prefix like `a` or `f1`, and aliases like `esc` or `lctrl`.
+pub fn parse_key(name: &str) -> Result<EV_KEY, KeyNameError> {
+    let lower = name.trim().to_ascii_lowercase();
+    if let Some((_, key)) = KEY_ALIASES.iter().find(|(alias, _)| *alias == lower) {
+        return Ok(*key);
+    }
+    let upper = lower.to_ascii_uppercase();
+    let key = if upper.starts_with("KEY_") || upper.starts_with("BTN_") {
+        key_by_name(&upper)
+    } else {
+        key_by_name(&format!("KEY_{}", upper)).or_else(|| key_by_name(&format!("BTN_{}", upper)))
+    };
+    key.ok_or_else(|| KeyNameError::UnknownKey(name.to_string()))
+}
+
+/// Parses the full name of any event code, like `REL_WHEEL` or `SYN_REPORT`, case-insensitively.
+/// Keys are also accepted in any form `parse_key` accepts.
+pub fn parse_event_code(name: &str) -> Option<EventCode> {
+    let upper = name.trim().to_ascii_uppercase();
+    EventType::EV_SYN
+        .iter()
+        .find_map(|event_type| code_by_name(event_type, &upper))
+        .or_else(|| parse_key(name).ok().map(EventCode::EV_KEY))
+}
+
+/// Formats an event code by its evdev name, or by its type and number if it has none. The
+/// alternate form `{:#}` prefers friendly key names like `esc` or `a`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CodeName<'a>(pub &'a EventCode);
+
+impl fmt::Display for CodeName<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (event_type, code) = event_code_to_int(self.0);
+        if f.alternate() {
+            if let EventCode::EV_KEY(key) = self.0 {
+                if let Some((alias, _)) = KEY_ALIASES.iter().find(|(_, k)| k == key) {
+                    return f.write_str(alias);
+                }
+                let name = format!("{}", self.0);
+                if let Some(rest) = name.strip_prefix("KEY_") {
+                    return f.write_str(&rest.to_ascii_lowercase());
+                }
+            }
+        }
+        let name = format!("{}", self.0);
+        if name.is_empty() {
+            write!(f, "{:#x}:{:#x}", event_type, code)
+        } else {
+            f.write_str(&name)
+        }
+    }
+}
@@ -0,0 +1,96 @@
+use crate::virtual_device::{PEN_MAX, PEN_PRESSURE_MAX};
+use crate::{Injector, VirtualDeviceBuilder};
+use evdev_rs::enums::{EventCode, EV_ABS, EV_KEY};
+use evdev_rs::UInputDevice;
+
+/// A virtual drawing tablet pen, e.g. for testing graphics applications. Positions range from 0 to
+/// `VirtualPen::MAX` and pressure from 0 to `VirtualPen::PRESSURE_MAX`.
+///
+/// The pen comes into proximity with its first move, touches the tablet while its pressure is
+/// above zero and leaves proximity with `leave`.
+pub struct VirtualPen<U = UInputDevice> {
+    uinput: U,
+    in_proximity: bool,
+    touching: bool,
+}
+
+impl VirtualPen {
+    pub fn build(name: &str) -> std::io::Result<Self> {
+        VirtualDeviceBuilder::new()
+            .name(name)
+            .pen()
+            .build()
+            .map(Self::new)
+    }
+}
+
+impl<U: Injector> VirtualPen<U> {
+    pub const MAX: i32 = PEN_MAX;
+    pub const PRESSURE_MAX: i32 = PEN_PRESSURE_MAX;
+
+    /// Wraps a device set up like `VirtualDeviceBuilder::pen`.
+    pub fn new(uinput: U) -> Self {
+        Self {
+            uinput,
+            in_proximity: false,
+            touching: false,
+        }
+    }
+
+    pub fn uinput(&self) -> &U {
+        &self.uinput
+    }
+
+    fn proximity_events(&mut self, events: &mut Vec<(EventCode, i32)>) {
+        if !self.in_proximity {
+            events.push((EventCode::EV_KEY(EV_KEY::BTN_TOOL_PEN), 1));
+            self.in_proximity = true;
+        }
+    }
+
+    pub fn pen_move(&mut self, x: i32, y: i32, pressure: i32) -> std::io::Result<()> {
+        let mut events = Vec::new();
+        self.proximity_events(&mut events);
+        events.push((EventCode::EV_ABS(EV_ABS::ABS_X), x));
+        events.push((EventCode::EV_ABS(EV_ABS::ABS_Y), y));
+        events.push((EventCode::EV_ABS(EV_ABS::ABS_PRESSURE), pressure));
+        let touching = pressure > 0;
+        if touching != self.touching {
+            events.push((EventCode::EV_KEY(EV_KEY::BTN_TOUCH), touching.into()));
+            self.touching = touching;
+        }
+        self.uinput.emit(&events)
+    }
+
+    /// Tilts the pen, in degrees from -64 to 63 away from vertical along each axis.
+    pub fn tilt(&mut self, x: i32, y: i32) -> std::io::Result<()> {
+        let mut events = Vec::new();
+        self.proximity_events(&mut events);
+        events.push((EventCode::EV_ABS(EV_ABS::ABS_TILT_X), x));
+        events.push((EventCode::EV_ABS(EV_ABS::ABS_TILT_Y), y));
+        self.uinput.emit(&events)
+    }
+
+    /// Presses or releases `BTN_STYLUS` or `BTN_STYLUS2`.
+    pub fn stylus_button(&mut self, button: EV_KEY, pressed: bool) -> std::io::Result<()> {
+        let mut events = Vec::new();
+        self.proximity_events(&mut events);
+        events.push((EventCode::EV_KEY(button), pressed.into()));
+        self.uinput.emit(&events)
+    }
+
+    /// Lifts the pen and takes it out of proximity.
+    pub fn leave(&mut self) -> std::io::Result<()> {
+        if !self.in_proximity {
+            return Ok(());
+        }
+        let mut events = vec![(EventCode::EV_ABS(EV_ABS::ABS_PRESSURE), 0)];
+        if self.touching {
+            events.push((EventCode::EV_KEY(EV_KEY::BTN_TOUCH), 0));
+            self.touching = false;
+        }
+        events.push((EventCode::EV_KEY(EV_KEY::BTN_TOOL_PEN), 0));
+        self.in_proximity = false;
+        self.uinput.emit(&events)
+    }
+}
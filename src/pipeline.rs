@@ -0,0 +1,136 @@
+use crate::grab::release_tracked;
+use crate::{AsyncDevice, Injector};
+use evdev_rs::enums::{EventCode, EV_SYN};
+use evdev_rs::{GrabMode, UInputDevice};
+use std::cell::RefCell;
+use std::os::unix::io::{AsRawFd as _, RawFd};
+
+/// The output end of a pipeline, which passes injected events on to `uinput` and keeps track of
+/// the keys held down through it, so that stopping the pipeline never leaves a key stuck.
+///
+/// `shutdown`, or dropping the pipeline, releases the held keys, then the grabs taken through
+/// `grab`, and then destroys `uinput`, in that order. Handing out `&Pipeline` as the injector,
+/// e.g. to a `Remapper`, keeps this working when the remapper is dropped mid-keypress:
+///
+/// ```no_run
+/// # use evdev_rs::enums::EV_KEY;
+/// # use evdev_utils::remap::{Remapper, Target};
+/// # use evdev_utils::{AsyncDevice, Pipeline};
+/// # async fn run(uinput: evdev_rs::UInputDevice) -> Result<(), Box<dyn std::error::Error>> {
+/// let mut device = AsyncDevice::new("/dev/input/event3")?;
+/// let pipeline = Pipeline::new(uinput);
+/// pipeline.grab(&mut device)?;
+/// let remapper = Remapper::new(device, &pipeline)
+///     .grab(false)
+///     .rule(EV_KEY::KEY_CAPSLOCK, Target::Key(EV_KEY::KEY_LEFTCTRL));
+/// let stop = async_io::Timer::after(std::time::Duration::from_secs(60));
+/// let _ = futures::future::select(Box::pin(remapper.run()), stop).await;
+/// pipeline.shutdown()?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct Pipeline<U: Injector = UInputDevice> {
+    // Only `None` once shut down.
+    uinput: Option<U>,
+    // In the order they were pressed, to be released in reverse.
+    held: RefCell<Vec<EventCode>>,
+    grabbed: RefCell<Vec<RawFd>>,
+}
+
+impl<U: Injector> Pipeline<U> {
+    pub fn new(uinput: U) -> Self {
+        Self {
+            uinput: Some(uinput),
+            held: RefCell::new(Vec::new()),
+            grabbed: RefCell::new(Vec::new()),
+        }
+    }
+
+    pub fn uinput(&self) -> &U {
+        self.uinput.as_ref().expect("pipeline already shut down")
+    }
+
+    /// The keys and buttons held down through the pipeline, in the order they were pressed.
+    pub fn held(&self) -> Vec<EventCode> {
+        self.held.borrow().clone()
+    }
+
+    /// Grabs `device`, to be released by `shutdown`. A device dropped before then has released
+    /// its grab already and is left alone.
+    pub fn grab(&self, device: &mut AsyncDevice) -> std::io::Result<()> {
+        device.grab(GrabMode::Grab)?;
+        self.grabbed
+            .borrow_mut()
+            .push(device.evdev().file().as_raw_fd());
+        Ok(())
+    }
+
+    /// Releases the held keys, then the grabs, then destroys the uinput device. If releasing the
+    /// keys fails, the rest happens all the same.
+    pub fn shutdown(mut self) -> std::io::Result<()> {
+        self.stop()
+    }
+
+    fn release_held(&self) -> std::io::Result<()> {
+        let uinput = match &self.uinput {
+            Some(uinput) => uinput,
+            None => return Ok(()),
+        };
+        let held = std::mem::take(&mut *self.held.borrow_mut());
+        if held.is_empty() {
+            return Ok(());
+        }
+        for event_code in held.into_iter().rev() {
+            uinput.inject_event(event_code, 0)?;
+        }
+        uinput.inject_event(EventCode::EV_SYN(EV_SYN::SYN_REPORT), 0)
+    }
+
+    fn stop(&mut self) -> std::io::Result<()> {
+        let released = self.release_held();
+        for fd in self.grabbed.get_mut().drain(..) {
+            release_tracked(fd);
+        }
+        self.uinput = None;
+        released
+    }
+
+    fn track(&self, event_code: EventCode, value: i32) {
+        if !matches!(event_code, EventCode::EV_KEY(_)) {
+            return;
+        }
+        let mut held = self.held.borrow_mut();
+        match value {
+            0 => held.retain(|held| *held != event_code),
+            _ if !held.contains(&event_code) => held.push(event_code),
+            _ => {}
+        }
+    }
+}
+
+impl<U: Injector> Injector for Pipeline<U> {
+    fn inject_event(&self, event_code: EventCode, value: i32) -> std::io::Result<()> {
+        self.uinput().inject_event(event_code, value)?;
+        self.track(event_code, value);
+        Ok(())
+    }
+
+    fn inject_event_at(
+        &self,
+        event_code: EventCode,
+        value: i32,
+        time: evdev_rs::TimeVal,
+    ) -> std::io::Result<()> {
+        self.uinput().inject_event_at(event_code, value, time)?;
+        self.track(event_code, value);
+        Ok(())
+    }
+}
+
+impl<U: Injector> Drop for Pipeline<U> {
+    fn drop(&mut self) {
+        if let Err(e) = self.stop() {
+            log::warn!("failed to release held keys: {}", e);
+        }
+    }
+}
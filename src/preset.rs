@@ -0,0 +1,150 @@
+use crate::{enable_abs_info, GamepadRanges};
+use evdev_rs::enums::{BusType, EventCode, EventType, EV_ABS, EV_KEY};
+use evdev_rs::{AbsInfo, DeviceWrapper};
+
+/// Controllers a virtual device can pose as, with the names, ids and capabilities their kernel
+/// drivers give them, so that SDL and games find them in their controller databases and map them
+/// without configuration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ControllerPreset {
+    /// A wired Xbox 360 controller, as driven by xpad.
+    Xbox360,
+    /// A second revision DualShock 4 over USB, as driven by hid-playstation.
+    DualShock4,
+    /// A Switch Pro Controller over USB, as driven by hid-nintendo.
+    SwitchPro,
+}
+
+const FACE_BUTTONS: [EV_KEY; 4] = [
+    EV_KEY::BTN_SOUTH,
+    EV_KEY::BTN_EAST,
+    EV_KEY::BTN_NORTH,
+    EV_KEY::BTN_WEST,
+];
+
+const XBOX360_BUTTONS: [EV_KEY; 7] = [
+    EV_KEY::BTN_TL,
+    EV_KEY::BTN_TR,
+    EV_KEY::BTN_SELECT,
+    EV_KEY::BTN_START,
+    EV_KEY::BTN_MODE,
+    EV_KEY::BTN_THUMBL,
+    EV_KEY::BTN_THUMBR,
+];
+
+// L2 and R2 are reported as buttons as well as analog triggers.
+const DUALSHOCK4_BUTTONS: [EV_KEY; 9] = [
+    EV_KEY::BTN_TL,
+    EV_KEY::BTN_TR,
+    EV_KEY::BTN_TL2,
+    EV_KEY::BTN_TR2,
+    EV_KEY::BTN_SELECT,
+    EV_KEY::BTN_START,
+    EV_KEY::BTN_MODE,
+    EV_KEY::BTN_THUMBL,
+    EV_KEY::BTN_THUMBR,
+];
+
+// ZL and ZR are digital only.
+const SWITCH_PRO_BUTTONS: [EV_KEY; 10] = [
+    EV_KEY::BTN_TL,
+    EV_KEY::BTN_TR,
+    EV_KEY::BTN_TL2,
+    EV_KEY::BTN_TR2,
+    EV_KEY::BTN_SELECT,
+    EV_KEY::BTN_START,
+    EV_KEY::BTN_MODE,
+    EV_KEY::BTN_THUMBL,
+    EV_KEY::BTN_THUMBR,
+    // The capture button.
+    EV_KEY::BTN_Z,
+];
+
+fn range(minimum: i32, maximum: i32, fuzz: i32, flat: i32) -> AbsInfo {
+    AbsInfo {
+        value: 0,
+        minimum,
+        maximum,
+        fuzz,
+        flat,
+        resolution: 0,
+    }
+}
+
+impl ControllerPreset {
+    pub fn name(self) -> &'static str {
+        match self {
+            ControllerPreset::Xbox360 => "Microsoft X-Box 360 pad",
+            ControllerPreset::DualShock4 => "Sony Interactive Entertainment Wireless Controller",
+            ControllerPreset::SwitchPro => "Nintendo Switch Pro Controller",
+        }
+    }
+
+    pub fn bustype(self) -> BusType {
+        BusType::BUS_USB
+    }
+
+    /// The USB vendor and product ids.
+    pub fn ids(self) -> (u16, u16) {
+        match self {
+            ControllerPreset::Xbox360 => (0x045e, 0x028e),
+            ControllerPreset::DualShock4 => (0x054c, 0x09cc),
+            ControllerPreset::SwitchPro => (0x057e, 0x2009),
+        }
+    }
+
+    /// The input device version. The PlayStation and Nintendo drivers set the high bit to tell
+    /// SDL they report the standard button layout.
+    pub fn version(self) -> u16 {
+        match self {
+            ControllerPreset::Xbox360 => 0x0110,
+            ControllerPreset::DualShock4 => 0x8111,
+            ControllerPreset::SwitchPro => 0x8111,
+        }
+    }
+
+    fn buttons(self) -> &'static [EV_KEY] {
+        match self {
+            ControllerPreset::Xbox360 => &XBOX360_BUTTONS,
+            ControllerPreset::DualShock4 => &DUALSHOCK4_BUTTONS,
+            ControllerPreset::SwitchPro => &SWITCH_PRO_BUTTONS,
+        }
+    }
+
+    fn stick(self) -> AbsInfo {
+        match self {
+            ControllerPreset::Xbox360 => GamepadRanges::default().stick,
+            ControllerPreset::DualShock4 => range(0, 255, 0, 0),
+            ControllerPreset::SwitchPro => range(-32767, 32767, 250, 500),
+        }
+    }
+
+    fn trigger(self) -> Option<AbsInfo> {
+        match self {
+            ControllerPreset::Xbox360 | ControllerPreset::DualShock4 => Some(range(0, 255, 0, 0)),
+            ControllerPreset::SwitchPro => None,
+        }
+    }
+
+    /// Enables the preset's buttons and axes on `device`, with the d-pad as a hat.
+    pub(crate) fn enable<D: DeviceWrapper + ?Sized>(self, device: &D) -> std::io::Result<()> {
+        device.enable(&EventType::EV_KEY)?;
+        for key in FACE_BUTTONS.iter().chain(self.buttons()) {
+            device.enable(&EventCode::EV_KEY(*key))?;
+        }
+        let stick = self.stick();
+        for abs in [EV_ABS::ABS_X, EV_ABS::ABS_Y, EV_ABS::ABS_RX, EV_ABS::ABS_RY].iter() {
+            enable_abs_info(device, *abs, &stick)?;
+        }
+        if let Some(trigger) = self.trigger() {
+            for abs in [EV_ABS::ABS_Z, EV_ABS::ABS_RZ].iter() {
+                enable_abs_info(device, *abs, &trigger)?;
+            }
+        }
+        let hat = GamepadRanges::default().hat;
+        for abs in [EV_ABS::ABS_HAT0X, EV_ABS::ABS_HAT0Y].iter() {
+            enable_abs_info(device, *abs, &hat)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,124 @@
+//! Dropping root privileges once devices are open. File descriptors keep the access they were
+//! opened with, so `AsyncDevice`s and `UInputDevice`s opened as root stay readable and writable
+//! after switching to an unprivileged user, and the rest of a daemon doesn't need to run as root:
+//!
+//! ```no_run
+//! # use evdev_utils::privileges::{open_then_drop, User};
+//! # fn run() -> Result<(), Box<dyn std::error::Error>> {
+//! let user = User::from_sudo().map_or_else(|| User::from_name("nobody"), Ok)?;
+//! let (keyboard, uinput) = open_then_drop(user, || -> std::io::Result<_> {
+//!     let keyboard = evdev_utils::AsyncDevice::new("/dev/input/event0")?;
+//!     let uinput = evdev_utils::VirtualDeviceBuilder::new().keyboard().build()?;
+//!     Ok((keyboard, uinput))
+//! })?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Anything opening devices later, e.g. reconnecting a `ManagedDevice` or a `Proxy`, will fail
+//! unless the user has access of its own.
+
+use std::ffi::CString;
+use thiserror::Error;
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+#[derive(Error, Debug)]
+pub enum PrivilegeError {
+    #[error("failed to open devices")]
+    Open(#[source] BoxError),
+    #[error("no user named {0}")]
+    UnknownUser(String),
+    #[error("failed to look up user")]
+    Lookup(#[source] std::io::Error),
+    #[error("failed to set supplementary groups")]
+    SetGroups(#[source] std::io::Error),
+    #[error("failed to set group id")]
+    SetGid(#[source] std::io::Error),
+    #[error("failed to set user id")]
+    SetUid(#[source] std::io::Error),
+    #[error("root privileges could be regained after dropping them")]
+    Regained,
+}
+
+/// The user and group to run as after dropping privileges.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct User {
+    pub uid: libc::uid_t,
+    pub gid: libc::gid_t,
+}
+
+fn check(result: libc::c_int) -> std::io::Result<()> {
+    if result < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+impl User {
+    /// Looks up a user in the password database, using their primary group.
+    pub fn from_name(name: &str) -> Result<Self, PrivilegeError> {
+        let unknown = || PrivilegeError::UnknownUser(name.to_string());
+        let c_name = CString::new(name).map_err(|_| unknown())?;
+        let mut passwd = unsafe { std::mem::zeroed::<libc::passwd>() };
+        let mut buf = vec![0 as libc::c_char; 4096];
+        let mut result = std::ptr::null_mut();
+        loop {
+            let e = unsafe {
+                libc::getpwnam_r(
+                    c_name.as_ptr(),
+                    &mut passwd,
+                    buf.as_mut_ptr(),
+                    buf.len(),
+                    &mut result,
+                )
+            };
+            match e {
+                0 if result.is_null() => return Err(unknown()),
+                0 => break,
+                libc::ERANGE => buf.resize(buf.len() * 2, 0),
+                e => return Err(PrivilegeError::Lookup(std::io::Error::from_raw_os_error(e))),
+            }
+        }
+        Ok(Self {
+            uid: passwd.pw_uid,
+            gid: passwd.pw_gid,
+        })
+    }
+
+    /// The user who ran the program through sudo, from `SUDO_UID` and `SUDO_GID`.
+    pub fn from_sudo() -> Option<Self> {
+        let var = |name| std::env::var(name).ok()?.parse().ok();
+        Some(Self {
+            uid: var("SUDO_UID")?,
+            gid: var("SUDO_GID")?,
+        })
+    }
+
+    /// Irrevocably switches the process to this user, with this user's group as its only group.
+    pub fn switch_to(self) -> Result<(), PrivilegeError> {
+        // Groups have to go first, as changing them needs the privileges being dropped.
+        check(unsafe { libc::setgroups(1, &self.gid) }).map_err(PrivilegeError::SetGroups)?;
+        check(unsafe { libc::setresgid(self.gid, self.gid, self.gid) })
+            .map_err(PrivilegeError::SetGid)?;
+        check(unsafe { libc::setresuid(self.uid, self.uid, self.uid) })
+            .map_err(PrivilegeError::SetUid)?;
+        if self.uid != 0 && unsafe { libc::setuid(0) } == 0 {
+            return Err(PrivilegeError::Regained);
+        }
+        Ok(())
+    }
+}
+
+/// Runs `open` with the current privileges, then switches to `user`, returning what `open`
+/// opened. Privileges aren't dropped if `open` fails.
+pub fn open_then_drop<T, E, F>(user: User, open: F) -> Result<T, PrivilegeError>
+where
+    F: FnOnce() -> Result<T, E>,
+    E: Into<BoxError>,
+{
+    let opened = open().map_err(|e| PrivilegeError::Open(e.into()))?;
+    user.switch_to()?;
+    log::info!("dropped privileges to uid {} gid {}", user.uid, user.gid);
+    Ok(opened)
+}
@@ -0,0 +1,197 @@
+use crate::{
+    BounceKeys, ButtonScroll, Debounce, EscapeHatch, EscapeSequence, Logged, ModifierTracker,
+    RateLimit, SlowKeys, StickyKeys, TimedTyper, Typer,
+};
+use async_io::Timer;
+use evdev_rs::enums::EV_KEY;
+use evdev_rs::InputEvent;
+use futures::{Future as _, Stream, StreamExt as _};
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+/// A synchronous state machine over input events which may need to act on timeouts.
+///
+/// Processors are driven by `Processed`, which takes care of timers, so implementations only
+/// need to report when they next want `timeout` to be called.
+pub trait Processor {
+    type Output;
+
+    fn process(&mut self, event: InputEvent, now: Instant, out: &mut VecDeque<Self::Output>);
+
+    /// When `timeout` should next be called, if at all.
+    fn deadline(&self) -> Option<Instant> {
+        None
+    }
+
+    fn timeout(&mut self, _now: Instant, _out: &mut VecDeque<Self::Output>) {}
+
+    /// Called once when the input stream ends, to flush any buffered state.
+    fn finish(&mut self, _out: &mut VecDeque<Self::Output>) {}
+}
+
+/// Stream adapter running every event of the underlying stream through a `Processor`.
+pub struct Processed<S, P: Processor> {
+    stream: S,
+    processor: P,
+    timer: Option<(Instant, Timer)>,
+    pending: VecDeque<P::Output>,
+    done: bool,
+}
+
+impl<S, P: Processor> Processed<S, P> {
+    pub fn new(stream: S, processor: P) -> Self {
+        Self {
+            stream,
+            processor,
+            timer: None,
+            pending: VecDeque::new(),
+            done: false,
+        }
+    }
+
+    pub fn processor(&self) -> &P {
+        &self.processor
+    }
+
+    pub fn processor_mut(&mut self) -> &mut P {
+        &mut self.processor
+    }
+
+    pub fn into_inner(self) -> (S, P) {
+        (self.stream, self.processor)
+    }
+
+    // Returns true if the processor's deadline has passed.
+    fn poll_deadline(&mut self, cx: &mut Context<'_>) -> bool {
+        let deadline = match self.processor.deadline() {
+            Some(deadline) => deadline,
+            None => {
+                self.timer = None;
+                return false;
+            }
+        };
+        if deadline <= Instant::now() {
+            return true;
+        }
+        let timer = match &mut self.timer {
+            Some((at, timer)) => {
+                if *at != deadline {
+                    timer.set_at(deadline);
+                    *at = deadline;
+                }
+                timer
+            }
+            None => &mut self.timer.get_or_insert((deadline, Timer::at(deadline))).1,
+        };
+        Pin::new(timer).poll(cx).is_ready()
+    }
+}
+
+impl<S, E, P> Stream for Processed<S, P>
+where
+    S: Stream<Item = Result<InputEvent, E>> + Unpin,
+    P: Processor + Unpin,
+    P::Output: Unpin,
+{
+    type Item = Result<P::Output, E>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        loop {
+            if let Some(output) = this.pending.pop_front() {
+                return Poll::Ready(Some(Ok(output)));
+            }
+            if this.done {
+                return Poll::Ready(None);
+            }
+            if this.poll_deadline(cx) {
+                this.processor.timeout(Instant::now(), &mut this.pending);
+                continue;
+            }
+            match this.stream.poll_next_unpin(cx) {
+                Poll::Ready(Some(Ok(event))) => {
+                    this.processor
+                        .process(event, Instant::now(), &mut this.pending)
+                }
+                Poll::Ready(Some(Err(e))) => return Poll::Ready(Some(Err(e))),
+                Poll::Ready(None) => {
+                    this.processor.finish(&mut this.pending);
+                    this.done = true;
+                }
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+/// Adapters for streams of input events.
+pub trait EventStreamExt: Stream + Sized {
+    fn process<P: Processor>(self, processor: P) -> Processed<Self, P> {
+        Processed::new(self, processor)
+    }
+
+    /// Logs every event at `level` with the `log` crate, formatted by `format_event`.
+    fn logged(self, level: log::Level) -> Logged<Self> {
+        Logged::new(self, level)
+    }
+
+    /// Decodes events into `TypedEvent`s, see `Typer`.
+    fn typed(self) -> Processed<Self, Typer> {
+        self.process(Typer::new())
+    }
+
+    /// Decodes events into `TypedEvent`s with their timestamps, see `TimedTyper`.
+    fn typed_timed(self) -> Processed<Self, TimedTyper> {
+        self.process(TimedTyper::new())
+    }
+
+    /// Pairs each event with the modifiers held after it.
+    fn track_modifiers(self) -> Processed<Self, ModifierTracker> {
+        self.process(ModifierTracker::new())
+    }
+
+    /// Suppresses key bounce, see `Debounce`.
+    fn debounce_keys(self, window: Duration) -> Processed<Self, Debounce> {
+        self.process(Debounce::new(window))
+    }
+
+    /// Reports relative motion at most `hz` times per second, see `RateLimit`. `hz` must be
+    /// positive.
+    fn rate_limit_rel(self, hz: f64) -> Processed<Self, RateLimit> {
+        self.process(RateLimit::new(hz))
+    }
+
+    /// Makes modifiers sticky, see `StickyKeys`.
+    fn sticky_keys(self) -> Processed<Self, StickyKeys> {
+        self.process(StickyKeys::new())
+    }
+
+    /// Only registers keys held for at least `delay`, see `SlowKeys`.
+    fn slow_keys(self, delay: Duration) -> Processed<Self, SlowKeys> {
+        self.process(SlowKeys::new(delay))
+    }
+
+    /// Ignores presses of a key within `window` of its release, see `BounceKeys`.
+    fn bounce_keys(self, window: Duration) -> Processed<Self, BounceKeys> {
+        self.process(BounceKeys::new(window))
+    }
+
+    /// Scrolls with pointer motion while `button` is held, see `ButtonScroll`.
+    fn button_scroll(self, button: EV_KEY) -> Processed<Self, ButtonScroll> {
+        self.process(ButtonScroll::new(button))
+    }
+
+    /// Releases all grabs and ends the stream when both Ctrl keys and Esc are held, see
+    /// `EscapeHatch`.
+    fn escape_hatch(self) -> EscapeHatch<Self> {
+        self.escape_hatch_with(EscapeSequence::default())
+    }
+
+    fn escape_hatch_with(self, sequence: EscapeSequence) -> EscapeHatch<Self> {
+        EscapeHatch::new(self, sequence)
+    }
+}
+
+impl<S, E> EventStreamExt for S where S: Stream<Item = Result<InputEvent, E>> {}
@@ -0,0 +1,302 @@
+//! Mapping gamepads onto a virtual mouse and keyboard.
+
+use crate::{
+    event, AsyncDevice, Curve, DeviceError, EventStreamExt as _, HiResWheel, Injector, Processor,
+    WHEEL_DETENT, ZERO_TIME,
+};
+use evdev_rs::enums::{EventCode, EV_ABS, EV_KEY, EV_REL, EV_SYN};
+use evdev_rs::{DeviceWrapper, GrabMode, InputEvent, UInputDevice};
+use futures::TryStreamExt as _;
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+/// Moves the pointer with a stick, at `speed` pixels per second at full deflection.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StickToMouse {
+    pub x: EV_ABS,
+    pub y: EV_ABS,
+    pub speed: f64,
+    pub curve: Curve,
+}
+
+/// Scrolls with an axis, at `speed` wheel notches per second at full deflection. Triggers
+/// resting at their minimum scroll in one direction, which `reverse` flips; centered axes scroll
+/// both ways.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AxisToScroll {
+    pub axis: EV_ABS,
+    pub speed: f64,
+    pub horizontal: bool,
+    pub reverse: bool,
+    pub trigger: bool,
+    pub curve: Curve,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Profile {
+    /// Buttons mapped to keys or mouse buttons. Unmapped buttons are dropped.
+    pub buttons: HashMap<EV_KEY, EV_KEY>,
+    pub sticks: Vec<StickToMouse>,
+    pub scroll: Vec<AxisToScroll>,
+    /// How often motion is injected while a stick or axis is deflected.
+    pub tick: Duration,
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Self {
+            buttons: HashMap::new(),
+            sticks: Vec::new(),
+            scroll: Vec::new(),
+            tick: Duration::from_millis(10),
+        }
+    }
+}
+
+impl Profile {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn button(mut self, from: EV_KEY, to: EV_KEY) -> Self {
+        let _: Option<EV_KEY> = self.buttons.insert(from, to);
+        self
+    }
+
+    pub fn stick_to_mouse(mut self, stick: StickToMouse) -> Self {
+        self.sticks.push(stick);
+        self
+    }
+
+    pub fn axis_to_scroll(mut self, scroll: AxisToScroll) -> Self {
+        self.scroll.push(scroll);
+        self
+    }
+}
+
+/// Runs a `Profile` over a gamepad's events, producing the events of the virtual mouse and
+/// keyboard.
+pub struct ProfileMapper {
+    profile: Profile,
+    ranges: HashMap<EV_ABS, (f64, f64)>,
+    values: HashMap<EV_ABS, i32>,
+    last_tick: Option<Instant>,
+    // Sub-unit motion carried over between ticks, per REL code.
+    remainders: HashMap<EV_REL, f64>,
+    // Partial detents, per wheel.
+    wheels: HashMap<EV_REL, HiResWheel>,
+    pending_syn: bool,
+}
+
+impl ProfileMapper {
+    /// Takes the axis ranges and current values from `device`.
+    pub fn new<D: DeviceWrapper + ?Sized>(profile: Profile, device: &D) -> Self {
+        let axes = profile
+            .sticks
+            .iter()
+            .flat_map(|stick| [stick.x, stick.y])
+            .chain(profile.scroll.iter().map(|scroll| scroll.axis));
+        let mut ranges = HashMap::new();
+        let mut values = HashMap::new();
+        for abs in axes {
+            if let Some(info) = device
+                .abs_info(&EventCode::EV_ABS(abs))
+                .filter(|info| info.maximum > info.minimum)
+            {
+                let _: Option<(f64, f64)> =
+                    ranges.insert(abs, (info.minimum.into(), info.maximum.into()));
+                let _: Option<i32> = values.insert(abs, info.value);
+            }
+        }
+        Self {
+            profile,
+            ranges,
+            values,
+            last_tick: None,
+            remainders: HashMap::new(),
+            wheels: HashMap::new(),
+            pending_syn: false,
+        }
+    }
+
+    fn centered(&self, abs: EV_ABS) -> f64 {
+        match (self.ranges.get(&abs), self.values.get(&abs)) {
+            (Some((minimum, maximum)), Some(value)) => {
+                (2.0 * (f64::from(*value) - minimum) / (maximum - minimum) - 1.0).clamp(-1.0, 1.0)
+            }
+            _ => 0.0,
+        }
+    }
+
+    fn unipolar(&self, abs: EV_ABS) -> f64 {
+        match (self.ranges.get(&abs), self.values.get(&abs)) {
+            (Some((minimum, maximum)), Some(value)) => {
+                ((f64::from(*value) - minimum) / (maximum - minimum)).clamp(0.0, 1.0)
+            }
+            _ => 0.0,
+        }
+    }
+
+    /// Pointer velocity and scroll speeds per second at the current deflection.
+    fn velocities(&self) -> Vec<(EV_REL, f64)> {
+        let mut velocities = Vec::new();
+        for stick in &self.profile.sticks {
+            let (x, y) = stick
+                .curve
+                .apply_stick((self.centered(stick.x), self.centered(stick.y)));
+            velocities.push((EV_REL::REL_X, x * stick.speed));
+            velocities.push((EV_REL::REL_Y, y * stick.speed));
+        }
+        for scroll in &self.profile.scroll {
+            let value = if scroll.trigger {
+                scroll.curve.apply_axis(self.unipolar(scroll.axis))
+            } else {
+                scroll.curve.apply_axis(self.centered(scroll.axis))
+            };
+            let rel = if scroll.horizontal {
+                EV_REL::REL_HWHEEL
+            } else {
+                EV_REL::REL_WHEEL
+            };
+            let sign = if scroll.reverse { -1.0 } else { 1.0 };
+            velocities.push((rel, value * scroll.speed * sign));
+        }
+        velocities
+    }
+
+    fn is_deflected(&self) -> bool {
+        self.velocities()
+            .iter()
+            .any(|(_, velocity)| *velocity != 0.0)
+    }
+}
+
+impl Processor for ProfileMapper {
+    type Output = InputEvent;
+
+    fn process(&mut self, input: InputEvent, now: Instant, out: &mut VecDeque<InputEvent>) {
+        match input.event_code {
+            EventCode::EV_KEY(button) => {
+                if let Some(key) = self.profile.buttons.get(&button) {
+                    out.push_back(event(ZERO_TIME, EventCode::EV_KEY(*key), input.value));
+                    self.pending_syn = true;
+                }
+            }
+            EventCode::EV_ABS(abs) if self.ranges.contains_key(&abs) => {
+                let _: Option<i32> = self.values.insert(abs, input.value);
+            }
+            EventCode::EV_SYN(EV_SYN::SYN_REPORT) => {
+                if std::mem::replace(&mut self.pending_syn, false) {
+                    out.push_back(event(ZERO_TIME, EventCode::EV_SYN(EV_SYN::SYN_REPORT), 0));
+                }
+                if !self.is_deflected() {
+                    self.last_tick = None;
+                    self.remainders.clear();
+                    self.wheels.clear();
+                } else if self.last_tick.is_none() {
+                    self.last_tick = Some(now);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn deadline(&self) -> Option<Instant> {
+        self.last_tick
+            .map(|last_tick| last_tick + self.profile.tick)
+    }
+
+    fn timeout(&mut self, now: Instant, out: &mut VecDeque<InputEvent>) {
+        let elapsed = self
+            .last_tick
+            .map(|last_tick| now.saturating_duration_since(last_tick))
+            .unwrap_or_default()
+            .as_secs_f64();
+        self.last_tick = Some(now);
+        let mut motion = HashMap::<EV_REL, f64>::new();
+        for (rel, velocity) in self.velocities() {
+            *motion.entry(rel).or_default() += velocity * elapsed;
+        }
+        let mut any = false;
+        for rel in [EV_REL::REL_X, EV_REL::REL_Y].iter() {
+            let remainder = self.remainders.entry(*rel).or_default();
+            let total = *remainder + motion.get(rel).copied().unwrap_or_default();
+            let whole = total.trunc();
+            *remainder = total - whole;
+            if whole != 0.0 {
+                out.push_back(event(ZERO_TIME, EventCode::EV_REL(*rel), whole as i32));
+                any = true;
+            }
+        }
+        for (rel, hi_res_rel) in [
+            (EV_REL::REL_WHEEL, EV_REL::REL_WHEEL_HI_RES),
+            (EV_REL::REL_HWHEEL, EV_REL::REL_HWHEEL_HI_RES),
+        ]
+        .iter()
+        {
+            let notches = motion.get(rel).copied().unwrap_or_default();
+            let (hi_res, detents) = self
+                .wheels
+                .entry(*rel)
+                .or_default()
+                .scroll(notches * f64::from(WHEEL_DETENT));
+            for (rel, value) in [(*hi_res_rel, hi_res), (*rel, detents)].iter() {
+                if *value != 0 {
+                    out.push_back(event(ZERO_TIME, EventCode::EV_REL(*rel), *value));
+                    any = true;
+                }
+            }
+        }
+        if any {
+            out.push_back(event(ZERO_TIME, EventCode::EV_SYN(EV_SYN::SYN_REPORT), 0));
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum ProfileError {
+    #[error("failed to grab device")]
+    Grab(#[source] std::io::Error),
+    #[error("error when reading an event")]
+    ReadEvent(#[source] DeviceError),
+    #[error("failed to inject event")]
+    Inject(#[source] std::io::Error),
+}
+
+/// Grabs a gamepad and drives a virtual mouse and keyboard from it according to a `Profile`.
+/// The virtual device needs the keys, buttons and REL axes the profile uses, e.g. one built with
+/// `VirtualDeviceBuilder::keyboard` and `mouse`.
+pub struct ProfileRunner<U = UInputDevice> {
+    device: AsyncDevice,
+    uinput: U,
+    mapper: ProfileMapper,
+}
+
+impl<U: Injector> ProfileRunner<U> {
+    pub fn new(device: AsyncDevice, uinput: U, profile: Profile) -> Self {
+        let mapper = ProfileMapper::new(profile, device.evdev());
+        Self {
+            device,
+            uinput,
+            mapper,
+        }
+    }
+
+    /// Runs until the gamepad's event stream ends.
+    pub async fn run(self) -> Result<(), ProfileError> {
+        let Self {
+            mut device,
+            uinput,
+            mapper,
+        } = self;
+        device.grab(GrabMode::Grab).map_err(ProfileError::Grab)?;
+        let mut events = device.process(mapper);
+        while let Some(event) = events.try_next().await.map_err(ProfileError::ReadEvent)? {
+            uinput
+                .inject_event(event.event_code, event.value)
+                .map_err(ProfileError::Inject)?;
+        }
+        Ok(())
+    }
+}
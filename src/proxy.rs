@@ -0,0 +1,304 @@
+use crate::{
+    AsyncDevice, DeviceError, DeviceEvent, EscapeSequence, EventStreamExt as _, Injector as _,
+    ManagedDevice, Modifiers, Processor, ZERO_TIME,
+};
+use evdev_rs::enums::{EventCode, EV_KEY, EV_SYN};
+use evdev_rs::{InputEvent, UInputDevice};
+use futures::future::Either;
+use futures::{Future, Stream, StreamExt as _, TryStreamExt as _};
+use std::collections::{HashSet, VecDeque};
+use std::os::unix::fs::MetadataExt as _;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ProxyError {
+    #[error("failed to create uinput device")]
+    CreateUInput(#[source] std::io::Error),
+    #[error("failed to grab device")]
+    Grab(#[source] std::io::Error),
+    #[error("failed to start the hotplug monitor")]
+    Monitor(#[source] std::io::Error),
+    #[error("error when reading an event")]
+    ReadEvent(#[source] DeviceError),
+    #[error("failed to inject event")]
+    Inject(#[source] std::io::Error),
+    /// The device reconnected as the proxy's own uinput clone, which would feed every injected
+    /// event back in.
+    #[error("the device at {} is the proxy's own output", .0.display())]
+    Loopback(PathBuf),
+}
+
+/// Grabs a physical device and re-exposes it through a uinput clone with the same name and
+/// capabilities, optionally rewriting or dropping events on the way through.
+pub struct Proxy {
+    device: AsyncDevice,
+    uinput: UInputDevice,
+    // The keys pressed on `uinput` when grabbing.
+    mirrored: HashSet<EV_KEY>,
+    escape: Option<EscapeSequence>,
+    reconnect: bool,
+}
+
+impl Proxy {
+    pub fn new(mut device: AsyncDevice) -> Result<Self, ProxyError> {
+        let uinput =
+            UInputDevice::create_from_device(device.evdev()).map_err(ProxyError::CreateUInput)?;
+        // Keys held now are pressed on the clone, so that their releases are forwarded to a key
+        // that is down.
+        let mirrored = device.grab_mirrored(&uinput).map_err(ProxyError::Grab)?;
+        Ok(Self {
+            device,
+            uinput,
+            mirrored,
+            escape: Some(EscapeSequence::default()),
+            reconnect: false,
+        })
+    }
+
+    /// Sets the keys which release all grabs and stop forwarding, or disables them with `None`.
+    /// Both Ctrl keys and Esc by default.
+    pub fn escape(mut self, escape: Option<EscapeSequence>) -> Self {
+        self.escape = escape;
+        self
+    }
+
+    /// Keeps forwarding when the device goes away and comes back, as across a system suspend or
+    /// replugging, instead of failing. The device is grabbed again, keys released in between are
+    /// released on the uinput device, and modifiers pressed in between are pressed. The uinput
+    /// clone itself is never taken for the device coming back; should it be reopened anyway,
+    /// forwarding stops with `ProxyError::Loopback`.
+    pub fn reconnect(mut self, reconnect: bool) -> Self {
+        self.reconnect = reconnect;
+        self
+    }
+
+    pub fn uinput(&self) -> &UInputDevice {
+        &self.uinput
+    }
+
+    /// Forwards all events unchanged until the device's event stream ends.
+    pub async fn run(self) -> Result<(), ProxyError> {
+        self.run_with(|event| futures::future::ready(Some(event)))
+            .await
+    }
+
+    /// Forwards every event through `filter`, injecting whatever it resolves to. Resolving to
+    /// `None` drops the event.
+    pub async fn run_with<F, Fut>(self, mut filter: F) -> Result<(), ProxyError>
+    where
+        F: FnMut(InputEvent) -> Fut,
+        Fut: Future<Output = Option<InputEvent>>,
+    {
+        let (mut events, uinput) = self.into_events()?;
+        while let Some(event) = events.try_next().await? {
+            if let Some(InputEvent {
+                time,
+                event_code,
+                value,
+            }) = filter(event).await
+            {
+                uinput
+                    .inject_event_at(event_code, value, time)
+                    .map_err(ProxyError::Inject)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Forwards all events through `processor`, e.g. a `Debounce` or `RateLimit`.
+    pub async fn run_processor<P>(self, processor: P) -> Result<(), ProxyError>
+    where
+        P: Processor<Output = InputEvent> + Unpin,
+    {
+        let (events, uinput) = self.into_events()?;
+        let mut events = events.process(processor);
+        while let Some(InputEvent {
+            time,
+            event_code,
+            value,
+        }) = events.try_next().await?
+        {
+            uinput
+                .inject_event_at(event_code, value, time)
+                .map_err(ProxyError::Inject)?;
+        }
+        Ok(())
+    }
+
+    fn into_events(
+        self,
+    ) -> Result<
+        (
+            impl Stream<Item = Result<InputEvent, ProxyError>> + Unpin,
+            UInputDevice,
+        ),
+        ProxyError,
+    > {
+        let Self {
+            device,
+            uinput,
+            mirrored,
+            escape,
+            reconnect,
+        } = self;
+        let escape = escape.unwrap_or_else(|| EscapeSequence::new(Vec::new()));
+        let events = if reconnect {
+            let device = ManagedDevice::new(device)
+                .reconnect(true)
+                .map_err(ProxyError::Monitor)?;
+            Either::Right(Resynced::new(device, &uinput, mirrored))
+        } else {
+            Either::Left(device.map_err(ProxyError::ReadEvent))
+        };
+        Ok((events.escape_hatch_with(escape), uinput))
+    }
+}
+
+/// The events of a reconnecting `ManagedDevice`, making up for key changes missed while it was
+/// away once it's back. `held` is what has been forwarded, i.e. the uinput device's state.
+struct Resynced {
+    device: ManagedDevice,
+    // The device number of the uinput device's event node, if it has one.
+    output: Option<u64>,
+    held: HashSet<EV_KEY>,
+    pending: VecDeque<InputEvent>,
+}
+
+impl Resynced {
+    fn new(device: ManagedDevice, uinput: &UInputDevice, held: HashSet<EV_KEY>) -> Self {
+        let output = uinput
+            .devnode()
+            .and_then(|devnode| std::fs::metadata(devnode).ok())
+            .map(|metadata| metadata.rdev());
+        Self {
+            device,
+            output,
+            held,
+            pending: VecDeque::new(),
+        }
+    }
+
+    // Whether the reconnected device is the uinput device.
+    fn loops_back(&self) -> bool {
+        let rdev = self
+            .device
+            .device()
+            .and_then(|device| device.evdev().file().metadata().ok())
+            .map(|metadata| metadata.rdev());
+        rdev.is_some() && rdev == self.output
+    }
+
+    fn resync(&mut self) {
+        if let Some(device) = self.device.device() {
+            self.pending
+                .extend(resync_events(&self.held, &device.current_keys()));
+        }
+    }
+}
+
+/// The events bringing the uinput device from `held` to the device's `now_held` after a
+/// reconnect, in one frame.
+fn resync_events(held: &HashSet<EV_KEY>, now_held: &HashSet<EV_KEY>) -> Vec<InputEvent> {
+    // Only modifiers are pressed again; repressing other keys would type them.
+    let released = held.difference(now_held).map(|key| (*key, 0));
+    let pressed = now_held
+        .difference(held)
+        .filter(|key| Modifiers::from_key(**key).is_some())
+        .map(|key| (*key, 1));
+    let mut events = released
+        .chain(pressed)
+        .map(|(key, value)| InputEvent {
+            time: ZERO_TIME,
+            event_code: EventCode::EV_KEY(key),
+            value,
+        })
+        .collect::<Vec<_>>();
+    if !events.is_empty() {
+        events.push(InputEvent {
+            time: ZERO_TIME,
+            event_code: EventCode::EV_SYN(EV_SYN::SYN_REPORT),
+            value: 0,
+        });
+    }
+    events
+}
+
+impl Stream for Resynced {
+    type Item = Result<InputEvent, ProxyError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        loop {
+            let event = match this.pending.pop_front() {
+                Some(event) => event,
+                None => match futures::ready!(this.device.poll_next_unpin(cx)) {
+                    Some(Ok(DeviceEvent::Event(event))) => event,
+                    Some(Ok(DeviceEvent::Disconnected)) => {
+                        log::warn!("device disconnected, waiting for it to come back");
+                        continue;
+                    }
+                    Some(Ok(DeviceEvent::Reconnected(path))) => {
+                        if this.loops_back() {
+                            return Poll::Ready(Some(Err(ProxyError::Loopback(path))));
+                        }
+                        log::info!("device reconnected at {}", path.display());
+                        this.resync();
+                        continue;
+                    }
+                    Some(Err(e)) => return Poll::Ready(Some(Err(ProxyError::ReadEvent(e)))),
+                    None => return Poll::Ready(None),
+                },
+            };
+            if let EventCode::EV_KEY(key) = event.event_code {
+                let _: bool = match event.value {
+                    0 => this.held.remove(&key),
+                    _ => this.held.insert(key),
+                };
+            }
+            return Poll::Ready(Some(Ok(event)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::keys;
+    use evdev_rs::enums::EV_KEY::{KEY_A, KEY_B, KEY_LEFTSHIFT};
+
+    fn resync(held: &[EV_KEY], now_held: &[EV_KEY]) -> HashSet<(EV_KEY, i32)> {
+        let events = resync_events(
+            &held.iter().copied().collect(),
+            &now_held.iter().copied().collect(),
+        );
+        if let Some(last) = events.last() {
+            assert_eq!(last.event_code, EventCode::EV_SYN(EV_SYN::SYN_REPORT));
+        }
+        keys(&events).into_iter().collect()
+    }
+
+    #[test]
+    fn releases_keys_released_while_away() {
+        assert_eq!(
+            resync(&[KEY_A, KEY_LEFTSHIFT], &[]),
+            [(KEY_A, 0), (KEY_LEFTSHIFT, 0)].iter().copied().collect()
+        );
+    }
+
+    #[test]
+    fn presses_only_modifiers_pressed_while_away() {
+        assert_eq!(
+            resync(&[], &[KEY_B, KEY_LEFTSHIFT]),
+            [(KEY_LEFTSHIFT, 1)].iter().copied().collect()
+        );
+    }
+
+    #[test]
+    fn nothing_changed() {
+        assert_eq!(resync(&[KEY_A], &[KEY_A]), HashSet::new());
+        assert!(resync_events(&HashSet::new(), &HashSet::new()).is_empty());
+    }
+}
@@ -0,0 +1,276 @@
+//! A compact binary trace format for input events.
+//!
+//! A trace starts with `MAGIC` and `VERSION`, followed by records, each starting with a tag byte.
+//! Device records identify a device under a small integer id; event records refer to that id.
+//! All integers are little-endian.
+
+use crate::macros::time_since;
+use crate::{DeviceInfo, Injector};
+use evdev_rs::enums::int_to_event_type;
+use evdev_rs::util::{event_code_to_int, int_to_event_code};
+use evdev_rs::{InputEvent, TimeVal};
+use std::convert::TryFrom;
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::time::Duration;
+
+const MAGIC: &[u8; 4] = b"EVDR";
+const VERSION: u8 = 1;
+
+const TAG_DEVICE: u8 = 0;
+const TAG_EVENT: u8 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Record {
+    Device {
+        id: u16,
+        path: PathBuf,
+        info: DeviceInfo,
+    },
+    Event {
+        device: u16,
+        event: InputEvent,
+    },
+}
+
+fn invalid_data(message: impl Into<String>) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message.into())
+}
+
+/// Writes a trace to `W`.
+pub struct Recorder<W> {
+    writer: W,
+    next_id: u16,
+}
+
+impl<W: Write> Recorder<W> {
+    pub fn new(mut writer: W) -> std::io::Result<Self> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[VERSION])?;
+        Ok(Self { writer, next_id: 0 })
+    }
+
+    fn write_str(&mut self, s: &str) -> std::io::Result<()> {
+        let len = u16::try_from(s.len()).map_err(|_| invalid_data("string too long"))?;
+        self.writer.write_all(&len.to_le_bytes())?;
+        self.writer.write_all(s.as_bytes())
+    }
+
+    fn write_opt_str(&mut self, s: Option<&str>) -> std::io::Result<()> {
+        match s {
+            Some(s) => {
+                self.writer.write_all(&[1])?;
+                self.write_str(s)
+            }
+            None => self.writer.write_all(&[0]),
+        }
+    }
+
+    /// Records a device's identity, returning the id to record its events under.
+    pub fn add_device(
+        &mut self,
+        path: &std::path::Path,
+        info: &DeviceInfo,
+    ) -> std::io::Result<u16> {
+        let id = self.next_id;
+        self.next_id = id
+            .checked_add(1)
+            .ok_or_else(|| invalid_data("too many devices"))?;
+        let DeviceInfo {
+            name,
+            phys,
+            uniq,
+            bustype,
+            vendor,
+            product,
+            version,
+            event_types,
+        } = info;
+        self.writer.write_all(&[TAG_DEVICE])?;
+        self.writer.write_all(&id.to_le_bytes())?;
+        self.write_str(&path.to_string_lossy())?;
+        self.write_str(name)?;
+        self.write_opt_str(phys.as_deref())?;
+        self.write_opt_str(uniq.as_deref())?;
+        for value in [bustype, vendor, product, version].iter() {
+            self.writer.write_all(&value.to_le_bytes())?;
+        }
+        self.writer.write_all(&[event_types.len() as u8])?;
+        for event_type in event_types {
+            self.writer.write_all(&[*event_type as u8])?;
+        }
+        Ok(id)
+    }
+
+    pub fn record(&mut self, device: u16, event: &InputEvent) -> std::io::Result<()> {
+        let InputEvent {
+            time: TimeVal { tv_sec, tv_usec },
+            event_code,
+            value,
+        } = event;
+        let (event_type, code) = event_code_to_int(event_code);
+        self.writer.write_all(&[TAG_EVENT])?;
+        self.writer.write_all(&device.to_le_bytes())?;
+        self.writer.write_all(&tv_sec.to_le_bytes())?;
+        self.writer.write_all(&tv_usec.to_le_bytes())?;
+        self.writer.write_all(&(event_type as u16).to_le_bytes())?;
+        self.writer.write_all(&(code as u16).to_le_bytes())?;
+        self.writer.write_all(&value.to_le_bytes())
+    }
+
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// Reads a trace written by `Recorder`.
+pub struct Player<R> {
+    reader: R,
+}
+
+impl<R: Read> Player<R> {
+    pub fn new(mut reader: R) -> std::io::Result<Self> {
+        let mut header = [0u8; 5];
+        reader.read_exact(&mut header)?;
+        if &header[..4] != MAGIC {
+            return Err(invalid_data("not an input event trace"));
+        }
+        if header[4] != VERSION {
+            return Err(invalid_data(format!(
+                "unsupported trace version {}",
+                header[4]
+            )));
+        }
+        Ok(Self { reader })
+    }
+
+    fn read_array<const N: usize>(&mut self) -> std::io::Result<[u8; N]> {
+        let mut buf = [0u8; N];
+        self.reader.read_exact(&mut buf)?;
+        Ok(buf)
+    }
+
+    fn read_u8(&mut self) -> std::io::Result<u8> {
+        self.read_array::<1>().map(|[b]| b)
+    }
+
+    fn read_u16(&mut self) -> std::io::Result<u16> {
+        self.read_array().map(u16::from_le_bytes)
+    }
+
+    fn read_str(&mut self) -> std::io::Result<String> {
+        let len = self.read_u16()?;
+        let mut buf = vec![0u8; len.into()];
+        self.reader.read_exact(&mut buf)?;
+        String::from_utf8(buf).map_err(|_| invalid_data("string is not UTF-8"))
+    }
+
+    fn read_opt_str(&mut self) -> std::io::Result<Option<String>> {
+        match self.read_u8()? {
+            0 => Ok(None),
+            _ => self.read_str().map(Some),
+        }
+    }
+
+    /// Returns `None` at the end of the trace.
+    pub fn next_record(&mut self) -> std::io::Result<Option<Record>> {
+        let mut tag = [0u8; 1];
+        if self.reader.read(&mut tag)? == 0 {
+            return Ok(None);
+        }
+        match tag[0] {
+            TAG_DEVICE => {
+                let id = self.read_u16()?;
+                let path = PathBuf::from(self.read_str()?);
+                let name = self.read_str()?;
+                let phys = self.read_opt_str()?;
+                let uniq = self.read_opt_str()?;
+                let bustype = self.read_u16()?;
+                let vendor = self.read_u16()?;
+                let product = self.read_u16()?;
+                let version = self.read_u16()?;
+                let count = self.read_u8()?;
+                let event_types = (0..count)
+                    .map(|_| {
+                        let raw = self.read_u8()?;
+                        int_to_event_type(raw.into())
+                            .ok_or_else(|| invalid_data(format!("unknown event type {}", raw)))
+                    })
+                    .collect::<std::io::Result<_>>()?;
+                Ok(Some(Record::Device {
+                    id,
+                    path,
+                    info: DeviceInfo {
+                        name,
+                        phys,
+                        uniq,
+                        bustype,
+                        vendor,
+                        product,
+                        version,
+                        event_types,
+                    },
+                }))
+            }
+            TAG_EVENT => {
+                let device = self.read_u16()?;
+                let tv_sec = i64::from_le_bytes(self.read_array()?);
+                let tv_usec = i64::from_le_bytes(self.read_array()?);
+                let event_type = self.read_u16()?;
+                let code = self.read_u16()?;
+                let value = i32::from_le_bytes(self.read_array()?);
+                if int_to_event_type(event_type.into()).is_none() {
+                    return Err(invalid_data(format!("unknown event type {}", event_type)));
+                }
+                Ok(Some(Record::Event {
+                    device,
+                    event: InputEvent {
+                        time: TimeVal::new(tv_sec, tv_usec),
+                        event_code: int_to_event_code(event_type.into(), code.into()),
+                        value,
+                    },
+                }))
+            }
+            tag => Err(invalid_data(format!("unknown record tag {}", tag))),
+        }
+    }
+
+    /// Injects the remaining events of the trace with their original timing, optionally only
+    /// those recorded for `device`.
+    pub async fn play<U: Injector>(
+        &mut self,
+        uinput: &U,
+        device: Option<u16>,
+    ) -> std::io::Result<()> {
+        let mut last_time = None;
+        while let Some(record) = self.next_record()? {
+            let event = match record {
+                Record::Event { device: id, event } if device.is_none_or(|device| device == id) => {
+                    event
+                }
+                Record::Event { .. } | Record::Device { .. } => continue,
+            };
+            if let Some(last_time) = &last_time {
+                let delay = time_since(last_time, &event.time);
+                if delay > Duration::ZERO {
+                    let _: std::time::Instant = async_io::Timer::after(delay).await;
+                }
+            }
+            last_time = Some(event.time);
+            uinput.inject_event_at(event.event_code, event.value, event.time)?;
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read> Iterator for Player<R> {
+    type Item = std::io::Result<Record>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_record().transpose()
+    }
+}
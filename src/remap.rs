@@ -0,0 +1,387 @@
+use crate::{
+    hid_scancode, mirror_lock_leds, ungrab_all, AsyncDevice, DeviceError, EscapeSequence,
+    EventSource, Injector,
+};
+use evdev_rs::enums::{EventCode, EV_KEY, EV_MSC, EV_SYN};
+use evdev_rs::{GrabMode, InputEvent, UInputDevice};
+use futures::future::{self, Either};
+use futures::{StreamExt as _, TryStreamExt as _};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Instant;
+use thiserror::Error;
+
+mod check;
+mod config;
+mod context;
+mod triggers;
+mod watch;
+
+pub use crate::{parse_key, KeyNameError};
+pub use check::{Diagnostic, Location, Problem, Severity};
+pub use config::{ConfigError, KeyCombo, RemapConfig, Rule};
+pub use context::{ContextProvider, NoContext};
+#[cfg(feature = "x11")]
+pub use context::{X11Context, X11Error};
+pub use triggers::Triggers;
+pub use watch::{ConfigWatcher, ReloadError};
+
+/// What a remapped key turns into.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Target {
+    /// Forward press, release and repeat as a different key.
+    Key(EV_KEY),
+    /// Press all keys in order on press and release them in reverse order on release.
+    Chord(Vec<EV_KEY>),
+    /// Tap each chord in turn when the key is pressed. Release and repeat are swallowed.
+    Macro(Vec<Vec<EV_KEY>>),
+}
+
+#[derive(Error, Debug)]
+pub enum RemapError {
+    #[error("failed to grab device")]
+    Grab(#[source] std::io::Error),
+    #[error("error when reading an event")]
+    ReadEvent(#[source] DeviceError),
+    #[error("failed to inject event")]
+    Inject(#[source] std::io::Error),
+}
+
+/// Grabs a device and forwards its events to a virtual device, rewriting keys according to a set
+/// of rules. Keys without a rule and all non-key events are forwarded unchanged.
+///
+/// The device can be any `EventSource`, e.g. a `MockDevice` together with a `MockInjector` to
+/// test a set of rules.
+///
+/// Rules can be limited to a context, e.g. the focused application's window class as reported by
+/// a `ContextProvider`. Context rules take precedence over rules without a context, and a key
+/// keeps the target it was pressed with until it's released, even if the context changes
+/// meanwhile.
+///
+/// Rules can be replaced while running from a `ConfigWatcher`, without regrabbing the device.
+/// Held keys likewise keep their targets across the swap.
+///
+/// Keys can also have `Triggers`, with different targets for taps, double taps and long presses.
+pub struct Remapper<U = UInputDevice, D = AsyncDevice, C = NoContext> {
+    device: D,
+    uinput: U,
+    context: C,
+    // Shared with `pressed`, so pressing a key doesn't copy its target.
+    rules: HashMap<EV_KEY, Arc<Target>>,
+    context_rules: HashMap<String, HashMap<EV_KEY, Arc<Target>>>,
+    current_context: Option<String>,
+    // The targets keys were pressed with, for keys with a rule.
+    pressed: HashMap<EV_KEY, Arc<Target>>,
+    // An MSC_SCAN held back until it's known whether the key it belongs to is remapped.
+    pending_scan: Option<i32>,
+    triggers: HashMap<EV_KEY, Triggers>,
+    pending_trigger: Option<triggers::PendingTrigger>,
+    escape: Option<EscapeSequence>,
+    watcher: Option<ConfigWatcher>,
+    grab: bool,
+}
+
+fn inject_chord<U: Injector>(uinput: &U, keys: &[EV_KEY], value: i32) -> std::io::Result<()> {
+    if value == 0 {
+        for key in keys.iter().rev() {
+            uinput.inject_event(EventCode::EV_KEY(*key), 0)?;
+        }
+    } else {
+        for key in keys {
+            uinput.inject_event(EventCode::EV_KEY(*key), value)?;
+        }
+    }
+    Ok(())
+}
+
+impl<U: Injector, D: EventSource> Remapper<U, D> {
+    pub fn new(device: D, uinput: U) -> Self {
+        Self {
+            device,
+            uinput,
+            context: NoContext,
+            rules: HashMap::new(),
+            context_rules: HashMap::new(),
+            current_context: None,
+            pressed: HashMap::new(),
+            pending_scan: None,
+            triggers: HashMap::new(),
+            pending_trigger: None,
+            escape: Some(EscapeSequence::default()),
+            watcher: None,
+            grab: true,
+        }
+    }
+}
+
+impl<U: Injector, D: EventSource, C: ContextProvider> Remapper<U, D, C> {
+    /// Maps `from` to `to`, replacing any previous rule for `from`.
+    pub fn rule(mut self, from: EV_KEY, to: Target) -> Self {
+        if let Some(previous) = self.rules.insert(from, Arc::new(to)) {
+            log::warn!(
+                "replacing rule {:?} for {}",
+                previous,
+                EventCode::EV_KEY(from)
+            );
+        }
+        self
+    }
+
+    /// Adds a rule which only applies while `context` is current.
+    pub fn context_rule(mut self, context: impl Into<String>, from: EV_KEY, to: Target) -> Self {
+        let context = context.into();
+        if let Some(previous) = self
+            .context_rules
+            .entry(context.clone())
+            .or_default()
+            .insert(from, Arc::new(to))
+        {
+            log::warn!(
+                "replacing rule {:?} for {} in context `{}`",
+                previous,
+                EventCode::EV_KEY(from),
+                context
+            );
+        }
+        self
+    }
+
+    /// Sets the keys which release all grabs and stop `run`, or disables them with `None`. Both
+    /// Ctrl keys and Esc by default.
+    pub fn escape(mut self, escape: Option<EscapeSequence>) -> Self {
+        self.escape = escape;
+        self
+    }
+
+    /// Replaces the rules without a context by those of `config` while `run` is running, whenever
+    /// `watcher` yields a valid config. Invalid configs are logged and the old rules kept.
+    pub fn watch_config(mut self, watcher: ConfigWatcher) -> Self {
+        self.watcher = Some(watcher);
+        self
+    }
+
+    /// Replaces the rules without a context by those of `config`, or leaves them alone if it's
+    /// invalid.
+    pub fn set_config(&mut self, config: &RemapConfig) -> Result<(), ConfigError> {
+        self.rules = config
+            .compile()?
+            .into_iter()
+            .map(|(from, to)| (from, Arc::new(to)))
+            .collect();
+        Ok(())
+    }
+
+    /// Whether `run` grabs the device, which it does by default. Without grabbing, the device's
+    /// events also reach everything else, so this is only useful together with an injector like
+    /// `DryRun`, to check what rules would do without losing the keyboard to a mistake.
+    pub fn grab(mut self, grab: bool) -> Self {
+        self.grab = grab;
+        self
+    }
+
+    /// Sets what decides which context rules apply.
+    pub fn context<P: ContextProvider>(self, context: P) -> Remapper<U, D, P> {
+        let Self {
+            device,
+            uinput,
+            context: _,
+            rules,
+            context_rules,
+            current_context,
+            pressed,
+            pending_scan,
+            triggers,
+            pending_trigger,
+            escape,
+            watcher,
+            grab,
+        } = self;
+        Remapper {
+            device,
+            uinput,
+            context,
+            rules,
+            context_rules,
+            current_context,
+            pressed,
+            pending_scan,
+            triggers,
+            pending_trigger,
+            escape,
+            watcher,
+            grab,
+        }
+    }
+
+    fn resolve(&mut self, key: EV_KEY, value: i32) -> Option<Arc<Target>> {
+        match value {
+            1 => {
+                let target = self
+                    .current_context
+                    .as_ref()
+                    .and_then(|context| self.context_rules.get(context))
+                    .and_then(|rules| rules.get(&key))
+                    .or_else(|| self.rules.get(&key))
+                    .cloned()?;
+                let _: Option<Arc<Target>> = self.pressed.insert(key, Arc::clone(&target));
+                Some(target)
+            }
+            0 => self.pressed.remove(&key),
+            _ => self.pressed.get(&key).cloned(),
+        }
+    }
+
+    fn handle_event(&mut self, event: InputEvent) -> std::io::Result<()> {
+        let InputEvent {
+            time: _,
+            event_code,
+            value,
+        } = event;
+        let scan_code = EventCode::EV_MSC(EV_MSC::MSC_SCAN);
+        let scan = self.pending_scan.take();
+        if event_code == scan_code {
+            if let Some(scan) = scan {
+                self.uinput.inject_event(scan_code, scan)?;
+            }
+            self.pending_scan = Some(value);
+            return Ok(());
+        }
+        if let EventCode::EV_KEY(key) = event_code {
+            if self.handle_trigger(key, value, Instant::now())? {
+                return Ok(());
+            }
+        }
+        let target = match event_code {
+            EventCode::EV_KEY(key) => self.resolve(key, value),
+            _ => None,
+        };
+        match target {
+            None => {
+                if let Some(scan) = scan {
+                    self.uinput.inject_event(scan_code, scan)?;
+                }
+                self.uinput.inject_event(event_code, value)
+            }
+            Some(target) => self.inject_target(&target, value, scan),
+        }
+    }
+
+    fn inject_target(&self, target: &Target, value: i32, scan: Option<i32>) -> std::io::Result<()> {
+        match target {
+            // Applications reading scancodes should see the key it was remapped to. Chords and
+            // macros have no single scancode, so theirs is dropped.
+            Target::Key(key) => {
+                if let Some(scan) = scan.and_then(|_| hid_scancode(*key)) {
+                    self.uinput
+                        .inject_event(EventCode::EV_MSC(EV_MSC::MSC_SCAN), scan as i32)?;
+                }
+                self.uinput.inject_event(EventCode::EV_KEY(*key), value)
+            }
+            Target::Chord(keys) => match value {
+                // Only the last key of a held chord repeats, like a physical chord would.
+                2 => keys
+                    .last()
+                    .map(|key| self.uinput.inject_event(EventCode::EV_KEY(*key), 2))
+                    .unwrap_or(Ok(())),
+                value => inject_chord(&self.uinput, keys, value),
+            },
+            Target::Macro(chords) => {
+                if value != 1 {
+                    return Ok(());
+                }
+                for keys in chords {
+                    inject_chord(&self.uinput, keys, 1)?;
+                    self.uinput
+                        .inject_event(EventCode::EV_SYN(EV_SYN::SYN_REPORT), 0)?;
+                    inject_chord(&self.uinput, keys, 0)?;
+                    self.uinput
+                        .inject_event(EventCode::EV_SYN(EV_SYN::SYN_REPORT), 0)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Grabs the device and runs the forwarding loop until the device's event stream ends.
+    pub async fn run(mut self) -> Result<(), RemapError> {
+        if self.grab {
+            self.device.grab(GrabMode::Grab).map_err(RemapError::Grab)?;
+        }
+        if let Some(device) = self.device.libevdev() {
+            mirror_lock_leds(device, &self.uinput).map_err(RemapError::Inject)?;
+        }
+        loop {
+            let deadline = self.trigger_deadline();
+            let input = async {
+                match &mut self.watcher {
+                    Some(watcher) => {
+                        match future::select(self.device.try_next(), watcher.next()).await {
+                            Either::Left((event, _)) => Some(Either::Left(event)),
+                            Either::Right((reload, _)) => Some(Either::Right(reload)),
+                        }
+                    }
+                    None => Some(Either::Left(self.device.try_next().await)),
+                }
+            };
+            // `None` when a key with triggers timed out.
+            let next = match deadline {
+                Some(deadline) => {
+                    match future::select(std::pin::pin!(input), async_io::Timer::at(deadline)).await
+                    {
+                        Either::Left((next, _)) => next,
+                        Either::Right(_) => None,
+                    }
+                }
+                None => input.await,
+            };
+            let event = match next {
+                Some(Either::Left(event)) => match event.map_err(RemapError::ReadEvent)? {
+                    Some(event) => event,
+                    None => break,
+                },
+                Some(Either::Right(reload)) => {
+                    self.reload(reload);
+                    continue;
+                }
+                None => {
+                    self.trigger_timeout().map_err(RemapError::Inject)?;
+                    continue;
+                }
+            };
+            if self
+                .escape
+                .as_mut()
+                .is_some_and(|escape| escape.handle(&event))
+            {
+                log::warn!("escape sequence pressed, releasing all grabs");
+                ungrab_all();
+                if !self.grab {
+                    return Ok(());
+                }
+                return self.device.grab(GrabMode::Ungrab).map_err(RemapError::Grab);
+            }
+            if !self.context_rules.is_empty()
+                && matches!(event.event_code, EventCode::EV_KEY(_))
+                && event.value == 1
+            {
+                self.current_context = self.context.current().await;
+            }
+            self.handle_event(event).map_err(RemapError::Inject)?;
+        }
+        self.interrupt_trigger().map_err(RemapError::Inject)
+    }
+
+    fn reload(&mut self, reload: Option<Result<RemapConfig, ReloadError>>) {
+        let result = match reload {
+            Some(reload) => reload.and_then(|config| Ok(self.set_config(&config)?)),
+            None => {
+                self.watcher = None;
+                return;
+            }
+        };
+        match result {
+            Ok(()) => log::info!("reloaded remap config"),
+            Err(e) => log::warn!("keeping previous remap config: {}", e),
+        }
+    }
+}
@@ -0,0 +1,173 @@
+use super::{ConfigError, ContextProvider, RemapConfig, Remapper, Target};
+use crate::{EventSource, Injector};
+use evdev_rs::enums::{EventCode, EV_KEY};
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::fmt;
+use thiserror::Error;
+
+/// Where a binding is, to point the user at it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Location {
+    /// The rule at this index of `RemapConfig::rules`.
+    Rule(usize),
+    /// The rule a `Remapper` has for a key, in a context or not.
+    Key {
+        context: Option<String>,
+        key: EV_KEY,
+    },
+    /// A layer of `Layers`, by index.
+    Layer(usize),
+    /// The action of a key on a layer.
+    LayerKey { layer: usize, key: EV_KEY },
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Location::Rule(index) => write!(f, "rules[{}]", index),
+            Location::Key { context, key } => {
+                write!(f, "rule for {}", EventCode::EV_KEY(*key))?;
+                match context {
+                    Some(context) => write!(f, " in context `{}`", context),
+                    None => Ok(()),
+                }
+            }
+            Location::Layer(layer) => write!(f, "layer {}", layer),
+            Location::LayerKey { layer, key } => {
+                write!(f, "{} on layer {}", EventCode::EV_KEY(*key), layer)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// The binding works, but likely not as intended.
+    Warning,
+    /// The config can't be used.
+    Error,
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum Problem {
+    #[error(transparent)]
+    Invalid(ConfigError),
+    #[error("the key is already mapped by {first}")]
+    Duplicate { first: Location },
+    #[error("never applies, as the key has triggers")]
+    ShadowedByTriggers,
+    #[error("maps the key to itself")]
+    NoOp,
+    #[error("refers to layer {0}, which doesn't exist")]
+    MissingLayer(usize),
+    #[error("no action activates it")]
+    UnreachableLayer,
+    #[error("never applies, as the layer is only active while {activator} is held")]
+    UnreachableBinding { activator: Location },
+}
+
+impl Problem {
+    pub fn severity(&self) -> Severity {
+        match self {
+            Problem::Invalid(_) | Problem::Duplicate { .. } | Problem::MissingLayer(_) => {
+                Severity::Error
+            }
+            Problem::ShadowedByTriggers
+            | Problem::NoOp
+            | Problem::UnreachableLayer
+            | Problem::UnreachableBinding { .. } => Severity::Warning,
+        }
+    }
+}
+
+/// A problem with a binding found by `RemapConfig::check`, `Remapper::check` or
+/// `Layers::check`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub location: Location,
+    pub problem: Problem,
+}
+
+impl Diagnostic {
+    pub fn new(location: Location, problem: Problem) -> Self {
+        Self { location, problem }
+    }
+
+    pub fn severity(&self) -> Severity {
+        self.problem.severity()
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity() {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        write!(f, "{}: {}: {}", severity, self.location, self.problem)
+    }
+}
+
+impl RemapConfig {
+    /// Finds all problems with the rules, rather than only the first error like `compile`.
+    pub fn check(&self) -> Vec<Diagnostic> {
+        let mut first = HashMap::new();
+        let mut diagnostics = Vec::new();
+        for (index, rule) in self.rules.iter().enumerate() {
+            let location = Location::Rule(index);
+            let (from, target) = match rule.compile() {
+                Ok(compiled) => compiled,
+                Err(e) => {
+                    diagnostics.push(Diagnostic::new(location, Problem::Invalid(e)));
+                    continue;
+                }
+            };
+            if target == Target::Key(from) {
+                diagnostics.push(Diagnostic::new(location.clone(), Problem::NoOp));
+            }
+            match first.entry(from) {
+                Entry::Occupied(entry) => diagnostics.push(Diagnostic::new(
+                    location,
+                    Problem::Duplicate {
+                        first: Location::Rule(*entry.get()),
+                    },
+                )),
+                Entry::Vacant(entry) => {
+                    let _: &mut usize = entry.insert(index);
+                }
+            }
+        }
+        diagnostics
+    }
+}
+
+impl<U: Injector, D: EventSource, C: ContextProvider> Remapper<U, D, C> {
+    /// Finds rules which never apply: those of keys with triggers, which take precedence over
+    /// context rules, which in turn take precedence over rules without a context.
+    pub fn check(&self) -> Vec<Diagnostic> {
+        let rules = self.rules.keys().map(|key| (None, key));
+        let context_rules = self
+            .context_rules
+            .iter()
+            .flat_map(|(context, rules)| rules.keys().map(move |key| (Some(context), key)));
+        let mut diagnostics = rules
+            .chain(context_rules)
+            .filter(|(_, key)| self.triggers.contains_key(key))
+            .map(|(context, key)| {
+                Diagnostic::new(
+                    Location::Key {
+                        context: context.cloned(),
+                        key: *key,
+                    },
+                    Problem::ShadowedByTriggers,
+                )
+            })
+            .collect::<Vec<_>>();
+        diagnostics.sort_by_key(|diagnostic| match &diagnostic.location {
+            Location::Key { context, key } => (context.clone(), *key as u32),
+            _ => (None, 0),
+        });
+        diagnostics
+    }
+}
@@ -0,0 +1,152 @@
+use super::{ContextProvider, Remapper, Target};
+use crate::{parse_key, EventSource, Injector, KeyNameError};
+use evdev_rs::enums::{EventCode, EV_KEY};
+use std::collections::HashSet;
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
+
+/// One or more keys, written as names joined by `+`, e.g. `ctrl+shift+a` or `KEY_LEFTMETA`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "String", into = "String")
+)]
+pub struct KeyCombo(pub Vec<EV_KEY>);
+
+impl FromStr for KeyCombo {
+    type Err = KeyNameError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.trim().is_empty() {
+            return Err(KeyNameError::Empty);
+        }
+        s.split('+')
+            .map(parse_key)
+            .collect::<Result<_, _>>()
+            .map(KeyCombo)
+    }
+}
+
+impl TryFrom<String> for KeyCombo {
+    type Error = KeyNameError;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for KeyCombo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, key) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str("+")?;
+            }
+            write!(f, "{}", EventCode::EV_KEY(*key))?;
+        }
+        Ok(())
+    }
+}
+
+impl From<KeyCombo> for String {
+    fn from(combo: KeyCombo) -> Self {
+        combo.to_string()
+    }
+}
+
+/// A remapping rule. Exactly one of `to` and `macro` must be set: `to` with a single key remaps
+/// to that key, with several keys to a chord; `macro` taps each combination in turn.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Rule {
+    pub from: KeyCombo,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub to: Option<KeyCombo>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(rename = "macro", default, skip_serializing_if = "Option::is_none")
+    )]
+    pub macro_: Option<Vec<KeyCombo>>,
+}
+
+/// A set of remapping rules, e.g. loaded with serde from a TOML file like
+///
+/// ```toml
+/// [[rules]]
+/// from = "capslock"
+/// to = "esc"
+///
+/// [[rules]]
+/// from = "f1"
+/// macro = ["ctrl+a", "ctrl+c"]
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RemapConfig {
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub rules: Vec<Rule>,
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ConfigError {
+    #[error("rule source `{0}` must be a single key")]
+    FromNotSingleKey(KeyCombo),
+    #[error("rule for `{0}` has neither `to` nor `macro`")]
+    MissingTarget(KeyCombo),
+    #[error("rule for `{0}` has both `to` and `macro`")]
+    ConflictingTargets(KeyCombo),
+    #[error("key `{0}` has more than one rule")]
+    DuplicateRule(KeyCombo),
+}
+
+impl Rule {
+    pub(super) fn compile(&self) -> Result<(EV_KEY, Target), ConfigError> {
+        let from = match self.from.0.as_slice() {
+            [key] => *key,
+            _ => return Err(ConfigError::FromNotSingleKey(self.from.clone())),
+        };
+        let target = match (&self.to, &self.macro_) {
+            (Some(KeyCombo(keys)), None) => match keys.as_slice() {
+                [key] => Target::Key(*key),
+                keys => Target::Chord(keys.to_vec()),
+            },
+            (None, Some(combos)) => {
+                Target::Macro(combos.iter().map(|combo| combo.0.clone()).collect())
+            }
+            (None, None) => return Err(ConfigError::MissingTarget(self.from.clone())),
+            (Some(_), Some(_)) => return Err(ConfigError::ConflictingTargets(self.from.clone())),
+        };
+        Ok((from, target))
+    }
+}
+
+impl RemapConfig {
+    pub fn compile(&self) -> Result<Vec<(EV_KEY, Target)>, ConfigError> {
+        let mut seen = HashSet::new();
+        self.rules
+            .iter()
+            .map(|rule| {
+                let (from, target) = rule.compile()?;
+                if !seen.insert(from) {
+                    return Err(ConfigError::DuplicateRule(rule.from.clone()));
+                }
+                Ok((from, target))
+            })
+            .collect()
+    }
+}
+
+impl<U: Injector, D: EventSource, C: ContextProvider> Remapper<U, D, C> {
+    /// Adds all rules of `config`.
+    pub fn config(self, config: &RemapConfig) -> Result<Self, ConfigError> {
+        Ok(config
+            .compile()?
+            .into_iter()
+            .fold(self, |remapper, (from, to)| remapper.rule(from, to)))
+    }
+}
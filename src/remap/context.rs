@@ -0,0 +1,98 @@
+use std::future::Future;
+
+/// Tells the `Remapper` which application has focus, so that rules can differ between, e.g.,
+/// games and terminals. It's consulted on every key press while context rules are set.
+pub trait ContextProvider {
+    /// An identifier of the focused application, e.g. its window class or app id, if known.
+    fn current(&mut self) -> impl Future<Output = Option<String>>;
+}
+
+/// No context, so only the rules without one apply.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoContext;
+
+impl ContextProvider for NoContext {
+    async fn current(&mut self) -> Option<String> {
+        None
+    }
+}
+
+#[cfg(feature = "x11")]
+pub use x11::{X11Context, X11Error};
+
+#[cfg(feature = "x11")]
+mod x11 {
+    use super::ContextProvider;
+    use thiserror::Error;
+    use x11rb::connection::Connection as _;
+    use x11rb::errors::{ConnectError, ConnectionError, ReplyError};
+    use x11rb::protocol::xproto::{Atom, AtomEnum, ConnectionExt as _, Window};
+    use x11rb::rust_connection::RustConnection;
+
+    #[derive(Error, Debug)]
+    pub enum X11Error {
+        #[error("failed to connect to the X server")]
+        Connect(#[source] ConnectError),
+        #[error("failed to send a request to the X server")]
+        Connection(#[from] ConnectionError),
+        #[error("X server returned an error")]
+        Reply(#[from] ReplyError),
+    }
+
+    /// Reports the class of the active X11 window, from its WM_CLASS property.
+    pub struct X11Context {
+        connection: RustConnection,
+        root: Window,
+        net_active_window: Atom,
+    }
+
+    impl X11Context {
+        pub fn new() -> Result<Self, X11Error> {
+            let (connection, screen) = x11rb::connect(None).map_err(X11Error::Connect)?;
+            let root = connection.setup().roots[screen].root;
+            let net_active_window = connection
+                .intern_atom(false, b"_NET_ACTIVE_WINDOW")?
+                .reply()?
+                .atom;
+            Ok(Self {
+                connection,
+                root,
+                net_active_window,
+            })
+        }
+
+        pub fn active_class(&self) -> Result<Option<String>, X11Error> {
+            let active = self
+                .connection
+                .get_property(
+                    false,
+                    self.root,
+                    self.net_active_window,
+                    AtomEnum::WINDOW,
+                    0,
+                    1,
+                )?
+                .reply()?;
+            let window = match active.value32().and_then(|mut values| values.next()) {
+                Some(window) if window != x11rb::NONE => window,
+                _ => return Ok(None),
+            };
+            let class = self
+                .connection
+                .get_property(false, window, AtomEnum::WM_CLASS, AtomEnum::STRING, 0, 1024)?
+                .reply()?;
+            // WM_CLASS holds the instance and class names, each terminated by a NUL.
+            Ok(class
+                .value
+                .split(|byte| *byte == 0)
+                .nth(1)
+                .map(|class| String::from_utf8_lossy(class).into_owned()))
+        }
+    }
+
+    impl ContextProvider for X11Context {
+        async fn current(&mut self) -> Option<String> {
+            self.active_class().ok().flatten()
+        }
+    }
+}
@@ -0,0 +1,312 @@
+use super::{ContextProvider, Remapper, Target};
+use crate::{EventSource, Injector};
+use evdev_rs::enums::{EventCode, EV_KEY, EV_SYN};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Up to four targets for one key, depending on how it's pressed: tapped, tapped twice, held for
+/// a while, or tapped and then held, like QMK's tap dance. Unlike a `TapHold` key, a long press
+/// is only recognized once the key has been held for `long_press_time`.
+///
+/// Targets are pressed once recognized and released with the key, or tapped if the key was
+/// already released. Pressing another key meanwhile settles on the tap, or double tap, right
+/// away.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Triggers {
+    tap: Target,
+    double_tap: Option<Target>,
+    long_press: Option<Target>,
+    tap_long_press: Option<Target>,
+    double_tap_window: Duration,
+    long_press_time: Duration,
+}
+
+impl Triggers {
+    /// Only `tap`, with a double tap window of 250ms and a long press after 500ms.
+    pub fn new(tap: Target) -> Self {
+        Self {
+            tap,
+            double_tap: None,
+            long_press: None,
+            tap_long_press: None,
+            double_tap_window: Duration::from_millis(250),
+            long_press_time: Duration::from_millis(500),
+        }
+    }
+
+    pub fn double_tap(mut self, target: Target) -> Self {
+        self.double_tap = Some(target);
+        self
+    }
+
+    pub fn long_press(mut self, target: Target) -> Self {
+        self.long_press = Some(target);
+        self
+    }
+
+    /// The target when the key is tapped and then held.
+    pub fn tap_long_press(mut self, target: Target) -> Self {
+        self.tap_long_press = Some(target);
+        self
+    }
+
+    /// How long after a tap the key may be pressed again to count as a double tap. A plain tap
+    /// is delayed by as much while a second press could follow.
+    pub fn double_tap_window(mut self, window: Duration) -> Self {
+        self.double_tap_window = window;
+        self
+    }
+
+    pub fn long_press_time(mut self, time: Duration) -> Self {
+        self.long_press_time = time;
+        self
+    }
+
+    fn waits_for_second_press(&self) -> bool {
+        self.double_tap.is_some() || self.tap_long_press.is_some()
+    }
+}
+
+/// A key with triggers which hasn't been recognized yet.
+#[derive(Debug)]
+pub(super) struct PendingTrigger {
+    key: EV_KEY,
+    // 1 or 2.
+    presses: u8,
+    down: bool,
+    deadline: Option<Instant>,
+}
+
+impl<U: Injector, D: EventSource, C: ContextProvider> Remapper<U, D, C> {
+    /// Gives `key` the targets of `triggers`, taking precedence over its rules.
+    pub fn triggers(mut self, key: EV_KEY, triggers: Triggers) -> Self {
+        let _: Option<Triggers> = self.triggers.insert(key, triggers);
+        self
+    }
+
+    pub(super) fn trigger_deadline(&self) -> Option<Instant> {
+        self.pending_trigger
+            .as_ref()
+            .and_then(|pending| pending.deadline)
+    }
+
+    fn fire(&mut self, key: EV_KEY, target: Target, held: bool) -> std::io::Result<()> {
+        self.inject_target(&target, 1, None)?;
+        self.uinput
+            .inject_event(EventCode::EV_SYN(EV_SYN::SYN_REPORT), 0)?;
+        if held {
+            let _: Option<Arc<Target>> = self.pressed.insert(key, Arc::new(target));
+            return Ok(());
+        }
+        self.inject_target(&target, 0, None)?;
+        self.uinput
+            .inject_event(EventCode::EV_SYN(EV_SYN::SYN_REPORT), 0)
+    }
+
+    /// Fires the tap or double tap of a pending key, two taps if it has no double tap.
+    fn fire_short(&mut self, pending: &PendingTrigger, held: bool) -> std::io::Result<()> {
+        let triggers = &self.triggers[&pending.key];
+        let (tap, double_tap) = (triggers.tap.clone(), triggers.double_tap.clone());
+        match (pending.presses, double_tap) {
+            (2, Some(double_tap)) => self.fire(pending.key, double_tap, held),
+            (2, None) => {
+                self.fire(pending.key, tap.clone(), false)?;
+                self.fire(pending.key, tap, held)
+            }
+            _ => self.fire(pending.key, tap, held),
+        }
+    }
+
+    /// Settles a pending key on its tap or double tap, as when another key is pressed.
+    pub(super) fn interrupt_trigger(&mut self) -> std::io::Result<()> {
+        match self.pending_trigger.take() {
+            Some(pending) => self.fire_short(&pending, pending.down),
+            None => Ok(()),
+        }
+    }
+
+    pub(super) fn trigger_timeout(&mut self) -> std::io::Result<()> {
+        let pending = match self.pending_trigger.take() {
+            Some(pending) => pending,
+            None => return Ok(()),
+        };
+        if !pending.down {
+            return self.fire_short(&pending, false);
+        }
+        let triggers = &self.triggers[&pending.key];
+        let long = match pending.presses {
+            1 => triggers.long_press.clone(),
+            _ => triggers.tap_long_press.clone(),
+        };
+        match long {
+            Some(target) => self.fire(pending.key, target, true),
+            None => self.fire_short(&pending, true),
+        }
+    }
+
+    /// Handles an event of a key with triggers, returning whether it was one.
+    pub(super) fn handle_trigger(
+        &mut self,
+        key: EV_KEY,
+        value: i32,
+        now: Instant,
+    ) -> std::io::Result<bool> {
+        if value == 1
+            && self
+                .pending_trigger
+                .as_ref()
+                .is_some_and(|pending| pending.key != key)
+        {
+            self.interrupt_trigger()?;
+        }
+        let triggers = match self.triggers.get(&key) {
+            Some(triggers) => triggers,
+            None => return Ok(false),
+        };
+        let long_press = now + triggers.long_press_time;
+        let (first_long, second_long) = (
+            triggers.long_press.as_ref().map(|_| long_press),
+            triggers.tap_long_press.as_ref().map(|_| long_press),
+        );
+        let double_tap_window = now + triggers.double_tap_window;
+        let waits = triggers.waits_for_second_press();
+        match (self.pending_trigger.take(), value) {
+            (None, 1) => {
+                if !waits && first_long.is_none() {
+                    let tap = triggers.tap.clone();
+                    return self.fire(key, tap, true).map(|()| true);
+                }
+                self.pending_trigger = Some(PendingTrigger {
+                    key,
+                    presses: 1,
+                    down: true,
+                    deadline: first_long,
+                });
+            }
+            // The release of a held target goes the way of those of rules; after a tap, it's
+            // swallowed.
+            (None, _) => return Ok(!self.pressed.contains_key(&key)),
+            (Some(pending), 0) if pending.presses == 1 && waits => {
+                self.pending_trigger = Some(PendingTrigger {
+                    down: false,
+                    deadline: Some(double_tap_window),
+                    ..pending
+                });
+            }
+            (Some(pending), 0) => self.fire_short(&pending, false)?,
+            (Some(pending), 1) => {
+                let pending = PendingTrigger {
+                    presses: 2,
+                    down: true,
+                    deadline: second_long,
+                    ..pending
+                };
+                if second_long.is_some() {
+                    self.pending_trigger = Some(pending);
+                } else {
+                    self.fire_short(&pending, true)?;
+                }
+            }
+            (pending, _) => self.pending_trigger = pending,
+        }
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::key;
+    use crate::{MockDevice, MockInjector};
+    use evdev_rs::enums::EV_KEY::{KEY_A, KEY_CAPSLOCK, KEY_ESC, KEY_LEFTCTRL, KEY_TAB};
+    use evdev_rs::InputEvent;
+
+    fn run(events: Vec<InputEvent>) -> Vec<(EV_KEY, i32)> {
+        let injector = MockInjector::new();
+        let triggers = Triggers::new(Target::Key(KEY_ESC))
+            .double_tap(Target::Key(KEY_CAPSLOCK))
+            .long_press(Target::Key(KEY_LEFTCTRL))
+            .tap_long_press(Target::Key(KEY_TAB))
+            .double_tap_window(Duration::from_millis(50))
+            .long_press_time(Duration::from_millis(100));
+        let remapper =
+            Remapper::new(MockDevice::paced(events), &injector).triggers(KEY_CAPSLOCK, triggers);
+        futures::executor::block_on(remapper.run()).expect("remapping failed");
+        injector
+            .events()
+            .into_iter()
+            .filter_map(|(event_code, value)| match event_code {
+                EventCode::EV_KEY(key) => Some((key, value)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn tap_after_double_tap_window() {
+        let events = vec![
+            key(0, KEY_CAPSLOCK, 1),
+            key(10, KEY_CAPSLOCK, 0),
+            key(200, KEY_A, 1),
+            key(210, KEY_A, 0),
+        ];
+        assert_eq!(
+            run(events),
+            vec![(KEY_ESC, 1), (KEY_ESC, 0), (KEY_A, 1), (KEY_A, 0)]
+        );
+    }
+
+    #[test]
+    fn double_tap() {
+        let events = vec![
+            key(0, KEY_CAPSLOCK, 1),
+            key(10, KEY_CAPSLOCK, 0),
+            key(20, KEY_CAPSLOCK, 1),
+            key(30, KEY_CAPSLOCK, 0),
+            key(200, KEY_A, 1),
+        ];
+        assert_eq!(
+            run(events),
+            vec![(KEY_CAPSLOCK, 1), (KEY_CAPSLOCK, 0), (KEY_A, 1)]
+        );
+    }
+
+    #[test]
+    fn long_press_is_held_until_release() {
+        let events = vec![
+            key(0, KEY_CAPSLOCK, 1),
+            key(200, KEY_A, 1),
+            key(210, KEY_A, 0),
+            key(220, KEY_CAPSLOCK, 0),
+        ];
+        assert_eq!(
+            run(events),
+            vec![(KEY_LEFTCTRL, 1), (KEY_A, 1), (KEY_A, 0), (KEY_LEFTCTRL, 0)]
+        );
+    }
+
+    #[test]
+    fn tap_long_press() {
+        let events = vec![
+            key(0, KEY_CAPSLOCK, 1),
+            key(10, KEY_CAPSLOCK, 0),
+            key(20, KEY_CAPSLOCK, 1),
+            key(200, KEY_CAPSLOCK, 0),
+        ];
+        assert_eq!(run(events), vec![(KEY_TAB, 1), (KEY_TAB, 0)]);
+    }
+
+    #[test]
+    fn other_key_settles_on_tap() {
+        let events = vec![
+            key(0, KEY_CAPSLOCK, 1),
+            key(10, KEY_CAPSLOCK, 0),
+            key(20, KEY_A, 1),
+            key(30, KEY_A, 0),
+        ];
+        assert_eq!(
+            run(events),
+            vec![(KEY_ESC, 1), (KEY_ESC, 0), (KEY_A, 1), (KEY_A, 0)]
+        );
+    }
+}
@@ -0,0 +1,139 @@
+use super::{ConfigError, RemapConfig};
+use async_io::Async;
+use futures::{ready, Stream};
+use std::ffi::{CString, OsStr};
+use std::fs::File;
+use std::io::Read as _;
+use std::os::unix::ffi::OsStrExt as _;
+use std::os::unix::io::{AsRawFd as _, FromRawFd as _};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use thiserror::Error;
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+type Parser = Box<dyn Fn(&str) -> Result<RemapConfig, BoxError>>;
+
+#[derive(Error, Debug)]
+pub enum ReloadError {
+    #[error("failed to watch the config file")]
+    Watch(#[source] std::io::Error),
+    #[error("failed to read {}", .path.display())]
+    Read {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("failed to parse config")]
+    Parse(#[source] BoxError),
+    #[error("invalid config")]
+    Invalid(#[from] ConfigError),
+}
+
+/// Watches a remap config file with inotify and yields it, parsed and validated, each time it's
+/// written. Editors saving through a temporary file and a rename are noticed too, since the
+/// directory is watched rather than the file.
+///
+/// Hand it to `Remapper::watch_config` to apply changes to a running remapper.
+pub struct ConfigWatcher {
+    inotify: Async<File>,
+    path: PathBuf,
+    parse: Parser,
+}
+
+impl ConfigWatcher {
+    /// Watches `path`, parsing its contents with `parse`, e.g. `toml::from_str`.
+    pub fn new<F, E>(path: impl Into<PathBuf>, parse: F) -> std::io::Result<Self>
+    where
+        F: Fn(&str) -> Result<RemapConfig, E> + 'static,
+        E: Into<BoxError>,
+    {
+        let path = path.into();
+        let fd = unsafe { libc::inotify_init1(libc::IN_NONBLOCK | libc::IN_CLOEXEC) };
+        if fd < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        let inotify = unsafe { File::from_raw_fd(fd) };
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        let dir = CString::new(dir.as_os_str().as_bytes())
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+        let wd = unsafe {
+            libc::inotify_add_watch(
+                inotify.as_raw_fd(),
+                dir.as_ptr(),
+                libc::IN_CLOSE_WRITE | libc::IN_MOVED_TO,
+            )
+        };
+        if wd < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(Self {
+            inotify: Async::new(inotify)?,
+            path,
+            parse: Box::new(move |text| parse(text).map_err(Into::into)),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Reads, parses and validates the file now. Everything `RemapConfig::check` finds is logged.
+    pub fn load(&self) -> Result<RemapConfig, ReloadError> {
+        let text = std::fs::read_to_string(&self.path).map_err(|source| ReloadError::Read {
+            path: self.path.clone(),
+            source,
+        })?;
+        let config = (self.parse)(&text).map_err(ReloadError::Parse)?;
+        for diagnostic in config.check() {
+            log::warn!("{}: {}", self.path.display(), diagnostic);
+        }
+        let _: Vec<_> = config.compile()?;
+        Ok(config)
+    }
+
+    fn concerns_file(&self, mut buf: &[u8]) -> bool {
+        const HEADER_LEN: usize = std::mem::size_of::<libc::inotify_event>();
+        let file_name = self.path.file_name();
+        let mut changed = false;
+        while buf.len() >= HEADER_LEN {
+            let header =
+                unsafe { std::ptr::read_unaligned(buf.as_ptr() as *const libc::inotify_event) };
+            let name = &buf[HEADER_LEN..HEADER_LEN + header.len as usize];
+            buf = &buf[HEADER_LEN + header.len as usize..];
+            let name = match name.iter().position(|&b| b == 0) {
+                Some(end) => &name[..end],
+                None => name,
+            };
+            changed |= file_name == Some(OsStr::from_bytes(name));
+        }
+        changed
+    }
+}
+
+impl Stream for ConfigWatcher {
+    type Item = Result<RemapConfig, ReloadError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut buf = [0u8; 4096];
+        let mut changed = false;
+        loop {
+            match self.inotify.get_ref().read(&mut buf) {
+                Ok(len) => changed |= self.concerns_file(&buf[..len]),
+                // Several writes in one go are one reload.
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock && changed => {
+                    return Poll::Ready(Some(self.load()));
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                    if let Err(e) = ready!(self.inotify.poll_readable(cx)) {
+                        return Poll::Ready(Some(Err(ReloadError::Watch(e))));
+                    }
+                }
+                Err(e) => return Poll::Ready(Some(Err(ReloadError::Watch(e)))),
+            }
+        }
+    }
+}
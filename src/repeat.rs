@@ -0,0 +1,78 @@
+use crate::{Injector, ScheduleHandle, Scheduler};
+use evdev_rs::enums::{EventCode, EV_KEY};
+use evdev_rs::UInputDevice;
+use std::time::{Duration, Instant};
+
+/// The kernel's default autorepeat delay.
+pub const DEFAULT_REPEAT_DELAY: Duration = Duration::from_millis(250);
+/// The kernel's default autorepeat period.
+pub const DEFAULT_REPEAT_PERIOD: Duration = Duration::from_millis(33);
+
+/// Synthesizes autorepeat (EV_KEY value 2) for keys injected into a virtual keyboard, which the
+/// kernel doesn't do for uinput devices. Like the kernel, only the most recently pressed key
+/// repeats. Repeats are scheduled on a `Scheduler`.
+///
+/// `tick` must be polled alongside whatever drives `press`/`release`, e.g. in a `select!` loop.
+/// It is cancel-safe.
+pub struct RepeatScheduler<U = UInputDevice> {
+    scheduler: Scheduler<U>,
+    delay: Duration,
+    period: Duration,
+    held: Option<(EV_KEY, ScheduleHandle)>,
+}
+
+impl<U: Injector> RepeatScheduler<U> {
+    pub fn new(uinput: U, delay: Duration, period: Duration) -> Self {
+        Self {
+            scheduler: Scheduler::new(uinput),
+            delay,
+            period,
+            held: None,
+        }
+    }
+
+    pub fn uinput(&self) -> &U {
+        self.scheduler.uinput()
+    }
+
+    fn schedule_repeat(&mut self, key: EV_KEY, delay: Duration) {
+        let handle = self
+            .scheduler
+            .emit_after(delay, &[(EventCode::EV_KEY(key), 2)]);
+        if let Some((_, previous)) = self.held.replace((key, handle)) {
+            let _: bool = self.scheduler.cancel(previous);
+        }
+    }
+
+    pub fn press(&mut self, key: EV_KEY) -> std::io::Result<()> {
+        self.scheduler.key(key, 1)?;
+        self.schedule_repeat(key, self.delay);
+        Ok(())
+    }
+
+    pub fn release(&mut self, key: EV_KEY) -> std::io::Result<()> {
+        self.scheduler.key(key, 0)?;
+        if let Some((_, handle)) = self.held.filter(|(held, _)| *held == key) {
+            let _: bool = self.scheduler.cancel(handle);
+            self.held = None;
+        }
+        Ok(())
+    }
+
+    /// When the next repeat is due, if any key is held.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.held.map(|(_, handle)| handle.at())
+    }
+
+    /// Waits for the next repeat to become due and injects it. Never completes while no key is
+    /// held.
+    pub async fn tick(&mut self) -> std::io::Result<()> {
+        let key = match self.held {
+            Some((key, _)) => key,
+            None => futures::future::pending().await,
+        };
+        self.scheduler.tick().await?;
+        self.schedule_repeat(key, self.period);
+        Ok(())
+    }
+}
@@ -0,0 +1,146 @@
+use evdev_rs::enums::EV_KEY;
+
+// USB HID keyboard usage page, which scancodes of USB keyboards are qualified with.
+const KEYBOARD_PAGE: u32 = 0x7_0000;
+
+// Usages of the keyboard page and the keys Linux maps them to.
+const USAGES: &[(u32, EV_KEY)] = &[
+    (0x04, EV_KEY::KEY_A),
+    (0x05, EV_KEY::KEY_B),
+    (0x06, EV_KEY::KEY_C),
+    (0x07, EV_KEY::KEY_D),
+    (0x08, EV_KEY::KEY_E),
+    (0x09, EV_KEY::KEY_F),
+    (0x0a, EV_KEY::KEY_G),
+    (0x0b, EV_KEY::KEY_H),
+    (0x0c, EV_KEY::KEY_I),
+    (0x0d, EV_KEY::KEY_J),
+    (0x0e, EV_KEY::KEY_K),
+    (0x0f, EV_KEY::KEY_L),
+    (0x10, EV_KEY::KEY_M),
+    (0x11, EV_KEY::KEY_N),
+    (0x12, EV_KEY::KEY_O),
+    (0x13, EV_KEY::KEY_P),
+    (0x14, EV_KEY::KEY_Q),
+    (0x15, EV_KEY::KEY_R),
+    (0x16, EV_KEY::KEY_S),
+    (0x17, EV_KEY::KEY_T),
+    (0x18, EV_KEY::KEY_U),
+    (0x19, EV_KEY::KEY_V),
+    (0x1a, EV_KEY::KEY_W),
+    (0x1b, EV_KEY::KEY_X),
+    (0x1c, EV_KEY::KEY_Y),
+    (0x1d, EV_KEY::KEY_Z),
+    (0x1e, EV_KEY::KEY_1),
+    (0x1f, EV_KEY::KEY_2),
+    (0x20, EV_KEY::KEY_3),
+    (0x21, EV_KEY::KEY_4),
+    (0x22, EV_KEY::KEY_5),
+    (0x23, EV_KEY::KEY_6),
+    (0x24, EV_KEY::KEY_7),
+    (0x25, EV_KEY::KEY_8),
+    (0x26, EV_KEY::KEY_9),
+    (0x27, EV_KEY::KEY_0),
+    (0x28, EV_KEY::KEY_ENTER),
+    (0x29, EV_KEY::KEY_ESC),
+    (0x2a, EV_KEY::KEY_BACKSPACE),
+    (0x2b, EV_KEY::KEY_TAB),
+    (0x2c, EV_KEY::KEY_SPACE),
+    (0x2d, EV_KEY::KEY_MINUS),
+    (0x2e, EV_KEY::KEY_EQUAL),
+    (0x2f, EV_KEY::KEY_LEFTBRACE),
+    (0x30, EV_KEY::KEY_RIGHTBRACE),
+    (0x31, EV_KEY::KEY_BACKSLASH),
+    (0x33, EV_KEY::KEY_SEMICOLON),
+    (0x34, EV_KEY::KEY_APOSTROPHE),
+    (0x35, EV_KEY::KEY_GRAVE),
+    (0x36, EV_KEY::KEY_COMMA),
+    (0x37, EV_KEY::KEY_DOT),
+    (0x38, EV_KEY::KEY_SLASH),
+    (0x39, EV_KEY::KEY_CAPSLOCK),
+    (0x3a, EV_KEY::KEY_F1),
+    (0x3b, EV_KEY::KEY_F2),
+    (0x3c, EV_KEY::KEY_F3),
+    (0x3d, EV_KEY::KEY_F4),
+    (0x3e, EV_KEY::KEY_F5),
+    (0x3f, EV_KEY::KEY_F6),
+    (0x40, EV_KEY::KEY_F7),
+    (0x41, EV_KEY::KEY_F8),
+    (0x42, EV_KEY::KEY_F9),
+    (0x43, EV_KEY::KEY_F10),
+    (0x44, EV_KEY::KEY_F11),
+    (0x45, EV_KEY::KEY_F12),
+    (0x46, EV_KEY::KEY_SYSRQ),
+    (0x47, EV_KEY::KEY_SCROLLLOCK),
+    (0x48, EV_KEY::KEY_PAUSE),
+    (0x49, EV_KEY::KEY_INSERT),
+    (0x4a, EV_KEY::KEY_HOME),
+    (0x4b, EV_KEY::KEY_PAGEUP),
+    (0x4c, EV_KEY::KEY_DELETE),
+    (0x4d, EV_KEY::KEY_END),
+    (0x4e, EV_KEY::KEY_PAGEDOWN),
+    (0x4f, EV_KEY::KEY_RIGHT),
+    (0x50, EV_KEY::KEY_LEFT),
+    (0x51, EV_KEY::KEY_DOWN),
+    (0x52, EV_KEY::KEY_UP),
+    (0x53, EV_KEY::KEY_NUMLOCK),
+    (0x54, EV_KEY::KEY_KPSLASH),
+    (0x55, EV_KEY::KEY_KPASTERISK),
+    (0x56, EV_KEY::KEY_KPMINUS),
+    (0x57, EV_KEY::KEY_KPPLUS),
+    (0x58, EV_KEY::KEY_KPENTER),
+    (0x59, EV_KEY::KEY_KP1),
+    (0x5a, EV_KEY::KEY_KP2),
+    (0x5b, EV_KEY::KEY_KP3),
+    (0x5c, EV_KEY::KEY_KP4),
+    (0x5d, EV_KEY::KEY_KP5),
+    (0x5e, EV_KEY::KEY_KP6),
+    (0x5f, EV_KEY::KEY_KP7),
+    (0x60, EV_KEY::KEY_KP8),
+    (0x61, EV_KEY::KEY_KP9),
+    (0x62, EV_KEY::KEY_KP0),
+    (0x63, EV_KEY::KEY_KPDOT),
+    (0x64, EV_KEY::KEY_102ND),
+    (0x65, EV_KEY::KEY_COMPOSE),
+    (0x66, EV_KEY::KEY_POWER),
+    (0x67, EV_KEY::KEY_KPEQUAL),
+    (0x68, EV_KEY::KEY_F13),
+    (0x69, EV_KEY::KEY_F14),
+    (0x6a, EV_KEY::KEY_F15),
+    (0x6b, EV_KEY::KEY_F16),
+    (0x6c, EV_KEY::KEY_F17),
+    (0x6d, EV_KEY::KEY_F18),
+    (0x6e, EV_KEY::KEY_F19),
+    (0x6f, EV_KEY::KEY_F20),
+    (0x70, EV_KEY::KEY_F21),
+    (0x71, EV_KEY::KEY_F22),
+    (0x72, EV_KEY::KEY_F23),
+    (0x73, EV_KEY::KEY_F24),
+    (0xe0, EV_KEY::KEY_LEFTCTRL),
+    (0xe1, EV_KEY::KEY_LEFTSHIFT),
+    (0xe2, EV_KEY::KEY_LEFTALT),
+    (0xe3, EV_KEY::KEY_LEFTMETA),
+    (0xe4, EV_KEY::KEY_RIGHTCTRL),
+    (0xe5, EV_KEY::KEY_RIGHTSHIFT),
+    (0xe6, EV_KEY::KEY_RIGHTALT),
+    (0xe7, EV_KEY::KEY_RIGHTMETA),
+];
+
+/// The `MSC_SCAN` value a USB keyboard reports for `key`, if it has one.
+pub fn hid_scancode(key: EV_KEY) -> Option<u32> {
+    USAGES
+        .iter()
+        .find(|(_, usage_key)| *usage_key == key)
+        .map(|(usage, _)| KEYBOARD_PAGE | usage)
+}
+
+/// The key a USB keyboard's `MSC_SCAN` value stands for, if it is on the keyboard usage page.
+pub fn key_from_hid_scancode(scancode: u32) -> Option<EV_KEY> {
+    if scancode & !0xffff != KEYBOARD_PAGE {
+        return None;
+    }
+    USAGES
+        .iter()
+        .find(|(usage, _)| *usage == scancode & 0xffff)
+        .map(|(_, key)| *key)
+}
@@ -0,0 +1,133 @@
+use crate::Injector;
+use async_io::Timer;
+use evdev_rs::enums::EventCode;
+use evdev_rs::UInputDevice;
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+// Frames by due time and then by order of scheduling.
+type Pending = BTreeMap<(Instant, u64), Vec<(EventCode, i32)>>;
+
+/// A frame waiting in a `Scheduler`, to cancel it with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ScheduleHandle {
+    at: Instant,
+    id: u64,
+}
+
+impl ScheduleHandle {
+    /// When the frame is due.
+    pub fn at(&self) -> Instant {
+        self.at
+    }
+}
+
+/// Injects frames of events into `uinput` at given times, in order of time and, at the same
+/// time, in the order they were scheduled. Events injected into it directly are passed through
+/// right away.
+///
+/// Await `tick` alongside whatever schedules frames, or drive the scheduler with `deadline` and
+/// `flush_due`. `tick` waits for the deadline as of when it was called, so it should be called
+/// again after scheduling an earlier frame, as a `select!` loop does. It is cancel-safe.
+pub struct Scheduler<U = UInputDevice> {
+    uinput: U,
+    frames: RefCell<Pending>,
+    next_id: Cell<u64>,
+}
+
+impl<U: Injector> Scheduler<U> {
+    pub fn new(uinput: U) -> Self {
+        Self {
+            uinput,
+            frames: RefCell::new(BTreeMap::new()),
+            next_id: Cell::new(0),
+        }
+    }
+
+    pub fn uinput(&self) -> &U {
+        &self.uinput
+    }
+
+    pub fn is_idle(&self) -> bool {
+        self.frames.borrow().is_empty()
+    }
+
+    /// Whether the frame of `handle` is yet to be injected.
+    pub fn is_pending(&self, handle: ScheduleHandle) -> bool {
+        self.frames.borrow().contains_key(&(handle.at, handle.id))
+    }
+
+    /// When the next frame is due, if any is pending.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.frames.borrow().keys().next().map(|(at, _)| *at)
+    }
+
+    /// Injects `events` followed by a SYN_REPORT at `at`, or with the next `flush_due` if that's
+    /// already past.
+    pub fn emit_at(&self, at: Instant, events: &[(EventCode, i32)]) -> ScheduleHandle {
+        let id = self.next_id.get();
+        self.next_id.set(id + 1);
+        let _: Option<Vec<(EventCode, i32)>> =
+            self.frames.borrow_mut().insert((at, id), events.to_vec());
+        ScheduleHandle { at, id }
+    }
+
+    pub fn emit_after(&self, delay: Duration, events: &[(EventCode, i32)]) -> ScheduleHandle {
+        self.emit_at(Instant::now() + delay, events)
+    }
+
+    /// Drops the frame of `handle`, returning whether it was still pending.
+    pub fn cancel(&self, handle: ScheduleHandle) -> bool {
+        self.frames
+            .borrow_mut()
+            .remove(&(handle.at, handle.id))
+            .is_some()
+    }
+
+    /// Drops all pending frames.
+    pub fn cancel_all(&self) {
+        self.frames.borrow_mut().clear();
+    }
+
+    /// Injects all frames due at `now`.
+    pub fn flush_due(&self, now: Instant) -> std::io::Result<()> {
+        loop {
+            let frame = {
+                let mut frames = self.frames.borrow_mut();
+                match frames.first_entry() {
+                    Some(entry) if entry.key().0 <= now => entry.remove(),
+                    _ => return Ok(()),
+                }
+            };
+            self.uinput.emit(&frame)?;
+        }
+    }
+
+    /// Waits for the next frame to become due and injects all frames due by then. Never
+    /// completes while idle.
+    pub async fn tick(&self) -> std::io::Result<()> {
+        match self.deadline() {
+            Some(deadline) => {
+                let now = Timer::at(deadline).await;
+                self.flush_due(now)
+            }
+            None => std::future::pending().await,
+        }
+    }
+}
+
+impl<U: Injector> Injector for Scheduler<U> {
+    fn inject_event(&self, event_code: EventCode, value: i32) -> std::io::Result<()> {
+        self.uinput.inject_event(event_code, value)
+    }
+
+    fn inject_event_at(
+        &self,
+        event_code: EventCode,
+        value: i32,
+        time: evdev_rs::TimeVal,
+    ) -> std::io::Result<()> {
+        self.uinput.inject_event_at(event_code, value, time)
+    }
+}
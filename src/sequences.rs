@@ -0,0 +1,284 @@
+use crate::{Processor, ZERO_TIME};
+use evdev_rs::enums::{EventCode, EV_KEY, EV_SYN};
+use evdev_rs::{InputEvent, TimeVal};
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// Where a key sequence being typed stands, as reported to `Sequences::feedback`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SequenceState {
+    /// The keys so far start one or more sequences.
+    Pending,
+    /// The keys make up a sequence, whose action was performed.
+    Matched,
+    /// The keys don't make up a sequence and were replayed.
+    Failed,
+}
+
+type Feedback = Box<dyn FnMut(SequenceState, &[EV_KEY])>;
+
+/// Vim- or Emacs-style key sequences, e.g. a leader key followed by `g` and `s`, each tapping a
+/// series of chords when typed. While the keys typed so far start a sequence, all events are held
+/// back; once they can't complete one, or nothing more is typed within the timeout, they are
+/// replayed unchanged. A sequence which is the start of a longer one only matches on the timeout.
+pub struct Sequences {
+    sequences: Vec<(Vec<EV_KEY>, Vec<Vec<EV_KEY>>)>,
+    timeout: Duration,
+    typed: Vec<EV_KEY>,
+    buffer: Vec<InputEvent>,
+    deadline: Option<Instant>,
+    // Keys of matched sequences whose releases are still to come.
+    swallowed: HashSet<EV_KEY>,
+    feedback: Option<Feedback>,
+}
+
+impl fmt::Debug for Sequences {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Sequences")
+            .field("sequences", &self.sequences)
+            .field("timeout", &self.timeout)
+            .field("typed", &self.typed)
+            .field("buffer", &self.buffer)
+            .field("deadline", &self.deadline)
+            .field("swallowed", &self.swallowed)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for Sequences {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Sequences {
+    /// No sequences, and a timeout of one second between keys, like Vim's.
+    pub fn new() -> Self {
+        Self {
+            sequences: Vec::new(),
+            timeout: Duration::from_secs(1),
+            typed: Vec::new(),
+            buffer: Vec::new(),
+            deadline: None,
+            swallowed: HashSet::new(),
+            feedback: None,
+        }
+    }
+
+    /// Taps each chord of `action` in turn when `keys` are pressed one after another.
+    pub fn sequence(
+        mut self,
+        keys: impl IntoIterator<Item = EV_KEY>,
+        action: Vec<Vec<EV_KEY>>,
+    ) -> Self {
+        let keys = keys.into_iter().collect::<Vec<_>>();
+        if !keys.is_empty() {
+            self.sequences.push((keys, action));
+        }
+        self
+    }
+
+    /// How long to wait for the next key of a sequence.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Calls `feedback` with the keys typed so far whenever a sequence progresses, matches or
+    /// fails, e.g. to show them on screen. Progress is also logged.
+    pub fn feedback(mut self, feedback: impl FnMut(SequenceState, &[EV_KEY]) + 'static) -> Self {
+        self.feedback = Some(Box::new(feedback));
+        self
+    }
+
+    fn report(&mut self, state: SequenceState) {
+        match state {
+            SequenceState::Pending => log::debug!("sequence so far: {:?}", self.typed),
+            SequenceState::Matched => log::info!("sequence {:?} matched", self.typed),
+            SequenceState::Failed => log::debug!("no sequence starts with {:?}", self.typed),
+        }
+        if let Some(feedback) = &mut self.feedback {
+            feedback(state, &self.typed);
+        }
+    }
+
+    fn exact(&self, keys: &[EV_KEY]) -> Option<usize> {
+        self.sequences
+            .iter()
+            .position(|(sequence, _)| sequence.as_slice() == keys)
+    }
+
+    fn extendable(&self, keys: &[EV_KEY]) -> bool {
+        self.sequences
+            .iter()
+            .any(|(sequence, _)| sequence.len() > keys.len() && sequence.starts_with(keys))
+    }
+
+    fn trigger(&mut self, index: usize, time: TimeVal, out: &mut VecDeque<InputEvent>) {
+        self.report(SequenceState::Matched);
+        // Events of the sequence's keys are dropped, and their releases still to come too, but
+        // everything else held back meanwhile is passed on.
+        let mut pressed = HashSet::new();
+        for event in self.buffer.drain(..) {
+            match event.event_code {
+                EventCode::EV_KEY(key) if event.value == 1 => {
+                    let _: bool = pressed.insert(key);
+                }
+                EventCode::EV_KEY(key) if pressed.contains(&key) => {
+                    if event.value == 0 {
+                        let _: bool = pressed.remove(&key);
+                    }
+                }
+                EventCode::EV_MSC(_) => {}
+                _ => out.push_back(event),
+            }
+        }
+        self.swallowed.extend(pressed);
+        for keys in &self.sequences[index].1 {
+            for (value, keys) in [(1, keys.clone()), (0, keys.iter().rev().copied().collect())] {
+                for key in keys {
+                    out.push_back(InputEvent {
+                        time,
+                        event_code: EventCode::EV_KEY(key),
+                        value,
+                    });
+                }
+                out.push_back(InputEvent {
+                    time,
+                    event_code: EventCode::EV_SYN(EV_SYN::SYN_REPORT),
+                    value: 0,
+                });
+            }
+        }
+        self.typed.clear();
+        self.deadline = None;
+    }
+
+    fn fail(&mut self, out: &mut VecDeque<InputEvent>) {
+        self.report(SequenceState::Failed);
+        out.extend(self.buffer.drain(..));
+        self.typed.clear();
+        self.deadline = None;
+    }
+
+    fn press(
+        &mut self,
+        event: InputEvent,
+        key: EV_KEY,
+        now: Instant,
+        out: &mut VecDeque<InputEvent>,
+    ) {
+        let time = event.time;
+        self.typed.push(key);
+        self.buffer.push(event);
+        if self.extendable(&self.typed) {
+            self.deadline = Some(now + self.timeout);
+            self.report(SequenceState::Pending);
+        } else if let Some(index) = self.exact(&self.typed) {
+            self.trigger(index, time, out);
+        } else if let Some(event) = self.buffer.pop() {
+            if self.typed.len() > 1 {
+                // Replay the prefix, then see whether the key starts a sequence of its own.
+                self.fail(out);
+                self.press(event, key, now, out);
+            } else {
+                self.typed.clear();
+                out.push_back(event);
+            }
+        }
+    }
+}
+
+impl Processor for Sequences {
+    type Output = InputEvent;
+
+    fn process(&mut self, event: InputEvent, now: Instant, out: &mut VecDeque<InputEvent>) {
+        let key = match event.event_code {
+            EventCode::EV_KEY(key) => key,
+            _ if self.deadline.is_some() => return self.buffer.push(event),
+            _ => return out.push_back(event),
+        };
+        match event.value {
+            1 => self.press(event, key, now, out),
+            _ if self.swallowed.contains(&key) => {
+                if event.value == 0 {
+                    let _: bool = self.swallowed.remove(&key);
+                }
+            }
+            _ if self.deadline.is_some() => self.buffer.push(event),
+            _ => out.push_back(event),
+        }
+    }
+
+    fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    fn timeout(&mut self, _now: Instant, out: &mut VecDeque<InputEvent>) {
+        match self.exact(&self.typed) {
+            Some(index) => self.trigger(index, ZERO_TIME, out),
+            None => self.fail(out),
+        }
+    }
+
+    fn finish(&mut self, out: &mut VecDeque<InputEvent>) {
+        if self.deadline.is_some() {
+            self.timeout(Instant::now(), out);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{key, keys, run};
+    use evdev_rs::enums::EV_KEY::{KEY_A, KEY_G, KEY_HOME, KEY_LEFTCTRL, KEY_RIGHTALT, KEY_S};
+
+    fn sequences() -> Sequences {
+        Sequences::new()
+            .timeout(Duration::from_millis(50))
+            .sequence(
+                vec![KEY_RIGHTALT, KEY_G, KEY_S],
+                vec![vec![KEY_LEFTCTRL, KEY_S]],
+            )
+            .sequence(vec![KEY_RIGHTALT, KEY_G], vec![vec![KEY_HOME]])
+    }
+
+    fn tap(ms: u64, tapped: EV_KEY) -> Vec<InputEvent> {
+        vec![key(ms, tapped, 1), key(ms + 5, tapped, 0)]
+    }
+
+    #[test]
+    fn matches_sequence() {
+        let events = [tap(0, KEY_RIGHTALT), tap(10, KEY_G), tap(20, KEY_S)].concat();
+        assert_eq!(
+            keys(&run(events, sequences())),
+            vec![(KEY_LEFTCTRL, 1), (KEY_S, 1), (KEY_S, 0), (KEY_LEFTCTRL, 0)]
+        );
+    }
+
+    #[test]
+    fn prefix_of_longer_sequence_matches_on_timeout() {
+        let events = [tap(0, KEY_RIGHTALT), tap(10, KEY_G), tap(200, KEY_A)].concat();
+        assert_eq!(
+            keys(&run(events, sequences())),
+            vec![(KEY_HOME, 1), (KEY_HOME, 0), (KEY_A, 1), (KEY_A, 0)]
+        );
+    }
+
+    #[test]
+    fn replays_keys_not_making_up_a_sequence() {
+        let events = [tap(0, KEY_RIGHTALT), tap(10, KEY_A)].concat();
+        assert_eq!(
+            keys(&run(events, sequences())),
+            vec![(KEY_RIGHTALT, 1), (KEY_RIGHTALT, 0), (KEY_A, 1), (KEY_A, 0)]
+        );
+    }
+
+    #[test]
+    fn forwards_release_of_key_held_at_start() {
+        let output = run(vec![key(0, KEY_G, 0)], sequences());
+        assert_eq!(keys(&output), vec![(KEY_G, 0)]);
+    }
+}
@@ -0,0 +1,297 @@
+//! Statistics over input events: per-device event counts, how often each key is pressed, and
+//! polling rate estimates, aggregated over fixed windows of event time, e.g. for ergonomics
+//! analysis or to check a device reports at the rate it claims.
+//!
+//! `KeyCounter` keeps per-key totals from within a running pipeline instead, exportable as CSV
+//! or JSON.
+
+use crate::macros::time_since;
+use crate::{CodeName, Processor};
+use evdev_rs::enums::{EventCode, EV_KEY, EV_SYN};
+use evdev_rs::{InputEvent, TimeVal};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+/// Frames further apart than this belong to separate bursts of activity, so their interval says
+/// nothing about the polling rate.
+const MAX_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The statistics of one device over a window.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DeviceStats {
+    pub events: u64,
+    /// Event counts by event type, e.g. `EV_KEY`.
+    pub by_type: BTreeMap<String, u64>,
+    /// Press counts by key, a heatmap of the keyboard's use.
+    pub key_presses: BTreeMap<String, u64>,
+    pub frames: u64,
+    /// Frames per second while the device is active, from the median interval between
+    /// consecutive frames.
+    pub polling_rate: Option<f64>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StatsReport {
+    /// The event time spanned, from the window's first event to its last.
+    pub duration: Duration,
+    pub devices: BTreeMap<PathBuf, DeviceStats>,
+}
+
+#[derive(Debug, Default)]
+struct DeviceState {
+    stats: DeviceStats,
+    last_frame: Option<TimeVal>,
+    intervals: Vec<Duration>,
+}
+
+impl DeviceState {
+    fn record(&mut self, event: &InputEvent) {
+        self.stats.events += 1;
+        if let Some(event_type) = event.event_type() {
+            *self
+                .stats
+                .by_type
+                .entry(event_type.to_string())
+                .or_default() += 1;
+        }
+        match event.event_code {
+            EventCode::EV_KEY(_) if event.value == 1 => {
+                *self
+                    .stats
+                    .key_presses
+                    .entry(CodeName(&event.event_code).to_string())
+                    .or_default() += 1;
+            }
+            EventCode::EV_SYN(EV_SYN::SYN_REPORT) => {
+                self.stats.frames += 1;
+                if let Some(last_frame) = self.last_frame {
+                    let interval = time_since(&last_frame, &event.time);
+                    if !interval.is_zero() && interval <= MAX_POLL_INTERVAL {
+                        self.intervals.push(interval);
+                    }
+                }
+                self.last_frame = Some(event.time);
+            }
+            _ => {}
+        }
+    }
+
+    fn stats(&self) -> DeviceStats {
+        let mut intervals = self.intervals.clone();
+        intervals.sort();
+        DeviceStats {
+            polling_rate: intervals
+                .get(intervals.len() / 2)
+                .map(|median| 1.0 / median.as_secs_f64()),
+            ..self.stats.clone()
+        }
+    }
+}
+
+/// Aggregates events from any number of devices into a report per `window` of event time.
+#[derive(Debug)]
+pub struct StatsCollector {
+    window: Option<Duration>,
+    start: Option<TimeVal>,
+    last: Option<TimeVal>,
+    devices: HashMap<PathBuf, DeviceState>,
+}
+
+impl StatsCollector {
+    /// Reports every `window`, or only when asked to with `None`.
+    pub fn new(window: Option<Duration>) -> Self {
+        Self {
+            window,
+            start: None,
+            last: None,
+            devices: HashMap::new(),
+        }
+    }
+
+    /// Records an event from the device at `path`. When the event falls past the current window,
+    /// returns the window's report and starts the next window with it.
+    pub fn record(&mut self, path: &Path, event: &InputEvent) -> Option<StatsReport> {
+        let report = match (self.window, self.start) {
+            (Some(window), Some(start)) if time_since(&start, &event.time) >= window => {
+                Some(self.take())
+            }
+            _ => None,
+        };
+        if self.start.is_none() {
+            self.start = Some(event.time);
+        }
+        self.last = Some(event.time);
+        self.devices
+            .entry(path.to_path_buf())
+            .or_default()
+            .record(event);
+        report
+    }
+
+    /// The report of the current window so far.
+    pub fn report(&self) -> StatsReport {
+        StatsReport {
+            duration: match (self.start, self.last) {
+                (Some(start), Some(last)) => time_since(&start, &last),
+                _ => Duration::ZERO,
+            },
+            devices: self
+                .devices
+                .iter()
+                .map(|(path, device)| (path.clone(), device.stats()))
+                .collect(),
+        }
+    }
+
+    /// The report of the current window so far, starting a new one.
+    pub fn take(&mut self) -> StatsReport {
+        let report = self.report();
+        self.start = None;
+        self.last = None;
+        self.devices.clear();
+        report
+    }
+}
+
+/// How one key was used.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct KeyCount {
+    pub presses: u64,
+    /// Autorepeats while the key was held.
+    pub repeats: u64,
+    /// How long the key was held down in total, by event time.
+    pub held: Duration,
+}
+
+/// Per-key counts, a heatmap of the keyboard's use. Only totals per key are kept, not the order
+/// keys were pressed in, so what was typed can't be recovered from it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KeyHeatmap {
+    pub keys: BTreeMap<String, KeyCount>,
+}
+
+impl KeyHeatmap {
+    pub fn total_presses(&self) -> u64 {
+        self.keys.values().map(|count| count.presses).sum()
+    }
+
+    /// Writes a `key,presses,repeats,held_ms` header and a line per key.
+    pub fn write_csv<W: Write>(&self, mut writer: W) -> std::io::Result<()> {
+        writeln!(writer, "key,presses,repeats,held_ms")?;
+        for (key, count) in &self.keys {
+            writeln!(
+                writer,
+                "{},{},{},{}",
+                key,
+                count.presses,
+                count.repeats,
+                count.held.as_millis()
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Writes an object mapping each key to its `presses`, `repeats` and `held_ms`.
+    pub fn write_json<W: Write>(&self, mut writer: W) -> std::io::Result<()> {
+        write!(writer, "{{")?;
+        for (i, (key, count)) in self.keys.iter().enumerate() {
+            write!(
+                writer,
+                "{}{:?}:{{\"presses\":{},\"repeats\":{},\"held_ms\":{}}}",
+                if i == 0 { "" } else { "," },
+                key,
+                count.presses,
+                count.repeats,
+                count.held.as_millis()
+            )?;
+        }
+        writeln!(writer, "}}")
+    }
+}
+
+#[derive(Debug, Default)]
+struct KeyCounts {
+    counts: HashMap<EV_KEY, KeyCount>,
+    // When each key held down was pressed.
+    pressed: HashMap<EV_KEY, TimeVal>,
+}
+
+/// A handle on the counts of a `KeyCounter`, to read or reset them while the counter runs in a
+/// pipeline.
+#[derive(Debug, Clone)]
+pub struct KeyCounterHandle(Rc<RefCell<KeyCounts>>);
+
+impl KeyCounterHandle {
+    pub fn snapshot(&self) -> KeyHeatmap {
+        KeyHeatmap {
+            keys: self
+                .0
+                .borrow()
+                .counts
+                .iter()
+                .map(|(key, count)| (CodeName(&EventCode::EV_KEY(*key)).to_string(), *count))
+                .collect(),
+        }
+    }
+
+    /// Starts counting from zero. Keys held down meanwhile count their whole hold when
+    /// released.
+    pub fn reset(&self) {
+        self.0.borrow_mut().counts.clear();
+    }
+
+    /// The counts so far, starting from zero.
+    pub fn take(&self) -> KeyHeatmap {
+        let heatmap = self.snapshot();
+        self.reset();
+        heatmap
+    }
+}
+
+/// Passes events through unchanged while counting the presses, autorepeats and hold time of
+/// every key, for typing ergonomics analysis. Nothing is counted unless a pipeline runs one;
+/// read the counts through `handle`.
+#[derive(Debug, Default)]
+pub struct KeyCounter {
+    counts: Rc<RefCell<KeyCounts>>,
+}
+
+impl KeyCounter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn handle(&self) -> KeyCounterHandle {
+        KeyCounterHandle(Rc::clone(&self.counts))
+    }
+}
+
+impl Processor for KeyCounter {
+    type Output = InputEvent;
+
+    fn process(&mut self, event: InputEvent, _now: Instant, out: &mut VecDeque<InputEvent>) {
+        if let EventCode::EV_KEY(key) = event.event_code {
+            let KeyCounts { counts, pressed } = &mut *self.counts.borrow_mut();
+            match event.value {
+                // Keys already held when counting started are left out.
+                0 => {
+                    if let Some(press) = pressed.remove(&key) {
+                        counts.entry(key).or_default().held += time_since(&press, &event.time);
+                    }
+                }
+                1 => {
+                    counts.entry(key).or_default().presses += 1;
+                    let _: Option<TimeVal> = pressed.insert(key, event.time);
+                }
+                _ => counts.entry(key).or_default().repeats += 1,
+            }
+        }
+        out.push_back(event);
+    }
+}
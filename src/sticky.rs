@@ -0,0 +1,270 @@
+use crate::{Modifiers, Processor};
+use evdev_rs::enums::{EventCode, EV_KEY, EV_SYN};
+use evdev_rs::InputEvent;
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+/// What releasing a modifier that was pressed on its own does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OnTap {
+    Latch,
+    Lock,
+    Release,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Down { used: bool, on_tap: OnTap },
+    Latched { at: Instant },
+    Locked,
+}
+
+/// Sticky modifiers: tapping a modifier on its own latches it, so it applies to the next key
+/// press. Tapping it again within the lock window locks it until it's tapped once more, and
+/// tapping a latched modifier later cancels it. Modifiers held while other keys are pressed act
+/// as usual.
+///
+/// Latched and locked modifiers stay pressed on the output, so this composes with processors
+/// and remappers downstream like a physical held modifier would.
+#[derive(Debug)]
+pub struct StickyKeys {
+    lock_window: Option<Duration>,
+    states: HashMap<EV_KEY, State>,
+    // Set once a key is pressed while modifiers are latched, to release them after its frame.
+    release_latched: bool,
+}
+
+impl Default for StickyKeys {
+    fn default() -> Self {
+        Self {
+            lock_window: Some(Duration::from_millis(500)),
+            states: HashMap::new(),
+            release_latched: false,
+        }
+    }
+}
+
+impl StickyKeys {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// How soon a second tap has to follow the first to lock a modifier. `None` disables
+    /// locking.
+    pub fn lock_window(mut self, lock_window: Option<Duration>) -> Self {
+        self.lock_window = lock_window;
+        self
+    }
+
+    /// The modifiers latched for the next key press, e.g. for an on-screen indicator.
+    pub fn latched(&self) -> Modifiers {
+        self.matching(|state| matches!(state, State::Latched { .. }))
+    }
+
+    pub fn locked(&self) -> Modifiers {
+        self.matching(|state| *state == State::Locked)
+    }
+
+    fn matching(&self, predicate: impl Fn(&State) -> bool) -> Modifiers {
+        Modifiers::from_keys(
+            self.states
+                .iter()
+                .filter(|(_, state)| predicate(state))
+                .map(|(key, _)| key),
+        )
+    }
+
+    fn modifier(&mut self, input: InputEvent, key: EV_KEY, now: Instant) -> Option<InputEvent> {
+        let state = self.states.get(&key).copied();
+        match (input.value, state) {
+            (1, None) => {
+                let _: Option<State> = self.states.insert(
+                    key,
+                    State::Down {
+                        used: false,
+                        on_tap: OnTap::Latch,
+                    },
+                );
+                Some(input)
+            }
+            // Already pressed on the output, so the press is swallowed.
+            (1, Some(State::Latched { at })) => {
+                let lock = self
+                    .lock_window
+                    .is_some_and(|window| now.saturating_duration_since(at) <= window);
+                let on_tap = if lock { OnTap::Lock } else { OnTap::Release };
+                let _: Option<State> = self.states.insert(
+                    key,
+                    State::Down {
+                        used: false,
+                        on_tap,
+                    },
+                );
+                None
+            }
+            (1, Some(State::Locked)) => {
+                let _: Option<State> = self.states.insert(
+                    key,
+                    State::Down {
+                        used: false,
+                        on_tap: OnTap::Release,
+                    },
+                );
+                None
+            }
+            (0, Some(State::Down { used, on_tap })) => {
+                let next = match on_tap {
+                    _ if used => None,
+                    OnTap::Latch => Some(State::Latched { at: now }),
+                    OnTap::Lock => Some(State::Locked),
+                    OnTap::Release => None,
+                };
+                match next {
+                    Some(next) => {
+                        let _: Option<State> = self.states.insert(key, next);
+                        None
+                    }
+                    None => {
+                        let _: Option<State> = self.states.remove(&key);
+                        Some(input)
+                    }
+                }
+            }
+            (2, Some(State::Down { .. })) => Some(input),
+            (2, _) => None,
+            _ => Some(input),
+        }
+    }
+}
+
+impl Processor for StickyKeys {
+    type Output = InputEvent;
+
+    fn process(&mut self, input: InputEvent, now: Instant, out: &mut VecDeque<InputEvent>) {
+        match input.event_code {
+            EventCode::EV_KEY(key) if Modifiers::from_key(key).is_some() => {
+                out.extend(self.modifier(input, key, now));
+            }
+            EventCode::EV_KEY(_) => {
+                if input.value == 1 {
+                    for state in self.states.values_mut() {
+                        match state {
+                            State::Down { used, .. } => *used = true,
+                            State::Latched { .. } => self.release_latched = true,
+                            State::Locked => {}
+                        }
+                    }
+                }
+                out.push_back(input);
+            }
+            EventCode::EV_SYN(EV_SYN::SYN_REPORT) => {
+                out.push_back(input.clone());
+                if std::mem::replace(&mut self.release_latched, false) {
+                    let latched = self
+                        .states
+                        .iter()
+                        .filter(|(_, state)| matches!(state, State::Latched { .. }))
+                        .map(|(key, _)| *key)
+                        .collect::<Vec<_>>();
+                    for key in latched {
+                        let _: Option<State> = self.states.remove(&key);
+                        out.push_back(InputEvent {
+                            time: input.time,
+                            event_code: EventCode::EV_KEY(key),
+                            value: 0,
+                        });
+                    }
+                    out.push_back(input);
+                }
+            }
+            _ => out.push_back(input),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{key, keys, run, syn};
+    use evdev_rs::enums::EV_KEY::{KEY_A, KEY_LEFTSHIFT};
+
+    #[test]
+    fn latched_modifier_applies_to_next_key() {
+        let output = run(
+            vec![
+                key(0, KEY_LEFTSHIFT, 1),
+                syn(0),
+                key(10, KEY_LEFTSHIFT, 0),
+                syn(10),
+                key(1000, KEY_A, 1),
+                syn(1000),
+                key(1010, KEY_A, 0),
+                syn(1010),
+            ],
+            StickyKeys::new(),
+        );
+        assert_eq!(
+            keys(&output),
+            vec![
+                (KEY_LEFTSHIFT, 1),
+                (KEY_A, 1),
+                (KEY_LEFTSHIFT, 0),
+                (KEY_A, 0)
+            ]
+        );
+    }
+
+    #[test]
+    fn double_tap_locks_modifier() {
+        let mut events = vec![];
+        for (i, ms) in [0, 100, 1000].iter().enumerate() {
+            events.push(key(*ms, KEY_LEFTSHIFT, 1));
+            events.push(key(*ms + 10, KEY_LEFTSHIFT, 0));
+            if i == 1 {
+                events.push(key(500, KEY_A, 1));
+                events.push(syn(500));
+                events.push(key(510, KEY_A, 0));
+                events.push(syn(510));
+            }
+        }
+        let output = run(events, StickyKeys::new());
+        // Locked across the key press, then released by the third tap.
+        assert_eq!(
+            keys(&output),
+            vec![
+                (KEY_LEFTSHIFT, 1),
+                (KEY_A, 1),
+                (KEY_A, 0),
+                (KEY_LEFTSHIFT, 0)
+            ]
+        );
+    }
+
+    #[test]
+    fn held_modifier_acts_as_usual() {
+        let output = run(
+            vec![
+                key(0, KEY_LEFTSHIFT, 1),
+                key(10, KEY_A, 1),
+                key(20, KEY_A, 0),
+                key(30, KEY_LEFTSHIFT, 0),
+            ],
+            StickyKeys::new(),
+        );
+        assert_eq!(
+            keys(&output),
+            vec![
+                (KEY_LEFTSHIFT, 1),
+                (KEY_A, 1),
+                (KEY_A, 0),
+                (KEY_LEFTSHIFT, 0)
+            ]
+        );
+    }
+
+    #[test]
+    fn releases_modifier_held_at_start() {
+        let output = run(vec![key(0, KEY_LEFTSHIFT, 0)], StickyKeys::new());
+        assert_eq!(keys(&output), vec![(KEY_LEFTSHIFT, 0)]);
+    }
+}
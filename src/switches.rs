@@ -0,0 +1,87 @@
+use crate::{AsyncDevice, DeviceError, IdentifyError, OpenError};
+use evdev_rs::enums::{EventCode, EventType, EV_SW};
+use evdev_rs::DeviceWrapper;
+use futures::{Stream, StreamExt as _};
+use std::path::PathBuf;
+
+/// A switch such as the lid, tablet mode or headphone insertion changing, or its state when
+/// queried.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SwitchEvent {
+    /// The device reporting the switch.
+    pub path: PathBuf,
+    pub switch: EV_SW,
+    pub state: bool,
+}
+
+fn switches<D: DeviceWrapper + ?Sized>(device: &D) -> Vec<(EV_SW, bool)> {
+    EventCode::EV_SW(EV_SW::SW_LID)
+        .iter()
+        .take_while(|code| matches!(code, EventCode::EV_SW(_)))
+        .filter_map(|code| match code {
+            EventCode::EV_SW(switch) => device.event_value(&code).map(|value| (switch, value != 0)),
+            _ => None,
+        })
+        .collect()
+}
+
+impl AsyncDevice {
+    /// The state of `switch` as of the last event read from the device, or `None` if the device
+    /// doesn't have it.
+    pub fn switch_state(&self, switch: EV_SW) -> Option<bool> {
+        self.evdev()
+            .event_value(&EventCode::EV_SW(switch))
+            .map(|value| value != 0)
+    }
+
+    /// The states of all switches the device has.
+    pub fn switches(&self) -> Vec<(EV_SW, bool)> {
+        switches(self.evdev())
+    }
+}
+
+/// Returns the current state of every switch on every device, without waiting for any input.
+/// Devices that can't be opened are left out.
+pub fn switch_states() -> Result<Vec<SwitchEvent>, IdentifyError> {
+    Ok(crate::open_available()?
+        .into_iter()
+        .flat_map(|(path, device)| {
+            switches(&device)
+                .into_iter()
+                .map(move |(switch, state)| SwitchEvent {
+                    path: path.clone(),
+                    switch,
+                    state,
+                })
+        })
+        .collect())
+}
+
+/// Combined stream of switch changes over all devices with switches. Other events are dropped.
+/// Use `switch_states` for the state before the first change. Devices that can't be opened are
+/// left out.
+pub fn watch_switches(
+) -> Result<impl Stream<Item = Result<SwitchEvent, DeviceError>>, IdentifyError> {
+    let mut devices = futures::stream::SelectAll::new();
+    for (path, device) in crate::open_available()? {
+        if device.has(&EventType::EV_SW) {
+            let device = AsyncDevice::from_device(device)
+                .map_err(|e| IdentifyError::AsyncDeviceNew(OpenError::Init(e)))?;
+            devices.push(device.filter_map(move |event| {
+                let event = match event {
+                    Ok(event) => match event.event_code {
+                        EventCode::EV_SW(switch) => Some(Ok(SwitchEvent {
+                            path: path.clone(),
+                            switch,
+                            state: event.value != 0,
+                        })),
+                        _ => None,
+                    },
+                    Err(e) => Some(Err(e)),
+                };
+                futures::future::ready(event)
+            }));
+        }
+    }
+    Ok(devices)
+}
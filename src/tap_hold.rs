@@ -0,0 +1,245 @@
+use crate::{event, Processor};
+use evdev_rs::enums::{EventCode, EV_KEY, EV_SYN};
+use evdev_rs::{InputEvent, TimeVal};
+use std::collections::{HashSet, VecDeque};
+use std::time::{Duration, Instant};
+
+/// How a tap-hold key resolves when other keys are used before it is released or times out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interrupt {
+    /// Only release or timeout decide; other keys are held back until then.
+    TapPreferred,
+    /// Another key being pressed resolves to hold immediately.
+    HoldOnOtherKeyPress,
+    /// Another key being pressed and released resolves to hold immediately.
+    PermissiveHold,
+}
+
+#[derive(Debug)]
+enum State {
+    Idle,
+    Pending {
+        deadline: Instant,
+        time: TimeVal,
+        pressed: HashSet<EV_KEY>,
+    },
+    Holding,
+}
+
+/// Turns a key into `tap` when tapped and `hold` while held.
+pub struct TapHold {
+    key: EV_KEY,
+    tap: EV_KEY,
+    hold: EV_KEY,
+    timeout: Duration,
+    interrupt: Interrupt,
+    state: State,
+    buffer: Vec<InputEvent>,
+}
+
+fn syn(time: TimeVal) -> InputEvent {
+    event(time, EventCode::EV_SYN(EV_SYN::SYN_REPORT), 0)
+}
+
+impl TapHold {
+    pub fn new(key: EV_KEY, tap: EV_KEY, hold: EV_KEY, timeout: Duration) -> Self {
+        Self {
+            key,
+            tap,
+            hold,
+            timeout,
+            interrupt: Interrupt::TapPreferred,
+            state: State::Idle,
+            buffer: Vec::new(),
+        }
+    }
+
+    pub fn interrupt(mut self, interrupt: Interrupt) -> Self {
+        self.interrupt = interrupt;
+        self
+    }
+
+    fn resolve_hold(&mut self, time: TimeVal, out: &mut VecDeque<InputEvent>) {
+        out.push_back(event(time, EventCode::EV_KEY(self.hold), 1));
+        out.push_back(syn(time));
+        out.extend(self.buffer.drain(..));
+        self.state = State::Holding;
+    }
+}
+
+impl Processor for TapHold {
+    type Output = InputEvent;
+
+    fn process(&mut self, input: InputEvent, now: Instant, out: &mut VecDeque<InputEvent>) {
+        let key = match input.event_code {
+            EventCode::EV_KEY(key) => Some(key),
+            _ => None,
+        };
+        match &mut self.state {
+            State::Idle => {
+                if key == Some(self.key) && input.value == 1 {
+                    self.state = State::Pending {
+                        deadline: now + self.timeout,
+                        time: input.time,
+                        pressed: HashSet::new(),
+                    };
+                } else {
+                    out.push_back(input);
+                }
+            }
+            State::Pending { time, pressed, .. } => {
+                let time = *time;
+                match (key, input.value) {
+                    (Some(key), 0) if key == self.key => {
+                        out.push_back(event(time, EventCode::EV_KEY(self.tap), 1));
+                        out.push_back(syn(time));
+                        out.extend(self.buffer.drain(..));
+                        out.push_back(event(input.time, EventCode::EV_KEY(self.tap), 0));
+                        self.state = State::Idle;
+                    }
+                    (Some(key), _) if key == self.key => {}
+                    (Some(other), 1) => {
+                        let _: bool = pressed.insert(other);
+                        self.buffer.push(input);
+                        if self.interrupt == Interrupt::HoldOnOtherKeyPress {
+                            self.resolve_hold(time, out);
+                        }
+                    }
+                    (Some(other), 0) => {
+                        let interrupted = pressed.contains(&other);
+                        self.buffer.push(input);
+                        if interrupted && self.interrupt == Interrupt::PermissiveHold {
+                            self.resolve_hold(time, out);
+                        }
+                    }
+                    _ => self.buffer.push(input),
+                }
+            }
+            State::Holding => match key {
+                Some(key) if key == self.key => {
+                    out.push_back(event(input.time, EventCode::EV_KEY(self.hold), input.value));
+                    if input.value == 0 {
+                        self.state = State::Idle;
+                    }
+                }
+                _ => out.push_back(input),
+            },
+        }
+    }
+
+    fn deadline(&self) -> Option<Instant> {
+        match self.state {
+            State::Pending { deadline, .. } => Some(deadline),
+            State::Idle | State::Holding => None,
+        }
+    }
+
+    fn timeout(&mut self, _now: Instant, out: &mut VecDeque<InputEvent>) {
+        if let State::Pending { time, .. } = self.state {
+            self.resolve_hold(time, out);
+        }
+    }
+
+    fn finish(&mut self, out: &mut VecDeque<InputEvent>) {
+        out.extend(self.buffer.drain(..));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{key, keys, run};
+    use evdev_rs::enums::EV_KEY::{KEY_A, KEY_CAPSLOCK, KEY_ESC, KEY_LEFTCTRL};
+
+    const TIMEOUT: Duration = Duration::from_millis(50);
+
+    fn tap_hold(interrupt: Interrupt) -> TapHold {
+        TapHold::new(KEY_CAPSLOCK, KEY_ESC, KEY_LEFTCTRL, TIMEOUT).interrupt(interrupt)
+    }
+
+    #[test]
+    fn tap() {
+        let output = run(
+            vec![key(0, KEY_CAPSLOCK, 1), key(10, KEY_CAPSLOCK, 0)],
+            tap_hold(Interrupt::TapPreferred),
+        );
+        assert_eq!(keys(&output), vec![(KEY_ESC, 1), (KEY_ESC, 0)]);
+    }
+
+    #[test]
+    fn hold_after_timeout() {
+        let output = run(
+            vec![
+                key(0, KEY_CAPSLOCK, 1),
+                key(100, KEY_A, 1),
+                key(110, KEY_A, 0),
+                key(120, KEY_CAPSLOCK, 0),
+            ],
+            tap_hold(Interrupt::TapPreferred),
+        );
+        assert_eq!(
+            keys(&output),
+            vec![(KEY_LEFTCTRL, 1), (KEY_A, 1), (KEY_A, 0), (KEY_LEFTCTRL, 0)]
+        );
+    }
+
+    #[test]
+    fn tap_preferred_holds_back_other_keys() {
+        let output = run(
+            vec![
+                key(0, KEY_CAPSLOCK, 1),
+                key(10, KEY_A, 1),
+                key(20, KEY_A, 0),
+                key(30, KEY_CAPSLOCK, 0),
+            ],
+            tap_hold(Interrupt::TapPreferred),
+        );
+        assert_eq!(
+            keys(&output),
+            vec![(KEY_ESC, 1), (KEY_A, 1), (KEY_A, 0), (KEY_ESC, 0)]
+        );
+    }
+
+    #[test]
+    fn hold_on_other_key_press() {
+        let output = run(
+            vec![
+                key(0, KEY_CAPSLOCK, 1),
+                key(10, KEY_A, 1),
+                key(20, KEY_CAPSLOCK, 0),
+                key(30, KEY_A, 0),
+            ],
+            tap_hold(Interrupt::HoldOnOtherKeyPress),
+        );
+        assert_eq!(
+            keys(&output),
+            vec![(KEY_LEFTCTRL, 1), (KEY_A, 1), (KEY_LEFTCTRL, 0), (KEY_A, 0)]
+        );
+    }
+
+    #[test]
+    fn permissive_hold() {
+        let output = run(
+            vec![
+                key(0, KEY_CAPSLOCK, 1),
+                key(10, KEY_A, 1),
+                key(20, KEY_A, 0),
+                key(30, KEY_CAPSLOCK, 0),
+            ],
+            tap_hold(Interrupt::PermissiveHold),
+        );
+        assert_eq!(
+            keys(&output),
+            vec![(KEY_LEFTCTRL, 1), (KEY_A, 1), (KEY_A, 0), (KEY_LEFTCTRL, 0)]
+        );
+    }
+
+    #[test]
+    fn passes_release_of_key_held_at_start() {
+        let output = run(
+            vec![key(0, KEY_CAPSLOCK, 0)],
+            tap_hold(Interrupt::TapPreferred),
+        );
+        assert_eq!(keys(&output), vec![(KEY_CAPSLOCK, 0)]);
+    }
+}
@@ -0,0 +1,52 @@
+//! Helpers for driving processors from a `MockDevice` in tests.
+
+use crate::{EventStreamExt as _, MockDevice, Processor};
+use evdev_rs::enums::{EventCode, EV_KEY, EV_SYN};
+use evdev_rs::{InputEvent, TimeVal};
+use futures::TryStreamExt as _;
+
+fn time(ms: u64) -> TimeVal {
+    TimeVal {
+        tv_sec: (ms / 1000) as i64,
+        tv_usec: (ms % 1000 * 1000) as i64,
+    }
+}
+
+/// A key event at `ms` milliseconds.
+pub(crate) fn key(ms: u64, key: EV_KEY, value: i32) -> InputEvent {
+    InputEvent {
+        time: time(ms),
+        event_code: EventCode::EV_KEY(key),
+        value,
+    }
+}
+
+pub(crate) fn syn(ms: u64) -> InputEvent {
+    InputEvent {
+        time: time(ms),
+        event_code: EventCode::EV_SYN(EV_SYN::SYN_REPORT),
+        value: 0,
+    }
+}
+
+/// Runs `events` through `processor`, paced by their timestamps so that timeouts fire in
+/// between, and collects the output.
+pub(crate) fn run<P>(events: Vec<InputEvent>, processor: P) -> Vec<P::Output>
+where
+    P: Processor + Unpin,
+    P::Output: Unpin,
+{
+    futures::executor::block_on(MockDevice::paced(events).process(processor).try_collect())
+        .expect("mock devices don't fail")
+}
+
+/// The key events among `events`, as `(key, value)`.
+pub(crate) fn keys(events: &[InputEvent]) -> Vec<(EV_KEY, i32)> {
+    events
+        .iter()
+        .filter_map(|event| match event.event_code {
+            EventCode::EV_KEY(key) => Some((key, event.value)),
+            _ => None,
+        })
+        .collect()
+}
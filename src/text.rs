@@ -0,0 +1,169 @@
+use evdev_rs::enums::{EventCode, EV_KEY};
+
+/// The key producing `c` on a US layout and whether shift needs to be held for it.
+fn us_key(c: char) -> Option<(EV_KEY, bool)> {
+    let shift = c.is_ascii_uppercase();
+    let key = match c.to_ascii_lowercase() {
+        'a' => EV_KEY::KEY_A,
+        'b' => EV_KEY::KEY_B,
+        'c' => EV_KEY::KEY_C,
+        'd' => EV_KEY::KEY_D,
+        'e' => EV_KEY::KEY_E,
+        'f' => EV_KEY::KEY_F,
+        'g' => EV_KEY::KEY_G,
+        'h' => EV_KEY::KEY_H,
+        'i' => EV_KEY::KEY_I,
+        'j' => EV_KEY::KEY_J,
+        'k' => EV_KEY::KEY_K,
+        'l' => EV_KEY::KEY_L,
+        'm' => EV_KEY::KEY_M,
+        'n' => EV_KEY::KEY_N,
+        'o' => EV_KEY::KEY_O,
+        'p' => EV_KEY::KEY_P,
+        'q' => EV_KEY::KEY_Q,
+        'r' => EV_KEY::KEY_R,
+        's' => EV_KEY::KEY_S,
+        't' => EV_KEY::KEY_T,
+        'u' => EV_KEY::KEY_U,
+        'v' => EV_KEY::KEY_V,
+        'w' => EV_KEY::KEY_W,
+        'x' => EV_KEY::KEY_X,
+        'y' => EV_KEY::KEY_Y,
+        'z' => EV_KEY::KEY_Z,
+        '1' => EV_KEY::KEY_1,
+        '2' => EV_KEY::KEY_2,
+        '3' => EV_KEY::KEY_3,
+        '4' => EV_KEY::KEY_4,
+        '5' => EV_KEY::KEY_5,
+        '6' => EV_KEY::KEY_6,
+        '7' => EV_KEY::KEY_7,
+        '8' => EV_KEY::KEY_8,
+        '9' => EV_KEY::KEY_9,
+        '0' => EV_KEY::KEY_0,
+        ' ' => EV_KEY::KEY_SPACE,
+        '\n' => EV_KEY::KEY_ENTER,
+        '\t' => EV_KEY::KEY_TAB,
+        '-' => EV_KEY::KEY_MINUS,
+        '=' => EV_KEY::KEY_EQUAL,
+        '[' => EV_KEY::KEY_LEFTBRACE,
+        ']' => EV_KEY::KEY_RIGHTBRACE,
+        '\\' => EV_KEY::KEY_BACKSLASH,
+        ';' => EV_KEY::KEY_SEMICOLON,
+        '\'' => EV_KEY::KEY_APOSTROPHE,
+        '`' => EV_KEY::KEY_GRAVE,
+        ',' => EV_KEY::KEY_COMMA,
+        '.' => EV_KEY::KEY_DOT,
+        '/' => EV_KEY::KEY_SLASH,
+        shifted => {
+            let key = match shifted {
+                '!' => EV_KEY::KEY_1,
+                '@' => EV_KEY::KEY_2,
+                '#' => EV_KEY::KEY_3,
+                '$' => EV_KEY::KEY_4,
+                '%' => EV_KEY::KEY_5,
+                '^' => EV_KEY::KEY_6,
+                '&' => EV_KEY::KEY_7,
+                '*' => EV_KEY::KEY_8,
+                '(' => EV_KEY::KEY_9,
+                ')' => EV_KEY::KEY_0,
+                '_' => EV_KEY::KEY_MINUS,
+                '+' => EV_KEY::KEY_EQUAL,
+                '{' => EV_KEY::KEY_LEFTBRACE,
+                '}' => EV_KEY::KEY_RIGHTBRACE,
+                '|' => EV_KEY::KEY_BACKSLASH,
+                ':' => EV_KEY::KEY_SEMICOLON,
+                '"' => EV_KEY::KEY_APOSTROPHE,
+                '~' => EV_KEY::KEY_GRAVE,
+                '<' => EV_KEY::KEY_COMMA,
+                '>' => EV_KEY::KEY_DOT,
+                '?' => EV_KEY::KEY_SLASH,
+                _ => return None,
+            };
+            return Some((key, true));
+        }
+    };
+    Some((key, shift))
+}
+
+/// How characters without a key are entered: a prefix chord, the code point in hex, and a
+/// commit key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnicodeEntry {
+    prefix: Vec<EV_KEY>,
+    commit: Option<EV_KEY>,
+}
+
+impl UnicodeEntry {
+    /// Ctrl+Shift+U followed by the hex code point and space, as understood by IBus and GTK.
+    pub fn ctrl_shift_u() -> Self {
+        Self::new(
+            vec![EV_KEY::KEY_LEFTCTRL, EV_KEY::KEY_LEFTSHIFT, EV_KEY::KEY_U],
+            Some(EV_KEY::KEY_SPACE),
+        )
+    }
+
+    /// Presses the keys of `prefix` in order and releases them in reverse before the hex digits,
+    /// and taps `commit`, if any, after them.
+    pub fn new(prefix: Vec<EV_KEY>, commit: Option<EV_KEY>) -> Self {
+        Self { prefix, commit }
+    }
+}
+
+impl Default for UnicodeEntry {
+    fn default() -> Self {
+        Self::ctrl_shift_u()
+    }
+}
+
+fn push_key(frames: &mut Vec<(EventCode, i32)>, key: EV_KEY, shift: bool) {
+    let key = EventCode::EV_KEY(key);
+    let shift_key = EventCode::EV_KEY(EV_KEY::KEY_LEFTSHIFT);
+    if shift {
+        frames.push((shift_key, 1));
+    }
+    frames.push((key, 1));
+    frames.push((key, 0));
+    if shift {
+        frames.push((shift_key, 0));
+    }
+}
+
+/// The key changes typing `text` on a US layout takes, each meant to be sent as its own frame.
+pub(crate) fn text_frames(text: &str) -> std::io::Result<Vec<(EventCode, i32)>> {
+    let mut frames = Vec::new();
+    for c in text.chars() {
+        let (key, shift) = us_key(c).ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("no key for {:?}", c),
+            )
+        })?;
+        push_key(&mut frames, key, shift);
+    }
+    Ok(frames)
+}
+
+/// The key changes typing `c` takes: its key on a US layout, or else `entry`.
+pub(crate) fn char_frames(c: char, entry: &UnicodeEntry) -> Vec<(EventCode, i32)> {
+    let mut frames = Vec::new();
+    if let Some((key, shift)) = us_key(c) {
+        push_key(&mut frames, key, shift);
+        return frames;
+    }
+    for key in &entry.prefix {
+        frames.push((EventCode::EV_KEY(*key), 1));
+    }
+    for key in entry.prefix.iter().rev() {
+        frames.push((EventCode::EV_KEY(*key), 0));
+    }
+    // Hex digits all have unshifted keys.
+    for digit in format!("{:x}", u32::from(c)).chars() {
+        if let Some((key, _)) = us_key(digit) {
+            push_key(&mut frames, key, false);
+        }
+    }
+    if let Some(commit) = entry.commit {
+        push_key(&mut frames, commit, false);
+    }
+    frames
+}
@@ -0,0 +1,193 @@
+//! A device read on a dedicated thread instead of through an async reactor, for when blocking
+//! reads give lower latency jitter, e.g. with a busy executor.
+
+use crate::grab::{grab_fd, track};
+use crate::{open_nonblocking, read_event, DeviceError, DeviceInfo, EventSource, OpenError};
+use concurrent_queue::{ConcurrentQueue, PopError, PushError};
+use evdev_rs::{GrabMode, InputEvent};
+use futures::task::AtomicWaker;
+use futures::Stream;
+use std::fs::File;
+use std::io::Write as _;
+use std::os::unix::io::{AsRawFd as _, FromRawFd as _, RawFd};
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::thread::JoinHandle;
+
+type Item = Result<InputEvent, DeviceError>;
+
+/// What the reader thread and the stream share.
+struct Shared {
+    // Only ever pushed to by the reader thread and popped from by the stream.
+    queue: ConcurrentQueue<Item>,
+    waker: AtomicWaker,
+    // Set while the reader thread is parked on a full queue.
+    reader_parked: AtomicBool,
+}
+
+impl Shared {
+    /// Waits while the queue is full, returning false if the stream has been dropped.
+    fn push(&self, mut item: Item) -> bool {
+        loop {
+            match self.queue.push(item) {
+                Ok(()) => {
+                    self.waker.wake();
+                    return true;
+                }
+                Err(PushError::Closed(_)) => return false,
+                Err(PushError::Full(full)) => {
+                    item = full;
+                    self.reader_parked.store(true, Ordering::SeqCst);
+                    // The stream may have popped before seeing the flag.
+                    if self.queue.is_full() {
+                        std::thread::park();
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn read_loop(device: &evdev_rs::Device, stop: RawFd, shared: &Shared) {
+    let mut syncing = false;
+    loop {
+        while let Some(event) = read_event(device, &mut syncing) {
+            let disconnected = matches!(event, Err(DeviceError::Disconnected(_)));
+            if !shared.push(event) || disconnected {
+                return;
+            }
+        }
+        let mut fds = [device.file().as_raw_fd(), stop].map(|fd| libc::pollfd {
+            fd,
+            events: libc::POLLIN,
+            revents: 0,
+        });
+        if unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, -1) } < 0 {
+            let e = std::io::Error::last_os_error();
+            if e.kind() != std::io::ErrorKind::Interrupted {
+                let _: bool = shared.push(Err(e.into()));
+                return;
+            }
+        }
+        if fds[1].revents != 0 {
+            return;
+        }
+    }
+}
+
+/// A device whose events are read on a dedicated thread, which blocks until the device is
+/// readable and then hands the events over to the stream through a bounded lock-free queue of
+/// `BURST_CAPACITY` events. The thread waits while the queue is full, leaving further events
+/// to the kernel's buffer, and is stopped when the device is dropped. Unlike an `AsyncDevice`,
+/// the stream ends after yielding `DeviceError::Disconnected`.
+///
+/// The libevdev device is owned by the thread, so `EventSource::libevdev` is `None`.
+pub struct ThreadedDevice {
+    shared: Arc<Shared>,
+    fd: RawFd,
+    stop: File,
+    // Hands the device back so its fd stays open, and `fd` valid, until `self` is dropped.
+    reader: Option<JoinHandle<evdev_rs::Device>>,
+    info: DeviceInfo,
+}
+
+impl ThreadedDevice {
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<Self, OpenError> {
+        Self::from_file(open_nonblocking(path.as_ref())?).map_err(OpenError::Init)
+    }
+
+    /// `file` must have been opened with `O_NONBLOCK`.
+    pub fn from_file(file: File) -> std::io::Result<Self> {
+        evdev_rs::Device::new_from_file(file).and_then(Self::from_device)
+    }
+
+    /// The device's file must have been opened with `O_NONBLOCK`.
+    pub fn from_device(device: evdev_rs::Device) -> std::io::Result<Self> {
+        let stop = unsafe { libc::eventfd(0, libc::EFD_CLOEXEC) };
+        if stop < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        let stop = unsafe { File::from_raw_fd(stop) };
+        let stop_fd = stop.as_raw_fd();
+        let shared = Arc::new(Shared {
+            queue: ConcurrentQueue::bounded(crate::BURST_CAPACITY),
+            waker: AtomicWaker::new(),
+            reader_parked: AtomicBool::new(false),
+        });
+        let fd = device.file().as_raw_fd();
+        let info = DeviceInfo::from_device(&device);
+        let reader = {
+            let shared = Arc::clone(&shared);
+            std::thread::Builder::new()
+                .name(format!("evdev reader {}", info.name))
+                .spawn(move || {
+                    read_loop(&device, stop_fd, &shared);
+                    let _: bool = shared.queue.close();
+                    shared.waker.wake();
+                    device
+                })?
+        };
+        Ok(Self {
+            shared,
+            fd,
+            stop,
+            reader: Some(reader),
+            info,
+        })
+    }
+
+    pub fn grab(&mut self, grab: GrabMode) -> std::io::Result<()> {
+        grab_fd(self.fd, grab)
+    }
+
+    pub fn info(&self) -> &DeviceInfo {
+        &self.info
+    }
+}
+
+impl Stream for ThreadedDevice {
+    type Item = Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let pop = || match self.shared.queue.pop() {
+            Ok(item) => {
+                if self.shared.reader_parked.swap(false, Ordering::SeqCst) {
+                    if let Some(reader) = &self.reader {
+                        reader.thread().unpark();
+                    }
+                }
+                Some(Poll::Ready(Some(item)))
+            }
+            Err(PopError::Closed) => Some(Poll::Ready(None)),
+            Err(PopError::Empty) => None,
+        };
+        if let Some(poll) = pop() {
+            return poll;
+        }
+        self.shared.waker.register(cx.waker());
+        // The reader may have pushed before the waker was registered.
+        pop().unwrap_or(Poll::Pending)
+    }
+}
+
+impl EventSource for ThreadedDevice {
+    fn grab(&mut self, grab: GrabMode) -> std::io::Result<()> {
+        ThreadedDevice::grab(self, grab)
+    }
+}
+
+impl Drop for ThreadedDevice {
+    fn drop(&mut self) {
+        let _: bool = self.shared.queue.close();
+        let _: std::io::Result<()> = self.stop.write_all(&1u64.to_ne_bytes());
+        // Closing the fd releases any grab, and the fd may be reused.
+        track(self.fd, false);
+        if let Some(reader) = self.reader.take() {
+            reader.thread().unpark();
+            let _: std::thread::Result<evdev_rs::Device> = reader.join();
+        }
+    }
+}
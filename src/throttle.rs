@@ -0,0 +1,246 @@
+use crate::{event, Processor, ZERO_TIME};
+use evdev_rs::enums::{EventCode, EV_KEY, EV_SYN};
+use evdev_rs::{InputEvent, TimeVal};
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+#[derive(Debug)]
+struct KeyState {
+    pressed: bool,
+    // What was last forwarded.
+    forwarded: bool,
+    locked_until: Instant,
+}
+
+/// Suppresses switch bounce: after a key changes state, further changes of that key within
+/// `window` are held back. If the key ends up in a different state than was forwarded once the
+/// window is over, that state is forwarded then.
+#[derive(Debug)]
+pub struct Debounce {
+    window: Duration,
+    keys: HashMap<EV_KEY, KeyState>,
+}
+
+impl Debounce {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            keys: HashMap::new(),
+        }
+    }
+}
+
+impl Processor for Debounce {
+    type Output = InputEvent;
+
+    fn process(&mut self, input: InputEvent, now: Instant, out: &mut VecDeque<InputEvent>) {
+        let key = match input.event_code {
+            EventCode::EV_KEY(key) => key,
+            _ => return out.push_back(input),
+        };
+        // A key first seen released or repeating was held before the processor started, e.g.
+        // while grabbing, and has been pressed downstream.
+        let held = input.value != 1;
+        let state = self.keys.entry(key).or_insert(KeyState {
+            pressed: held,
+            forwarded: held,
+            locked_until: now,
+        });
+        match input.value {
+            // Repeats follow the forwarded state.
+            2 => {
+                if state.forwarded {
+                    out.push_back(input);
+                }
+            }
+            value => {
+                state.pressed = value != 0;
+                if now >= state.locked_until && state.pressed != state.forwarded {
+                    state.forwarded = state.pressed;
+                    state.locked_until = now + self.window;
+                    out.push_back(input);
+                }
+            }
+        }
+    }
+
+    fn deadline(&self) -> Option<Instant> {
+        self.keys
+            .values()
+            .filter(|state| state.pressed != state.forwarded)
+            .map(|state| state.locked_until)
+            .min()
+    }
+
+    fn timeout(&mut self, now: Instant, out: &mut VecDeque<InputEvent>) {
+        let mut any = false;
+        for (key, state) in &mut self.keys {
+            if state.pressed != state.forwarded && state.locked_until <= now {
+                state.forwarded = state.pressed;
+                state.locked_until = now + self.window;
+                out.push_back(event(
+                    ZERO_TIME,
+                    EventCode::EV_KEY(*key),
+                    state.pressed.into(),
+                ));
+                any = true;
+            }
+        }
+        if any {
+            out.push_back(event(ZERO_TIME, EventCode::EV_SYN(EV_SYN::SYN_REPORT), 0));
+        }
+    }
+}
+
+/// Coalesces EV_REL motion so that it is reported at most `hz` times per second, e.g. to tame
+/// mice polling at several kHz. Motion is summed rather than dropped, and other events pass
+/// through without delay.
+#[derive(Debug)]
+pub struct RateLimit {
+    period: Duration,
+    frame: Vec<InputEvent>,
+    motion: Vec<(EventCode, i32)>,
+    last_report: Option<Instant>,
+}
+
+impl RateLimit {
+    /// `hz` must be positive; panics otherwise.
+    pub fn new(hz: f64) -> Self {
+        Self {
+            period: Duration::from_secs_f64(1.0 / hz),
+            frame: Vec::new(),
+            motion: Vec::new(),
+            last_report: None,
+        }
+    }
+
+    fn accumulate(&mut self, event_code: EventCode, value: i32) {
+        match self.motion.iter_mut().find(|(code, _)| *code == event_code) {
+            Some((_, total)) => *total += value,
+            None => self.motion.push((event_code, value)),
+        }
+    }
+
+    fn flush_motion(&mut self, time: TimeVal, now: Instant, out: &mut VecDeque<InputEvent>) {
+        out.extend(
+            self.motion
+                .drain(..)
+                .filter(|(_, value)| *value != 0)
+                .map(|(event_code, value)| event(time, event_code, value)),
+        );
+        self.last_report = Some(now);
+    }
+}
+
+impl Processor for RateLimit {
+    type Output = InputEvent;
+
+    fn process(&mut self, input: InputEvent, now: Instant, out: &mut VecDeque<InputEvent>) {
+        match input.event_code {
+            EventCode::EV_REL(_) => self.accumulate(input.event_code, input.value),
+            EventCode::EV_SYN(EV_SYN::SYN_REPORT) => {
+                let due = self
+                    .last_report
+                    .is_none_or(|last_report| now >= last_report + self.period);
+                let has_motion = !self.motion.is_empty();
+                if has_motion && due {
+                    self.flush_motion(input.time, now, out);
+                }
+                if !self.frame.is_empty() || (has_motion && due) {
+                    out.extend(self.frame.drain(..));
+                    out.push_back(input);
+                }
+            }
+            _ => self.frame.push(input),
+        }
+    }
+
+    fn deadline(&self) -> Option<Instant> {
+        if self.motion.is_empty() {
+            return None;
+        }
+        self.last_report
+            .map(|last_report| last_report + self.period)
+    }
+
+    fn timeout(&mut self, now: Instant, out: &mut VecDeque<InputEvent>) {
+        self.flush_motion(ZERO_TIME, now, out);
+        out.push_back(event(ZERO_TIME, EventCode::EV_SYN(EV_SYN::SYN_REPORT), 0));
+    }
+
+    fn finish(&mut self, out: &mut VecDeque<InputEvent>) {
+        out.extend(self.frame.drain(..));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{key, keys, run, syn};
+    use evdev_rs::enums::EV_KEY::KEY_A;
+    use evdev_rs::enums::EV_REL;
+
+    const WINDOW: Duration = Duration::from_millis(50);
+
+    #[test]
+    fn debounce_suppresses_chatter() {
+        let output = run(
+            vec![
+                key(0, KEY_A, 1),
+                key(5, KEY_A, 0),
+                key(10, KEY_A, 1),
+                key(200, KEY_A, 0),
+            ],
+            Debounce::new(WINDOW),
+        );
+        assert_eq!(keys(&output), vec![(KEY_A, 1), (KEY_A, 0)]);
+    }
+
+    #[test]
+    fn debounce_forwards_late_state_change() {
+        // The release within the window is forwarded once the window is over.
+        let output = run(
+            vec![key(0, KEY_A, 1), key(10, KEY_A, 0), syn(200)],
+            Debounce::new(WINDOW),
+        );
+        assert_eq!(keys(&output), vec![(KEY_A, 1), (KEY_A, 0)]);
+    }
+
+    #[test]
+    fn debounce_releases_key_held_at_start() {
+        let output = run(vec![key(0, KEY_A, 0)], Debounce::new(WINDOW));
+        assert_eq!(keys(&output), vec![(KEY_A, 0)]);
+    }
+
+    #[test]
+    fn rate_limit_sums_motion() {
+        let motion = |ms: u64, value| InputEvent {
+            value,
+            event_code: EventCode::EV_REL(EV_REL::REL_X),
+            ..syn(ms)
+        };
+        let output = run(
+            vec![
+                motion(0, 1),
+                syn(0),
+                motion(1, 2),
+                syn(1),
+                motion(2, 3),
+                syn(2),
+                // Keeps the stream open past the next report.
+                syn(200),
+            ],
+            RateLimit::new(10.0),
+        );
+        let total: i32 = output
+            .iter()
+            .filter(|event| event.event_code == EventCode::EV_REL(EV_REL::REL_X))
+            .map(|event| event.value)
+            .sum();
+        let reports = output
+            .iter()
+            .filter(|event| event.event_code == EventCode::EV_REL(EV_REL::REL_X))
+            .count();
+        assert_eq!((total, reports), (6, 2));
+    }
+}
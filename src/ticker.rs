@@ -0,0 +1,115 @@
+use crate::Injector;
+use async_io::Timer;
+use evdev_rs::enums::{EventCode, EV_SYN};
+use evdev_rs::UInputDevice;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Holds back events injected into it and writes them to `uinput` at a fixed rate, at most one
+/// frame per tick. Bursts are coalesced: relative motion adds up and absolute axes keep their
+/// latest value, while a second change of a key or other code within a tick is deferred to the
+/// next one, so no press or release is lost. SYN_REPORTs injected are dropped in favour of the
+/// ticker's own.
+///
+/// This decouples output frames from the input's, e.g. for virtual gamepads whose consumers
+/// expect a steady polling rate, and limits wakeups. Await `tick` alongside reading input, or
+/// drive the ticker with `deadline` and `flush_due`. It's idle, without deadline, while nothing
+/// is pending.
+pub struct OutputTicker<U = UInputDevice> {
+    uinput: U,
+    period: Duration,
+    frames: RefCell<VecDeque<Vec<(EventCode, i32)>>>,
+    last_tick: Option<Instant>,
+}
+
+impl<U: Injector> OutputTicker<U> {
+    /// Ticks `hz` times per second, e.g. 250, 500 or 1000. `hz` must be positive; panics otherwise.
+    pub fn new(uinput: U, hz: f64) -> Self {
+        Self {
+            uinput,
+            period: Duration::from_secs_f64(1.0 / hz),
+            frames: RefCell::new(VecDeque::new()),
+            last_tick: None,
+        }
+    }
+
+    pub fn uinput(&self) -> &U {
+        &self.uinput
+    }
+
+    pub fn is_idle(&self) -> bool {
+        self.frames.borrow().is_empty()
+    }
+
+    /// When the next frame is due, if any is pending.
+    pub fn deadline(&self) -> Option<Instant> {
+        if self.is_idle() {
+            return None;
+        }
+        Some(
+            self.last_tick
+                .map_or_else(Instant::now, |last_tick| last_tick + self.period),
+        )
+    }
+
+    /// Writes the next pending frame now, regardless of the rate.
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        let frame = self.frames.get_mut().pop_front();
+        if let Some(frame) = frame {
+            self.uinput.emit(&frame)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the next pending frame if it's due at `now`.
+    pub fn flush_due(&mut self, now: Instant) -> std::io::Result<()> {
+        if self.is_idle() {
+            return Ok(());
+        }
+        // Stay on schedule, unless the ticker was idle or has fallen behind.
+        let tick = match self.last_tick {
+            Some(last_tick) if last_tick + self.period > now => return Ok(()),
+            Some(last_tick) if last_tick + self.period * 2 > now => last_tick + self.period,
+            _ => now,
+        };
+        self.last_tick = Some(tick);
+        self.flush()
+    }
+
+    /// Waits for the next tick and writes the pending frame. Never completes while idle.
+    pub async fn tick(&mut self) -> std::io::Result<()> {
+        match self.deadline() {
+            Some(deadline) => {
+                let now = Timer::at(deadline).await;
+                self.flush_due(now)
+            }
+            None => std::future::pending().await,
+        }
+    }
+}
+
+impl<U: Injector> Injector for OutputTicker<U> {
+    fn inject_event(&self, event_code: EventCode, value: i32) -> std::io::Result<()> {
+        if let EventCode::EV_SYN(EV_SYN::SYN_REPORT) = event_code {
+            return Ok(());
+        }
+        let mut frames = self.frames.borrow_mut();
+        let last = frames.back_mut().and_then(|frame| {
+            frame
+                .iter_mut()
+                .find(|(code, _)| *code == event_code)
+                .map(|(_, pending)| pending)
+        });
+        match (event_code, last) {
+            (EventCode::EV_REL(_), Some(pending)) => *pending += value,
+            (EventCode::EV_ABS(_), Some(pending)) => *pending = value,
+            (_, Some(_)) => frames.push_back(vec![(event_code, value)]),
+            (_, None) => match frames.back_mut() {
+                Some(frame) => frame.push((event_code, value)),
+                None => frames.push_back(vec![(event_code, value)]),
+            },
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,204 @@
+//! `AsyncDevice` backed by tokio's reactor instead of async-io's.
+
+use crate::burst::BurstBuffer;
+use crate::grab::{self, emit_keys};
+use crate::{
+    key_state, open_nonblocking, read_event, Device, DeviceError, DeviceInfo, EventStreamExt as _,
+    Frames, Injector, LedExt, OpenError, Processed,
+};
+use ::tokio::io::unix::AsyncFd;
+use evdev_rs::enums::{EV_KEY, EV_LED};
+use evdev_rs::{GrabMode, InputEvent};
+use futures::ready;
+use std::collections::HashSet;
+use std::fs::File;
+use std::ops::{Deref, DerefMut};
+use std::os::unix::io::{AsRawFd as _, FromRawFd as _, RawFd};
+use std::path::Path;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+pub struct AsyncDevice {
+    device: AsyncFd<Device>,
+    syncing: bool,
+    burst: BurstBuffer,
+}
+
+impl futures::Stream for AsyncDevice {
+    type Item = Result<InputEvent, DeviceError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let Self {
+            device, syncing, ..
+        } = &mut *self;
+        loop {
+            if let Some(event) = read_event(&device.get_ref().0, syncing) {
+                return Poll::Ready(Some(event));
+            }
+            match ready!(device.poll_read_ready(cx)) {
+                Ok(mut guard) => guard.clear_ready(),
+                Err(e) => return Poll::Ready(Some(Err(e.into()))),
+            }
+        }
+    }
+}
+
+impl AsyncDevice {
+    /// Must be called from within a tokio runtime.
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<Self, OpenError> {
+        Self::from_file(open_nonblocking(path.as_ref())?).map_err(OpenError::Init)
+    }
+
+    /// Useful when the file needs to be opened with different flags, e.g. read-write for LEDs.
+    pub fn from_file(file: File) -> std::io::Result<Self> {
+        evdev_rs::Device::new_from_file(file).and_then(Self::from_device)
+    }
+
+    pub fn from_device(device: evdev_rs::Device) -> std::io::Result<Self> {
+        AsyncFd::new(Device(device)).map(|device| AsyncDevice {
+            device,
+            syncing: false,
+            burst: BurstBuffer::default(),
+        })
+    }
+
+    /// Takes ownership of an already open evdev fd, e.g. one passed down by a privileged parent
+    /// process.
+    ///
+    /// # Safety
+    ///
+    /// `fd` must be an open file descriptor that is not owned by anything else.
+    pub unsafe fn from_raw_fd(fd: RawFd) -> std::io::Result<Self> {
+        Self::from_file(File::from_raw_fd(fd))
+    }
+
+    pub fn grab(&mut self, grab: GrabMode) -> std::io::Result<()> {
+        let grabbed = matches!(grab, GrabMode::Grab);
+        self.device.get_mut().0.grab(grab)?;
+        grab::track(self.fd(), grabbed);
+        Ok(())
+    }
+
+    /// Grabs the device until the returned guard is dropped. See `crate::AsyncDevice::grab_guard`.
+    pub fn grab_guard(&mut self) -> std::io::Result<GrabGuard<'_>> {
+        self.grab(GrabMode::Grab)?;
+        Ok(GrabGuard { device: self })
+    }
+
+    /// Grabs the device and presses the keys held on it on `uinput`. See
+    /// `crate::AsyncDevice::grab_mirrored`.
+    pub fn grab_mirrored<U: Injector>(&mut self, uinput: &U) -> std::io::Result<HashSet<EV_KEY>> {
+        self.grab(GrabMode::Grab)?;
+        emit_keys(uinput, self.fd(), 1)
+    }
+
+    /// Releases the keys held on the device on `uinput`, and then the grab.
+    pub fn ungrab_mirrored<U: Injector>(&mut self, uinput: &U) -> std::io::Result<()> {
+        let _: HashSet<EV_KEY> = emit_keys(uinput, self.fd(), 0)?;
+        self.grab(GrabMode::Ungrab)
+    }
+
+    fn fd(&self) -> RawFd {
+        self.device.get_ref().0.file().as_raw_fd()
+    }
+
+    pub fn info(&self) -> DeviceInfo {
+        DeviceInfo::from_device(&self.device.get_ref().0)
+    }
+
+    pub fn next_event(
+        &self,
+        flags: evdev_rs::ReadFlag,
+    ) -> std::io::Result<(evdev_rs::ReadStatus, InputEvent)> {
+        self.device.get_ref().0.next_event(flags)
+    }
+
+    pub fn has_event_pending(&self) -> bool {
+        self.device.get_ref().0.has_event_pending()
+    }
+
+    /// Waits for events and returns all that are available, up to `BURST_CAPACITY`, at once.
+    /// See `crate::AsyncDevice::read_burst`.
+    pub async fn read_burst(
+        &mut self,
+    ) -> Result<std::collections::vec_deque::Drain<'_, InputEvent>, DeviceError> {
+        futures::future::poll_fn(|cx| self.poll_burst(cx)).await?;
+        Ok(self.burst.events.drain(..))
+    }
+
+    fn poll_burst(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), DeviceError>> {
+        let Self {
+            device,
+            syncing,
+            burst,
+        } = self;
+        loop {
+            burst.fill(&device.get_ref().0, syncing);
+            if let Some(ready) = burst.take_ready() {
+                return Poll::Ready(ready);
+            }
+            match ready!(device.poll_read_ready(cx)) {
+                Ok(mut guard) => guard.clear_ready(),
+                Err(e) => return Poll::Ready(Err(e.into())),
+            }
+        }
+    }
+
+    /// Returns a stream of whole reports instead of individual events.
+    pub fn frames(self) -> Processed<Self, Frames> {
+        self.process(Frames::new())
+    }
+
+    /// Whether `key` is held, as of the last event read from the device.
+    pub fn key_state(&self, key: EV_KEY) -> bool {
+        key_state::key_state(&self.device.get_ref().0, key)
+    }
+
+    /// All keys held, as of the last event read from the device.
+    pub fn current_keys(&self) -> HashSet<EV_KEY> {
+        key_state::current_keys(&self.device.get_ref().0)
+    }
+}
+
+impl Drop for AsyncDevice {
+    fn drop(&mut self) {
+        // Closing the fd releases any grab, and the fd may be reused.
+        grab::track(self.fd(), false);
+    }
+}
+
+/// Holds an exclusive grab on an `AsyncDevice`, releasing it when dropped. See
+/// `crate::GrabGuard`.
+pub struct GrabGuard<'a> {
+    device: &'a mut AsyncDevice,
+}
+
+impl Deref for GrabGuard<'_> {
+    type Target = AsyncDevice;
+
+    fn deref(&self) -> &AsyncDevice {
+        self.device
+    }
+}
+
+impl DerefMut for GrabGuard<'_> {
+    fn deref_mut(&mut self) -> &mut AsyncDevice {
+        self.device
+    }
+}
+
+impl Drop for GrabGuard<'_> {
+    fn drop(&mut self) {
+        let _: std::io::Result<()> = self.device.grab(GrabMode::Ungrab);
+    }
+}
+
+impl LedExt for AsyncDevice {
+    fn set_led(&self, led: EV_LED, on: bool) -> std::io::Result<()> {
+        self.device.get_ref().0.set_led(led, on)
+    }
+
+    fn led(&self, led: EV_LED) -> Option<bool> {
+        self.device.get_ref().0.led(led)
+    }
+}
@@ -0,0 +1,177 @@
+use crate::virtual_device::{TOUCHPAD_MAX, TOUCHPAD_SLOTS};
+use crate::{Injector, MtInjector, Touch, VirtualDeviceBuilder};
+use evdev_rs::enums::EV_ABS;
+use evdev_rs::{AbsInfo, UInputDevice};
+use std::time::Duration;
+
+// Units per millimeter, making the touchpad roughly 100mm across. libinput needs a resolution to
+// tell how far fingers moved.
+const RESOLUTION: i32 = 40;
+const CENTER: i32 = TOUCHPAD_MAX / 2;
+// Distance between adjacent fingers, about 20mm.
+const SPACING: i32 = 20 * RESOLUTION;
+const SWIPE_DISTANCE: i32 = TOUCHPAD_MAX / 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SwipeDirection {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+/// A virtual touchpad performing whole gestures that libinput recognizes, e.g. for remote
+/// desktops and testing. Each gesture puts fingers down around the center of the touchpad, moves
+/// them in a number of steps and lifts them again.
+pub struct VirtualTouchpad<U = UInputDevice> {
+    injector: MtInjector<U>,
+    steps: u32,
+    step_delay: Duration,
+}
+
+impl VirtualTouchpad {
+    pub fn build(name: &str) -> std::io::Result<Self> {
+        let mut builder = VirtualDeviceBuilder::new().name(name).touchpad();
+        for abs in [
+            EV_ABS::ABS_X,
+            EV_ABS::ABS_Y,
+            EV_ABS::ABS_MT_POSITION_X,
+            EV_ABS::ABS_MT_POSITION_Y,
+        ]
+        .iter()
+        {
+            builder = builder.abs(
+                *abs,
+                AbsInfo {
+                    value: 0,
+                    minimum: 0,
+                    maximum: TOUCHPAD_MAX,
+                    fuzz: 0,
+                    flat: 0,
+                    resolution: RESOLUTION,
+                },
+            );
+        }
+        let uinput = builder.build()?;
+        Ok(Self::new(uinput))
+    }
+}
+
+fn clamp((x, y): (i32, i32)) -> (i32, i32) {
+    (x.clamp(0, TOUCHPAD_MAX), y.clamp(0, TOUCHPAD_MAX))
+}
+
+fn lerp(from: i32, to: i32, t: f64) -> i32 {
+    from + (f64::from(to - from) * t).round() as i32
+}
+
+impl<U: Injector> VirtualTouchpad<U> {
+    /// Wraps a device set up like `VirtualDeviceBuilder::touchpad`.
+    pub fn new(uinput: U) -> Self {
+        Self {
+            injector: MtInjector::new(uinput, TOUCHPAD_SLOTS as usize),
+            steps: 20,
+            step_delay: Duration::from_millis(10),
+        }
+    }
+
+    /// The number of frames fingers move over during a gesture.
+    pub fn steps(mut self, steps: u32) -> Self {
+        self.steps = steps.max(1);
+        self
+    }
+
+    pub fn step_delay(mut self, step_delay: Duration) -> Self {
+        self.step_delay = step_delay;
+        self
+    }
+
+    pub fn uinput(&self) -> &U {
+        self.injector.uinput()
+    }
+
+    /// Scrolls by moving two fingers `dx` and `dy` touchpad units.
+    pub async fn two_finger_scroll(&mut self, dx: i32, dy: i32) -> std::io::Result<()> {
+        let start = [
+            (CENTER - SPACING / 2, CENTER),
+            (CENTER + SPACING / 2, CENTER),
+        ];
+        let end = [
+            (start[0].0 + dx, start[0].1 + dy),
+            (start[1].0 + dx, start[1].1 + dy),
+        ];
+        self.gesture(&start, &end).await
+    }
+
+    /// Pinches two fingers apart (`scale` above 1) or together (`scale` below 1).
+    pub async fn pinch(&mut self, scale: f64) -> std::io::Result<()> {
+        let half = f64::from(SPACING);
+        let end_half = (half * scale).round() as i32;
+        let half = half as i32;
+        let start = [(CENTER - half, CENTER), (CENTER + half, CENTER)];
+        let end = [(CENTER - end_half, CENTER), (CENTER + end_half, CENTER)];
+        self.gesture(&start, &end).await
+    }
+
+    /// Swipes with 3 or 4 fingers, as libinput doesn't recognize other swipes.
+    pub async fn swipe(
+        &mut self,
+        fingers: usize,
+        direction: SwipeDirection,
+    ) -> std::io::Result<()> {
+        if !(3..=4).contains(&fingers) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("cannot swipe with {} fingers", fingers),
+            ));
+        }
+        let (dx, dy) = match direction {
+            SwipeDirection::Up => (0, -SWIPE_DISTANCE),
+            SwipeDirection::Down => (0, SWIPE_DISTANCE),
+            SwipeDirection::Left => (-SWIPE_DISTANCE, 0),
+            SwipeDirection::Right => (SWIPE_DISTANCE, 0),
+        };
+        // Start on the opposite side so that the whole swipe fits on the touchpad.
+        let origin = (CENTER - dx / 2, CENTER - dy / 2);
+        let width = SPACING * (fingers as i32 - 1);
+        let start = (0..fingers as i32)
+            .map(|finger| (origin.0 - width / 2 + finger * SPACING, origin.1))
+            .collect::<Vec<_>>();
+        let end = start
+            .iter()
+            .map(|(x, y)| (x + dx, y + dy))
+            .collect::<Vec<_>>();
+        self.gesture(&start, &end).await
+    }
+
+    async fn gesture(&mut self, start: &[(i32, i32)], end: &[(i32, i32)]) -> std::io::Result<()> {
+        let down = start
+            .iter()
+            .enumerate()
+            .map(|(slot, position)| {
+                let (x, y) = clamp(*position);
+                Touch::Down { slot, x, y }
+            })
+            .collect::<Vec<_>>();
+        self.injector.frame(&down)?;
+        for step in 1..=self.steps {
+            let _: std::time::Instant = async_io::Timer::after(self.step_delay).await;
+            let t = f64::from(step) / f64::from(self.steps);
+            let moves = start
+                .iter()
+                .zip(end)
+                .enumerate()
+                .map(|(slot, ((x0, y0), (x1, y1)))| {
+                    let (x, y) = clamp((lerp(*x0, *x1, t), lerp(*y0, *y1, t)));
+                    Touch::Move { slot, x, y }
+                })
+                .collect::<Vec<_>>();
+            self.injector.frame(&moves)?;
+        }
+        let _: std::time::Instant = async_io::Timer::after(self.step_delay).await;
+        let up = (0..start.len())
+            .map(|slot| Touch::Up { slot })
+            .collect::<Vec<_>>();
+        self.injector.frame(&up)
+    }
+}
@@ -0,0 +1,306 @@
+//! Composable transforms for relative pointer motion, and a `Processor` applying them to the
+//! REL_X/REL_Y events of a mouse, e.g. between a grabbed mouse and its uinput clone.
+
+use crate::macros::time_since;
+use crate::Processor;
+use evdev_rs::enums::{EventCode, EV_KEY, EV_REL, EV_SYN};
+use evdev_rs::{InputEvent, TimeVal};
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// A transform of one frame's worth of pointer motion. `dt` is the time since the previous frame
+/// with motion, if known.
+pub trait Transform {
+    fn apply(&mut self, motion: (f64, f64), dt: Option<Duration>) -> (f64, f64);
+
+    /// Applies `next` to the output of this transform.
+    fn then<T: Transform>(self, next: T) -> Then<Self, T>
+    where
+        Self: Sized,
+    {
+        Then(self, next)
+    }
+}
+
+impl<F: FnMut((f64, f64), Option<Duration>) -> (f64, f64)> Transform for F {
+    fn apply(&mut self, motion: (f64, f64), dt: Option<Duration>) -> (f64, f64) {
+        self(motion, dt)
+    }
+}
+
+impl Transform for Box<dyn Transform> {
+    fn apply(&mut self, motion: (f64, f64), dt: Option<Duration>) -> (f64, f64) {
+        (**self).apply(motion, dt)
+    }
+}
+
+pub struct Then<A, B>(A, B);
+
+impl<A: Transform, B: Transform> Transform for Then<A, B> {
+    fn apply(&mut self, motion: (f64, f64), dt: Option<Duration>) -> (f64, f64) {
+        let motion = self.0.apply(motion, dt);
+        self.1.apply(motion, dt)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Scale {
+    pub x: f64,
+    pub y: f64,
+}
+
+impl Scale {
+    pub fn uniform(factor: f64) -> Self {
+        Self {
+            x: factor,
+            y: factor,
+        }
+    }
+}
+
+impl Transform for Scale {
+    fn apply(&mut self, (dx, dy): (f64, f64), _dt: Option<Duration>) -> (f64, f64) {
+        (dx * self.x, dy * self.y)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Invert {
+    pub x: bool,
+    pub y: bool,
+}
+
+impl Transform for Invert {
+    fn apply(&mut self, (dx, dy): (f64, f64), _dt: Option<Duration>) -> (f64, f64) {
+        (if self.x { -dx } else { dx }, if self.y { -dy } else { dy })
+    }
+}
+
+/// Rotates motion clockwise on screen, e.g. to compensate for a mouse held at an angle.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rotate {
+    sin: f64,
+    cos: f64,
+}
+
+impl Rotate {
+    pub fn radians(angle: f64) -> Self {
+        let (sin, cos) = angle.sin_cos();
+        Self { sin, cos }
+    }
+
+    pub fn degrees(angle: f64) -> Self {
+        Self::radians(angle.to_radians())
+    }
+}
+
+impl Transform for Rotate {
+    fn apply(&mut self, (dx, dy): (f64, f64), _dt: Option<Duration>) -> (f64, f64) {
+        // y grows downwards, so this is a clockwise rotation on screen.
+        (dx * self.cos - dy * self.sin, dx * self.sin + dy * self.cos)
+    }
+}
+
+/// Speed-dependent gain. Speeds are in device units per millisecond.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Accel {
+    /// The same gain at every speed.
+    Flat(f64),
+    /// Unit gain up to `threshold`, then growing by `slope` per unit of speed up to `max`.
+    Adaptive {
+        threshold: f64,
+        slope: f64,
+        max: f64,
+    },
+}
+
+impl Accel {
+    pub fn gain(&self, speed: f64) -> f64 {
+        match *self {
+            Accel::Flat(gain) => gain,
+            Accel::Adaptive {
+                threshold,
+                slope,
+                max,
+            } => (1.0 + (speed - threshold).max(0.0) * slope).min(max),
+        }
+    }
+}
+
+impl Transform for Accel {
+    fn apply(&mut self, (dx, dy): (f64, f64), dt: Option<Duration>) -> (f64, f64) {
+        // Without a usable interval, e.g. for the first motion, treat the motion as slow.
+        let speed = dt
+            .filter(|dt| *dt > Duration::ZERO)
+            .map(|dt| dx.hypot(dy) / (dt.as_secs_f64() * 1000.0))
+            .unwrap_or(0.0);
+        let gain = self.gain(speed);
+        (dx * gain, dy * gain)
+    }
+}
+
+/// Applies a `Transform` to the REL_X/REL_Y motion of each frame. Fractional motion is carried
+/// over to later frames rather than lost to rounding, unless turned off with `carry_remainder`.
+/// All other events pass through unchanged.
+pub struct PointerTransformer<T> {
+    transform: T,
+    motion: (i32, i32),
+    frame: Vec<InputEvent>,
+    last_motion: Option<TimeVal>,
+    carry: bool,
+    remainder: (f64, f64),
+}
+
+impl<T: Transform> PointerTransformer<T> {
+    pub fn new(transform: T) -> Self {
+        Self {
+            transform,
+            motion: (0, 0),
+            frame: Vec::new(),
+            last_motion: None,
+            carry: true,
+            remainder: (0.0, 0.0),
+        }
+    }
+
+    /// Whether to carry fractional motion over to later frames, so that scaling below 1 doesn't
+    /// lose slow motion. Otherwise it's dropped.
+    pub fn carry_remainder(mut self, carry: bool) -> Self {
+        self.carry = carry;
+        self
+    }
+
+    pub fn transform_mut(&mut self) -> &mut T {
+        &mut self.transform
+    }
+}
+
+impl<T: Transform> Processor for PointerTransformer<T> {
+    type Output = InputEvent;
+
+    fn process(&mut self, event: InputEvent, _now: Instant, out: &mut VecDeque<InputEvent>) {
+        match event.event_code {
+            EventCode::EV_REL(EV_REL::REL_X) => self.motion.0 += event.value,
+            EventCode::EV_REL(EV_REL::REL_Y) => self.motion.1 += event.value,
+            EventCode::EV_SYN(EV_SYN::SYN_REPORT) => {
+                let time = event.time;
+                if self.motion != (0, 0) {
+                    let dt = self
+                        .last_motion
+                        .as_ref()
+                        .map(|last_motion| time_since(last_motion, &time));
+                    let (dx, dy) = self
+                        .transform
+                        .apply((f64::from(self.motion.0), f64::from(self.motion.1)), dt);
+                    let (dx, dy) = (dx + self.remainder.0, dy + self.remainder.1);
+                    let (x, y) = (dx.trunc(), dy.trunc());
+                    if self.carry {
+                        self.remainder = (dx - x, dy - y);
+                    }
+                    self.motion = (0, 0);
+                    self.last_motion = Some(time);
+                    for (rel, value) in [(EV_REL::REL_X, x as i32), (EV_REL::REL_Y, y as i32)]
+                        .iter()
+                        .filter(|(_, value)| *value != 0)
+                    {
+                        self.frame.push(InputEvent {
+                            time,
+                            event_code: EventCode::EV_REL(*rel),
+                            value: *value,
+                        });
+                    }
+                }
+                out.extend(self.frame.drain(..));
+                out.push_back(event);
+            }
+            _ => self.frame.push(event),
+        }
+    }
+
+    fn finish(&mut self, out: &mut VecDeque<InputEvent>) {
+        out.extend(self.frame.drain(..));
+    }
+}
+
+/// Scales pointer motion by one of several factors, like the DPI switch of a gaming mouse. A
+/// press of the cycle key, e.g. one of the mouse's extra buttons, switches to the next profile
+/// and is not passed on. Profiles can also be selected through `Processed::processor_mut`.
+pub struct SensitivityManager {
+    profiles: Vec<f64>,
+    active: usize,
+    cycle_key: Option<EV_KEY>,
+    transformer: PointerTransformer<Scale>,
+}
+
+impl SensitivityManager {
+    /// Starts with the first of `profiles`, or at unit scale if there are none.
+    pub fn new(profiles: impl IntoIterator<Item = f64>) -> Self {
+        let mut profiles = profiles.into_iter().collect::<Vec<_>>();
+        if profiles.is_empty() {
+            profiles.push(1.0);
+        }
+        let transformer = PointerTransformer::new(Scale::uniform(profiles[0]));
+        Self {
+            profiles,
+            active: 0,
+            cycle_key: None,
+            transformer,
+        }
+    }
+
+    pub fn cycle_key(mut self, key: EV_KEY) -> Self {
+        self.cycle_key = Some(key);
+        self
+    }
+
+    /// See `PointerTransformer::carry_remainder`.
+    pub fn carry_remainder(mut self, carry: bool) -> Self {
+        self.transformer = self.transformer.carry_remainder(carry);
+        self
+    }
+
+    /// The index of the active profile.
+    pub fn active(&self) -> usize {
+        self.active
+    }
+
+    pub fn factor(&self) -> f64 {
+        self.profiles[self.active]
+    }
+
+    /// Switches to the profile at `index`, returning whether there is one.
+    pub fn select(&mut self, index: usize) -> bool {
+        let factor = match self.profiles.get(index) {
+            Some(factor) => *factor,
+            None => return false,
+        };
+        self.active = index;
+        *self.transformer.transform_mut() = Scale::uniform(factor);
+        log::info!("pointer speed profile {} ({}x)", index, factor);
+        true
+    }
+
+    /// Switches to the next profile, wrapping around.
+    pub fn cycle(&mut self) {
+        let _: bool = self.select((self.active + 1) % self.profiles.len());
+    }
+}
+
+impl Processor for SensitivityManager {
+    type Output = InputEvent;
+
+    fn process(&mut self, event: InputEvent, now: Instant, out: &mut VecDeque<InputEvent>) {
+        match event.event_code {
+            EventCode::EV_KEY(key) if Some(key) == self.cycle_key => {
+                if event.value == 1 {
+                    self.cycle();
+                }
+            }
+            _ => self.transformer.process(event, now, out),
+        }
+    }
+
+    fn finish(&mut self, out: &mut VecDeque<InputEvent>) {
+        self.transformer.finish(out);
+    }
+}
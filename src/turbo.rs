@@ -0,0 +1,185 @@
+use crate::{Processor, ZERO_TIME};
+use evdev_rs::enums::{EventCode, EV_KEY, EV_SYN};
+use evdev_rs::InputEvent;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy)]
+struct Pulse {
+    down: bool,
+    next: Instant,
+}
+
+/// Rapid fire: while a turbo button is held, presses and releases it `hz` times per second,
+/// staying down for the duty cycle's fraction of each period. Pressing all keys of the toggle
+/// chord together turns turbo off and on; while off, held turbo buttons are simply held. Other
+/// events pass through.
+#[derive(Debug)]
+pub struct Turbo {
+    buttons: HashSet<EV_KEY>,
+    period: Duration,
+    duty_cycle: f64,
+    chord: Vec<EV_KEY>,
+    chord_held: HashSet<EV_KEY>,
+    enabled: bool,
+    pulses: HashMap<EV_KEY, Pulse>,
+}
+
+impl Turbo {
+    /// No buttons, a duty cycle of one half, and no toggle chord. `hz` must be positive; panics
+    /// otherwise.
+    pub fn new(hz: f64) -> Self {
+        Self {
+            buttons: HashSet::new(),
+            period: Duration::from_secs_f64(1.0 / hz),
+            duty_cycle: 0.5,
+            chord: Vec::new(),
+            chord_held: HashSet::new(),
+            enabled: true,
+            pulses: HashMap::new(),
+        }
+    }
+
+    pub fn button(mut self, button: EV_KEY) -> Self {
+        let _: bool = self.buttons.insert(button);
+        self
+    }
+
+    /// The fraction of each period a button is down, clamped to (0, 1).
+    pub fn duty_cycle(mut self, duty_cycle: f64) -> Self {
+        self.duty_cycle = duty_cycle.clamp(0.01, 0.99);
+        self
+    }
+
+    /// Keys which toggle turbo when held together. They are passed on as well.
+    pub fn toggle_chord(mut self, keys: impl IntoIterator<Item = EV_KEY>) -> Self {
+        self.chord = keys.into_iter().collect();
+        self
+    }
+
+    pub fn enabled(mut self, enabled: bool) -> Self {
+        self.enabled = enabled;
+        self
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    fn toggle(&mut self, out: &mut VecDeque<InputEvent>) {
+        self.enabled = !self.enabled;
+        if self.enabled {
+            return;
+        }
+        // Leave held buttons down until they are released.
+        let mut any = false;
+        for (button, pulse) in self.pulses.drain() {
+            if !pulse.down {
+                out.push_back(InputEvent {
+                    time: ZERO_TIME,
+                    event_code: EventCode::EV_KEY(button),
+                    value: 1,
+                });
+                any = true;
+            }
+        }
+        if any {
+            out.push_back(InputEvent {
+                time: ZERO_TIME,
+                event_code: EventCode::EV_SYN(EV_SYN::SYN_REPORT),
+                value: 0,
+            });
+        }
+    }
+
+    fn update_chord(&mut self, key: EV_KEY, value: i32, out: &mut VecDeque<InputEvent>) {
+        if !self.chord.contains(&key) {
+            return;
+        }
+        match value {
+            1 => {
+                let _: bool = self.chord_held.insert(key);
+                if self.chord_held.len() == self.chord.len() {
+                    self.toggle(out);
+                }
+            }
+            0 => {
+                let _: bool = self.chord_held.remove(&key);
+            }
+            _ => {}
+        }
+    }
+
+    fn phase(&self, down: bool) -> Duration {
+        if down {
+            self.period.mul_f64(self.duty_cycle)
+        } else {
+            self.period.mul_f64(1.0 - self.duty_cycle)
+        }
+    }
+}
+
+impl Processor for Turbo {
+    type Output = InputEvent;
+
+    fn process(&mut self, input: InputEvent, now: Instant, out: &mut VecDeque<InputEvent>) {
+        let key = match input.event_code {
+            EventCode::EV_KEY(key) => key,
+            _ => return out.push_back(input),
+        };
+        self.update_chord(key, input.value, out);
+        if !self.enabled || !self.buttons.contains(&key) {
+            return out.push_back(input);
+        }
+        match input.value {
+            1 => {
+                let pulse = Pulse {
+                    down: true,
+                    next: now + self.phase(true),
+                };
+                let _: Option<Pulse> = self.pulses.insert(key, pulse);
+                out.push_back(input);
+            }
+            0 => match self.pulses.remove(&key) {
+                Some(Pulse { down: false, .. }) => {}
+                // Also a button held from before turbo was turned on.
+                _ => out.push_back(input),
+            },
+            _ => {}
+        }
+    }
+
+    fn deadline(&self) -> Option<Instant> {
+        self.pulses.values().map(|pulse| pulse.next).min()
+    }
+
+    fn timeout(&mut self, now: Instant, out: &mut VecDeque<InputEvent>) {
+        let mut any = false;
+        let buttons = self.pulses.keys().copied().collect::<Vec<_>>();
+        for button in buttons {
+            let Pulse { down, next } = self.pulses[&button];
+            if next > now {
+                continue;
+            }
+            // Keep the rhythm even if the timer fired late.
+            let pulse = Pulse {
+                down: !down,
+                next: (next + self.phase(!down)).max(now),
+            };
+            let _: Option<Pulse> = self.pulses.insert(button, pulse);
+            out.push_back(InputEvent {
+                time: ZERO_TIME,
+                event_code: EventCode::EV_KEY(button),
+                value: (!down).into(),
+            });
+            any = true;
+        }
+        if any {
+            out.push_back(InputEvent {
+                time: ZERO_TIME,
+                event_code: EventCode::EV_SYN(EV_SYN::SYN_REPORT),
+                value: 0,
+            });
+        }
+    }
+}
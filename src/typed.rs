@@ -0,0 +1,143 @@
+use crate::{Processor, Timestamp};
+use evdev_rs::enums::{EventCode, EV_ABS, EV_KEY, EV_LED, EV_MSC, EV_REL, EV_SW, EV_SYN};
+use evdev_rs::InputEvent;
+use std::collections::VecDeque;
+use std::time::Instant;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KeyState {
+    Released,
+    Pressed,
+    Repeated,
+}
+
+/// An `InputEvent` decoded by type, without its timestamp. Events that don't fit any of the
+/// specific variants, including key events with out of range values, are kept as `Other`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TypedEvent {
+    /// `scancode` is the `MSC_SCAN` value the device reported along with the key, if any.
+    Key {
+        key: EV_KEY,
+        state: KeyState,
+        scancode: Option<i32>,
+    },
+    RelMotion {
+        axis: EV_REL,
+        delta: i32,
+    },
+    AbsMotion {
+        axis: EV_ABS,
+        value: i32,
+    },
+    Msc {
+        msc: EV_MSC,
+        value: i32,
+    },
+    Switch {
+        switch: EV_SW,
+        on: bool,
+    },
+    Led {
+        led: EV_LED,
+        on: bool,
+    },
+    Syn(EV_SYN),
+    Other(InputEvent),
+}
+
+impl From<InputEvent> for TypedEvent {
+    fn from(event: InputEvent) -> Self {
+        let value = event.value;
+        match event.event_code {
+            EventCode::EV_KEY(key) => {
+                let state = match value {
+                    0 => KeyState::Released,
+                    1 => KeyState::Pressed,
+                    2 => KeyState::Repeated,
+                    _ => return TypedEvent::Other(event),
+                };
+                TypedEvent::Key {
+                    key,
+                    state,
+                    scancode: None,
+                }
+            }
+            EventCode::EV_REL(axis) => TypedEvent::RelMotion { axis, delta: value },
+            EventCode::EV_ABS(axis) => TypedEvent::AbsMotion { axis, value },
+            EventCode::EV_MSC(msc) => TypedEvent::Msc { msc, value },
+            EventCode::EV_SW(switch) => TypedEvent::Switch {
+                switch,
+                on: value != 0,
+            },
+            EventCode::EV_LED(led) => TypedEvent::Led {
+                led,
+                on: value != 0,
+            },
+            EventCode::EV_SYN(syn) => TypedEvent::Syn(syn),
+            _ => TypedEvent::Other(event),
+        }
+    }
+}
+
+/// Decodes events into `TypedEvent`s, attaching the `MSC_SCAN` value reported before a key event
+/// in the same frame to it. The `MSC_SCAN` events themselves are still passed on as `Msc`.
+#[derive(Debug, Default)]
+pub struct Typer {
+    scancode: Option<i32>,
+}
+
+impl Typer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Processor for Typer {
+    type Output = TypedEvent;
+
+    fn process(&mut self, event: InputEvent, _now: Instant, out: &mut VecDeque<TypedEvent>) {
+        let mut typed = TypedEvent::from(event);
+        match &mut typed {
+            TypedEvent::Msc {
+                msc: EV_MSC::MSC_SCAN,
+                value,
+            } => self.scancode = Some(*value),
+            TypedEvent::Key { scancode, .. } => *scancode = self.scancode.take(),
+            TypedEvent::Syn(_) => self.scancode = None,
+            _ => {}
+        }
+        out.push_back(typed);
+    }
+}
+
+/// An event along with its timestamp.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Timed<T> {
+    pub time: Timestamp,
+    pub event: T,
+}
+
+/// Like `Typer`, but keeps each event's timestamp. Set the device's clock to
+/// `ClockId::Monotonic` to compare them with `Instant`s.
+#[derive(Debug, Default)]
+pub struct TimedTyper {
+    typer: Typer,
+    // Reused between events, so that typing them doesn't allocate.
+    typed: VecDeque<TypedEvent>,
+}
+
+impl TimedTyper {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Processor for TimedTyper {
+    type Output = Timed<TypedEvent>;
+
+    fn process(&mut self, event: InputEvent, now: Instant, out: &mut VecDeque<Self::Output>) {
+        let time = Timestamp::from(event.time);
+        self.typer.process(event, now, &mut self.typed);
+        out.extend(self.typed.drain(..).map(|event| Timed { time, event }));
+    }
+}
@@ -0,0 +1,332 @@
+use crate::{
+    enable_abs_info, AbsInjector, ControllerPreset, DeviceWrapperExt as _, GamepadRanges,
+    VirtualComboDevice,
+};
+use evdev_rs::enums::{BusType, EventCode, EventType, InputProp, EV_ABS, EV_FF, EV_KEY};
+use evdev_rs::{AbsInfo, DeviceWrapper as _, UInputDevice, UninitDevice};
+use std::collections::HashSet;
+use std::ffi::{CStr, OsStr};
+use std::os::unix::ffi::OsStrExt as _;
+use std::os::unix::io::RawFd;
+use std::path::{Path, PathBuf};
+
+pub(crate) const TOUCHPAD_MAX: i32 = 4095;
+pub(crate) const TOUCHPAD_SLOTS: i32 = 5;
+pub(crate) const PEN_MAX: i32 = 32767;
+pub(crate) const PEN_PRESSURE_MAX: i32 = 4095;
+// Units per millimeter, making the tablet roughly 160mm across.
+const PEN_RESOLUTION: i32 = 200;
+// Tilt is in degrees, with the resolution in units per radian.
+const PEN_TILT_MAX: i32 = 64;
+const PEN_TILT_RESOLUTION: i32 = 57;
+
+/// Fluent wrapper around `UninitDevice` + `UInputDevice::create_from_device`.
+///
+/// Capability bundles are applied first and explicit ABS ranges last, so `abs` can be used to
+/// override the default ranges of e.g. `touchpad`.
+#[derive(Default)]
+pub struct VirtualDeviceBuilder {
+    name: Option<String>,
+    bustype: Option<BusType>,
+    vendor: Option<u16>,
+    product: Option<u16>,
+    version: Option<u16>,
+    keyboard: bool,
+    mouse: bool,
+    gamepad: bool,
+    preset: Option<ControllerPreset>,
+    touchpad: bool,
+    pen: bool,
+    rumble: bool,
+    abs: Vec<(EV_ABS, AbsInfo)>,
+}
+
+fn abs_info(minimum: i32, maximum: i32) -> AbsInfo {
+    AbsInfo {
+        value: 0,
+        minimum,
+        maximum,
+        fuzz: 0,
+        flat: 0,
+        resolution: 0,
+    }
+}
+
+impl VirtualDeviceBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    pub fn bustype(mut self, bustype: BusType) -> Self {
+        self.bustype = Some(bustype);
+        self
+    }
+
+    pub fn vendor(mut self, vendor: u16) -> Self {
+        self.vendor = Some(vendor);
+        self
+    }
+
+    pub fn product(mut self, product: u16) -> Self {
+        self.product = Some(product);
+        self
+    }
+
+    pub fn version(mut self, version: u16) -> Self {
+        self.version = Some(version);
+        self
+    }
+
+    pub fn keyboard(mut self) -> Self {
+        self.keyboard = true;
+        self
+    }
+
+    pub fn mouse(mut self) -> Self {
+        self.mouse = true;
+        self
+    }
+
+    /// A gamepad with two sticks, analog triggers and a hat, with the default `GamepadRanges`.
+    pub fn gamepad(mut self) -> Self {
+        self.gamepad = true;
+        self
+    }
+
+    /// Poses as a well-known controller: sets the name, bus type, ids and version the real one
+    /// has, along with exactly its buttons and axes. Any of these can be overridden afterwards.
+    pub fn preset(self, preset: ControllerPreset) -> Self {
+        let (vendor, product) = preset.ids();
+        let mut builder = self
+            .name(preset.name())
+            .bustype(preset.bustype())
+            .vendor(vendor)
+            .product(product)
+            .version(preset.version());
+        builder.preset = Some(preset);
+        builder
+    }
+
+    pub fn touchpad(mut self) -> Self {
+        self.touchpad = true;
+        self
+    }
+
+    /// A drawing tablet pen with pressure, tilt and two stylus buttons, as driven by
+    /// `VirtualPen`.
+    pub fn pen(mut self) -> Self {
+        self.pen = true;
+        self
+    }
+
+    /// Claims support for rumble and periodic force feedback effects, which can then be served
+    /// with `ff::UInputFf`.
+    pub fn rumble(mut self) -> Self {
+        self.rumble = true;
+        self
+    }
+
+    pub fn abs(mut self, abs: EV_ABS, info: AbsInfo) -> Self {
+        self.abs.push((abs, info));
+        self
+    }
+
+    pub fn abs_range(self, abs: EV_ABS, minimum: i32, maximum: i32) -> Self {
+        self.abs(abs, abs_info(minimum, maximum))
+    }
+
+    fn enable_touchpad(device: &UninitDevice) -> std::io::Result<()> {
+        device.enable(&EventType::EV_KEY)?;
+        for key in [
+            EV_KEY::BTN_LEFT,
+            EV_KEY::BTN_TOUCH,
+            EV_KEY::BTN_TOOL_FINGER,
+            EV_KEY::BTN_TOOL_DOUBLETAP,
+            EV_KEY::BTN_TOOL_TRIPLETAP,
+            EV_KEY::BTN_TOOL_QUADTAP,
+            EV_KEY::BTN_TOOL_QUINTTAP,
+        ]
+        .iter()
+        {
+            device.enable(&EventCode::EV_KEY(*key))?;
+        }
+        for (abs, info) in [
+            (EV_ABS::ABS_X, abs_info(0, TOUCHPAD_MAX)),
+            (EV_ABS::ABS_Y, abs_info(0, TOUCHPAD_MAX)),
+            (EV_ABS::ABS_MT_SLOT, abs_info(0, TOUCHPAD_SLOTS - 1)),
+            (EV_ABS::ABS_MT_POSITION_X, abs_info(0, TOUCHPAD_MAX)),
+            (EV_ABS::ABS_MT_POSITION_Y, abs_info(0, TOUCHPAD_MAX)),
+            (EV_ABS::ABS_MT_TRACKING_ID, abs_info(0, u16::MAX.into())),
+        ]
+        .iter()
+        {
+            enable_abs_info(device, *abs, info)?;
+        }
+        device.enable(&InputProp::INPUT_PROP_POINTER)?;
+        device.enable(&InputProp::INPUT_PROP_BUTTONPAD)?;
+        Ok(())
+    }
+
+    fn enable_pen(device: &UninitDevice) -> std::io::Result<()> {
+        device.enable(&EventType::EV_KEY)?;
+        for key in [
+            EV_KEY::BTN_TOOL_PEN,
+            EV_KEY::BTN_TOUCH,
+            EV_KEY::BTN_STYLUS,
+            EV_KEY::BTN_STYLUS2,
+        ]
+        .iter()
+        {
+            device.enable(&EventCode::EV_KEY(*key))?;
+        }
+        for (abs, minimum, maximum, resolution) in [
+            (EV_ABS::ABS_X, 0, PEN_MAX, PEN_RESOLUTION),
+            (EV_ABS::ABS_Y, 0, PEN_MAX, PEN_RESOLUTION),
+            (EV_ABS::ABS_PRESSURE, 0, PEN_PRESSURE_MAX, 0),
+            (
+                EV_ABS::ABS_TILT_X,
+                -PEN_TILT_MAX,
+                PEN_TILT_MAX - 1,
+                PEN_TILT_RESOLUTION,
+            ),
+            (
+                EV_ABS::ABS_TILT_Y,
+                -PEN_TILT_MAX,
+                PEN_TILT_MAX - 1,
+                PEN_TILT_RESOLUTION,
+            ),
+        ]
+        .iter()
+        {
+            let info = AbsInfo {
+                resolution: *resolution,
+                ..abs_info(*minimum, *maximum)
+            };
+            enable_abs_info(device, *abs, &info)?;
+        }
+        Ok(())
+    }
+
+    fn enable_rumble(device: &UninitDevice) -> std::io::Result<()> {
+        device.enable(&EventType::EV_FF)?;
+        for ff in [
+            EV_FF::FF_RUMBLE,
+            EV_FF::FF_PERIODIC,
+            EV_FF::FF_SQUARE,
+            EV_FF::FF_TRIANGLE,
+            EV_FF::FF_SINE,
+            EV_FF::FF_SAW_UP,
+            EV_FF::FF_SAW_DOWN,
+            EV_FF::FF_GAIN,
+        ]
+        .iter()
+        {
+            device.enable(&EventCode::EV_FF(*ff))?;
+        }
+        Ok(())
+    }
+
+    fn configure(&self) -> std::io::Result<UninitDevice> {
+        let device = UninitDevice::new()
+            .ok_or_else(|| std::io::Error::other("failed to allocate libevdev device"))?;
+        if let Some(name) = &self.name {
+            device.set_name(name);
+        }
+        if let Some(bustype) = self.bustype {
+            device.set_bustype(bustype as u16);
+        }
+        if let Some(vendor) = self.vendor {
+            device.set_vendor_id(vendor);
+        }
+        if let Some(product) = self.product {
+            device.set_product_id(product);
+        }
+        if let Some(version) = self.version {
+            device.set_version(version);
+        }
+        if self.keyboard {
+            device.enable_keys()?;
+        }
+        if self.mouse {
+            device.enable_mouse()?;
+        }
+        if self.gamepad {
+            device.enable_gamepad_with_axes(&GamepadRanges::default())?;
+        }
+        if let Some(preset) = self.preset {
+            device.enable_controller(preset)?;
+        }
+        if self.touchpad {
+            Self::enable_touchpad(&device)?;
+        }
+        if self.pen {
+            Self::enable_pen(&device)?;
+        }
+        if self.rumble {
+            Self::enable_rumble(&device)?;
+        }
+        for (abs, info) in &self.abs {
+            enable_abs_info(&device, *abs, info)?;
+        }
+        Ok(device)
+    }
+
+    pub fn build(self) -> std::io::Result<UInputDevice> {
+        UInputDevice::create_from_device(&self.configure()?)
+    }
+
+    /// Builds the device wrapped in an `AbsInjector` that knows all of its axis ranges.
+    pub fn build_abs_injector(self) -> std::io::Result<AbsInjector> {
+        let template = self.configure()?;
+        let uinput = UInputDevice::create_from_device(&template)?;
+        Ok(AbsInjector::from_template(uinput, &template))
+    }
+
+    /// Builds the device wrapped in a `VirtualComboDevice`, which rejects events the device
+    /// wasn't configured with.
+    pub fn build_combo(self) -> std::io::Result<VirtualComboDevice> {
+        let template = self.configure()?;
+        let uinput = UInputDevice::create_from_device(&template)?;
+        Ok(VirtualComboDevice::from_template(uinput, template))
+    }
+}
+
+const UI_GET_SYSNAME_LEN: usize = 64;
+const UI_GET_SYSNAME: libc::Ioctl = libc::_IOR::<[u8; UI_GET_SYSNAME_LEN]>(b'U' as u32, 44);
+
+/// The event nodes, e.g. `/dev/input/event7`, of the uinput devices this process has created
+/// and not yet destroyed, found through the process' open uinput fds. Reading them back would
+/// make a pipeline see its own output.
+pub(crate) fn own_event_nodes() -> std::io::Result<HashSet<PathBuf>> {
+    let mut nodes = HashSet::new();
+    for entry in std::fs::read_dir("/proc/self/fd")? {
+        let entry = entry?;
+        let is_uinput = std::fs::read_link(entry.path()).is_ok_and(|target| {
+            target == Path::new("/dev/uinput") || target == Path::new("/dev/input/uinput")
+        });
+        let fd = match entry.file_name().to_str().map(str::parse::<RawFd>) {
+            Some(Ok(fd)) if is_uinput => fd,
+            _ => continue,
+        };
+        let mut sysname = [0u8; UI_GET_SYSNAME_LEN];
+        if unsafe { libc::ioctl(fd, UI_GET_SYSNAME, sysname.as_mut_ptr()) } < 0 {
+            // The fd hasn't been turned into a device yet.
+            continue;
+        }
+        let sysname = CStr::from_bytes_until_nul(&sysname)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        let sysfs = Path::new("/sys/class/input").join(OsStr::from_bytes(sysname.to_bytes()));
+        for node in std::fs::read_dir(sysfs)? {
+            let name = node?.file_name();
+            if name.as_bytes().starts_with(b"event") {
+                let _: bool = nodes.insert(Path::new("/dev/input").join(name));
+            }
+        }
+    }
+    Ok(nodes)
+}
@@ -0,0 +1,331 @@
+//! An injector for Wayland sessions, speaking the virtual-keyboard protocol supported by
+//! wlroots-based compositors instead of writing to /dev/uinput, so it needs no privileges. Also a
+//! `ContextProvider` following the focused window through the foreign-toplevel protocol.
+
+use crate::remap::ContextProvider;
+use crate::xkb::{compile_keymap, EVDEV_OFFSET};
+use crate::{Injector, XkbError};
+use evdev_rs::enums::EventCode;
+use evdev_rs::util::event_code_to_int;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Write as _;
+use std::os::unix::io::{AsFd as _, FromRawFd as _};
+use std::time::Instant;
+use thiserror::Error;
+use wayland_client::backend::ObjectId;
+use wayland_client::globals::{registry_queue_init, BindError, GlobalError, GlobalListContents};
+use wayland_client::protocol::{wl_registry::WlRegistry, wl_seat::WlSeat};
+use wayland_client::{
+    ConnectError, Connection, Dispatch, DispatchError, EventQueue, Proxy as _, QueueHandle, WEnum,
+};
+use wayland_protocols_misc::zwp_virtual_keyboard_v1::client::zwp_virtual_keyboard_manager_v1::ZwpVirtualKeyboardManagerV1;
+use wayland_protocols_misc::zwp_virtual_keyboard_v1::client::zwp_virtual_keyboard_v1::ZwpVirtualKeyboardV1;
+use wayland_protocols_wlr::foreign_toplevel::v1::client::zwlr_foreign_toplevel_handle_v1::{
+    self, ZwlrForeignToplevelHandleV1,
+};
+use wayland_protocols_wlr::foreign_toplevel::v1::client::zwlr_foreign_toplevel_manager_v1::{
+    self, ZwlrForeignToplevelManagerV1,
+};
+use xkbcommon::xkb;
+
+const KEYMAP_FORMAT_XKB_V1: u32 = 1;
+
+#[derive(Error, Debug)]
+pub enum WaylandError {
+    #[error("failed to connect to the Wayland compositor")]
+    Connect(#[source] ConnectError),
+    #[error("failed to list Wayland globals")]
+    Globals(#[source] GlobalError),
+    #[error("compositor doesn't offer a seat or the virtual keyboard protocol")]
+    Bind(#[source] BindError),
+    #[error(transparent)]
+    Keymap(#[from] XkbError),
+    #[error("failed to share keymap with the compositor")]
+    ShareKeymap(#[source] std::io::Error),
+    #[error("compositor refused the virtual keyboard")]
+    Dispatch(#[source] DispatchError),
+    #[error("compositor doesn't offer the foreign-toplevel protocol")]
+    BindToplevels(#[source] BindError),
+    #[error("failed to list windows")]
+    ListToplevels(#[source] DispatchError),
+}
+
+// Nothing is listened to, events are only dispatched to surface protocol errors.
+struct State;
+
+impl Dispatch<WlRegistry, GlobalListContents> for State {
+    fn event(
+        _: &mut Self,
+        _: &WlRegistry,
+        _: <WlRegistry as wayland_client::Proxy>::Event,
+        _: &GlobalListContents,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+wayland_client::delegate_noop!(State: ignore WlSeat);
+wayland_client::delegate_noop!(State: ZwpVirtualKeyboardManagerV1);
+wayland_client::delegate_noop!(State: ZwpVirtualKeyboardV1);
+
+fn keymap_file(keymap: &str) -> std::io::Result<File> {
+    let name = b"evdev-utils-keymap\0";
+    let fd = unsafe { libc::memfd_create(name.as_ptr().cast(), libc::MFD_CLOEXEC) };
+    if fd < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    let mut file = unsafe { File::from_raw_fd(fd) };
+    file.write_all(keymap.as_bytes())?;
+    // Keymaps are shared as nul-terminated strings.
+    file.write_all(&[0])?;
+    Ok(file)
+}
+
+/// A keyboard injecting key events into a Wayland session. Only `EV_KEY` events are supported;
+/// `EV_MSC` events are ignored, repeats are left to the compositor and `SYN_REPORT` flushes the
+/// connection.
+pub struct WaylandKeyboard {
+    connection: Connection,
+    queue: RefCell<EventQueue<State>>,
+    keyboard: ZwpVirtualKeyboardV1,
+    state: RefCell<xkb::State>,
+    modifiers: Cell<[u32; 4]>,
+    start: Instant,
+}
+
+impl WaylandKeyboard {
+    /// Connects to the compositor in `WAYLAND_DISPLAY` with a keyboard using the given xkb layout.
+    pub fn new(layout: &str) -> Result<Self, WaylandError> {
+        let keymap = compile_keymap("", "", layout, "", None)?;
+        let connection = Connection::connect_to_env().map_err(WaylandError::Connect)?;
+        let (globals, mut queue) =
+            registry_queue_init::<State>(&connection).map_err(WaylandError::Globals)?;
+        let handle = queue.handle();
+        let seat: WlSeat = globals
+            .bind(&handle, 1..=1, ())
+            .map_err(WaylandError::Bind)?;
+        let manager: ZwpVirtualKeyboardManagerV1 = globals
+            .bind(&handle, 1..=1, ())
+            .map_err(WaylandError::Bind)?;
+        let keyboard = manager.create_virtual_keyboard(&seat, &handle, ());
+        let keymap_string = keymap.get_as_string(xkb::KEYMAP_FORMAT_TEXT_V1);
+        let file = keymap_file(&keymap_string).map_err(WaylandError::ShareKeymap)?;
+        keyboard.keymap(
+            KEYMAP_FORMAT_XKB_V1,
+            file.as_fd(),
+            keymap_string.len() as u32 + 1,
+        );
+        let _: usize = queue
+            .roundtrip(&mut State)
+            .map_err(WaylandError::Dispatch)?;
+        Ok(Self {
+            connection,
+            queue: RefCell::new(queue),
+            keyboard,
+            state: RefCell::new(xkb::State::new(&keymap)),
+            modifiers: Cell::new([0; 4]),
+            start: Instant::now(),
+        })
+    }
+
+    /// Waits until the compositor has processed everything sent so far, surfacing any protocol
+    /// error.
+    pub fn roundtrip(&self) -> Result<(), WaylandError> {
+        let _: usize = self
+            .queue
+            .borrow_mut()
+            .roundtrip(&mut State)
+            .map_err(WaylandError::Dispatch)?;
+        Ok(())
+    }
+
+    // Not every compositor derives modifiers from the keys of virtual keyboards, so track them
+    // and send them along.
+    fn update_modifiers(&self, keycode: u32, pressed: bool) {
+        let mut state = self.state.borrow_mut();
+        let direction = if pressed {
+            xkb::KeyDirection::Down
+        } else {
+            xkb::KeyDirection::Up
+        };
+        let _: xkb::StateComponent =
+            state.update_key(xkb::Keycode::new(keycode + EVDEV_OFFSET), direction);
+        let modifiers = [
+            state.serialize_mods(xkb::STATE_MODS_DEPRESSED),
+            state.serialize_mods(xkb::STATE_MODS_LATCHED),
+            state.serialize_mods(xkb::STATE_MODS_LOCKED),
+            state.serialize_layout(xkb::STATE_LAYOUT_EFFECTIVE),
+        ];
+        if modifiers != self.modifiers.replace(modifiers) {
+            let [depressed, latched, locked, group] = modifiers;
+            self.keyboard.modifiers(depressed, latched, locked, group);
+        }
+    }
+}
+
+impl Injector for WaylandKeyboard {
+    fn inject_event(&self, event_code: EventCode, value: i32) -> std::io::Result<()> {
+        match event_code {
+            EventCode::EV_KEY(_) => {
+                if value == 2 {
+                    return Ok(());
+                }
+                let keycode = event_code_to_int(&event_code).1;
+                let time = self.start.elapsed().as_millis() as u32;
+                self.keyboard.key(time, keycode, (value != 0).into());
+                self.update_modifiers(keycode, value != 0);
+                Ok(())
+            }
+            EventCode::EV_SYN(_) => self.connection.flush().map_err(std::io::Error::other),
+            EventCode::EV_MSC(_) => Ok(()),
+            event_code => Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                format!(
+                    "cannot inject {} into a Wayland virtual keyboard",
+                    event_code
+                ),
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Toplevel {
+    app_id: Option<String>,
+    activated: bool,
+}
+
+#[derive(Debug, Default)]
+struct Toplevels(HashMap<ObjectId, Toplevel>);
+
+impl Dispatch<WlRegistry, GlobalListContents> for Toplevels {
+    fn event(
+        _: &mut Self,
+        _: &WlRegistry,
+        _: <WlRegistry as wayland_client::Proxy>::Event,
+        _: &GlobalListContents,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<ZwlrForeignToplevelManagerV1, ()> for Toplevels {
+    fn event(
+        toplevels: &mut Self,
+        _: &ZwlrForeignToplevelManagerV1,
+        event: zwlr_foreign_toplevel_manager_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let zwlr_foreign_toplevel_manager_v1::Event::Toplevel { toplevel } = event {
+            let _: Option<Toplevel> = toplevels.0.insert(toplevel.id(), Toplevel::default());
+        }
+    }
+
+    wayland_client::event_created_child!(Toplevels, ZwlrForeignToplevelManagerV1, [
+        zwlr_foreign_toplevel_manager_v1::EVT_TOPLEVEL_OPCODE => (ZwlrForeignToplevelHandleV1, ()),
+    ]);
+}
+
+impl Dispatch<ZwlrForeignToplevelHandleV1, ()> for Toplevels {
+    fn event(
+        toplevels: &mut Self,
+        handle: &ZwlrForeignToplevelHandleV1,
+        event: zwlr_foreign_toplevel_handle_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        let toplevel = toplevels.0.entry(handle.id()).or_default();
+        match event {
+            zwlr_foreign_toplevel_handle_v1::Event::AppId { app_id } => {
+                toplevel.app_id = Some(app_id)
+            }
+            // The states are an array of native-endian u32s.
+            zwlr_foreign_toplevel_handle_v1::Event::State { state } => {
+                toplevel.activated = state.chunks_exact(4).any(|chunk| {
+                    let value = u32::from_ne_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+                    WEnum::from(value)
+                        == WEnum::Value(zwlr_foreign_toplevel_handle_v1::State::Activated)
+                })
+            }
+            zwlr_foreign_toplevel_handle_v1::Event::Closed => {
+                let _: Option<Toplevel> = toplevels.0.remove(&handle.id());
+                handle.destroy();
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Reports the app id of the focused window, as told by compositors implementing the wlroots
+/// foreign-toplevel protocol.
+pub struct WaylandContext {
+    connection: Connection,
+    queue: EventQueue<Toplevels>,
+    toplevels: Toplevels,
+    _manager: ZwlrForeignToplevelManagerV1,
+}
+
+impl WaylandContext {
+    /// Connects to the compositor in `WAYLAND_DISPLAY` and waits for the list of windows.
+    pub fn new() -> Result<Self, WaylandError> {
+        let connection = Connection::connect_to_env().map_err(WaylandError::Connect)?;
+        let (globals, mut queue) =
+            registry_queue_init::<Toplevels>(&connection).map_err(WaylandError::Globals)?;
+        let manager = globals
+            .bind(&queue.handle(), 1..=3, ())
+            .map_err(WaylandError::BindToplevels)?;
+        let mut toplevels = Toplevels::default();
+        let _: usize = queue
+            .roundtrip(&mut toplevels)
+            .map_err(WaylandError::ListToplevels)?;
+        Ok(Self {
+            connection,
+            queue,
+            toplevels,
+            _manager: manager,
+        })
+    }
+
+    /// Processes the window updates received so far without blocking.
+    pub fn update(&mut self) -> Result<(), WaylandError> {
+        self.connection
+            .flush()
+            .map_err(|e| WaylandError::ListToplevels(e.into()))?;
+        if let Some(guard) = self.queue.prepare_read() {
+            match guard.read() {
+                Ok(_) => {}
+                Err(wayland_client::backend::WaylandError::Io(e))
+                    if e.kind() == std::io::ErrorKind::WouldBlock => {}
+                Err(e) => return Err(WaylandError::ListToplevels(e.into())),
+            }
+        }
+        let _: usize = self
+            .queue
+            .dispatch_pending(&mut self.toplevels)
+            .map_err(WaylandError::ListToplevels)?;
+        Ok(())
+    }
+
+    /// The app id of the focused window, as of the last `update`.
+    pub fn focused_app_id(&self) -> Option<&str> {
+        self.toplevels
+            .0
+            .values()
+            .find(|toplevel| toplevel.activated)
+            .and_then(|toplevel| toplevel.app_id.as_deref())
+    }
+}
+
+impl ContextProvider for WaylandContext {
+    async fn current(&mut self) -> Option<String> {
+        self.update().ok()?;
+        self.focused_app_id().map(str::to_owned)
+    }
+}
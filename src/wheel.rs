@@ -0,0 +1,35 @@
+/// High-resolution wheel units per detent, as for REL_WHEEL_HI_RES and REL_HWHEEL_HI_RES.
+pub const WHEEL_DETENT: i32 = 120;
+
+/// Splits scrolling in high-resolution units into the REL_WHEEL_HI_RES values and the legacy
+/// REL_WHEEL detents to go with them, so that consumers of either scroll by the same amount.
+/// Fractions are carried over to the next call. See `Injector::inject_scroll_hi_res`.
+#[derive(Debug, Clone, Default)]
+pub struct HiResWheel {
+    // Fractions of a high-resolution unit not yet output.
+    remainder: f64,
+    // High-resolution units output since the last detent.
+    hi_res: i32,
+}
+
+impl HiResWheel {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Scrolls by `units`, returning the high-resolution value and detents to output.
+    pub fn scroll(&mut self, units: f64) -> (i32, i32) {
+        let units = units + self.remainder;
+        let whole = units.trunc();
+        self.remainder = units - whole;
+        self.hi_res += whole as i32;
+        let detents = self.hi_res / WHEEL_DETENT;
+        self.hi_res -= detents * WHEEL_DETENT;
+        (whole as i32, detents)
+    }
+
+    /// Forgets any partial detent.
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
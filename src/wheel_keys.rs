@@ -0,0 +1,107 @@
+use crate::Processor;
+use evdev_rs::enums::{EventCode, EV_KEY, EV_REL, EV_SYN};
+use evdev_rs::InputEvent;
+use std::collections::{HashSet, VecDeque};
+use std::time::Instant;
+
+// The macros for the positive and the negative direction.
+type Directions = (Vec<Vec<EV_KEY>>, Vec<Vec<EV_KEY>>);
+
+/// Turns wheel detents into key taps, e.g. volume up and down for a free-spinning wheel or a DIY
+/// knob. Each detent taps each chord of its macro in turn. Wheels without a mapping, and all
+/// other events, are passed on, as are the high-resolution wheel events of unmapped wheels.
+#[derive(Debug, Default)]
+pub struct WheelKeys {
+    wheel: Option<Directions>,
+    hwheel: Option<Directions>,
+    while_held: Option<EV_KEY>,
+    held: HashSet<EV_KEY>,
+}
+
+impl WheelKeys {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The vertical wheel controlling the volume.
+    pub fn volume() -> Self {
+        Self::new().wheel(
+            vec![vec![EV_KEY::KEY_VOLUMEUP]],
+            vec![vec![EV_KEY::KEY_VOLUMEDOWN]],
+        )
+    }
+
+    pub fn wheel(mut self, up: Vec<Vec<EV_KEY>>, down: Vec<Vec<EV_KEY>>) -> Self {
+        self.wheel = Some((up, down));
+        self
+    }
+
+    pub fn hwheel(mut self, right: Vec<Vec<EV_KEY>>, left: Vec<Vec<EV_KEY>>) -> Self {
+        self.hwheel = Some((right, left));
+        self
+    }
+
+    /// Only maps the wheels while `key` is held, e.g. a modifier or a side button; they scroll as
+    /// usual otherwise. The key itself is passed on.
+    pub fn while_held(mut self, key: Option<EV_KEY>) -> Self {
+        self.while_held = key;
+        self
+    }
+
+    fn active(&self) -> bool {
+        self.while_held.is_none_or(|key| self.held.contains(&key))
+    }
+
+    fn mapping(&self, axis: EV_REL) -> Option<&Directions> {
+        match axis {
+            EV_REL::REL_WHEEL | EV_REL::REL_WHEEL_HI_RES => self.wheel.as_ref(),
+            EV_REL::REL_HWHEEL | EV_REL::REL_HWHEEL_HI_RES => self.hwheel.as_ref(),
+            _ => None,
+        }
+    }
+}
+
+impl Processor for WheelKeys {
+    type Output = InputEvent;
+
+    fn process(&mut self, input: InputEvent, _now: Instant, out: &mut VecDeque<InputEvent>) {
+        let axis = match input.event_code {
+            EventCode::EV_KEY(key) => {
+                let _: bool = match input.value {
+                    0 => self.held.remove(&key),
+                    _ => self.held.insert(key),
+                };
+                return out.push_back(input);
+            }
+            EventCode::EV_REL(axis) => axis,
+            _ => return out.push_back(input),
+        };
+        let (positive, negative) = match self.mapping(axis) {
+            Some(mapping) if self.active() => mapping,
+            _ => return out.push_back(input),
+        };
+        if matches!(axis, EV_REL::REL_WHEEL_HI_RES | EV_REL::REL_HWHEEL_HI_RES) {
+            return;
+        }
+        let chords = if input.value > 0 { positive } else { negative };
+        for _ in 0..input.value.abs() {
+            for keys in chords {
+                for (value, keys) in [(1, keys.clone()), (0, keys.iter().rev().copied().collect())]
+                {
+                    for key in keys {
+                        out.push_back(InputEvent {
+                            event_code: EventCode::EV_KEY(key),
+                            value,
+                            ..input
+                        });
+                    }
+                    out.push_back(InputEvent {
+                        event_code: EventCode::EV_SYN(EV_SYN::SYN_REPORT),
+                        value: 0,
+                        ..input
+                    });
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,99 @@
+use crate::Processor;
+use evdev_rs::enums::EventCode;
+use evdev_rs::util::event_code_to_int;
+use evdev_rs::InputEvent;
+use std::collections::VecDeque;
+use std::time::Instant;
+use thiserror::Error;
+use xkbcommon::xkb;
+
+// evdev keycodes are offset by 8 in xkb, for historical X11 reasons.
+pub(crate) const EVDEV_OFFSET: u32 = 8;
+
+#[derive(Error, Debug)]
+pub enum XkbError {
+    #[error("failed to compile keymap")]
+    Keymap,
+}
+
+pub(crate) fn compile_keymap(
+    rules: &str,
+    model: &str,
+    layout: &str,
+    variant: &str,
+    options: Option<String>,
+) -> Result<xkb::Keymap, XkbError> {
+    let context = xkb::Context::new(xkb::CONTEXT_NO_FLAGS);
+    xkb::Keymap::new_from_names(
+        &context,
+        rules,
+        model,
+        layout,
+        variant,
+        options,
+        xkb::KEYMAP_COMPILE_NO_FLAGS,
+    )
+    .ok_or(XkbError::Keymap)
+}
+
+/// Translates key events into the text they produce under an xkb keymap, tracking modifier and
+/// lock state. As a `Processor`, it turns a stream of input events into a stream of text.
+pub struct XkbTranslator {
+    state: xkb::State,
+}
+
+impl XkbTranslator {
+    /// Compiles a keymap from RMLVO names. Empty strings and `None` select the system defaults,
+    /// which may be overridden with the `XKB_DEFAULT_*` environment variables.
+    pub fn from_names(
+        rules: &str,
+        model: &str,
+        layout: &str,
+        variant: &str,
+        options: Option<String>,
+    ) -> Result<Self, XkbError> {
+        let keymap = compile_keymap(rules, model, layout, variant, options)?;
+        Ok(Self {
+            state: xkb::State::new(&keymap),
+        })
+    }
+
+    pub fn new(layout: &str) -> Result<Self, XkbError> {
+        Self::from_names("", "", layout, "", None)
+    }
+
+    /// Updates the keyboard state with a key event, returning the text produced by presses and
+    /// repeats, if any.
+    pub fn translate(&mut self, event: &InputEvent) -> Option<String> {
+        let code = match event.event_code {
+            EventCode::EV_KEY(_) => event_code_to_int(&event.event_code).1,
+            _ => return None,
+        };
+        let keycode = xkb::Keycode::new(code + EVDEV_OFFSET);
+        let text = match event.value {
+            0 => {
+                let _: xkb::StateComponent = self.state.update_key(keycode, xkb::KeyDirection::Up);
+                return None;
+            }
+            1 => {
+                // Text is looked up before the state update so that e.g. Shift itself doesn't
+                // latch onto its own press.
+                let text = self.state.key_get_utf8(keycode);
+                let _: xkb::StateComponent =
+                    self.state.update_key(keycode, xkb::KeyDirection::Down);
+                text
+            }
+            _ if self.state.get_keymap().key_repeats(keycode) => self.state.key_get_utf8(keycode),
+            _ => return None,
+        };
+        Some(text).filter(|text| !text.is_empty())
+    }
+}
+
+impl Processor for XkbTranslator {
+    type Output = String;
+
+    fn process(&mut self, event: InputEvent, _now: Instant, out: &mut VecDeque<String>) {
+        out.extend(self.translate(&event));
+    }
+}
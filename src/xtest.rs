@@ -0,0 +1,208 @@
+//! An injector for X11 sessions, faking input through the XTest extension instead of writing to
+//! /dev/uinput, so it needs no privileges, and `Backend` for choosing between the two at runtime.
+
+use crate::{Injector, VirtualDeviceBuilder};
+use evdev_rs::enums::{EventCode, EV_KEY, EV_REL};
+use evdev_rs::util::event_code_to_int;
+use std::cell::Cell;
+use std::convert::TryFrom as _;
+use std::str::FromStr;
+use thiserror::Error;
+use x11rb::connection::{Connection as _, RequestConnection as _};
+use x11rb::cookie::VoidCookie;
+use x11rb::errors::{ConnectError, ConnectionError, ReplyError};
+use x11rb::protocol::xproto::{
+    Window, BUTTON_PRESS_EVENT, BUTTON_RELEASE_EVENT, KEY_PRESS_EVENT, KEY_RELEASE_EVENT,
+    MOTION_NOTIFY_EVENT,
+};
+use x11rb::protocol::xtest::{self, ConnectionExt as _};
+use x11rb::rust_connection::RustConnection;
+
+// X11 keycodes are evdev keycodes offset by 8, with the evdev and libinput drivers.
+const EVDEV_OFFSET: u32 = 8;
+
+/// Environment variable overriding `Backend::detect`, set to `uinput` or `xtest`.
+pub const BACKEND_VAR: &str = "EVDEV_UTILS_BACKEND";
+
+#[derive(Error, Debug)]
+pub enum XTestError {
+    #[error("failed to connect to the X server")]
+    Connect(#[source] ConnectError),
+    #[error("failed to send a request to the X server")]
+    Connection(#[from] ConnectionError),
+    #[error("X server returned an error")]
+    Reply(#[from] ReplyError),
+    #[error("X server doesn't support the XTest extension")]
+    MissingExtension,
+    #[error("failed to create uinput device")]
+    CreateUInput(#[source] std::io::Error),
+}
+
+fn button(key: EV_KEY) -> Option<u8> {
+    match key {
+        EV_KEY::BTN_LEFT => Some(1),
+        EV_KEY::BTN_MIDDLE => Some(2),
+        EV_KEY::BTN_RIGHT => Some(3),
+        EV_KEY::BTN_SIDE => Some(8),
+        EV_KEY::BTN_EXTRA => Some(9),
+        _ => None,
+    }
+}
+
+/// Injects key, button, pointer motion and wheel events into the X server in `DISPLAY`. Motion is
+/// gathered until the SYN_REPORT ending its frame, and wheel detents become clicks of buttons 4
+/// to 7 as in the core protocol; high-resolution wheel events and other events are dropped.
+pub struct XTestInjector {
+    connection: RustConnection,
+    root: Window,
+    motion: Cell<(i32, i32)>,
+}
+
+impl XTestInjector {
+    pub fn new() -> Result<Self, XTestError> {
+        let (connection, screen) = x11rb::connect(None).map_err(XTestError::Connect)?;
+        if connection
+            .extension_information(xtest::X11_EXTENSION_NAME)?
+            .is_none()
+        {
+            return Err(XTestError::MissingExtension);
+        }
+        let _: xtest::GetVersionReply = connection.xtest_get_version(2, 2)?.reply()?;
+        let root = connection.setup().roots[screen].root;
+        Ok(Self {
+            connection,
+            root,
+            motion: Cell::new((0, 0)),
+        })
+    }
+
+    fn fake(&self, type_: u8, detail: u8, (x, y): (i16, i16)) -> std::io::Result<()> {
+        let _: VoidCookie<'_, RustConnection> = self
+            .connection
+            .xtest_fake_input(type_, detail, x11rb::CURRENT_TIME, self.root, x, y, 0)
+            .map_err(std::io::Error::other)?;
+        Ok(())
+    }
+
+    fn click(&self, button: u8, clicks: i32) -> std::io::Result<()> {
+        for _ in 0..clicks {
+            self.fake(BUTTON_PRESS_EVENT, button, (0, 0))?;
+            self.fake(BUTTON_RELEASE_EVENT, button, (0, 0))?;
+        }
+        Ok(())
+    }
+
+    fn flush_motion(&self) -> std::io::Result<()> {
+        let (x, y) = self.motion.replace((0, 0));
+        if (x, y) == (0, 0) {
+            return Ok(());
+        }
+        let clamp = |value: i32| value.clamp(i16::MIN.into(), i16::MAX.into()) as i16;
+        // A detail of 1 makes the motion relative.
+        self.fake(MOTION_NOTIFY_EVENT, 1, (clamp(x), clamp(y)))
+    }
+}
+
+impl Injector for XTestInjector {
+    fn inject_event(&self, event_code: EventCode, value: i32) -> std::io::Result<()> {
+        match event_code {
+            // The X server repeats keys itself.
+            EventCode::EV_KEY(_) if value == 2 => Ok(()),
+            EventCode::EV_KEY(key) => {
+                let (type_, detail) = match button(key) {
+                    Some(button) if value != 0 => (BUTTON_PRESS_EVENT, button),
+                    Some(button) => (BUTTON_RELEASE_EVENT, button),
+                    None => {
+                        let keycode = event_code_to_int(&event_code).1 + EVDEV_OFFSET;
+                        let keycode = u8::try_from(keycode).map_err(|_| {
+                            std::io::Error::new(
+                                std::io::ErrorKind::Unsupported,
+                                format!("{} has no X11 keycode", event_code),
+                            )
+                        })?;
+                        let type_ = if value != 0 {
+                            KEY_PRESS_EVENT
+                        } else {
+                            KEY_RELEASE_EVENT
+                        };
+                        (type_, keycode)
+                    }
+                };
+                self.fake(type_, detail, (0, 0))
+            }
+            EventCode::EV_REL(EV_REL::REL_X) => {
+                let (x, y) = self.motion.get();
+                self.motion.set((x + value, y));
+                Ok(())
+            }
+            EventCode::EV_REL(EV_REL::REL_Y) => {
+                let (x, y) = self.motion.get();
+                self.motion.set((x, y + value));
+                Ok(())
+            }
+            EventCode::EV_REL(EV_REL::REL_WHEEL) => {
+                self.click(if value > 0 { 4 } else { 5 }, value.abs())
+            }
+            EventCode::EV_REL(EV_REL::REL_HWHEEL) => {
+                self.click(if value > 0 { 7 } else { 6 }, value.abs())
+            }
+            EventCode::EV_SYN(_) => {
+                self.flush_motion()?;
+                self.connection.flush().map_err(std::io::Error::other)
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Where injected events go.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    /// A uinput device, which works everywhere but needs write access to /dev/uinput.
+    UInput,
+    /// The X server, through `XTestInjector`.
+    XTest,
+}
+
+impl FromStr for Backend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "uinput" => Ok(Backend::UInput),
+            "xtest" | "x11" => Ok(Backend::XTest),
+            _ => Err(format!("unknown injection backend {:?}", s)),
+        }
+    }
+}
+
+impl Backend {
+    /// The backend named by `EVDEV_UTILS_BACKEND` if set, else uinput if /dev/uinput is writable
+    /// or no X server is around, else XTest.
+    pub fn detect() -> Self {
+        if let Some(backend) = std::env::var(BACKEND_VAR)
+            .ok()
+            .and_then(|backend| backend.parse().ok())
+        {
+            return backend;
+        }
+        let uinput_writable = std::fs::OpenOptions::new()
+            .write(true)
+            .open("/dev/uinput")
+            .is_ok();
+        if uinput_writable || std::env::var_os("DISPLAY").is_none() {
+            Backend::UInput
+        } else {
+            Backend::XTest
+        }
+    }
+
+    /// Creates an injector on this backend. `device` describes the uinput device to create and
+    /// is unused with XTest.
+    pub fn build(self, device: VirtualDeviceBuilder) -> Result<Box<dyn Injector>, XTestError> {
+        Ok(match self {
+            Backend::UInput => Box::new(device.build().map_err(XTestError::CreateUInput)?),
+            Backend::XTest => Box::new(XTestInjector::new()?),
+        })
+    }
+}